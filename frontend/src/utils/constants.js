@@ -9,6 +9,7 @@ export const LOGOUT_URL = '/auth/v1/oidc/logout';
 export const REDIRECT_URI = '/auth/v1/oidc/callback';
 export const REDIRECT_URI_SUCCESS = '/auth/v1/admin';
 export const REDIRECT_URI_SUCCESS_ACC = '/auth/v1/account';
+export const REDIRECT_URI_SUCCESS_DEVICE = '/auth/v1/oidc/device';
 export const POST_LOGOUT_REDIRECT_URI = '/auth/v1/';
 
 export const REGEX_API_KEY = /^[a-zA-Z0-9_/-]{2,24}$/gm;
@@ -17,6 +18,8 @@ export const REGEX_ATTR_DESC = /^[a-zA-Z0-9\-_/\s]{0,128}$/gm;
 export const REGEX_ATTR_KEY = /^[a-zA-Z0-9\-_/]{2,32}$/gm;
 export const REGEX_CLIENT_ID = /^[a-zA-Z0-9\-_/]{2,128}$/gm;
 export const REGEX_CLIENT_NAME = /^[a-zA-Z0-9À-ÿ\-\s]{0,128}$/gm;
+export const REGEX_CERT_THUMBPRINT = /^[a-zA-Z0-9\-_]{43}$/gm;
+export const REGEX_SUBJECT_DN = /^[a-zA-Z0-9À-ÿ_.,=+@:/'\s\-]{2,512}$/gm;
 export const REGEX_ROLES = /^[a-z0-9\-_/]{2,128}$/gm;
 export const REGEX_URI = /^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+$/gm;
 export const REGEX_URI_SPACE = /^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%\s]+$/m;
@@ -37,8 +40,29 @@ export const TOKEN_ALGS = [
 	'RS256',
 	'RS384',
 	'RS512',
+	'PS256',
+	'ES256',
+	'ES384',
 	'EdDSA'
 ]
+export const JWE_ALGS = [
+	'none',
+	'RSA-OAEP-256',
+	'ECDH-ES'
+]
+export const JWE_ENC = 'A256GCM';
+export const SUBJECT_TYPES = ['public', 'pairwise'];
+export const TOKEN_ENDPOINT_AUTH_METHODS = [
+	'client_secret_basic',
+	'client_secret_post',
+	'client_secret_jwt',
+	'private_key_jwt',
+	'tls_client_auth',
+	'self_signed_tls_client_auth'
+]
+
+export const FLOW_TOKEN_EXCHANGE = 'urn:ietf:params:oauth:grant-type:token-exchange';
+export const FLOW_DEVICE_CODE = 'urn:ietf:params:oauth:grant-type:device_code';
 
 export const FLOWS = [{
 		label: 'authorization_code',
@@ -56,6 +80,14 @@ export const FLOWS = [{
 		label: 'refresh_token',
 		value: false,
 	},
+	{
+		label: 'urn:ietf:params:oauth:grant-type:token-exchange',
+		value: false,
+	},
+	{
+		label: 'urn:ietf:params:oauth:grant-type:device_code',
+		value: false,
+	},
 ];
 
 export const PKCE_CHALLENGES = [{
@@ -69,6 +69,29 @@ export async function getToken(data) {
 	});
 }
 
+export async function getConsentInfo(id) {
+	return await fetch(`/auth/v1/oidc/consent/${id}`, {
+		method: 'GET',
+		headers: HEADERS.json,
+	});
+}
+
+export async function postConsent(data) {
+	return await fetch('/auth/v1/oidc/consent', {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+		body: JSON.stringify(data),
+	});
+}
+
+export async function postDeviceVerify(data) {
+	return await fetch('/auth/v1/oidc/device/verify', {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+		body: JSON.stringify(data),
+	});
+}
+
 export async function getSessionInfo() {
 	return await fetch('/auth/v1/oidc/sessioninfo', {
 		method: 'GET',
@@ -87,10 +110,15 @@ export async function getSessionInfoXsrf(accessToken) {
 }
 
 export async function logout(data) {
-	return await fetch('/auth/v1/oidc/logout', {
+	// the logout params are expected as query params, like for the GET request
+	const params = new URLSearchParams();
+	Object.entries(data)
+		.filter(([, value]) => value)
+		.forEach(([key, value]) => params.append(key, value));
+
+	return await fetch(`/auth/v1/oidc/logout?${params.toString()}`, {
 		method: 'POST',
 		headers: getCsrfHeaders(),
-		body: data,
 	});
 }
 
@@ -100,6 +128,13 @@ export async function getClientLogo(id) {
 	});
 }
 
+export async function getUpstreamProvidersMinimal() {
+	return await fetch('/auth/v1/providers/minimal', {
+		method: 'GET',
+		headers: HEADERS.json,
+	});
+}
+
 export async function getPasswordPolicy() {
 	return await fetch('/auth/v1/password_policy', {
 		method: 'GET',
@@ -253,6 +288,79 @@ export async function webauthnAuthFinish(id, data) {
 	});
 }
 
+export async function getUserConsents(id) {
+	return await fetch(`/auth/v1/users/${id}/consents`, {
+		method: 'GET',
+		headers: HEADERS.json,
+	});
+}
+
+export async function deleteUserConsent(id, clientId) {
+	return await fetch(`/auth/v1/users/${id}/consents/${clientId}`, {
+		method: 'DELETE',
+		headers: getCsrfHeaders(),
+	});
+}
+
+export async function getUserOfflineTokens(id) {
+	return await fetch(`/auth/v1/users/${id}/offline_tokens`, {
+		method: 'GET',
+		headers: HEADERS.json,
+	});
+}
+
+export async function deleteUserOfflineToken(id, tokenId) {
+	return await fetch(`/auth/v1/users/${id}/offline_tokens/${tokenId}`, {
+		method: 'DELETE',
+		headers: getCsrfHeaders(),
+	});
+}
+
+export async function totpAuth(id, data) {
+	return await fetch(`/auth/v1/users/${id}/totp/auth`, {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+		body: JSON.stringify(data),
+	});
+}
+
+export async function totpRegStart(id) {
+	return await fetch(`/auth/v1/users/${id}/totp/register/start`, {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+	});
+}
+
+export async function totpRegFinish(id, data) {
+	return await fetch(`/auth/v1/users/${id}/totp/register/finish`, {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+		body: JSON.stringify(data),
+	});
+}
+
+export async function totpDelete(id) {
+	return await fetch(`/auth/v1/users/${id}/totp`, {
+		method: 'DELETE',
+		headers: getCsrfHeaders(),
+	});
+}
+
+export async function recoveryCodeAuth(id, data) {
+	return await fetch(`/auth/v1/users/${id}/recovery_codes/auth`, {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+		body: JSON.stringify(data),
+	});
+}
+
+export async function recoveryCodesRegenerate(id) {
+	return await fetch(`/auth/v1/users/${id}/recovery_codes`, {
+		method: 'POST',
+		headers: getCsrfHeaders(),
+	});
+}
+
 export async function webauthnDelete(id, name) {
 	return await fetch(`/auth/v1/users/${id}/webauthn/delete/${name}`, {
 		method: 'DELETE',
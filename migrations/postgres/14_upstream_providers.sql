@@ -0,0 +1,43 @@
+create table upstream_providers
+(
+    id                     varchar not null
+        constraint upstream_providers_pk
+            primary key,
+    name                   varchar not null,
+    enabled                bool    not null,
+    issuer                 varchar not null,
+    authorization_endpoint varchar not null,
+    token_endpoint         varchar not null,
+    userinfo_endpoint      varchar not null,
+    client_id              varchar not null,
+    secret                 bytea,
+    secret_kid             varchar,
+    scope                  varchar not null,
+    use_pkce               bool    not null,
+    claim_sub              varchar not null,
+    claim_email            varchar not null,
+    claim_email_verified   varchar,
+    claim_given_name       varchar,
+    claim_family_name      varchar,
+    auto_link              bool    not null,
+    auto_provision         bool    not null
+);
+
+create table upstream_provider_links
+(
+    provider_id  varchar not null
+        constraint upstream_provider_links_upstream_providers_id_fk
+            references upstream_providers
+            on update cascade on delete cascade,
+    upstream_sub varchar not null,
+    user_id      varchar not null
+        constraint upstream_provider_links_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    created_at   bigint  not null,
+    constraint upstream_provider_links_pk
+        primary key (provider_id, upstream_sub)
+);
+
+create index upstream_provider_links_user_id_index
+    on upstream_provider_links (user_id);
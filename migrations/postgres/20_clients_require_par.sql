@@ -0,0 +1,5 @@
+alter table clients
+    add require_par bool default false not null;
+
+alter table clients
+    alter column require_par drop default;
@@ -0,0 +1,5 @@
+alter table clients
+    add jwks varchar;
+
+alter table clients
+    add jwks_uri varchar;
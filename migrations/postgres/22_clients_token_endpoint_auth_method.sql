@@ -0,0 +1,17 @@
+-- the default is kept on purpose, because the anti-lockout rule upserts the 'rauthy' client
+-- without knowing about this column
+alter table clients
+    add token_endpoint_auth_method varchar default 'client_secret_basic' not null;
+
+update clients
+set token_endpoint_auth_method = 'none'
+where confidential = false;
+
+update clients
+set token_endpoint_auth_method = clients_dyn.token_endpoint_auth_method
+from clients_dyn
+where clients.id = clients_dyn.id;
+
+-- same as above
+alter table clients
+    alter column require_par set default false;
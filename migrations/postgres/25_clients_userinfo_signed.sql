@@ -0,0 +1,2 @@
+alter table clients
+    add userinfo_signed_response_alg varchar;
@@ -0,0 +1,22 @@
+alter table clients
+    add require_consent bool default false not null;
+
+alter table refresh_tokens
+    add client_id varchar;
+
+create table user_consents
+(
+    user_id     varchar not null
+        constraint user_consents_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    client_id   varchar not null
+        constraint user_consents_clients_id_fk
+            references clients
+            on update cascade on delete cascade,
+    scopes      varchar not null,
+    created_at  bigint  not null,
+    last_update bigint  not null,
+    constraint user_consents_pk
+        primary key (user_id, client_id)
+);
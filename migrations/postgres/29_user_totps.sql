@@ -0,0 +1,16 @@
+alter table users
+    add totp_enabled bool default false not null;
+
+create table user_totps
+(
+    user_id    varchar not null
+        constraint user_totps_pk
+            primary key
+        constraint user_totps_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    secret     bytea   not null,
+    enc_key_id varchar not null,
+    last_step  bigint,
+    created_at bigint  not null
+);
@@ -0,0 +1,14 @@
+alter table clients
+    add refresh_token_rotation bool default false not null;
+
+alter table refresh_tokens
+    add family_id varchar;
+
+alter table refresh_tokens
+    add session_id varchar;
+
+alter table refresh_tokens
+    add used bool default false not null;
+
+create index refresh_tokens_family_id_index
+    on refresh_tokens (family_id);
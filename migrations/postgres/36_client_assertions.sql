@@ -0,0 +1,11 @@
+create table client_assertions
+(
+    client_id varchar not null,
+    jti       varchar not null,
+    exp       bigint  not null,
+    constraint client_assertions_pk
+        primary key (client_id, jti)
+);
+
+create index client_assertions_exp_index
+    on client_assertions (exp);
@@ -0,0 +1,2 @@
+alter table clients
+    add token_exchange_audiences varchar;
@@ -0,0 +1,12 @@
+create table clients_dyn
+(
+    id                         varchar not null
+        constraint clients_dyn_pk
+            primary key
+        constraint clients_dyn_clients_id_fk
+            references clients
+            on update cascade on delete cascade,
+    created                    bigint  not null,
+    registration_token         varchar not null,
+    token_endpoint_auth_method varchar not null
+);
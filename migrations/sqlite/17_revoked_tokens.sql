@@ -0,0 +1,7 @@
+create table revoked_tokens
+(
+    jti varchar not null
+        constraint revoked_tokens_pk
+            primary key,
+    exp bigint  not null
+);
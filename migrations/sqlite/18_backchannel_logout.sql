@@ -0,0 +1,17 @@
+alter table clients
+    add backchannel_logout_uri varchar;
+
+create table session_clients
+(
+    session_id varchar not null
+        constraint session_clients_sessions_id_fk
+            references sessions
+            on update cascade on delete cascade,
+    client_id  varchar not null
+        constraint session_clients_clients_id_fk
+            references clients
+            on update cascade on delete cascade,
+    created    bigint  not null,
+    constraint session_clients_pk
+        primary key (session_id, client_id)
+);
@@ -0,0 +1,2 @@
+alter table clients
+    add frontchannel_logout_uri varchar;
@@ -0,0 +1,2 @@
+alter table clients
+    add require_par bool default false not null;
@@ -0,0 +1,12 @@
+alter table clients
+    add token_endpoint_auth_method varchar default 'client_secret_basic' not null;
+
+update clients
+set token_endpoint_auth_method = 'none'
+where confidential = false;
+
+update clients
+set token_endpoint_auth_method = (select token_endpoint_auth_method
+                                  from clients_dyn
+                                  where clients_dyn.id = clients.id)
+where id in (select id from clients_dyn);
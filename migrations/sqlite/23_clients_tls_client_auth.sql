@@ -0,0 +1,5 @@
+alter table clients
+    add tls_client_auth_subject_dn varchar;
+
+alter table clients
+    add tls_client_auth_thumbprints varchar;
@@ -0,0 +1,11 @@
+alter table clients
+    add id_token_encrypted_response_alg varchar;
+
+alter table clients
+    add id_token_encrypted_response_enc varchar;
+
+alter table clients
+    add userinfo_encrypted_response_alg varchar;
+
+alter table clients
+    add userinfo_encrypted_response_enc varchar;
@@ -0,0 +1,5 @@
+alter table clients
+    add subject_type varchar;
+
+alter table clients
+    add sector_identifier_uri varchar;
@@ -0,0 +1,2 @@
+alter table sessions
+    add auth_time bigint;
@@ -0,0 +1,12 @@
+create table user_recovery_codes
+(
+    user_id    varchar not null
+        constraint user_recovery_codes_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    id         varchar not null,
+    hash       varchar not null,
+    created_at bigint  not null,
+    constraint user_recovery_codes_pk
+        primary key (user_id, id)
+);
@@ -0,0 +1,15 @@
+alter table refresh_tokens
+    add offline bool default false not null;
+
+alter table refresh_tokens
+    add created_at bigint;
+
+alter table refresh_tokens
+    add last_used bigint;
+
+alter table refresh_tokens
+    add ip varchar;
+
+insert into scopes (id, name, attr_include_access, attr_include_id)
+select 'oQt69pOrTHTMu150WBgo3uEU', 'offline_access', null, null
+where not exists (select 1 from scopes where name = 'offline_access');
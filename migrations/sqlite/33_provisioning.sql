@@ -0,0 +1,59 @@
+create table provisioning_connectors
+(
+    id         varchar not null
+        constraint provisioning_connectors_pk
+            primary key,
+    client_id  varchar not null
+        constraint provisioning_connectors_clients_id_fk
+            references clients
+            on update cascade on delete cascade,
+    typ        varchar not null,
+    endpoint   varchar not null,
+    secret     blob,
+    secret_kid varchar,
+    enabled    bool    not null,
+    created_at bigint  not null
+);
+
+create index provisioning_connectors_client_id_index
+    on provisioning_connectors (client_id);
+
+create table provisioning_deliveries
+(
+    id           varchar not null
+        constraint provisioning_deliveries_pk
+            primary key,
+    connector_id varchar not null
+        constraint provisioning_deliveries_provisioning_connectors_id_fk
+            references provisioning_connectors
+            on update cascade on delete cascade,
+    resource_typ varchar not null,
+    resource_id  varchar not null,
+    action       varchar not null,
+    payload      varchar not null,
+    status       varchar not null,
+    attempts     bigint  not null,
+    next_attempt bigint  not null,
+    last_error   varchar,
+    created_at   bigint  not null,
+    delivered_at bigint
+);
+
+create index provisioning_deliveries_connector_id_index
+    on provisioning_deliveries (connector_id);
+
+create index provisioning_deliveries_status_index
+    on provisioning_deliveries (status);
+
+create table provisioning_resources
+(
+    connector_id varchar not null
+        constraint provisioning_resources_provisioning_connectors_id_fk
+            references provisioning_connectors
+            on update cascade on delete cascade,
+    resource_typ varchar not null,
+    resource_id  varchar not null,
+    remote_id    varchar not null,
+    constraint provisioning_resources_pk
+        primary key (connector_id, resource_typ, resource_id)
+);
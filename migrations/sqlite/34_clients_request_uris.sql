@@ -0,0 +1,2 @@
+alter table clients
+    add request_uris varchar;
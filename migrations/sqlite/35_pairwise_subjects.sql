@@ -0,0 +1,13 @@
+create table pairwise_subjects
+(
+    sub     varchar not null
+        constraint pairwise_subjects_pk
+            primary key,
+    user_id varchar not null
+        constraint pairwise_subjects_users_id_fk
+            references users
+            on update cascade on delete cascade
+);
+
+create index pairwise_subjects_user_id_index
+    on pairwise_subjects (user_id);
@@ -12,12 +12,24 @@ pub const HEADER_DPOP_NONCE: &str = "DPoP-Nonce";
 pub const HEADER_HTML: (&str, &str) = ("content-type", "text/html;charset=utf-8");
 pub const HEADER_RETRY_NOT_BEFORE: &str = "x-retry-not-before";
 pub const APPLICATION_JSON: &str = "application/json";
+pub const APPLICATION_JWT: &str = "application/jwt";
+pub const APPLICATION_SCIM_JSON: &str = "application/scim+json";
 pub const TEXT_TURTLE: &str = "text/turtle";
 
 pub const TOKEN_API_KEY: &str = "API-Key";
 pub const TOKEN_BEARER: &str = "Bearer";
 pub const TOKEN_DPOP: &str = "DPoP";
 pub const TOKEN_DPOP_NONCE: &str = "DPoP-nonce";
+pub const TOKEN_INTROSPECTION_JWT: &str = "application/token-introspection+jwt";
+pub const TOKEN_INTROSPECTION_JWT_TYP: &str = "token-introspection+jwt";
+pub const TOKEN_LOGOUT_JWT_TYP: &str = "logout+jwt";
+pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+pub const TOKEN_TYPE_ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+pub const GRANT_TYPE_TOKEN_EXCHANGE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+pub const GRANT_TYPE_DEVICE_CODE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+pub const PAR_REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+pub const CLIENT_ASSERTION_TYPE_JWT_BEARER: &str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
 pub const COOKIE_SESSION: &str = "rauthy-session";
 pub const COOKIE_MFA: &str = "rauthy-mfa";
 pub const COOKIE_LOCALE: &str = "locale";
@@ -33,16 +45,23 @@ pub const EVENTS_LATEST_LIMIT: u16 = 100;
 
 pub const CACHE_NAME_12HR: &str = "12hr";
 pub const CACHE_NAME_AUTH_CODES: &str = "auth-codes";
+pub const CACHE_NAME_CONSENT: &str = "consent";
+pub const CACHE_NAME_DEVICE_CODES: &str = "device-codes";
 pub const CACHE_NAME_DPOP_NONCES: &str = "dpop-nonces";
 pub const CACHE_NAME_EPHEMERAL_CLIENTS: &str = "ephemeral-clients";
 pub const CACHE_NAME_LOGIN_DELAY: &str = "login-dly";
+pub const CACHE_NAME_PAR: &str = "par";
 pub const CACHE_NAME_SESSIONS: &str = "sessions";
 pub const CACHE_NAME_POW: &str = "pow";
+pub const CACHE_NAME_REVOKED_TOKENS: &str = "revoked-tokens";
+pub const CACHE_NAME_UPSTREAM_AUTH: &str = "upstream-auth";
 pub const CACHE_NAME_WEBAUTHN: &str = "webauthn";
 pub const CACHE_NAME_WEBAUTHN_DATA: &str = "webauthn-data";
 
 pub const IDX_APP_VERSION: &str = "rauthy_app_version";
 pub const IDX_CLIENTS: &str = "clients_";
+pub const IDX_CLIENT_JWKS: &str = "client_jwks_";
+pub const IDX_CLIENT_JWKS_REFRESH: &str = "client_jwks_refresh_";
 pub const IDX_CLIENT_LOGO: &str = "client_logo_";
 pub const IDX_GROUPS: &str = "groups_";
 pub const IDX_JWK_KID: &str = "jwk_kid_";
@@ -51,11 +70,16 @@ pub const IDX_JWKS: &str = "jkws_";
 pub const IDX_LOGIN_TIME: &str = "login_time_";
 pub const IDX_MFA_APP: &str = "mfa_app_";
 pub const IDX_MFA_LOGIN_REQ: &str = "mfa_login_req_";
+pub const IDX_PAIRWISE_SUBJECT: &str = "pairwise_sub_";
 pub const IDX_PASSWORD_RULES: &str = "password_rules_";
+pub const IDX_PROVISIONING_CONNECTORS: &str = "provisioning_connectors_";
+pub const IDX_REVOKED_TOKEN: &str = "revoked_";
+pub const IDX_NOT_REVOKED_TOKEN: &str = "not_revoked_";
 pub const IDX_ROLES: &str = "roles_";
 pub const IDX_SCOPES: &str = "scopes_";
 pub const IDX_SESSION: &str = "session_";
 pub const IDX_SESSIONS: &str = "sessions";
+pub const IDX_UPSTREAM_PROVIDERS: &str = "upstream_providers_";
 pub const IDX_USERS: &str = "users_";
 pub const IDX_USER_ATTR_CONFIG: &str = "user_attrs_";
 pub const IDX_WEBAUTHN: &str = "webauthn_";
@@ -89,17 +113,26 @@ lazy_static! {
     pub static ref RE_CHALLENGE: Regex = Regex::new(r"^(plain|S256)$").unwrap();
     pub static ref RE_CLIENT_ID_EPHEMERAL: Regex = Regex::new(r"^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$").unwrap();
     pub static ref RE_CLIENT_NAME: Regex = Regex::new(r"^[a-zA-Z0-9À-ÿ-\s]{2,128}$").unwrap();
+    pub static ref RE_CERT_THUMBPRINT: Regex = Regex::new(r"^[a-zA-Z0-9-_]{43}$").unwrap();
     pub static ref RE_CODE_CHALLENGE: Regex = Regex::new(r"^[a-zA-Z0-9-\._~]{43,128}$").unwrap();
     pub static ref RE_CODE_VERIFIER: Regex = Regex::new(r"^[a-zA-Z0-9-\._~+/=]+$").unwrap();
-    pub static ref RE_FLOWS: Regex = Regex::new(r"^(authorization_code|client_credentials|password|refresh_token)$").unwrap();
-    pub static ref RE_GRANT_TYPES: Regex = Regex::new(r"^(authorization_code|client_credentials|password|refresh_token)$").unwrap();
+    pub static ref RE_FLOWS: Regex = Regex::new(r"^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$").unwrap();
+    pub static ref RE_GRANT_TYPES: Regex = Regex::new(r"^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$").unwrap();
     pub static ref RE_LOWERCASE: Regex = Regex::new(r"^[a-z0-9-_/]{2,128}$").unwrap();
     pub static ref RE_LOWERCASE_SPACE: Regex = Regex::new(r"^[a-z0-9-_/\s]{2,128}$").unwrap();
+    pub static ref RE_JWE_ALG: Regex = Regex::new(r"^(RSA-OAEP-256|ECDH-ES)$").unwrap();
+    pub static ref RE_JWE_ENC: Regex = Regex::new(r"^(A256GCM)$").unwrap();
     pub static ref RE_GROUPS: Regex = Regex::new(r"^[a-z0-9-_/,]{2,32}$").unwrap();
+    pub static ref RE_SUBJECT_TYPE: Regex = Regex::new(r"^(public|pairwise)$").unwrap();
+    pub static ref RE_SUBJECT_DN: Regex = Regex::new(r"^[a-zA-Z0-9À-ÿ_.,=+@:/'\s-]{2,512}$").unwrap();
+    pub static ref RE_USER_CODE: Regex = Regex::new(r"^[BCDFGHJKLMNPQRSTVWXZbcdfghjklmnpqrstvwxz-]{4,32}$").unwrap();
     pub static ref RE_MFA_CODE: Regex = Regex::new(r"^[a-zA-Z0-9]{48}$").unwrap();
     pub static ref RE_URI: Regex = Regex::new(r"^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+$").unwrap();
     pub static ref RE_USER_NAME: Regex = Regex::new(r"^[a-zA-Z0-9À-ÿ-\s]{2,32}$").unwrap();
+    pub static ref RE_TOKEN_ENDPOINT_AUTH_METHOD: Regex = Regex::new(r"^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt|tls_client_auth|self_signed_tls_client_auth|none)$").unwrap();
     pub static ref RE_TOKEN_68: Regex = Regex::new(r"^[a-zA-Z0-9-._~+/]+=*$").unwrap();
+    pub static ref RE_TOTP: Regex = Regex::new(r"^[0-9]{6}$").unwrap();
+    pub static ref RE_RECOVERY_CODE: Regex = Regex::new(r"^[a-zA-Z0-9]{8}-[a-zA-Z0-9]{16}$").unwrap();
 
     pub static ref PUB_URL: String = env::var("PUB_URL").expect("PUB_URL env var is not set");
     pub static ref PUB_URL_WITH_SCHEME: String = {
@@ -160,6 +193,39 @@ lazy_static! {
             .parse::<u64>()
             .expect("EPHEMERAL_CLIENTS_CACHE_LIFETIME cannot be parsed to u64 - bad format");
 
+    pub static ref ENABLE_DYN_CLIENT_REG: bool = env::var("ENABLE_DYN_CLIENT_REG")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("ENABLE_DYN_CLIENT_REG cannot be parsed to bool - bad format");
+    pub static ref DYN_CLIENT_REG_TOKEN: Option<String> = env::var("DYN_CLIENT_REG_TOKEN")
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty());
+    pub static ref DYN_CLIENT_REG_ALLOWED_FLOWS: Vec<String> = env::var("DYN_CLIENT_REG_ALLOWED_FLOWS")
+            .unwrap_or_else(|_| String::from("authorization_code refresh_token"))
+            .split(' ')
+            .filter(|flow| !flow.is_empty())
+            .map(|flow| {
+                let flow = flow.trim();
+                if !RE_FLOWS.is_match(flow) {
+                    panic!("unknown DYN_CLIENT_REG_ALLOWED_FLOWS: {}", flow)
+                }
+                flow.to_string()
+            })
+            .collect();
+    pub static ref DYN_CLIENT_REG_ALLOWED_SCOPES: Vec<String> = env::var("DYN_CLIENT_REG_ALLOWED_SCOPES")
+            .unwrap_or_else(|_| String::from("openid profile email"))
+            .split(' ')
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| scope.to_string())
+            .collect();
+    pub static ref DYN_CLIENT_REG_REDIRECT_URIS: Vec<String> = env::var("DYN_CLIENT_REG_REDIRECT_URIS")
+            .unwrap_or_else(|_| String::from("https://* http://localhost:* http://127.0.0.1:*"))
+            .split(' ')
+            .filter(|uri| !uri.is_empty())
+            .map(|uri| uri.to_string())
+            .collect();
+
     pub static ref PROXY_MODE: bool = env::var("PROXY_MODE")
         .unwrap_or_else(|_| String::from("false"))
         .parse::<bool>()
@@ -199,6 +265,12 @@ lazy_static! {
         lt * 3600
     };
 
+    // Secret for the HMAC of pairwise subject identifiers, which are disabled without it
+    pub static ref PAIRWISE_SUB_SECRET: Option<String> = env::var("PAIRWISE_SUB_SECRET")
+        .ok()
+        .map(|secret| secret.trim().to_string())
+        .filter(|secret| !secret.is_empty());
+
     pub static ref ADMIN_FORCE_MFA: bool = env::var("ADMIN_FORCE_MFA")
         .unwrap_or_else(|_| String::from("true"))
         .parse::<bool>()
@@ -227,6 +299,39 @@ lazy_static! {
         .parse::<bool>()
         .expect("SESSION_VALIDATE_IP cannot be parsed to bool - bad format");
 
+    pub static ref DEVICE_GRANT_CODE_LIFETIME: u64 = env::var("DEVICE_GRANT_CODE_LIFETIME")
+        .unwrap_or_else(|_| String::from("300"))
+        .parse::<u64>()
+        .expect("DEVICE_GRANT_CODE_LIFETIME cannot be parsed to u64 - bad format");
+    pub static ref DEVICE_GRANT_USER_CODE_LENGTH: u8 = env::var("DEVICE_GRANT_USER_CODE_LENGTH")
+        .unwrap_or_else(|_| String::from("8"))
+        .parse::<u8>()
+        .expect("DEVICE_GRANT_USER_CODE_LENGTH cannot be parsed to u8 - bad format");
+    pub static ref DEVICE_GRANT_POLL_INTERVAL: u32 = env::var("DEVICE_GRANT_POLL_INTERVAL")
+        .unwrap_or_else(|_| String::from("5"))
+        .parse::<u32>()
+        .expect("DEVICE_GRANT_POLL_INTERVAL cannot be parsed to u32 - bad format");
+
+    pub static ref PAR_REQUEST_URI_LIFETIME: u64 = env::var("PAR_REQUEST_URI_LIFETIME")
+        .unwrap_or_else(|_| String::from("300"))
+        .parse::<u64>()
+        .expect("PAR_REQUEST_URI_LIFETIME cannot be parsed to u64 - bad format");
+
+    pub static ref CLIENT_ASSERTION_MAX_LIFETIME: u64 = env::var("CLIENT_ASSERTION_MAX_LIFETIME")
+        .unwrap_or_else(|_| String::from("300"))
+        .parse::<u64>()
+        .expect("CLIENT_ASSERTION_MAX_LIFETIME cannot be parsed to u64 - bad format");
+
+    pub static ref TLS_CLIENT_AUTH: bool = env::var("TLS_CLIENT_AUTH")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("TLS_CLIENT_AUTH cannot be parsed to bool - bad format");
+
+    pub static ref UPSTREAM_AUTH_EXP: u64 = env::var("UPSTREAM_AUTH_EXP")
+        .unwrap_or_else(|_| String::from("300"))
+        .parse::<u64>()
+        .expect("UPSTREAM_AUTH_EXP cannot be parsed to u64 - bad format");
+
     pub static ref SSE_KEEP_ALIVE: u16 = env::var("SSE_KEEP_ALIVE")
         .unwrap_or_else(|_| String::from("30"))
         .parse::<u16>()
@@ -250,6 +355,19 @@ lazy_static! {
         .trim()
         .to_string();
 
+    pub static ref PROVISIONING_INTERVAL: u64 = env::var("PROVISIONING_INTERVAL")
+        .unwrap_or_else(|_| String::from("10"))
+        .parse::<u64>()
+        .expect("PROVISIONING_INTERVAL cannot be parsed to u64 - bad format");
+    pub static ref PROVISIONING_MAX_ATTEMPTS: i64 = env::var("PROVISIONING_MAX_ATTEMPTS")
+        .unwrap_or_else(|_| String::from("10"))
+        .parse::<i64>()
+        .expect("PROVISIONING_MAX_ATTEMPTS cannot be parsed to i64 - bad format");
+    pub static ref SCIM_ALLOW_PASSWORD: bool = env::var("SCIM_ALLOW_PASSWORD")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("SCIM_ALLOW_PASSWORD cannot be parsed to bool - bad format");
+
     pub static ref SWAGGER_UI_INTERNAL: bool = env::var("SESSION_VALIDATE_IP")
         .unwrap_or_else(|_| String::from("true"))
         .parse::<bool>()
@@ -259,6 +377,13 @@ lazy_static! {
         .parse::<bool>()
         .expect("SWAGGER_UI_EXTERNAL cannot be parsed to bool - bad format");
 
+    pub static ref TOTP_ISSUER: String = env::var("TOTP_ISSUER")
+        .unwrap_or_else(|_| String::from("Rauthy"));
+    pub static ref TOTP_SKEW: u8 = env::var("TOTP_SKEW")
+        .unwrap_or_else(|_| String::from("1"))
+        .parse::<u8>()
+        .expect("TOTP_SKEW cannot be parsed to u8 - bad format");
+
     pub static ref WEBAUTHN_REQ_EXP: u64 = env::var("WEBAUTHN_REQ_EXP")
         .unwrap_or_else(|_| String::from("60"))
         .parse::<u64>()
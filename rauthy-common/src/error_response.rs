@@ -37,6 +37,9 @@ pub enum ErrorResponseType {
     MfaRequired,
     NoSession,
     NotFound,
+    // RFC 6749 style error with the `error` code, which is needed as-is by some OAuth flows like
+    // the device authorization grant
+    OAuth(String),
     PasswordExpired,
     PasswordRefresh,
     SessionExpired,
@@ -83,9 +86,9 @@ impl ErrorResponse {
 impl ResponseError for ErrorResponse {
     fn status_code(&self) -> StatusCode {
         match self.error {
-            ErrorResponseType::BadRequest | ErrorResponseType::UseDpopNonce(_) => {
-                StatusCode::BAD_REQUEST
-            }
+            ErrorResponseType::BadRequest
+            | ErrorResponseType::OAuth(_)
+            | ErrorResponseType::UseDpopNonce(_) => StatusCode::BAD_REQUEST,
             ErrorResponseType::Forbidden => StatusCode::FORBIDDEN,
             ErrorResponseType::MfaRequired => StatusCode::NOT_ACCEPTABLE,
             ErrorResponseType::NotFound => StatusCode::NOT_FOUND,
@@ -130,6 +133,16 @@ impl ResponseError for ErrorResponse {
                 }
             }
 
+            ErrorResponseType::OAuth(error) => HttpResponseBuilder::new(status)
+                .content_type(APPLICATION_JSON)
+                .body(
+                    serde_json::json!({
+                        "error": error,
+                        "error_description": self.message,
+                    })
+                    .to_string(),
+                ),
+
             ErrorResponseType::UseDpopNonce((header_origin, value)) => {
                 if let Some(origin) = header_origin {
                     HttpResponseBuilder::new(status)
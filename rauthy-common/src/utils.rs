@@ -21,6 +21,17 @@ pub fn build_csp_header(nonce: &str) -> (&str, String) {
     ("content-security-policy", value)
 }
 
+/// Same as [build_csp_header], but additionally allows loading the given origins inside iframes.
+pub fn build_csp_header_frame_src(nonce: &str, frame_src: &[String]) -> (&'static str, String) {
+    let value = format!(
+        "default-src 'self'; script-src 'strict-dynamic' 'nonce-{}'; style-src 'self' 'unsafe-inline'; \
+        frame-src 'self' {}; frame-ancestors 'none'; object-src 'none'; img-src 'self' data:;",
+        nonce,
+        frame_src.join(" "),
+    );
+    ("content-security-policy", value)
+}
+
 // Decrypts a `&Vec<u8>` which was [encrypted](encrypt) before with the same key.
 pub fn decrypt(ciphertext: &[u8], key: &[u8]) -> Result<Vec<u8>, ErrorResponse> {
     use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
@@ -5,8 +5,13 @@ use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_models::entity::clients::Client;
 use rauthy_models::entity::colors::ColorEntity;
-use rauthy_models::request::{ColorsRequest, NewClientRequest, UpdateClientRequest};
-use rauthy_models::response::ClientResponse;
+use rauthy_models::entity::provisioning::{ProvisioningConnector, ProvisioningDelivery};
+use rauthy_models::request::{
+    ColorsRequest, NewClientRequest, ProvisioningConnectorRequest, UpdateClientRequest,
+};
+use rauthy_models::response::{
+    ClientResponse, ProvisioningConnectorResponse, ProvisioningDeliveryResponse,
+};
 use rauthy_service::client;
 
 /// Returns all existing OIDC clients with all their information, except for the client secrets.
@@ -360,6 +365,206 @@ pub async fn put_generate_client_secret(
         .map(|r| HttpResponse::Ok().json(r))
 }
 
+/// Returns the outbound provisioning connectors for this client
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/clients/{id}/provisioning",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = [ProvisioningConnectorResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/clients/{id}/provisioning")]
+pub async fn get_client_provisioning(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Read)?;
+
+    ProvisioningConnector::find_for_client(&data, id.as_str())
+        .await
+        .map(|connectors| {
+            let res = connectors
+                .into_iter()
+                .map(ProvisioningConnectorResponse::from)
+                .collect::<Vec<ProvisioningConnectorResponse>>();
+            HttpResponse::Ok().json(res)
+        })
+}
+
+/// Adds an outbound provisioning connector to this client
+///
+/// Every change of a user, group or role will be queued and pushed to the downstream app,
+/// either via SCIM 2.0 or as a signed JSON webhook.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/clients/{id}/provisioning",
+    tag = "clients",
+    request_body = ProvisioningConnectorRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProvisioningConnectorResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/clients/{id}/provisioning")]
+pub async fn post_client_provisioning(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: actix_web_validator::Json<ProvisioningConnectorRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Create)?;
+
+    ProvisioningConnector::create(&data, id.into_inner(), payload.into_inner())
+        .await
+        .map(|c| HttpResponse::Ok().json(ProvisioningConnectorResponse::from(c)))
+}
+
+/// Modifies an outbound provisioning connector
+///
+/// If `secret` is not given, the current secret will be kept. An empty string removes the secret.
+/// Changing the `typ` or `endpoint` forgets about all resources, which have been provisioned
+/// downstream so far.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/clients/{id}/provisioning/{connector_id}",
+    tag = "clients",
+    request_body = ProvisioningConnectorRequest,
+    responses(
+        (status = 200, description = "Ok", body = ProvisioningConnectorResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/clients/{id}/provisioning/{connector_id}")]
+pub async fn put_client_provisioning(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    payload: actix_web_validator::Json<ProvisioningConnectorRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+
+    let (id, connector_id) = path.into_inner();
+    ProvisioningConnector::update(&data, &id, &connector_id, payload.into_inner())
+        .await
+        .map(|c| HttpResponse::Ok().json(ProvisioningConnectorResponse::from(c)))
+}
+
+/// Deletes an outbound provisioning connector
+///
+/// All deliveries, which are still queued, will be deleted as well.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/clients/{id}/provisioning/{connector_id}",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/clients/{id}/provisioning/{connector_id}")]
+pub async fn delete_client_provisioning(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Delete)?;
+
+    let (id, connector_id) = path.into_inner();
+    ProvisioningConnector::delete(&data, &id, &connector_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the latest 500 deliveries of an outbound provisioning connector, newest first
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/clients/{id}/provisioning/{connector_id}/deliveries",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = [ProvisioningDeliveryResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/clients/{id}/provisioning/{connector_id}/deliveries")]
+pub async fn get_client_provisioning_deliveries(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Read)?;
+
+    let (id, connector_id) = path.into_inner();
+    let connector = ProvisioningConnector::find(&data, &id, &connector_id).await?;
+    ProvisioningDelivery::find_for_connector(&data, &connector.id)
+        .await
+        .map(|deliveries| {
+            let res = deliveries
+                .into_iter()
+                .map(ProvisioningDeliveryResponse::from)
+                .collect::<Vec<ProvisioningDeliveryResponse>>();
+            HttpResponse::Ok().json(res)
+        })
+}
+
+/// Re-queues a failed delivery of an outbound provisioning connector
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/clients/{id}/provisioning/{connector_id}/deliveries/{delivery_id}/retry",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = ProvisioningDeliveryResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/clients/{id}/provisioning/{connector_id}/deliveries/{delivery_id}/retry")]
+pub async fn post_client_provisioning_delivery_retry(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+
+    let (id, connector_id, delivery_id) = path.into_inner();
+    let connector = ProvisioningConnector::find(&data, &id, &connector_id).await?;
+    ProvisioningDelivery::retry(&data, &connector.id, &delivery_id)
+        .await
+        .map(|d| HttpResponse::Ok().json(ProvisioningDeliveryResponse::from(d)))
+}
+
 /// Deletes an OIDC client
 ///
 /// **Permissions**
@@ -11,7 +11,7 @@ use rauthy_models::entity::api_keys::ApiKey;
 use rauthy_models::entity::principal::Principal;
 use rauthy_models::entity::sessions::Session;
 use rauthy_models::entity::webauthn::WebauthnCookie;
-use rauthy_models::response::WebauthnLoginResponse;
+use rauthy_models::response::{TotpLoginResponse, WebauthnLoginResponse};
 use rauthy_models::AuthStep;
 use rust_embed::RustEmbed;
 use tracing::error;
@@ -25,7 +25,9 @@ pub mod groups;
 pub mod middleware;
 pub mod oidc;
 pub mod openapi;
+pub mod providers;
 pub mod roles;
+pub mod scim;
 pub mod scopes;
 pub mod sessions;
 pub mod users;
@@ -86,6 +88,24 @@ pub async fn map_auth_step(
 
             Ok((resp, res.has_password_been_hashed))
         }
+
+        AuthStep::AwaitTotp(res) => {
+            let body = TotpLoginResponse {
+                code: res.code,
+                user_id: res.user_id,
+                exp: res.exp,
+                totp: true,
+            };
+            let mut resp = HttpResponse::Ok()
+                .insert_header(res.header_csrf)
+                .json(&body);
+
+            if let Some((name, value)) = res.header_origin {
+                resp.headers_mut().insert(name, value);
+            }
+
+            Ok((resp, res.has_password_been_hashed))
+        }
     }
 }
 
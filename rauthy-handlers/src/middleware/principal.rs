@@ -4,7 +4,7 @@ use actix_web::{
     http, web, Error, HttpMessage,
 };
 use futures::future::LocalBoxFuture;
-use rauthy_common::constants::{COOKIE_SESSION, SESSION_VALIDATE_IP, TOKEN_API_KEY};
+use rauthy_common::constants::{COOKIE_SESSION, SESSION_VALIDATE_IP, TOKEN_API_KEY, TOKEN_BEARER};
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{ApiKey, ApiKeyEntity};
@@ -98,11 +98,11 @@ async fn get_api_key_from_headers(
             "Malformed 'Authorization' header".to_string(),
         ));
     };
-    let api_key_value = if k.ne(TOKEN_API_KEY) || k.is_empty() {
-        None
-    } else {
-        Some(v)
-    };
+    // SCIM clients usually only support static `Bearer` tokens, which is why the API Key is
+    // accepted in this format on the SCIM endpoints as well.
+    let is_api_key =
+        k == TOKEN_API_KEY || (k == TOKEN_BEARER && req.path().starts_with("/auth/v1/scim/v2/"));
+    let api_key_value = if is_api_key { Some(v) } else { None };
 
     if let Some(api_key_value) = api_key_value {
         ApiKeyEntity::api_key_from_token_validated(data, api_key_value)
@@ -4,45 +4,68 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use actix_web::cookie::time::OffsetDateTime;
 use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
 use actix_web::http::{header, StatusCode};
-use actix_web::{get, post, web, HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError};
-use tracing::debug;
+use actix_web::{
+    delete, get, post, put, web, Either, HttpRequest, HttpResponse, HttpResponseBuilder,
+    ResponseError,
+};
+use tracing::{debug, error};
 
-use rauthy_common::constants::{APPLICATION_JSON, COOKIE_MFA, HEADER_HTML, SESSION_LIFETIME};
-use rauthy_common::error_response::ErrorResponse;
-use rauthy_common::utils::build_csp_header;
+use rauthy_common::constants::{
+    APPLICATION_JSON, APPLICATION_JWT, COOKIE_MFA, ENABLE_DYN_CLIENT_REG, HEADER_HTML,
+    PAR_REQUEST_URI_PREFIX, SESSION_LIFETIME, TOKEN_INTROSPECTION_JWT,
+};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::{build_csp_header, build_csp_header_frame_src};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::jwk::{JWKSPublicKey, JwkKeyPair, JWKS};
+use rauthy_models::entity::pushed_auth_requests::PushedAuthRequest;
+use rauthy_models::entity::session_clients::SessionClient;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_consents::ConsentReq;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::webauthn::WebauthnCookie;
 use rauthy_models::entity::well_known::WellKnown;
 use rauthy_models::language::Language;
 use rauthy_models::request::{
-    AuthRequest, LoginRefreshRequest, LoginRequest, LogoutRequest, TokenRequest,
-    TokenValidationRequest,
+    AuthRequest, AuthRequestRef, ConsentParams, ConsentRequest, DeviceRequest, DeviceVerifyRequest,
+    DynClientRequest, IntrospectionRequest, LoginRefreshRequest, LoginRequest, LogoutRequest,
+    ParRequest, RevokeRequest, TokenRequest, TokenValidationRequest, UpstreamAuthRequest,
+    UpstreamCallbackRequest,
+};
+use rauthy_models::response::{
+    ConsentInfoResponse, JWKSCerts, JWKSPublicKeyCerts, LogoutResponse, SessionInfoResponse,
 };
-use rauthy_models::response::{JWKSCerts, JWKSPublicKeyCerts, SessionInfoResponse};
 use rauthy_models::templates::{
-    AuthorizeHtml, CallbackHtml, Error1Html, ErrorHtml, FrontendAction,
+    AuthorizeHtml, CallbackHtml, ConsentHtml, DeviceHtml, Error1Html, Error2Html, Error3Html,
+    ErrorHtml, FrontendAction,
 };
-use rauthy_models::JwtCommonClaims;
-use rauthy_service::auth;
+use rauthy_models::{AuthStep, JwtCommonClaims};
+use rauthy_service::{auth, client, consent, device, par, upstream};
 
 use crate::{map_auth_step, real_ip_from_req, ReqPrincipal};
 
 /// OIDC Authorization HTML
 ///
 /// Starts the authorization_code flow. Log in with username / password.<br>
-/// If one does not exist, a new session will be opened with the 'Init' state and set's a cookie.
+/// If one does not exist, a new session will be opened with the 'Init' state and set's a cookie.<br>
+/// Instead of the full request, only the `client_id` and a `request_uri` from a pushed
+/// authorization request (RFC 9126) can be given.<br>
+/// A signed request object (RFC 9101) can be given via `request` or `request_uri`. After its
+/// validation, the user agent will be redirected to this endpoint again with a `request_uri`,
+/// which references the validated request.<br>
+/// With `prompt=none`, the user agent will always be redirected back to the client, either with
+/// a code for an existing session or with a `login_required` / `interaction_required` /
+/// `consent_required` error.
 #[utoipa::path(
     get,
     path = "/oidc/authorize",
     tag = "oidc",
-    params(AuthRequest),
+    params(AuthRequest, AuthRequestRef),
     responses(
         (status = 200, description = "If the params match the allowed settings, returns the pre-rendered HTML",),
+        (status = 302, description = "Redirect after the validation of a signed request object or for 'prompt=none'",),
         (status = 400, description = "If any params do not match the backend config", body = ErrorResponse),
     ),
 )]
@@ -50,14 +73,56 @@ use crate::{map_auth_step, real_ip_from_req, ReqPrincipal};
 pub async fn get_authorize(
     data: web::Data<AppState>,
     req: HttpRequest,
-    req_data: actix_web_validator::Query<AuthRequest>,
+    req_data: Either<
+        actix_web_validator::Query<AuthRequestRef>,
+        actix_web_validator::Query<AuthRequest>,
+    >,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
-    let colors = ColorEntity::find(&data, &req_data.client_id)
+    let client_id = match &req_data {
+        Either::Left(params) => &params.client_id,
+        Either::Right(params) => &params.client_id,
+    };
+    let colors = ColorEntity::find(&data, client_id)
         .await
         .unwrap_or_default();
     let lang = Language::try_from(&req).unwrap_or_default();
 
+    // with a pushed authorization request, only the already validated params will be used
+    let res = match req_data {
+        Either::Left(params) => match params.request_uri.as_deref() {
+            Some(uri) if uri.starts_with(PAR_REQUEST_URI_PREFIX) => {
+                PushedAuthRequest::find_by_request_uri(&data, &params.client_id, uri)
+                    .await
+                    .map(|par| (AuthRequest::from(par.clone()), Some(par)))
+            }
+            _ => {
+                // a signed request object will be validated only once and then handled in the
+                // same way as a pushed request from here on
+                return match par::push_request_object(&data, &req, params.into_inner()).await {
+                    Ok(par) => Ok(HttpResponse::Found()
+                        .insert_header((header::LOCATION, par.authorize_uri(&data.issuer)))
+                        .finish()),
+                    Err(err) => {
+                        let status = err.status_code();
+                        let (body, nonce) =
+                            Error1Html::build(&colors, &lang, status, Some(err.message));
+                        Ok(ErrorHtml::response(body, nonce, status))
+                    }
+                };
+            }
+        },
+        Either::Right(params) => Ok((params.into_inner(), None)),
+    };
+    let (req_data, par) = match res {
+        Ok(res) => res,
+        Err(err) => {
+            let status = err.status_code();
+            let (body, nonce) = Error1Html::build(&colors, &lang, status, Some(err.message));
+            return Ok(ErrorHtml::response(body, nonce, status));
+        }
+    };
+
     let (client, origin_header) = match auth::validate_auth_req_param(
         &data,
         &req,
@@ -67,7 +132,10 @@ pub async fn get_authorize(
         &req_data.code_challenge_method,
     )
     .await
-    {
+    .and_then(|(client, origin_header)| {
+        client.validate_par(par.is_some())?;
+        Ok((client, origin_header))
+    }) {
         Ok(res) => res,
         Err(err) => {
             let status = err.status_code();
@@ -76,7 +144,61 @@ pub async fn get_authorize(
         }
     };
 
-    if principal.validate_session_auth().is_ok() {
+    // an existing session only counts, if the authentication is not older than `max_age`
+    let session = principal
+        .validate_session_auth()
+        .ok()
+        .filter(|session| !session.is_auth_expired(req_data.max_age));
+
+    if req_data.has_prompt("none") {
+        let loc = if req_data.prompt.as_deref().map(str::trim) != Some("none") {
+            req_data.error_redirect_uri(
+                "invalid_request",
+                "'prompt=none' must not be combined with other values",
+            )
+        } else if let Some(session) = session {
+            match auth::authorize_refresh(
+                &data,
+                session,
+                client,
+                origin_header,
+                LoginRefreshRequest::from(req_data.clone()),
+                true,
+            )
+            .await
+            {
+                Ok(AuthStep::LoggedIn(step)) => {
+                    // a pushed request can only be used once to issue a code
+                    if let Some(par) = par {
+                        par.delete(&data).await?;
+                    }
+                    step.header_loc.1.to_str().unwrap_or_default().to_string()
+                }
+                Ok(_) => req_data
+                    .error_redirect_uri("interaction_required", "The user must verify the passkey"),
+                Err(err) => match err.error {
+                    ErrorResponseType::OAuth(error) => {
+                        req_data.error_redirect_uri(&error, &err.message)
+                    }
+                    ErrorResponseType::MfaRequired => {
+                        req_data.error_redirect_uri("interaction_required", &err.message)
+                    }
+                    _ => req_data.error_redirect_uri("login_required", &err.message),
+                },
+            }
+        } else {
+            req_data.error_redirect_uri("login_required", "No valid session")
+        };
+
+        return Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, loc))
+            .finish());
+    }
+
+    // Rauthy has no account chooser, the login form is the place to switch accounts
+    let force_login = req_data.has_prompt("login") || req_data.has_prompt("select_account");
+
+    if session.is_some() && !force_login {
         let csrf = principal.get_session_csrf_token()?;
         let (body, nonce) =
             AuthorizeHtml::build(&client.name, csrf, FrontendAction::Refresh, &colors, &lang);
@@ -115,6 +237,10 @@ pub async fn get_authorize(
         }
     }
 
+    if let (FrontendAction::None, Some(hint)) = (&action, req_data.login_hint) {
+        action = FrontendAction::LoginHint(hint);
+    }
+
     // if let Some(mfa_cookie) = req.cookie(COOKIE_MFA) {
     //     action = FrontendAction::MfaLogin(mfa_cookie.value().to_string())
     // }
@@ -209,6 +335,19 @@ pub async fn post_authorize_refresh(
 ) -> Result<HttpResponse, ErrorResponse> {
     let session = principal.validate_session_auth()?;
 
+    // with a pushed authorization request, only the already validated params will be used
+    let mut req_data = req_data.into_inner();
+    let par = match req_data.request_uri.take() {
+        Some(request_uri) => {
+            let par =
+                PushedAuthRequest::find_by_request_uri(&data, &req_data.client_id, &request_uri)
+                    .await?;
+            par.apply_to_login_refresh(&mut req_data);
+            Some(par)
+        }
+        None => None,
+    };
+
     let (client, header_origin) = auth::validate_auth_req_param(
         &data,
         &req,
@@ -218,16 +357,96 @@ pub async fn post_authorize_refresh(
         &req_data.code_challenge_method,
     )
     .await?;
+    client.validate_par(par.is_some())?;
 
     let auth_step =
-        auth::authorize_refresh(&data, session, client, header_origin, req_data.into_inner())
-            .await?;
+        auth::authorize_refresh(&data, session, client, header_origin, req_data, false).await?;
+    // a pushed request can only be used once to issue a code
+    if let Some(par) = par {
+        par.delete(&data).await?;
+    }
     map_auth_step(&data, auth_step, &req)
         .await
         .map(|res| res.0)
         .map_err(|err| err.0)
 }
 
+/// Login via an upstream provider
+///
+/// Starts the authorization_code flow with the login at an upstream identity provider. Takes the
+/// same params as `GET /oidc/authorize` and additionally the `provider_id`. On success, the
+/// user agent will be redirected to the upstream authorization endpoint.
+#[utoipa::path(
+    get,
+    path = "/oidc/authorize/upstream",
+    tag = "oidc",
+    params(UpstreamAuthRequest),
+    responses(
+        (status = 302, description = "Redirect to the upstream provider"),
+        (status = 400, description = "If any params do not match the backend config", body = ErrorResponse),
+    ),
+)]
+#[get("/oidc/authorize/upstream")]
+pub async fn get_authorize_upstream(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    req_data: actix_web_validator::Query<UpstreamAuthRequest>,
+) -> HttpResponse {
+    let colors = ColorEntity::find(&data, &req_data.client_id)
+        .await
+        .unwrap_or_default();
+    let lang = Language::try_from(&req).unwrap_or_default();
+
+    match upstream::authorize_upstream(&data, &req, req_data.into_inner()).await {
+        Ok(loc) => HttpResponse::build(StatusCode::from_u16(302).unwrap())
+            .insert_header(("location", loc))
+            .finish(),
+        Err(err) => {
+            let status = err.status_code();
+            let (body, nonce) = Error2Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, nonce, status)
+        }
+    }
+}
+
+/// Callback for logins via an upstream provider
+///
+/// The upstream provider redirects the user agent back to this endpoint after the login. On
+/// success, a new session will be created and the user agent will be redirected to the
+/// `redirect_uri` of the downstream client with the authorization code.
+#[utoipa::path(
+    get,
+    path = "/oidc/authorize/upstream/callback",
+    tag = "oidc",
+    params(UpstreamCallbackRequest),
+    responses(
+        (status = 302, description = "Redirect to the downstream client"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/oidc/authorize/upstream/callback")]
+pub async fn get_authorize_upstream_callback(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    req_data: actix_web_validator::Query<UpstreamCallbackRequest>,
+) -> HttpResponse {
+    match upstream::callback_upstream(&data, req_data.into_inner(), real_ip_from_req(&req)).await {
+        Ok((loc, session)) => HttpResponse::build(StatusCode::from_u16(302).unwrap())
+            .cookie(session.client_cookie())
+            .insert_header(("location", loc))
+            .finish(),
+        Err(err) => {
+            let colors = ColorEntity::find_rauthy(&data).await.unwrap_or_default();
+            let lang = Language::try_from(&req).unwrap_or_default();
+            let status = err.status_code();
+            let (body, nonce) = Error3Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, nonce, status)
+        }
+    }
+}
+
 // TODO request at least session state init ?
 #[get("/oidc/callback")]
 pub async fn get_callback_html(
@@ -290,11 +509,259 @@ pub async fn get_cert_by_kid(
     Ok(HttpResponse::Ok().json(JWKSPublicKeyCerts::from(pub_key)))
 }
 
+/// Device Authorization HTML
+///
+/// The verification page for the device authorization grant, where a logged-in user can enter
+/// the `user_code` from the device and accept or deny the request.
+#[utoipa::path(
+    get,
+    path = "/oidc/device",
+    tag = "oidc",
+    responses((status = 200, description = "Ok")),
+)]
+#[get("/oidc/device")]
+pub async fn get_device_html(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let colors = ColorEntity::find_rauthy(&data).await?;
+    let lang = Language::try_from(&req).unwrap_or_default();
+    let (body, nonce) = DeviceHtml::build(&colors, &lang);
+
+    Ok(HttpResponse::Ok()
+        .insert_header(HEADER_HTML)
+        .insert_header(build_csp_header(&nonce))
+        .body(body))
+}
+
+/// Consent HTML
+///
+/// The page, where a user grants the requested scopes to a client, which requires a consent.
+/// The user will be redirected here during the authorization code flow.
+#[utoipa::path(
+    get,
+    path = "/oidc/consent",
+    tag = "oidc",
+    params(ConsentParams),
+    responses((status = 200, description = "Ok")),
+)]
+#[get("/oidc/consent")]
+pub async fn get_consent_html(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    params: actix_web_validator::Query<ConsentParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    // the colors of the client will be used if the request still exists
+    let colors = match ConsentReq::find(&data, params.into_inner().id).await {
+        Ok(consent_req) => ColorEntity::find(&data, &consent_req.client_id).await?,
+        Err(_) => ColorEntity::find_rauthy(&data).await?,
+    };
+    let lang = Language::try_from(&req).unwrap_or_default();
+    let (body, nonce) = ConsentHtml::build(&colors, &lang);
+
+    Ok(HttpResponse::Ok()
+        .insert_header(HEADER_HTML)
+        .insert_header(build_csp_header(&nonce))
+        .body(body))
+}
+
+/// Returns the client and the requested scopes for a pending consent
+///
+/// **Permissions**
+/// - the session, which started the authorization request
+#[utoipa::path(
+    get,
+    path = "/oidc/consent/{id}",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok", body = ConsentInfoResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/oidc/consent/{id}")]
+pub async fn get_consent_info(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth_or_init()?;
+    let session = principal.get_session()?;
+
+    consent::get_consent_info(&data, session, id.into_inner())
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
+/// Grant or deny a pending consent
+///
+/// On success, the `Location` header contains the redirect back to the client, either with the
+/// authorization code or with an `access_denied` error.
+///
+/// **Permissions**
+/// - the session, which started the authorization request
+#[utoipa::path(
+    post,
+    path = "/oidc/consent",
+    tag = "oidc",
+    request_body = ConsentRequest,
+    responses(
+        (status = 202, description = "Accepted"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/consent")]
+pub async fn post_consent(
+    data: web::Data<AppState>,
+    req_data: actix_web_validator::Json<ConsentRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth_or_init()?;
+    let session = principal.get_session()?;
+
+    let loc = consent::post_consent(&data, session, req_data.into_inner()).await?;
+    Ok(HttpResponse::Accepted()
+        .insert_header((header::LOCATION, loc))
+        .finish())
+}
+
+/// The Pushed Authorization Request Endpoint (RFC 9126)
+///
+/// Authenticates the client and validates the authorization request in the same way as on the
+/// `GET /oidc/authorize`. The returned `request_uri` can be used afterward together with the
+/// `client_id` on the authorization endpoint instead of the full request.
+#[utoipa::path(
+    post,
+    path = "/oidc/par",
+    tag = "oidc",
+    request_body(content = ParRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 201, description = "Created", body = ParResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/par")]
+pub async fn post_par(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    req_data: actix_web_validator::Form<ParRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    par::pushed_authorization_request(&data, &req, req_data.into_inner())
+        .await
+        .map(|res| HttpResponse::Created().json(res))
+}
+
+/// The Device Authorization Endpoint (RFC 8628)
+///
+/// Starts the device authorization grant and returns a new `device_code` and `user_code`. The
+/// device must show the `user_code` and the `verification_uri` to the user and poll the token
+/// endpoint with the `device_code` afterward.
+#[utoipa::path(
+    post,
+    path = "/oidc/device",
+    tag = "oidc",
+    request_body(content = DeviceRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Ok", body = DeviceCodeResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/device")]
+pub async fn post_device_auth(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    req_data: actix_web_validator::Form<DeviceRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    device::device_authorization(&data, &req, req_data.into_inner())
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
+/// Accept or deny a device authorization request
+///
+/// **Permissions**
+/// - authenticated and logged in user
+#[utoipa::path(
+    post,
+    path = "/oidc/device/verify",
+    tag = "oidc",
+    request_body = DeviceVerifyRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+        (status = 406, description = "MfaRequired", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/device/verify")]
+pub async fn post_device_verify(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    req_data: actix_web_validator::Json<DeviceVerifyRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let session = principal.validate_session_auth()?;
+
+    device::verify_device(&data, session, req_data.into_inner())
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
+
+/// The Token Introspection Endpoint (RFC 7662)
+///
+/// The requester must authenticate as a client in the same way as on the token endpoint. Only
+/// tokens, which have been issued to the client or contain it in their `aud`, will be reported
+/// as active. If the `Accept` header contains `application/token-introspection+jwt`, the
+/// response will be a signed JWT (RFC 9701).
+#[utoipa::path(
+    post,
+    path = "/oidc/introspect",
+    tag = "oidc",
+    request_body(content = IntrospectionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Ok", body = IntrospectionResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/introspect")]
+pub async fn post_introspect(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    req_data: actix_web_validator::Form<IntrospectionRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (resp, client) = auth::introspect_token(&data, &req, req_data.into_inner()).await?;
+
+    let wants_jwt = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.contains(TOKEN_INTROSPECTION_JWT))
+        .unwrap_or(false);
+
+    if wants_jwt {
+        let jwt = auth::sign_introspection_response(&data, &client, &resp).await?;
+        Ok(HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, TOKEN_INTROSPECTION_JWT))
+            .body(jwt))
+    } else {
+        Ok(HttpResponse::Ok().json(resp))
+    }
+}
+
 // Logout HTML page
 //
 // Returns an HTML page which can be used for logging the user out. Invalidates the session and deletes
 // all possibly existing refresh tokens from the database. Does an automatic logout if the
-// `id_token_hint` is given.
+// `id_token_hint` is given. The `post_logout_redirect_uri` is validated against the client from
+// either the `id_token_hint` or the `client_id`.
 #[utoipa::path(
     get,
     path = "/oidc/logout",
@@ -324,32 +791,36 @@ pub async fn get_logout(
     };
 
     let lang = Language::try_from(&req).unwrap_or_default();
-    let (body, nonce) = match auth::logout(req_data.into_inner(), session, &data, &lang).await {
-        Ok(t) => t,
-        Err(_) => {
-            return HttpResponse::build(StatusCode::from_u16(302).unwrap())
-                .insert_header(("location", "/auth/v1/"))
-                .finish()
-        }
-    };
+    let (body, nonce, frame_origins) =
+        match auth::logout(req_data.into_inner(), session, &data, &lang).await {
+            Ok(t) => t,
+            Err(_) => {
+                return HttpResponse::build(StatusCode::from_u16(302).unwrap())
+                    .insert_header(("location", "/auth/v1/"))
+                    .finish()
+            }
+        };
 
     return HttpResponse::build(StatusCode::OK)
         .append_header(HEADER_HTML)
-        .append_header(build_csp_header(&nonce))
+        .append_header(build_csp_header_frame_src(&nonce, &frame_origins))
         .body(body);
 }
 
 /// Send the logout confirmation
 ///
-/// This is the corresponding endpoint for the `GET /auth/v1/oidc/logout`
+/// This is the corresponding endpoint for the `GET /auth/v1/oidc/logout`.<br>
+/// The response contains the Front-Channel Logout URIs of all clients the session has been
+/// logged into, which must be loaded inside (hidden) iframes, and the validated
+/// `post_logout_redirect_uri` with the `state` appended, which the user agent should be
+/// redirected to afterward.
 #[utoipa::path(
     post,
     path = "/oidc/logout",
     tag = "oidc",
     params(LogoutRequest),
     responses(
-        (status = 200, description = "Ok without `post_logout_redirect_uri`"),
-        (status = 301, description = "if a `post_logout_redirect_uri` was given"),
+        (status = 200, description = "Ok", body = LogoutResponse),
         (status = 400, description = "BadRequest", body = ErrorResponse),
     ),
 )]
@@ -360,26 +831,185 @@ pub async fn post_logout(
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     let mut session = principal.get_session()?.clone();
+
+    // validate the redirect before doing the logout to not end up in an undefined state
+    let req_data = req_data.into_inner();
+    let id_claims = auth::validate_id_token_hint(&data, req_data.id_token_hint.as_deref()).await?;
+    let post_logout_redirect_uri =
+        auth::post_logout_redirect_uri(&data, &req_data, id_claims.as_ref()).await?;
+
     let cookie = session.invalidate(&data).await?;
 
-    if req_data.post_logout_redirect_uri.is_some() {
-        let state = if req_data.state.is_some() {
-            req_data.state.as_ref().unwrap().as_str()
-        } else {
-            ""
-        };
-        let loc = format!(
-            "{}?state={}",
-            req_data.post_logout_redirect_uri.as_ref().unwrap(),
-            state
-        );
-        return Ok(HttpResponse::build(StatusCode::MOVED_PERMANENTLY)
-            .append_header((header::LOCATION, loc))
-            .cookie(cookie)
-            .finish());
+    // a failed back- or front-channel logout must never prevent the local logout
+    let clients = SessionClient::take_clients(&data, &session.id)
+        .await
+        .unwrap_or_else(|err| {
+            error!(
+                "Error looking up the clients for the session: {}",
+                err.message
+            );
+            Vec::default()
+        });
+    if let Err(err) = SessionClient::backchannel_logout(&data, &session, &clients).await {
+        error!("Error during the back-channel logout: {}", err.message);
+    }
+    let frontchannel_logout_uris =
+        SessionClient::frontchannel_logout_uris(&data.issuer, &session, &clients);
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(LogoutResponse {
+        frontchannel_logout_uris,
+        post_logout_redirect_uri,
+    }))
+}
+
+/// Dynamic Client Registration (RFC 7591)
+///
+/// Registers a new client. If `DYN_CLIENT_REG_TOKEN` is set, it must be given as
+/// `Authorization: Bearer <token>`. The requested metadata is checked against the configured
+/// policy for dynamically registered clients.<br>
+/// The returned `registration_access_token` is needed to read, update or delete the client
+/// afterward and will only be shown once.
+#[utoipa::path(
+    post,
+    path = "/oidc/register",
+    tag = "oidc",
+    request_body = DynClientRequest,
+    responses(
+        (status = 201, description = "Created", body = DynClientResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 405, description = "MethodNotAllowed - dynamic client registration is disabled"),
+    ),
+)]
+#[post("/oidc/register")]
+pub async fn post_clients_dyn(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    payload: actix_web_validator::Json<DynClientRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    if !*ENABLE_DYN_CLIENT_REG {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
+    }
+
+    client::register_dyn_client(&data, &req, payload.into_inner())
+        .await
+        .map(|res| HttpResponse::Created().json(res))
+}
+
+/// Read a dynamically registered client (RFC 7592)
+///
+/// **Permissions**
+/// - `registration_access_token` as Bearer token
+#[utoipa::path(
+    get,
+    path = "/oidc/register/{id}",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok", body = DynClientResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 405, description = "MethodNotAllowed - dynamic client registration is disabled"),
+    ),
+)]
+#[get("/oidc/register/{id}")]
+pub async fn get_clients_dyn(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ErrorResponse> {
+    if !*ENABLE_DYN_CLIENT_REG {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
+    }
+
+    client::get_dyn_client(&data, &req, id.into_inner())
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
+/// Update a dynamically registered client (RFC 7592)
+///
+/// Replaces the complete client metadata. The same policy as for the registration applies.
+///
+/// **Permissions**
+/// - `registration_access_token` as Bearer token
+#[utoipa::path(
+    put,
+    path = "/oidc/register/{id}",
+    tag = "oidc",
+    request_body = DynClientRequest,
+    responses(
+        (status = 200, description = "Ok", body = DynClientResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 405, description = "MethodNotAllowed - dynamic client registration is disabled"),
+    ),
+)]
+#[put("/oidc/register/{id}")]
+pub async fn put_clients_dyn(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<DynClientRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    if !*ENABLE_DYN_CLIENT_REG {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
+    }
+
+    client::update_dyn_client(&data, &req, id.into_inner(), payload.into_inner())
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
+/// Delete a dynamically registered client (RFC 7592)
+///
+/// **Permissions**
+/// - `registration_access_token` as Bearer token
+#[utoipa::path(
+    delete,
+    path = "/oidc/register/{id}",
+    tag = "oidc",
+    responses(
+        (status = 204, description = "NoContent"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 405, description = "MethodNotAllowed - dynamic client registration is disabled"),
+    ),
+)]
+#[delete("/oidc/register/{id}")]
+pub async fn delete_clients_dyn(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    id: web::Path<String>,
+) -> Result<HttpResponse, ErrorResponse> {
+    if !*ENABLE_DYN_CLIENT_REG {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
     }
 
-    return Ok(HttpResponse::build(StatusCode::OK).cookie(cookie).finish());
+    client::delete_dyn_client(&data, &req, id.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The Token Revocation Endpoint (RFC 7009)
+///
+/// Revokes a refresh or access token. The client must authenticate in the same way as on the
+/// token endpoint. Invalid or already expired tokens will be answered with a `200` as well.
+#[utoipa::path(
+    post,
+    path = "/oidc/revoke",
+    tag = "oidc",
+    request_body(content = RevokeRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/revoke")]
+pub async fn post_revoke(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    req_data: actix_web_validator::Form<RevokeRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    auth::revoke_token(&data, &req, req_data.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
 }
 
 /// Rotate JWKs
@@ -561,7 +1191,7 @@ pub async fn post_token_info(
     data: web::Data<AppState>,
     req_data: actix_web_validator::Json<TokenValidationRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    auth::get_token_info(&data, &req_data.token)
+    auth::get_token_info(&data, &req_data.token, req_data.x5t_s256.as_deref())
         .await
         .map(|i| HttpResponse::Ok().json(i))
 }
@@ -612,8 +1242,8 @@ pub async fn post_validate_token(
     data: web::Data<AppState>,
     req_data: actix_web_validator::Json<TokenValidationRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    auth::validate_token::<JwtCommonClaims>(&data, &req_data.token)
-        .await
+    let claims = auth::validate_token::<JwtCommonClaims>(&data, &req_data.token).await?;
+    auth::validate_cert_binding(&claims.custom.cnf, req_data.x5t_s256.as_deref())
         .map(|_| HttpResponse::Accepted().finish())
 }
 
@@ -621,6 +1251,11 @@ pub async fn post_validate_token(
 ///
 /// Depending on the JWT token from the *Authorization* header, it will return information about
 /// the requesting user / token.
+///
+/// If the client has a `userinfo_signed_response_alg` or the `Accept` header contains
+/// `application/jwt`, the response will be a signed JWT. If the client has a
+/// `userinfo_encrypted_response_alg`, the response will be encrypted as a JWE afterward. Both
+/// will be returned with the `application/jwt` content type.
 #[utoipa::path(
     post,
     path = "/oidc/userinfo",
@@ -637,9 +1272,36 @@ pub async fn get_userinfo(
     data: web::Data<AppState>,
     req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
-    auth::get_userinfo(&data, req)
-        .await
-        .map(|u| HttpResponse::Ok().json(u))
+    let wants_jwt = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.contains(APPLICATION_JWT))
+        .unwrap_or(false);
+    let (userinfo, client) = auth::get_userinfo(&data, req).await?;
+
+    let is_signed = wants_jwt || client.userinfo_signed_response_alg.is_some();
+    let payload = if is_signed {
+        auth::sign_userinfo(&data, &client, &userinfo)
+            .await?
+            .into_bytes()
+    } else {
+        serde_json::to_vec(&userinfo)?
+    };
+
+    if let Some(jwe) = client.encrypt_userinfo(&data, &payload, is_signed).await? {
+        Ok(HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, APPLICATION_JWT))
+            .body(jwe))
+    } else if is_signed {
+        Ok(HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, APPLICATION_JWT))
+            .body(payload))
+    } else {
+        Ok(HttpResponse::Ok()
+            .insert_header((CONTENT_TYPE, APPLICATION_JSON))
+            .body(payload))
+    }
 }
 
 /// The `.well-known` endpoint for OIDC Client auto discovery.
@@ -1,5 +1,6 @@
 use crate::{
-    api_keys, blacklist, clients, events, generic, groups, oidc, roles, scopes, sessions, users,
+    api_keys, blacklist, clients, events, generic, groups, oidc, providers, roles, scim, scopes,
+    sessions, users,
 };
 use actix_web::web;
 use rauthy_common::constants::{PROXY_MODE, RAUTHY_VERSION};
@@ -39,6 +40,12 @@ use utoipa::{openapi, OpenApi};
         clients::post_clients,
         clients::put_clients,
         clients::put_generate_client_secret,
+        clients::get_client_provisioning,
+        clients::post_client_provisioning,
+        clients::put_client_provisioning,
+        clients::delete_client_provisioning,
+        clients::get_client_provisioning_deliveries,
+        clients::post_client_provisioning_delivery_retry,
         clients::delete_client,
 
         events::sse_events,
@@ -66,10 +73,25 @@ use utoipa::{openapi, OpenApi};
 
         oidc::get_authorize,
         oidc::post_authorize,
+        oidc::get_authorize_upstream,
+        oidc::get_authorize_upstream_callback,
         oidc::get_certs,
         oidc::get_cert_by_kid,
+        oidc::get_consent_html,
+        oidc::get_consent_info,
+        oidc::post_consent,
+        oidc::get_device_html,
+        oidc::post_device_auth,
+        oidc::post_device_verify,
+        oidc::post_introspect,
         oidc::get_logout,
         oidc::post_logout,
+        oidc::post_par,
+        oidc::post_clients_dyn,
+        oidc::get_clients_dyn,
+        oidc::put_clients_dyn,
+        oidc::delete_clients_dyn,
+        oidc::post_revoke,
         oidc::rotate_jwk,
         oidc::get_session_info,
         oidc::get_session_xsrf,
@@ -79,11 +101,33 @@ use utoipa::{openapi, OpenApi};
         oidc::get_userinfo,
         oidc::get_well_known,
 
+        providers::get_providers,
+        providers::get_providers_minimal,
+        providers::post_provider,
+        providers::put_provider,
+        providers::delete_provider,
+
         roles::get_roles,
         roles::post_role,
         roles::put_role,
         roles::delete_role,
 
+        scim::get_scim_users,
+        scim::post_scim_user,
+        scim::get_scim_user,
+        scim::put_scim_user,
+        scim::patch_scim_user,
+        scim::delete_scim_user,
+        scim::get_scim_groups,
+        scim::post_scim_group,
+        scim::get_scim_group,
+        scim::put_scim_group,
+        scim::patch_scim_group,
+        scim::delete_scim_group,
+        scim::get_scim_service_provider_config,
+        scim::get_scim_resource_types,
+        scim::get_scim_schemas,
+
         scopes::get_scopes,
         scopes::post_scope,
         scopes::put_scope,
@@ -112,6 +156,16 @@ use utoipa::{openapi, OpenApi};
         users::post_webauthn_auth_start,
         users::post_webauthn_auth_finish,
         users::delete_webauthn,
+        users::post_totp_auth,
+        users::delete_totp,
+        users::post_totp_reg_start,
+        users::post_totp_reg_finish,
+        users::post_recovery_code_auth,
+        users::post_recovery_codes,
+        users::get_user_consents,
+        users::delete_user_consent,
+        users::get_user_offline_tokens,
+        users::delete_user_offline_token,
         users::post_webauthn_reg_start,
         users::post_user_password_request_reset,
         users::get_user_by_email,
@@ -132,6 +186,10 @@ use utoipa::{openapi, OpenApi};
             entity::jwk::JwkKeyPairType,
             entity::password::PasswordHashTime,
             entity::password::PasswordHashTimes,
+            entity::provisioning::ProvisioningAction,
+            entity::provisioning::ProvisioningConnectorType,
+            entity::provisioning::ProvisioningResource,
+            entity::provisioning::ProvisioningStatus,
             entity::roles::Role,
             entity::scopes::Scope,
             entity::sessions::SessionState,
@@ -151,29 +209,45 @@ use utoipa::{openapi, OpenApi};
             request::ApiKeyRequest,
             request::AuthCodeRequest,
             request::AuthRequest,
+            request::AuthRequestRef,
             request::IpBlacklistRequest,
             request::ColorsRequest,
+            request::ConsentParams,
+            request::ConsentRequest,
+            request::DeviceRequest,
+            request::DeviceVerifyRequest,
+            request::DynClientRequest,
             request::EncKeyMigrateRequest,
+            request::IntrospectionRequest,
             request::LoginRequest,
             request::LogoutRequest,
             request::MfaAwaitRequest,
             request::MfaPurpose,
             request::NewClientRequest,
             request::NewGroupRequest,
+            request::ParRequest,
             request::PasswordHashTimesRequest,
             request::PasswordPolicyRequest,
             request::PasswordResetRequest,
+            request::RecoveryCodeAuthRequest,
             request::RequestResetRequest,
+            request::RevokeRequest,
             request::NewUserRequest,
             request::NewUserRegistrationRequest,
             request::PowRequest,
+            request::ProvisioningConnectorRequest,
             request::NewRoleRequest,
             request::ScopeRequest,
             request::TokenRequest,
             request::TokenValidationRequest,
+            request::TotpAuthRequest,
+            request::TotpRegFinishRequest,
             request::UpdateClientRequest,
             request::UpdateUserRequest,
             request::UpdateUserSelfRequest,
+            request::UpstreamAuthRequest,
+            request::UpstreamCallbackRequest,
+            request::UpstreamProviderRequest,
             request::UserAttrConfigRequest,
             request::UserAttrValueRequest,
             request::UserAttrValuesUpdateRequest,
@@ -189,19 +263,34 @@ use utoipa::{openapi, OpenApi};
             response::LoginTimeResponse,
             response::ClientResponse,
             response::ClientSecretResponse,
+            response::ConsentInfoResponse,
+            response::DeviceCodeResponse,
+            response::DynClientResponse,
             response::EncKeysResponse,
             response::HealthResponse,
+            response::IntrospectionResponse,
             response::JWKSCerts,
             response::JWKSPublicKeyCerts,
+            response::LogoutResponse,
+            response::OfflineTokenResponse,
             response::Argon2ParamsResponse,
+            response::ParResponse,
             response::PasswordPolicyResponse,
+            response::ProvisioningConnectorResponse,
+            response::ProvisioningDeliveryResponse,
+            response::RecoveryCodesResponse,
             response::ScopeResponse,
             response::SessionResponse,
             response::SessionInfoResponse,
             response::TokenInfo,
+            response::TotpLoginResponse,
+            response::TotpRegStartResponse,
+            response::UpstreamProviderLoginResponse,
+            response::UpstreamProviderResponse,
             response::UserAttrConfigResponse,
             response::UserAttrValueResponse,
             response::UserAttrValuesResponse,
+            response::UserConsentResponse,
             response::Userinfo,
             response::UserAccountTypeResponse,
             response::UserResponse,
@@ -223,6 +312,8 @@ use utoipa::{openapi, OpenApi};
         (name = "groups", description = "Groups endpoints"),
         (name = "roles", description = "Roles endpoints"),
         (name = "scopes", description = "Scopes endpoints"),
+        (name = "scim", description = "SCIM 2.0 provisioning endpoints"),
+        (name = "providers", description = "Upstream identity providers"),
         (name = "events", description = "Events Stream"),
         (name = "health", description = "Ping, Health, Ready Check"),
         (name = "blacklist", description = "IP Blacklist endpoints"),
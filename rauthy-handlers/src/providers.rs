@@ -0,0 +1,166 @@
+use crate::ReqPrincipal;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use rauthy_common::error_response::ErrorResponse;
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::upstream_providers::UpstreamProvider;
+use rauthy_models::request::UpstreamProviderRequest;
+use rauthy_models::response::{UpstreamProviderLoginResponse, UpstreamProviderResponse};
+
+/// Returns all upstream identity providers
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/providers",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok", body = [UpstreamProviderResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/providers")]
+pub async fn get_providers(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UpstreamProviders, AccessRights::Read)?;
+
+    UpstreamProvider::find_all(&data).await.map(|providers| {
+        let res = providers
+            .into_iter()
+            .map(UpstreamProviderResponse::from)
+            .collect::<Vec<UpstreamProviderResponse>>();
+        HttpResponse::Ok().json(res)
+    })
+}
+
+/// Returns a minimal version of all enabled upstream identity providers
+///
+/// This endpoint is public, since the information is needed to show the provider login buttons
+/// on the login page.
+#[utoipa::path(
+    get,
+    path = "/providers/minimal",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok", body = [UpstreamProviderLoginResponse]),
+    ),
+)]
+#[get("/providers/minimal")]
+pub async fn get_providers_minimal(
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ErrorResponse> {
+    UpstreamProvider::find_all(&data).await.map(|providers| {
+        let res = providers
+            .into_iter()
+            .filter(|p| p.enabled)
+            .map(|p| UpstreamProviderLoginResponse {
+                id: p.id,
+                name: p.name,
+            })
+            .collect::<Vec<UpstreamProviderLoginResponse>>();
+        HttpResponse::Ok().json(res)
+    })
+}
+
+/// Adds a new upstream identity provider
+///
+/// If any of the endpoints is not given, they will be looked up via the OIDC discovery of the
+/// `issuer`.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/providers",
+    tag = "providers",
+    request_body = UpstreamProviderRequest,
+    responses(
+        (status = 200, description = "Ok", body = UpstreamProviderResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/providers")]
+pub async fn post_provider(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    payload: actix_web_validator::Json<UpstreamProviderRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UpstreamProviders, AccessRights::Create)?;
+
+    UpstreamProvider::create(&data, payload.into_inner())
+        .await
+        .map(|p| HttpResponse::Ok().json(UpstreamProviderResponse::from(p)))
+}
+
+/// Modifies an upstream identity provider
+///
+/// If `client_secret` is not given, the current secret will be kept. An empty string removes
+/// the secret.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/providers/{id}",
+    tag = "providers",
+    request_body = UpstreamProviderRequest,
+    responses(
+        (status = 200, description = "Ok", body = UpstreamProviderResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/providers/{id}")]
+pub async fn put_provider(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: actix_web_validator::Json<UpstreamProviderRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UpstreamProviders, AccessRights::Update)?;
+
+    UpstreamProvider::update(&data, path.into_inner(), payload.into_inner())
+        .await
+        .map(|p| HttpResponse::Ok().json(UpstreamProviderResponse::from(p)))
+}
+
+/// Deletes an upstream identity provider
+///
+/// All existing links from local users to this provider will be deleted as well.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/providers/{id}",
+    tag = "providers",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/providers/{id}")]
+pub async fn delete_provider(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UpstreamProviders, AccessRights::Delete)?;
+
+    UpstreamProvider::delete(&data, path.as_str())
+        .await
+        .map(|_| HttpResponse::Ok().finish())
+}
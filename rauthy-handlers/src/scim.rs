@@ -0,0 +1,486 @@
+use crate::ReqPrincipal;
+use actix_web::http::header::LOCATION;
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use rauthy_common::constants::APPLICATION_SCIM_JSON;
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::user_attr::UserAttrConfigEntity;
+use rauthy_models::scim::discovery;
+use rauthy_models::scim::{
+    ScimError, ScimGroup, ScimListParams, ScimListResponse, ScimPatchOp, ScimUser,
+};
+use rauthy_service::scim;
+use serde::Serialize;
+use validator::Validate;
+
+/// SCIM 2.0: Lists all users
+///
+/// Supports the `filter`, `startIndex` and `count` query parameters (RFC 7644 Section 3.4.2).
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Users",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/scim/v2/Users")]
+pub async fn get_scim_users(
+    data: web::Data<AppState>,
+    params: web::Query<ScimListParams>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+    params.validate()?;
+
+    let res = scim::list_users(&data, params.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &res))
+}
+
+/// SCIM 2.0: Provisions a new user
+///
+/// The `userName` is the E-Mail address of the user. Custom user attributes can be set with the
+/// `urn:ietf:params:scim:schemas:extension:rauthy:2.0:User` extension.
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/scim/v2/Users",
+    tag = "scim",
+    responses(
+        (status = 201, description = "Created"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Conflict"),
+    ),
+)]
+#[post("/scim/v2/Users")]
+pub async fn post_scim_user(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+    payload: web::Json<ScimUser>,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Create)?;
+
+    let user = scim::create_user(&data, &req, payload.into_inner()).await?;
+    Ok(scim_created(
+        user.meta.as_ref().map(|m| m.location.as_str()),
+        &user,
+    ))
+}
+
+/// SCIM 2.0: Returns a single user
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[get("/scim/v2/Users/{id}")]
+pub async fn get_scim_user(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+
+    let user = scim::get_user(&data, path.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &user))
+}
+
+/// SCIM 2.0: Replaces a user
+///
+/// Attributes, which are not given, keep their current value. `groups` are read-only and can
+/// only be modified via the `/scim/v2/Groups` endpoints.
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+        (status = 409, description = "Conflict"),
+    ),
+)]
+#[put("/scim/v2/Users/{id}")]
+pub async fn put_scim_user(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: web::Json<ScimUser>,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Update)?;
+
+    let user = scim::replace_user(&data, path.into_inner(), payload.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &user))
+}
+
+/// SCIM 2.0: Modifies a user with PATCH operations
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    patch,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[patch("/scim/v2/Users/{id}")]
+pub async fn patch_scim_user(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: web::Json<ScimPatchOp>,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Update)?;
+
+    let user = scim::patch_user(&data, path.into_inner(), payload.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &user))
+}
+
+/// SCIM 2.0: Deletes a user
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    responses(
+        (status = 204, description = "NoContent"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[delete("/scim/v2/Users/{id}")]
+pub async fn delete_scim_user(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Delete)?;
+
+    scim::delete_user(&data, path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// SCIM 2.0: Lists all groups
+///
+/// Supports the `filter`, `startIndex` and `count` query parameters (RFC 7644 Section 3.4.2).
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Groups",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/scim/v2/Groups")]
+pub async fn get_scim_groups(
+    data: web::Data<AppState>,
+    params: web::Query<ScimListParams>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+    params.validate()?;
+
+    let res = scim::list_groups(&data, params.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &res))
+}
+
+/// SCIM 2.0: Provisions a new group
+///
+/// The `displayName` is the name of the group and `members` reference user ids.
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/scim/v2/Groups",
+    tag = "scim",
+    responses(
+        (status = 201, description = "Created"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 409, description = "Conflict"),
+    ),
+)]
+#[post("/scim/v2/Groups")]
+pub async fn post_scim_group(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    payload: web::Json<ScimGroup>,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Create)?;
+
+    let group = scim::create_group(&data, payload.into_inner()).await?;
+    Ok(scim_created(
+        group.meta.as_ref().map(|m| m.location.as_str()),
+        &group,
+    ))
+}
+
+/// SCIM 2.0: Returns a single group
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[get("/scim/v2/Groups/{id}")]
+pub async fn get_scim_group(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+
+    let group = scim::get_group(&data, path.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &group))
+}
+
+/// SCIM 2.0: Replaces a group
+///
+/// The current members will be kept, if `members` is not given.
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+        (status = 409, description = "Conflict"),
+    ),
+)]
+#[put("/scim/v2/Groups/{id}")]
+pub async fn put_scim_group(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: web::Json<ScimGroup>,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Update)?;
+
+    let group = scim::replace_group(&data, path.into_inner(), payload.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &group))
+}
+
+/// SCIM 2.0: Modifies a group with PATCH operations
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    patch,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[patch("/scim/v2/Groups/{id}")]
+pub async fn patch_scim_group(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: web::Json<ScimPatchOp>,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Update)?;
+
+    let group = scim::patch_group(&data, path.into_inner(), payload.into_inner()).await?;
+    Ok(scim_response(StatusCode::OK, &group))
+}
+
+/// SCIM 2.0: Deletes a group
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    responses(
+        (status = 204, description = "NoContent"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[delete("/scim/v2/Groups/{id}")]
+pub async fn delete_scim_group(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Delete)?;
+
+    scim::delete_group(&data, path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// SCIM 2.0: The supported features of this service provider
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/ServiceProviderConfig",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/scim/v2/ServiceProviderConfig")]
+pub async fn get_scim_service_provider_config(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+
+    Ok(scim_response(
+        StatusCode::OK,
+        &discovery::service_provider_config(&data.issuer),
+    ))
+}
+
+/// SCIM 2.0: The supported resource types
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/ResourceTypes",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/scim/v2/ResourceTypes")]
+pub async fn get_scim_resource_types(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+
+    let res = ScimListResponse::paginate(discovery::resource_types(&data.issuer), None, None);
+    Ok(scim_response(StatusCode::OK, &res))
+}
+
+/// SCIM 2.0: The schemas of all resources
+///
+/// The `urn:ietf:params:scim:schemas:extension:rauthy:2.0:User` extension contains all
+/// currently configured custom user attributes.
+///
+/// **Permissions**
+/// - `Scim` API Key, which can be given as `Authorization: Bearer <name>$<secret>`
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Schemas",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+    ),
+)]
+#[get("/scim/v2/Schemas")]
+pub async fn get_scim_schemas(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ScimError> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Scim, AccessRights::Read)?;
+
+    let attrs = UserAttrConfigEntity::find_all(&data).await?;
+    let res = ScimListResponse::paginate(discovery::schemas(&data.issuer, attrs), None, None);
+    Ok(scim_response(StatusCode::OK, &res))
+}
+
+#[inline(always)]
+fn scim_response<T: Serialize>(status: StatusCode, body: &T) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(APPLICATION_SCIM_JSON)
+        .body(serde_json::to_string(body).unwrap())
+}
+
+#[inline(always)]
+fn scim_created<T: Serialize>(location: Option<&str>, body: &T) -> HttpResponse {
+    let mut res = scim_response(StatusCode::CREATED, body);
+    if let Some(Ok(location)) = location.map(|l| l.parse()) {
+        res.headers_mut().insert(LOCATION, location);
+    }
+    res
+}
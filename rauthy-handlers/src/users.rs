@@ -12,25 +12,30 @@ use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::password::PasswordPolicy;
+use rauthy_models::entity::recovery_codes::UserRecoveryCode;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::entity::totp::UserTotp;
 use rauthy_models::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use rauthy_models::entity::user_consents::UserConsent;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::webauthn;
-use rauthy_models::entity::webauthn::PasskeyEntity;
+use rauthy_models::entity::webauthn::{PasskeyEntity, WebauthnAdditionalData};
 use rauthy_models::entity::webids::WebId;
 use rauthy_models::events::event::Event;
 use rauthy_models::language::Language;
 use rauthy_models::request::{
     MfaPurpose, NewUserRegistrationRequest, NewUserRequest, PasswordResetRequest,
-    RequestResetRequest, UpdateUserRequest, UpdateUserSelfRequest, UserAttrConfigRequest,
-    UserAttrValuesUpdateRequest, WebIdRequest, WebauthnAuthFinishRequest, WebauthnAuthStartRequest,
-    WebauthnRegFinishRequest, WebauthnRegStartRequest,
+    RecoveryCodeAuthRequest, RequestResetRequest, TotpAuthRequest, TotpRegFinishRequest,
+    UpdateUserRequest, UpdateUserSelfRequest, UserAttrConfigRequest, UserAttrValuesUpdateRequest,
+    WebIdRequest, WebauthnAuthFinishRequest, WebauthnAuthStartRequest, WebauthnRegFinishRequest,
+    WebauthnRegStartRequest,
 };
 use rauthy_models::response::{
-    PasskeyResponse, UserAttrConfigResponse, UserAttrValueResponse, UserAttrValuesResponse,
-    UserResponse, WebIdResponse,
+    OfflineTokenResponse, PasskeyResponse, UserAttrConfigResponse, UserAttrValueResponse,
+    UserAttrValuesResponse, UserConsentResponse, UserResponse, WebIdResponse,
 };
 use rauthy_models::templates::{Error1Html, Error3Html, ErrorHtml, UserRegisterHtml};
-use rauthy_service::password_reset;
+use rauthy_service::{consent, offline_tokens, password_reset, recovery, totp};
 use std::ops::Add;
 use time::OffsetDateTime;
 use tracing::{error, warn};
@@ -497,6 +502,156 @@ pub async fn put_user_password_reset(
     })
 }
 
+/// Get all consents a user has given to clients
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/consents",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [UserConsentResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/consents")]
+pub async fn get_user_consents(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        // make sure a non-admin can only access its own information
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let consents = consent::get_user_consents(&data, &id).await?;
+    Ok(HttpResponse::Ok().json(consents))
+}
+
+/// Revokes the consent for a client
+///
+/// All refresh tokens, which have been issued to this client for the user, will be invalidated.
+/// The user will see the consent page again with the next login to the client.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/consents/{client_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/consents/{client_id}")]
+pub async fn delete_user_consent(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (id, client_id) = path.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)
+        .is_err()
+    {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    UserConsent::revoke(&data, &id, &client_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Get all offline tokens, which have been issued to clients with the `offline_access` scope
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/offline_tokens",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [OfflineTokenResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/offline_tokens")]
+pub async fn get_user_offline_tokens(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Read)
+        .is_err()
+    {
+        // make sure a non-admin can only access its own information
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let tokens = offline_tokens::get_for_user(&data, &id).await?;
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+/// Revokes a single offline token
+///
+/// All tokens of the same family will be invalidated. Interactive sessions of the user stay
+/// untouched.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/offline_tokens/{token_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/offline_tokens/{token_id}")]
+pub async fn delete_user_offline_token(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (id, token_id) = path.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)
+        .is_err()
+    {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    RefreshToken::delete_offline_family(&data, &id, &token_id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// Get all registered Webauthn Passkeys for a user
 ///
 /// **Permissions**
@@ -713,6 +868,9 @@ pub async fn delete_webauthn(
         }
 
         user.save(&data, None, Some(&mut txn)).await?;
+        if !user.has_mfa_enabled() {
+            UserRecoveryCode::delete_all(&data, &user.id, Some(&mut txn)).await?;
+        }
         txn.commit().await?;
     } else {
         txn.commit().await?;
@@ -792,7 +950,7 @@ pub async fn post_webauthn_reg_start(
     tag = "mfa",
     request_body = WebauthnRegFinishRequest,
     responses(
-        (status = 201, description = "Created"),
+        (status = 201, description = "Created - with the initial recovery codes after the first MFA enrollment", body = RecoveryCodesResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
@@ -823,11 +981,222 @@ pub async fn post_webauthn_reg_finish(
         let id = id.into_inner();
         principal.is_user(&id)?;
 
-        webauthn::reg_finish(&data, id, req_data.into_inner()).await?;
-        Ok(HttpResponse::Created().finish())
+        webauthn::reg_finish(&data, id.clone(), req_data.into_inner()).await?;
+        // the very first MFA enrollment returns the initial recovery codes
+        match recovery::init(&data, &id).await? {
+            Some(codes) => Ok(HttpResponse::Created().json(codes)),
+            None => Ok(HttpResponse::Created().finish()),
+        }
     }
 }
 
+/// Finishes a login, which waits for a TOTP as the 2nd factor
+///
+/// **Permissions**
+/// - session in init state during the login with the `code` from the login response
+#[utoipa::path(
+    post,
+    path = "/users/{id}/totp/auth",
+    tag = "mfa",
+    request_body = TotpAuthRequest,
+    responses(
+        (status = 202, description = "Accepted", body = WebauthnLoginFinishResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/totp/auth")]
+pub async fn post_totp_auth(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req_data: Json<TotpAuthRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    // same as for passkeys, the 'code' from the login response is the proof for the 1st factor
+    principal.validate_session_auth_or_init()?;
+
+    let login_req = totp::auth(&data, id.into_inner(), req_data.into_inner()).await?;
+    Ok(WebauthnAdditionalData::Login(login_req).into_response())
+}
+
+/// Deletes the TOTP for this user
+///
+/// This can be used by an admin to reset the TOTP, if a user lost access to the authenticator app.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/totp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/totp")]
+pub async fn delete_totp(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    // Note: Currently, this is not allowed with an ApiKey on purpose
+    let is_admin = match principal.validate_admin_session() {
+        Ok(()) => true,
+        Err(_) => {
+            principal.validate_session_auth()?;
+            false
+        }
+    };
+
+    let id = id.into_inner();
+    if !is_admin {
+        principal.is_user(&id)?;
+        warn!("TOTP delete for user {}", id);
+    } else {
+        warn!("TOTP reset from admin for user {}", id);
+    }
+
+    UserTotp::delete(&data, id).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Starts the TOTP enrollment for this user
+///
+/// The returned secret will only become active after the enrollment has been finished with a
+/// first valid code.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/totp/register/start",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = TotpRegStartResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/totp/register/start")]
+pub async fn post_totp_reg_start(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth()?;
+
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    totp::reg_start(&data, id)
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
+/// Finishes the TOTP enrollment for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/totp/register/finish",
+    tag = "mfa",
+    request_body = TotpRegFinishRequest,
+    responses(
+        (status = 200, description = "Ok - with the initial recovery codes after the first MFA enrollment", body = RecoveryCodesResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/totp/register/finish")]
+pub async fn post_totp_reg_finish(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req_data: Json<TotpRegFinishRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth()?;
+
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    totp::reg_finish(&data, id.clone(), req_data.into_inner()).await?;
+    match recovery::init(&data, &id).await? {
+        Some(codes) => Ok(HttpResponse::Ok().json(codes)),
+        None => Ok(HttpResponse::Ok().finish()),
+    }
+}
+
+/// Finishes a login, which waits for the MFA step, with a one-time recovery code
+///
+/// The used code will be deleted and an event will be generated.
+///
+/// **Permissions**
+/// - session in init state during the login with the `code` from the login response
+#[utoipa::path(
+    post,
+    path = "/users/{id}/recovery_codes/auth",
+    tag = "mfa",
+    request_body = RecoveryCodeAuthRequest,
+    responses(
+        (status = 202, description = "Accepted", body = WebauthnLoginFinishResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/recovery_codes/auth")]
+pub async fn post_recovery_code_auth(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    req_data: Json<RecoveryCodeAuthRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth_or_init()?;
+
+    let login_req = recovery::auth(&data, &req, id.into_inner(), req_data.into_inner()).await?;
+    Ok(WebauthnAdditionalData::Login(login_req).into_response())
+}
+
+/// Creates a new set of recovery codes for this user
+///
+/// All existing codes will be invalidated. The new codes will only be returned this single time.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/recovery_codes",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = RecoveryCodesResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/recovery_codes")]
+pub async fn post_recovery_codes(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth()?;
+
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    recovery::regenerate(&data, id)
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
 /// Returns a user's webid document, if enabled
 ///
 /// Note: The way the SwaggerUI is set up currently, the path will not be correct for this single
@@ -7,10 +7,13 @@ use actix_web::{middleware, web, App, HttpServer};
 use actix_web_prom::PrometheusMetricsBuilder;
 use prometheus::Registry;
 use rauthy_common::constants::{
-    CACHE_NAME_12HR, CACHE_NAME_AUTH_CODES, CACHE_NAME_DPOP_NONCES, CACHE_NAME_EPHEMERAL_CLIENTS,
-    CACHE_NAME_LOGIN_DELAY, CACHE_NAME_POW, CACHE_NAME_SESSIONS, CACHE_NAME_WEBAUTHN,
-    CACHE_NAME_WEBAUTHN_DATA, DPOP_NONCE_EXP, EPHEMERAL_CLIENTS_CACHE_LIFETIME, POW_EXP,
-    RAUTHY_VERSION, SWAGGER_UI_EXTERNAL, SWAGGER_UI_INTERNAL, WEBAUTHN_DATA_EXP, WEBAUTHN_REQ_EXP,
+    CACHE_NAME_12HR, CACHE_NAME_AUTH_CODES, CACHE_NAME_CONSENT, CACHE_NAME_DEVICE_CODES,
+    CACHE_NAME_DPOP_NONCES, CACHE_NAME_EPHEMERAL_CLIENTS, CACHE_NAME_LOGIN_DELAY, CACHE_NAME_PAR,
+    CACHE_NAME_POW, CACHE_NAME_REVOKED_TOKENS, CACHE_NAME_SESSIONS, CACHE_NAME_UPSTREAM_AUTH,
+    CACHE_NAME_WEBAUTHN, CACHE_NAME_WEBAUTHN_DATA, DEVICE_GRANT_CODE_LIFETIME, DPOP_NONCE_EXP,
+    EPHEMERAL_CLIENTS_CACHE_LIFETIME, PAR_REQUEST_URI_LIFETIME, POW_EXP, RAUTHY_VERSION,
+    SWAGGER_UI_EXTERNAL, SWAGGER_UI_INTERNAL, UPSTREAM_AUTH_EXP, WEBAUTHN_DATA_EXP,
+    WEBAUTHN_REQ_EXP,
 };
 use rauthy_common::password_hasher;
 use rauthy_handlers::middleware::ip_blacklist::RauthyIpBlacklistMiddleware;
@@ -18,7 +21,8 @@ use rauthy_handlers::middleware::logging::RauthyLoggingMiddleware;
 use rauthy_handlers::middleware::principal::RauthyPrincipalMiddleware;
 use rauthy_handlers::openapi::ApiDoc;
 use rauthy_handlers::{
-    api_keys, blacklist, clients, events, generic, groups, oidc, roles, scopes, sessions, users,
+    api_keys, blacklist, clients, events, generic, groups, oidc, providers, roles, scim, scopes,
+    sessions, users,
 };
 use rauthy_models::app_state::{AppState, Caches};
 use rauthy_models::email::EMail;
@@ -101,6 +105,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Some(64),
     );
 
+    // pending consent requests, which may follow a passkey verification
+    cache_config.spawn_cache(
+        CACHE_NAME_CONSENT.to_string(),
+        redhac::TimedCache::with_lifespan(300 + *WEBAUTHN_REQ_EXP),
+        Some(32),
+    );
+
     // DPoP nonces
     cache_config.spawn_cache(
         CACHE_NAME_DPOP_NONCES.to_string(),
@@ -134,6 +145,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Some(16),
     );
 
+    // revoked access tokens - must live at least as long as the max access token lifetime
+    cache_config.spawn_cache(
+        CACHE_NAME_REVOKED_TOKENS.to_string(),
+        redhac::TimedCache::with_lifespan(86400),
+        Some(64),
+    );
+
     // webauthn requests
     cache_config.spawn_cache(
         CACHE_NAME_WEBAUTHN.to_string(),
@@ -146,6 +164,27 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Some(32),
     );
 
+    // device authorization grant codes
+    cache_config.spawn_cache(
+        CACHE_NAME_DEVICE_CODES.to_string(),
+        redhac::TimedCache::with_lifespan(*DEVICE_GRANT_CODE_LIFETIME),
+        Some(32),
+    );
+
+    // pushed authorization requests
+    cache_config.spawn_cache(
+        CACHE_NAME_PAR.to_string(),
+        redhac::TimedCache::with_lifespan(*PAR_REQUEST_URI_LIFETIME),
+        Some(16),
+    );
+
+    // upstream provider logins
+    cache_config.spawn_cache(
+        CACHE_NAME_UPSTREAM_AUTH.to_string(),
+        redhac::TimedCache::with_lifespan(*UPSTREAM_AUTH_EXP),
+        Some(16),
+    );
+
     // login delay cache
     cache_config.spawn_cache(
         CACHE_NAME_LOGIN_DELAY.to_string(),
@@ -410,11 +449,26 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                         .service(oidc::get_authorize)
                         .service(oidc::post_authorize)
                         .service(oidc::post_authorize_refresh)
+                        .service(oidc::get_authorize_upstream)
+                        .service(oidc::get_authorize_upstream_callback)
                         .service(oidc::get_callback_html)
                         .service(oidc::get_certs)
                         .service(oidc::get_cert_by_kid)
+                        .service(oidc::get_consent_html)
+                        .service(oidc::get_consent_info)
+                        .service(oidc::post_consent)
+                        .service(oidc::get_device_html)
+                        .service(oidc::post_device_auth)
+                        .service(oidc::post_device_verify)
+                        .service(oidc::post_introspect)
                         .service(oidc::get_logout)
                         .service(oidc::post_logout)
+                        .service(oidc::post_par)
+                        .service(oidc::post_clients_dyn)
+                        .service(oidc::get_clients_dyn)
+                        .service(oidc::put_clients_dyn)
+                        .service(oidc::delete_clients_dyn)
+                        .service(oidc::post_revoke)
                         .service(oidc::rotate_jwk)
                         .service(oidc::get_session_info)
                         .service(oidc::get_session_xsrf)
@@ -430,6 +484,12 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                         .service(clients::post_clients)
                         .service(clients::put_clients)
                         .service(clients::put_generate_client_secret)
+                        .service(clients::get_client_provisioning)
+                        .service(clients::post_client_provisioning)
+                        .service(clients::put_client_provisioning)
+                        .service(clients::delete_client_provisioning)
+                        .service(clients::get_client_provisioning_deliveries)
+                        .service(clients::post_client_provisioning_delivery_retry)
                         .service(clients::delete_client)
                         .service(generic::get_login_time)
                         .service(users::get_users)
@@ -458,12 +518,22 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                         .service(users::put_user_self)
                         .service(users::delete_user_by_id)
                         .service(users::post_user_password_request_reset)
+                        .service(users::get_user_consents)
+                        .service(users::delete_user_consent)
+                        .service(users::get_user_offline_tokens)
+                        .service(users::delete_user_offline_token)
                         .service(users::get_user_webauthn_passkeys)
                         .service(users::post_webauthn_reg_start)
                         .service(users::post_webauthn_reg_finish)
                         .service(users::post_webauthn_auth_start)
                         .service(users::post_webauthn_auth_finish)
                         .service(users::delete_webauthn)
+                        .service(users::post_totp_auth)
+                        .service(users::delete_totp)
+                        .service(users::post_totp_reg_start)
+                        .service(users::post_totp_reg_finish)
+                        .service(users::post_recovery_code_auth)
+                        .service(users::post_recovery_codes)
                         .service(generic::get_password_policy)
                         .service(generic::put_password_policy)
                         .service(generic::get_pow)
@@ -475,10 +545,30 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
                         .service(roles::post_role)
                         .service(roles::put_role)
                         .service(roles::delete_role)
+                        .service(providers::get_providers)
+                        .service(providers::get_providers_minimal)
+                        .service(providers::post_provider)
+                        .service(providers::put_provider)
+                        .service(providers::delete_provider)
                         .service(scopes::get_scopes)
                         .service(scopes::post_scope)
                         .service(scopes::put_scope)
                         .service(scopes::delete_scope)
+                        .service(scim::get_scim_users)
+                        .service(scim::post_scim_user)
+                        .service(scim::get_scim_user)
+                        .service(scim::put_scim_user)
+                        .service(scim::patch_scim_user)
+                        .service(scim::delete_scim_user)
+                        .service(scim::get_scim_groups)
+                        .service(scim::post_scim_group)
+                        .service(scim::get_scim_group)
+                        .service(scim::put_scim_group)
+                        .service(scim::patch_scim_group)
+                        .service(scim::delete_scim_group)
+                        .service(scim::get_scim_service_provider_config)
+                        .service(scim::get_scim_resource_types)
+                        .service(scim::get_scim_schemas)
                         .service(oidc::post_token)
                         .service(oidc::post_token_info)
                         .service(oidc::get_userinfo)
@@ -502,7 +592,9 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
     })
     // overwrites the number of worker threads -> default == available cpu cores
     .workers(workers)
-    .shutdown_timeout(10);
+    .shutdown_timeout(10)
+    // makes the TLS client certificate available for the mutual-TLS client authentication
+    .on_connect(tls::on_connect);
 
     match listen_scheme {
         ListenScheme::Http => {
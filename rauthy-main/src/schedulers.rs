@@ -2,13 +2,14 @@ use ::time::OffsetDateTime;
 use actix_web::web;
 use chrono::Utc;
 use rauthy_common::constants::{
-    CACHE_NAME_12HR, DB_TYPE, IDX_JWK_KID, OFFLINE_TOKEN_LT, RAUTHY_VERSION,
+    CACHE_NAME_12HR, DB_TYPE, IDX_JWK_KID, OFFLINE_TOKEN_LT, PROVISIONING_INTERVAL, RAUTHY_VERSION,
 };
 use rauthy_common::DbType;
 use rauthy_models::app_state::{AppState, DbPool};
 use rauthy_models::email::send_pwd_reset_info;
 use rauthy_models::entity::app_version::LatestAppVersion;
 use rauthy_models::entity::jwk::Jwk;
+use rauthy_models::entity::provisioning::{ProvisioningDelivery, ProvisioningStatus};
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::sessions::Session;
 use rauthy_models::entity::users::User;
@@ -31,13 +32,20 @@ pub async fn scheduler_main(data: web::Data<AppState>) {
     let rx_health = data.caches.ha_cache_config.rx_health_state.clone();
 
     tokio::spawn(db_backup(data.db.clone()));
+    tokio::spawn(client_assertions_cleanup(
+        data.db.clone(),
+        rx_health.clone(),
+    ));
     tokio::spawn(events_cleanup(data.db.clone(), rx_health.clone()));
     tokio::spawn(magic_link_cleanup(data.db.clone(), rx_health.clone()));
+    tokio::spawn(provisioning_cleanup(data.db.clone(), rx_health.clone()));
     tokio::spawn(refresh_tokens_cleanup(data.db.clone(), rx_health.clone()));
+    tokio::spawn(revoked_tokens_cleanup(data.db.clone(), rx_health.clone()));
     tokio::spawn(sessions_cleanup(data.db.clone(), rx_health.clone()));
     tokio::spawn(jwks_auto_rotate(data.clone(), rx_health.clone()));
     tokio::spawn(jwks_cleanup(data.clone(), rx_health.clone()));
     tokio::spawn(password_expiry_checker(data.clone(), rx_health.clone()));
+    tokio::spawn(provisioning_deliveries(data.clone(), rx_health.clone()));
     tokio::spawn(user_expiry_checker(data.clone(), rx_health.clone()));
     tokio::spawn(app_version_check(data, rx_health));
 }
@@ -354,6 +362,126 @@ pub async fn refresh_tokens_cleanup(db: DbPool, rx_health: Receiver<Option<Quoru
     }
 }
 
+// Cleans up the remembered 'jti's of already expired client assertions
+pub async fn client_assertions_cleanup(db: DbPool, rx_health: Receiver<Option<QuorumHealthState>>) {
+    let mut interval = time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        // will return None in a non-HA deployment
+        if let Some(is_ha_leader) = is_ha_leader(&rx_health) {
+            if !is_ha_leader {
+                debug!("Running HA mode without being the leader - skipping client_assertions_cleanup scheduler");
+                continue;
+            }
+        }
+
+        debug!("Running client_assertions_cleanup scheduler");
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let res = sqlx::query!("delete from client_assertions where exp < $1", now)
+            .execute(&db)
+            .await;
+
+        match res {
+            Ok(_) => {}
+            Err(err) => error!("Client Assertions Cleanup Error: {:?}", err),
+        }
+    }
+}
+
+// Cleans up entries from the revocation list for already expired access tokens
+pub async fn revoked_tokens_cleanup(db: DbPool, rx_health: Receiver<Option<QuorumHealthState>>) {
+    let mut interval = time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        // will return None in a non-HA deployment
+        if let Some(is_ha_leader) = is_ha_leader(&rx_health) {
+            if !is_ha_leader {
+                debug!("Running HA mode without being the leader - skipping revoked_tokens_cleanup scheduler");
+                continue;
+            }
+        }
+
+        debug!("Running revoked_tokens_cleanup scheduler");
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let res = sqlx::query("delete from revoked_tokens where exp < $1")
+            .bind(now)
+            .execute(&db)
+            .await;
+
+        match res {
+            Ok(_) => {}
+            Err(err) => error!("Revoked Tokens Cleanup Error: {:?}", err),
+        }
+    }
+}
+
+// Pushes queued user / group / role changes to the outbound provisioning connectors
+pub async fn provisioning_deliveries(
+    data: web::Data<AppState>,
+    rx_health: Receiver<Option<QuorumHealthState>>,
+) {
+    let mut interval = time::interval(Duration::from_secs(*PROVISIONING_INTERVAL));
+
+    loop {
+        interval.tick().await;
+
+        // will return None in a non-HA deployment
+        if let Some(is_ha_leader) = is_ha_leader(&rx_health) {
+            if !is_ha_leader {
+                debug!("Running HA mode without being the leader - skipping provisioning_deliveries scheduler");
+                continue;
+            }
+        }
+
+        debug!("Running provisioning_deliveries scheduler");
+
+        if let Err(err) = ProvisioningDelivery::process_due(&data).await {
+            error!("Provisioning Deliveries Error: {}", err.message);
+        }
+    }
+}
+
+// Cleans up successful provisioning deliveries after 7 days
+pub async fn provisioning_cleanup(db: DbPool, rx_health: Receiver<Option<QuorumHealthState>>) {
+    let mut interval = time::interval(Duration::from_secs(3600 * 6));
+
+    loop {
+        interval.tick().await;
+
+        // will return None in a non-HA deployment
+        if let Some(is_ha_leader) = is_ha_leader(&rx_health) {
+            if !is_ha_leader {
+                debug!("Running HA mode without being the leader - skipping provisioning_cleanup scheduler");
+                continue;
+            }
+        }
+
+        debug!("Running provisioning_cleanup scheduler");
+
+        let thres = OffsetDateTime::now_utc().sub(::time::Duration::days(7));
+        let thres = (thres.unix_timestamp_nanos() / 1_000_000) as i64;
+
+        let res = sqlx::query!(
+            "delete from provisioning_deliveries where status = $1 and delivered_at < $2",
+            ProvisioningStatus::Delivered.as_str(),
+            thres,
+        )
+        .execute(&db)
+        .await;
+
+        match res {
+            Ok(_) => {}
+            Err(err) => error!("Provisioning Cleanup Error: {:?}", err),
+        }
+    }
+}
+
 // Cleans up old / expired Sessions
 pub async fn sessions_cleanup(db: DbPool, rx_health: Receiver<Option<QuorumHealthState>>) {
     let mut interval = time::interval(Duration::from_secs(3595 * 2));
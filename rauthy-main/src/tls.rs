@@ -1,8 +1,21 @@
+use actix_tls::accept::rustls_0_21::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use rauthy_common::constants::TLS_CLIENT_AUTH;
+use rauthy_models::entity::client_certificates::ClientCertificate;
+use rustls::server::{AllowAnyAuthenticatedClient, ClientCertVerified, ClientCertVerifier};
+use rustls::{Certificate, DistinguishedName, RootCertStore};
 use rustls_pemfile::Item;
+use std::any::Any;
 use std::io::BufReader;
+use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
 use std::{env, iter};
 use tokio::fs;
-use tracing::error;
+use tracing::{error, info, warn};
+
+// validates client certificate chains against the `TLS_CLIENT_AUTH_CA` for `tls_client_auth`
+static CLIENT_CA_VERIFIER: OnceLock<AllowAnyAuthenticatedClient> = OnceLock::new();
 
 // Loads TLS key and cert file from disk and returns a `rustls::ServerConfig`
 pub async fn load_tls() -> rustls::ServerConfig {
@@ -34,10 +47,86 @@ pub async fn load_tls() -> rustls::ServerConfig {
         .map(|cert| rustls::Certificate(cert.to_owned()))
         .collect();
 
-    rustls::ServerConfig::builder()
-        .with_safe_defaults()
-        .with_no_client_auth()
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = if *TLS_CLIENT_AUTH {
+        load_client_ca().await;
+        builder.with_client_cert_verifier(Arc::new(AnyClientCertVerifier))
+    } else {
+        builder.with_no_client_auth()
+    };
+    builder
         .with_single_cert(cert_chain, key)
         .map_err(|err| error!("Error building rustls ServerConfig: {}", err))
         .expect("bad certificate/key")
 }
+
+async fn load_client_ca() {
+    let path = match env::var("TLS_CLIENT_AUTH_CA") {
+        Ok(path) => path,
+        Err(_) => {
+            info!("No TLS_CLIENT_AUTH_CA given - only 'self_signed_tls_client_auth' is possible");
+            return;
+        }
+    };
+
+    let ca_file = fs::read(&path).await.expect("Reading TLS_CLIENT_AUTH_CA");
+    let mut reader = BufReader::new(ca_file.as_slice());
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut reader).expect("bad TLS_CLIENT_AUTH_CA file") {
+        roots
+            .add(&Certificate(cert))
+            .expect("Invalid certificate in TLS_CLIENT_AUTH_CA");
+    }
+    let _ = CLIENT_CA_VERIFIER.set(AllowAnyAuthenticatedClient::new(roots));
+}
+
+/// Extracts the TLS client certificate of each new connection, which will then be available
+/// via `req.conn_data::<ClientCertificate>()`.
+pub fn on_connect(conn: &dyn Any, ext: &mut Extensions) {
+    if let Some(stream) = conn.downcast_ref::<TlsStream<TcpStream>>() {
+        let (_, session) = stream.get_ref();
+        if let Some((leaf, intermediates)) =
+            session.peer_certificates().and_then(|c| c.split_first())
+        {
+            let is_ca_verified = CLIENT_CA_VERIFIER
+                .get()
+                .map(|v| {
+                    v.verify_client_cert(leaf, intermediates, SystemTime::now())
+                        .is_ok()
+                })
+                .unwrap_or(false);
+
+            match ClientCertificate::from_der(&leaf.0, is_ca_verified) {
+                Ok(cert) => {
+                    ext.insert(cert);
+                }
+                Err(err) => warn!("{}", err.message),
+            }
+        }
+    }
+}
+
+/// Accepts any client certificate during the handshake, because self-signed certificates are
+/// valid for `self_signed_tls_client_auth` as well. The handshake signature is still verified,
+/// which proves the possession of the private key. The certificate itself will be validated
+/// against the registered values of the client during the client authentication.
+struct AnyClientCertVerifier;
+
+impl ClientCertVerifier for AnyClientCertVerifier {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+}
@@ -100,6 +100,10 @@ pub async fn session_headers() -> (HeaderMap, TokenSet) {
         nonce: Some("MySuperNonce".to_string()),
         code_challenge: Some(challenge_s256),
         code_challenge_method: Some("S256".to_string()),
+        claims: None,
+        acr_values: None,
+        prompt: None,
+        request_uri: None,
     };
 
     let mut res = reqwest::Client::new()
@@ -118,10 +122,20 @@ pub async fn session_headers() -> (HeaderMap, TokenSet) {
         redirect_uri: None,
         client_id: Some("rauthy".to_string()),
         client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: Some(challenge_plain.to_string()),
         username: None,
         password: None,
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
 
     let url_token = format!("{}/oidc/token", backend_url);
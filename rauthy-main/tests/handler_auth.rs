@@ -8,16 +8,18 @@ use ed25519_compact::Noise;
 use josekit::jwk;
 use pretty_assertions::assert_eq;
 use rauthy_common::constants::{
-    APPLICATION_JSON, DPOP_TOKEN_ENDPOINT, HEADER_DPOP_NONCE, TOKEN_DPOP,
+    APPLICATION_JSON, DPOP_TOKEN_ENDPOINT, GRANT_TYPE_TOKEN_EXCHANGE, HEADER_DPOP_NONCE,
+    TOKEN_DPOP, TOKEN_INTROSPECTION_JWT, TOKEN_TYPE_ACCESS_TOKEN,
 };
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_encode, get_rand};
 use rauthy_models::entity::dpop_proof::{DPoPClaims, DPoPHeader};
 use rauthy_models::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg, JwkKeyPairType, JWKS};
 use rauthy_models::request::{
-    LoginRequest, TokenRequest, TokenValidationRequest, UpdateClientRequest,
+    IntrospectionRequest, LoginRequest, NewClientRequest, RevokeRequest, TokenRequest,
+    TokenValidationRequest, UpdateClientRequest,
 };
-use rauthy_models::response::TokenInfo;
+use rauthy_models::response::{ClientSecretResponse, IntrospectionResponse, TokenInfo};
 use rauthy_models::JwtTokenType;
 use rauthy_service::token_set::TokenSet;
 use ring::digest;
@@ -45,7 +47,7 @@ async fn test_certs() -> Result<(), Box<dyn Error>> {
     let res = reqwest::get(&url).await?;
     assert_eq!(res.status(), 200);
     let certs = res.json::<JWKS>().await?;
-    assert_eq!(certs.keys.len(), 4);
+    assert_eq!(certs.keys.len(), 7);
 
     // for _ in 1..1000 {
     //     aw!(reqwest::get(&url)).unwrap();
@@ -63,7 +65,7 @@ async fn test_certs() -> Result<(), Box<dyn Error>> {
     let res = reqwest::get(&url).await?;
     assert_eq!(res.status(), 200);
     let new_certs = res.json::<JWKS>().await?;
-    assert_eq!(new_certs.keys.len(), 8);
+    assert_eq!(new_certs.keys.len(), 14);
 
     Ok(())
 }
@@ -102,6 +104,10 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         nonce: Some(nonce.to_owned()),
         code_challenge: Some(challenge_plain.to_owned()),
         code_challenge_method: None,
+        claims: None,
+        acr_values: None,
+        prompt: None,
+        request_uri: None,
     };
     let res = reqwest::Client::new()
         .post(&url_auth)
@@ -132,10 +138,20 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: None,
         password: None,
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
     let url_token = format!("{}/oidc/token", backend_url);
     let res = reqwest::Client::new()
@@ -278,6 +294,25 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         default_scopes: vec!["openid".to_string(), "email".to_string()],
         challenges: None,
         force_mfa: false,
+        token_exchange_audiences: None,
+        backchannel_logout_uri: None,
+        frontchannel_logout_uri: None,
+        require_par: false,
+        require_consent: false,
+        refresh_token_rotation: false,
+        request_uris: None,
+        jwks: None,
+        jwks_uri: None,
+        token_endpoint_auth_method: None,
+        tls_client_auth_subject_dn: None,
+        tls_client_auth_thumbprints: None,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        subject_type: None,
+        sector_identifier_uri: None,
     };
     let url_client = format!("{}/clients/{}", backend_url, CLIENT_ID);
     let auth_headers = get_auth_headers().await?;
@@ -347,10 +382,20 @@ async fn test_client_credentials_flow() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: None,
         password: None,
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
     let url = format!("{}/oidc/token", backend_url);
     let client = reqwest::Client::new();
@@ -372,6 +417,7 @@ async fn test_client_credentials_flow() -> Result<(), Box<dyn Error>> {
 
     let req = TokenValidationRequest {
         token: ts.access_token,
+        x5t_s256: None,
     };
     validate_token(req).await?;
 
@@ -416,6 +462,10 @@ async fn test_concurrent_logins() -> Result<(), Box<dyn Error>> {
         nonce: None,
         code_challenge: Some(challenge_plain.to_owned()),
         code_challenge_method: None,
+        claims: None,
+        acr_values: None,
+        prompt: None,
+        request_uri: None,
     };
 
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
@@ -476,10 +526,20 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: Some(USERNAME.to_string()),
         password: None,
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
     let client = reqwest::Client::new();
     let res = client.post(&url).form(&body).send().await?;
@@ -518,6 +578,7 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
     // validate against the backend
     let req = TokenValidationRequest {
         token: ts.access_token.to_owned(),
+        x5t_s256: None,
     };
     validate_token(req).await?;
 
@@ -529,10 +590,20 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: None,
         password: None,
         refresh_token: Some(ts.refresh_token.clone().unwrap()),
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
     let url = format!("{}/oidc/token", get_backend_url());
     let res = reqwest::Client::new().post(&url).form(&req).send().await?;
@@ -552,6 +623,608 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_token_revocation() -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/oidc/token", get_backend_url());
+    let body = TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
+        code_verifier: None,
+        username: Some(USERNAME.to_string()),
+        password: Some(PASSWORD.to_string()),
+        refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
+    };
+    let client = reqwest::Client::new();
+    let mut res = client.post(&url).form(&body).send().await?;
+    res = check_status(res, 200).await?;
+    let ts = res.json::<TokenSet>().await?;
+
+    let url_revoke = format!("{}/oidc/revoke", get_backend_url());
+    let mut req = RevokeRequest {
+        token: ts.access_token.clone(),
+        token_type_hint: Some("access_token".to_string()),
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    // should be 401 because of the missing client secret
+    let res = client.post(&url_revoke).form(&req).send().await?;
+    check_status(res, 401).await?;
+
+    req.client_secret = Some(CLIENT_SECRET.to_string());
+    let res = client.post(&url_revoke).form(&req).send().await?;
+    check_status(res, 200).await?;
+
+    let info = validate_token(TokenValidationRequest {
+        token: ts.access_token,
+        x5t_s256: None,
+    })
+    .await?;
+    assert!(!info.active);
+
+    // revoking it again must not lead to an error
+    let res = client.post(&url_revoke).form(&req).send().await?;
+    check_status(res, 200).await?;
+
+    // revoke the refresh token, which must not be usable afterward
+    req.token = ts.refresh_token.clone().unwrap();
+    req.token_type_hint = Some("refresh_token".to_string());
+    let res = client.post(&url_revoke).form(&req).send().await?;
+    check_status(res, 200).await?;
+
+    let body = TokenRequest {
+        grant_type: "refresh_token".to_string(),
+        username: None,
+        password: None,
+        refresh_token: ts.refresh_token,
+        ..body
+    };
+    let res = client.post(&url).form(&body).send().await?;
+    assert_ne!(res.status(), 200);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refresh_token_reuse() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let auth_headers = get_auth_headers().await?;
+    let client_id = "refresh_rotation";
+    let redirect_uri = "http://localhost:3000/oidc/callback";
+
+    // a dedicated client, which does not interfere with the other tests
+    let url_clients = format!("{}/clients", backend_url);
+    let new_client = NewClientRequest {
+        id: client_id.to_string(),
+        secret: None,
+        name: Some("Refresh Rotation".to_string()),
+        confidential: false,
+        redirect_uris: vec![redirect_uri.to_string()],
+        post_logout_redirect_uris: None,
+    };
+    let res = reqwest::Client::new()
+        .post(&url_clients)
+        .headers(auth_headers.clone())
+        .json(&new_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let update_client = UpdateClientRequest {
+        id: client_id.to_string(),
+        name: Some("Refresh Rotation".to_string()),
+        confidential: false,
+        redirect_uris: vec![redirect_uri.to_string()],
+        post_logout_redirect_uris: None,
+        allowed_origins: None,
+        enabled: true,
+        flows_enabled: vec![
+            "authorization_code".to_string(),
+            "refresh_token".to_string(),
+        ],
+        access_token_alg: JwkKeyPairAlg::RS256,
+        id_token_alg: JwkKeyPairAlg::RS256,
+        refresh_token: true,
+        auth_code_lifetime: 60,
+        access_token_lifetime: 60,
+        scopes: vec!["openid".to_string(), "email".to_string()],
+        default_scopes: vec!["openid".to_string()],
+        challenges: Some(vec!["S256".to_string()]),
+        force_mfa: false,
+        token_exchange_audiences: None,
+        backchannel_logout_uri: None,
+        frontchannel_logout_uri: None,
+        require_par: false,
+        require_consent: false,
+        refresh_token_rotation: true,
+        request_uris: None,
+        jwks: None,
+        jwks_uri: None,
+        token_endpoint_auth_method: None,
+        tls_client_auth_subject_dn: None,
+        tls_client_auth_thumbprints: None,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+    };
+    let url_client = format!("{}/clients/{}", backend_url, client_id);
+    let res = reqwest::Client::new()
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&update_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    // login to get a session and the first refresh token of a new family
+    let challenge_plain = get_rand(64);
+    let hash = digest::digest(&digest::SHA256, challenge_plain.as_bytes());
+    let challenge_s256 = base64_url_encode(hash.as_ref());
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id={}&redirect_uri={}&response_type=code&code_challenge={}\
+        &code_challenge_method=S256",
+        backend_url, client_id, redirect_uri, challenge_s256
+    );
+    let mut res = reqwest::get(&url_auth).await?;
+    res = check_status(res, 200).await?;
+    let session_headers = cookie_csrf_headers_from_res(res).await?;
+
+    let req_login = LoginRequest {
+        email: USERNAME.to_string(),
+        password: Some(PASSWORD.to_string()),
+        client_id: client_id.to_string(),
+        redirect_uri: redirect_uri.to_string(),
+        scopes: None,
+        state: None,
+        nonce: None,
+        code_challenge: Some(challenge_s256),
+        code_challenge_method: Some("S256".to_string()),
+        claims: None,
+        acr_values: None,
+        prompt: None,
+        request_uri: None,
+    };
+    let mut res = reqwest::Client::new()
+        .post(&url_auth)
+        .headers(session_headers.clone())
+        .json(&req_login)
+        .send()
+        .await?;
+    res = check_status(res, 202).await?;
+    let (code, _) = code_state_from_headers(res)?;
+
+    let url_token = format!("{}/oidc/token", backend_url);
+    let req_token = TokenRequest {
+        grant_type: "authorization_code".to_string(),
+        code: Some(code),
+        redirect_uri: None,
+        client_id: Some(client_id.to_string()),
+        client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
+        code_verifier: Some(challenge_plain),
+        username: None,
+        password: None,
+        refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
+    };
+    let client = reqwest::Client::new();
+    let mut res = client.post(&url_token).form(&req_token).send().await?;
+    res = check_status(res, 200).await?;
+    let ts = res.json::<TokenSet>().await?;
+    let rt_old = ts.refresh_token.unwrap();
+
+    let url_session = format!("{}/oidc/sessioninfo", backend_url);
+    let res = client
+        .get(&url_session)
+        .headers(session_headers.clone())
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    // rotate the refresh token
+    time::sleep(Duration::from_secs(1)).await;
+    let req_refresh = TokenRequest {
+        grant_type: "refresh_token".to_string(),
+        code: None,
+        code_verifier: None,
+        refresh_token: Some(rt_old.clone()),
+        ..req_token
+    };
+    let mut res = client.post(&url_token).form(&req_refresh).send().await?;
+    res = check_status(res, 200).await?;
+    let ts_new = res.json::<TokenSet>().await?;
+    let rt_new = ts_new.refresh_token.unwrap();
+    assert_ne!(rt_old, rt_new);
+
+    // replay the already rotated one
+    let res = client.post(&url_token).form(&req_refresh).send().await?;
+    check_status(res, 400).await?;
+
+    // the whole family must be revoked, even the newest token which has never been used
+    let req_refresh = TokenRequest {
+        refresh_token: Some(rt_new),
+        ..req_refresh
+    };
+    let res = client.post(&url_token).form(&req_refresh).send().await?;
+    assert_ne!(res.status(), 200);
+
+    // the session the family originates from must be revoked as well
+    let res = client
+        .get(&url_session)
+        .headers(session_headers)
+        .send()
+        .await?;
+    check_status(res, 401).await?;
+
+    // the reuse must have been reported
+    let url_events = format!("{}/events/stream?latest=100", backend_url);
+    let mut res = client
+        .get(&url_events)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    let mut stream = String::new();
+    let found = time::timeout(Duration::from_secs(10), async {
+        while let Ok(Some(chunk)) = res.chunk().await {
+            stream.push_str(&String::from_utf8_lossy(&chunk));
+            if stream.lines().any(|line| {
+                line.contains("\"typ\":\"RefreshTokenReuse\"")
+                    && line.contains(&format!("Client: {}", client_id))
+            }) {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap_or(false);
+    assert!(found, "no RefreshTokenReuse event received: {}", stream);
+
+    // cleanup
+    let res = client
+        .delete(&url_client)
+        .headers(auth_headers)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_introspection() -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/oidc/token", get_backend_url());
+    let body = TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
+        code_verifier: None,
+        username: Some(USERNAME.to_string()),
+        password: Some(PASSWORD.to_string()),
+        refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
+    };
+    let client = reqwest::Client::new();
+    let mut res = client.post(&url).form(&body).send().await?;
+    res = check_status(res, 200).await?;
+    let ts = res.json::<TokenSet>().await?;
+
+    let url_introspect = format!("{}/oidc/introspect", get_backend_url());
+    let mut req = IntrospectionRequest {
+        token: ts.access_token.clone(),
+        token_type_hint: None,
+        client_id: None,
+        client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    // should be 401 without any authentication
+    let res = client.post(&url_introspect).form(&req).send().await?;
+    check_status(res, 401).await?;
+
+    // client authentication
+    req.client_id = Some(CLIENT_ID.to_string());
+    req.client_secret = Some(CLIENT_SECRET.to_string());
+    let mut res = client.post(&url_introspect).form(&req).send().await?;
+    res = check_status(res, 200).await?;
+    let info = res.json::<IntrospectionResponse>().await?;
+    assert!(info.active);
+    assert_eq!(info.client_id.as_deref(), Some(CLIENT_ID));
+    assert_eq!(info.token_type.as_deref(), Some("Bearer"));
+    assert_eq!(info.sub.as_deref(), Some(USERNAME));
+    assert_eq!(info.username.as_deref(), Some(USERNAME));
+    assert!(info.iss.is_some());
+    assert!(info.exp.is_some());
+    assert!(info.iat.is_some());
+
+    // refresh tokens
+    let req = IntrospectionRequest {
+        token: ts.refresh_token.clone().unwrap(),
+        token_type_hint: Some("refresh_token".to_string()),
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let mut res = client.post(&url_introspect).form(&req).send().await?;
+    res = check_status(res, 200).await?;
+    let info = res.json::<IntrospectionResponse>().await?;
+    assert!(info.active);
+    assert_eq!(info.token_type.as_deref(), Some("refresh_token"));
+
+    // signed JWT response
+    let mut res = client
+        .post(&url_introspect)
+        .header(http::header::ACCEPT, TOKEN_INTROSPECTION_JWT)
+        .form(&req)
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    assert_eq!(
+        res.headers()
+            .get(http::header::CONTENT_TYPE)
+            .unwrap()
+            .to_str()?,
+        TOKEN_INTROSPECTION_JWT
+    );
+    let jwt = res.text().await?;
+    assert_eq!(jwt.split('.').count(), 3);
+
+    // an access token is not enough to authenticate anymore
+    let req = IntrospectionRequest {
+        client_id: None,
+        client_secret: None,
+        ..req
+    };
+    let res = client
+        .post(&url_introspect)
+        .bearer_auth(&ts.access_token)
+        .form(&req)
+        .send()
+        .await?;
+    check_status(res, 401).await?;
+
+    // invalid tokens must only be inactive
+    let req = IntrospectionRequest {
+        token: "invalid".to_string(),
+        token_type_hint: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let mut res = client.post(&url_introspect).form(&req).send().await?;
+    res = check_status(res, 200).await?;
+    let info = res.json::<IntrospectionResponse>().await?;
+    assert!(!info.active);
+    assert!(info.client_id.is_none());
+
+    // tokens of other clients must be inactive as well
+    let backend_url = get_backend_url();
+    let auth_headers = get_auth_headers().await?;
+    let client_id = "introspection_b";
+    let new_client = NewClientRequest {
+        id: client_id.to_string(),
+        secret: None,
+        name: Some("Introspection B".to_string()),
+        confidential: true,
+        redirect_uris: vec!["http://localhost:3000/oidc/callback".to_string()],
+        post_logout_redirect_uris: None,
+    };
+    let res = client
+        .post(format!("{}/clients", backend_url))
+        .headers(auth_headers.clone())
+        .json(&new_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let url_client = format!("{}/clients/{}", backend_url, client_id);
+    let mut res = client
+        .get(format!("{}/secret", url_client))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    let secret = res.json::<ClientSecretResponse>().await?.secret;
+
+    let req = IntrospectionRequest {
+        token: ts.access_token.clone(),
+        token_type_hint: None,
+        client_id: Some(client_id.to_string()),
+        client_secret: secret,
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let mut res = client.post(&url_introspect).form(&req).send().await?;
+    res = check_status(res, 200).await?;
+    let info = res.json::<IntrospectionResponse>().await?;
+    assert!(!info.active);
+    assert!(info.sub.is_none());
+
+    // cleanup
+    let res = client
+        .delete(&url_client)
+        .headers(auth_headers)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_exchange_foreign_subject() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let auth_headers = get_auth_headers().await?;
+    let client_id = "token_exchange_b";
+    let redirect_uri = "http://localhost:3000/oidc/callback";
+    let client = reqwest::Client::new();
+
+    // a second, confidential client which is allowed to do token exchange
+    let url_clients = format!("{}/clients", backend_url);
+    let new_client = NewClientRequest {
+        id: client_id.to_string(),
+        secret: None,
+        name: Some("Token Exchange B".to_string()),
+        confidential: true,
+        redirect_uris: vec![redirect_uri.to_string()],
+        post_logout_redirect_uris: None,
+    };
+    let res = client
+        .post(&url_clients)
+        .headers(auth_headers.clone())
+        .json(&new_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let update_client = UpdateClientRequest {
+        id: client_id.to_string(),
+        name: Some("Token Exchange B".to_string()),
+        confidential: true,
+        redirect_uris: vec![redirect_uri.to_string()],
+        post_logout_redirect_uris: None,
+        allowed_origins: None,
+        enabled: true,
+        flows_enabled: vec![GRANT_TYPE_TOKEN_EXCHANGE.to_string()],
+        access_token_alg: JwkKeyPairAlg::RS256,
+        id_token_alg: JwkKeyPairAlg::RS256,
+        refresh_token: false,
+        auth_code_lifetime: 60,
+        access_token_lifetime: 60,
+        scopes: vec!["openid".to_string(), "email".to_string()],
+        default_scopes: vec!["openid".to_string()],
+        challenges: None,
+        force_mfa: false,
+        token_exchange_audiences: None,
+        backchannel_logout_uri: None,
+        frontchannel_logout_uri: None,
+        require_par: false,
+        require_consent: false,
+        refresh_token_rotation: false,
+        request_uris: None,
+        jwks: None,
+        jwks_uri: None,
+        token_endpoint_auth_method: None,
+        tls_client_auth_subject_dn: None,
+        tls_client_auth_thumbprints: None,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+    };
+    let url_client = format!("{}/clients/{}", backend_url, client_id);
+    let res = client
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&update_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let url_secret = format!("{}/clients/{}/secret", backend_url, client_id);
+    let mut res = client
+        .get(&url_secret)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    let secret = res.json::<ClientSecretResponse>().await?.secret.unwrap();
+
+    // an access token issued to the init_client
+    let url_token = format!("{}/oidc/token", backend_url);
+    let req_token = TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
+        code_verifier: None,
+        username: Some(USERNAME.to_string()),
+        password: Some(PASSWORD.to_string()),
+        refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
+    };
+    let mut res = client.post(&url_token).form(&req_token).send().await?;
+    res = check_status(res, 200).await?;
+    let ts = res.json::<TokenSet>().await?;
+
+    // client B must not be able to exchange a token which has been issued to another client
+    let req_exchange = TokenRequest {
+        grant_type: GRANT_TYPE_TOKEN_EXCHANGE.to_string(),
+        client_id: Some(client_id.to_string()),
+        client_secret: Some(secret),
+        username: None,
+        password: None,
+        subject_token: Some(ts.access_token),
+        subject_token_type: Some(TOKEN_TYPE_ACCESS_TOKEN.to_string()),
+        ..req_token
+    };
+    let res = client.post(&url_token).form(&req_exchange).send().await?;
+    check_status(res, 403).await?;
+
+    // cleanup
+    let res = client
+        .delete(&url_client)
+        .headers(auth_headers)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_dpop() -> Result<(), Box<dyn Error>> {
     let client = reqwest::Client::new();
@@ -565,10 +1238,20 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: Some(USERNAME.to_string()),
         password: Some(PASSWORD.to_string()),
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
 
     // dpop header
@@ -586,6 +1269,7 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
             n: None,
             e: None,
             x: Some(base64_url_encode(kp.pk.as_slice())),
+            y: None,
         },
         kid: None,
     };
@@ -660,10 +1344,14 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
     assert_eq!(ts.token_type, JwtTokenType::DPoP);
     let req = TokenValidationRequest {
         token: ts.access_token.to_owned(),
+        x5t_s256: None,
     };
     let token_info = validate_token(req).await?;
     assert!(token_info.cnf.is_some());
-    assert_eq!(token_info.cnf.unwrap().jkt, fingerprint);
+    assert_eq!(
+        token_info.cnf.unwrap().jkt.as_deref(),
+        Some(fingerprint.as_str())
+    );
 
     // refresh it
     time::sleep(Duration::from_secs(1)).await;
@@ -673,10 +1361,20 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: None,
         password: None,
         refresh_token: Some(ts.refresh_token.clone().unwrap()),
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
 
     // without DPoP header, it should fail
@@ -700,10 +1398,14 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
     assert_eq!(ts.token_type, JwtTokenType::DPoP);
     let req = TokenValidationRequest {
         token: ts.access_token.to_owned(),
+        x5t_s256: None,
     };
     let token_info = validate_token(req).await?;
     assert!(token_info.cnf.is_some());
-    assert_eq!(token_info.cnf.unwrap().jkt, fingerprint);
+    assert_eq!(
+        token_info.cnf.unwrap().jkt.as_deref(),
+        Some(fingerprint.as_str())
+    );
 
     Ok(())
 }
@@ -754,6 +1456,10 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         nonce: Some(nonce.to_owned()),
         code_challenge: Some(challenge_s256),
         code_challenge_method: Some("S256".to_string()),
+        claims: None,
+        acr_values: None,
+        prompt: None,
+        request_uri: None,
     };
     let res = client
         .post(&url_auth)
@@ -773,10 +1479,20 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         redirect_uri: None,
         client_id: Some(client_id.to_string()),
         client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: Some(challenge_plain.to_string()),
         username: None,
         password: None,
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
 
     let url_token = format!("{}/oidc/token", backend_url);
@@ -797,10 +1513,20 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         redirect_uri: None,
         client_id: Some(client_id.to_string()),
         client_secret: None,
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: None,
         password: None,
         refresh_token: Some(ts.refresh_token.clone().unwrap()),
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
     let res = client.post(&url_token).form(&req).send().await?;
     assert!(res.status().is_success());
@@ -142,10 +142,20 @@ async fn test_get_pwd_reset_form() -> Result<(), Box<dyn Error>> {
         redirect_uri: None,
         client_id: Some(CLIENT_ID.to_string()),
         client_secret: Some(CLIENT_SECRET.to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
         code_verifier: None,
         username: Some(username.to_string()),
         password: Some(req.password.to_string()),
         refresh_token: None,
+        scope: None,
+        audience: None,
+        subject_token: None,
+        subject_token_type: None,
+        actor_token: None,
+        actor_token_type: None,
+        requested_token_type: None,
+        device_code: None,
     };
     let client = reqwest::Client::new();
     let res = client.post(&url).form(&body).send().await?;
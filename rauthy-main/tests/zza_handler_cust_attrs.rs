@@ -126,6 +126,25 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         default_scopes,
         challenges: c.challenges,
         force_mfa: c.force_mfa,
+        token_exchange_audiences: c.token_exchange_audiences,
+        backchannel_logout_uri: c.backchannel_logout_uri,
+        frontchannel_logout_uri: c.frontchannel_logout_uri,
+        require_par: c.require_par,
+        require_consent: c.require_consent,
+        refresh_token_rotation: c.refresh_token_rotation,
+        request_uris: c.request_uris,
+        jwks: c.jwks,
+        jwks_uri: c.jwks_uri,
+        token_endpoint_auth_method: Some(c.token_endpoint_auth_method),
+        tls_client_auth_subject_dn: c.tls_client_auth_subject_dn,
+        tls_client_auth_thumbprints: c.tls_client_auth_thumbprints,
+        id_token_encrypted_response_alg: c.id_token_encrypted_response_alg,
+        id_token_encrypted_response_enc: c.id_token_encrypted_response_enc,
+        userinfo_encrypted_response_alg: c.userinfo_encrypted_response_alg,
+        userinfo_encrypted_response_enc: c.userinfo_encrypted_response_enc,
+        userinfo_signed_response_alg: c.userinfo_signed_response_alg,
+        subject_type: c.subject_type,
+        sector_identifier_uri: c.sector_identifier_uri,
     };
     let res = client
         .put(&url_client)
@@ -116,6 +116,25 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
         ],
         challenges: Some(vec!["S256".to_string(), "plain".to_string()]),
         force_mfa: false,
+        token_exchange_audiences: None,
+        backchannel_logout_uri: None,
+        frontchannel_logout_uri: None,
+        require_par: false,
+        require_consent: false,
+        refresh_token_rotation: false,
+        request_uris: None,
+        jwks: None,
+        jwks_uri: None,
+        token_endpoint_auth_method: None,
+        tls_client_auth_subject_dn: None,
+        tls_client_auth_thumbprints: None,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        subject_type: None,
+        sector_identifier_uri: None,
     };
 
     let url_id = format!("{}/clients/{}", backend_url, client.id);
@@ -362,6 +362,11 @@ impl AppState {
             migrate_dev_data(&pool).await.expect("Migrating DEV DATA");
         }
 
+        // new signing algorithms need their keys on existing databases
+        db_migrate::migrate_missing_jwks(&pool, enc_key_active, enc_key)
+            .await
+            .map_err(|err| anyhow::Error::msg(err.message))?;
+
         if let Err(err) = db_migrate::anti_lockout(&pool, issuer).await {
             error!("Error when applying anti-lockout check: {:?}", err);
         }
@@ -264,8 +264,12 @@ pub enum AccessGroup {
     Secrets,
     Sessions,
     Scopes,
+    UpstreamProviders,
     UserAttributes,
     Users,
+    // The access is stored serialized with bincode, which is why new groups must always be
+    // appended at the end to keep existing keys valid.
+    Scim,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -1,4 +1,5 @@
 use crate::app_state::AppState;
+use crate::request::ClaimsRequest;
 use actix_web::web;
 use rauthy_common::constants::CACHE_NAME_AUTH_CODES;
 use rauthy_common::error_response::ErrorResponse;
@@ -20,6 +21,15 @@ pub struct AuthCode {
     pub challenge_method: Option<String>,
     pub nonce: Option<String>,
     pub scopes: Vec<String>,
+    pub claims: Option<ClaimsRequest>,
+    // the satisfied authentication context, only set if one has been requested
+    pub acr: Option<String>,
+    // overwrites the `amr` derived from the users MFA methods, like after a recovery code login
+    pub amr: Option<Vec<String>>,
+    pub auth_time: Option<i64>,
+    // a code cannot be exchanged, as long as the consent for the client is still pending
+    #[serde(default)]
+    pub consent_pending: bool,
 }
 
 // CRUD
@@ -74,6 +84,9 @@ impl AuthCode {
         challenge_method: Option<String>,
         nonce: Option<String>,
         scopes: Vec<String>,
+        claims: Option<ClaimsRequest>,
+        acr: Option<String>,
+        auth_time: Option<i64>,
         lifetime_secs: i32,
     ) -> Self {
         let id = get_rand(64);
@@ -90,6 +103,11 @@ impl AuthCode {
             challenge_method,
             nonce,
             scopes,
+            claims,
+            acr,
+            amr: None,
+            auth_time,
+            consent_pending: false,
         }
     }
 }
@@ -0,0 +1,254 @@
+use crate::app_state::AppState;
+use crate::entity::clients::Client;
+use crate::entity::jwk::JwkKeyPairAlg;
+use crate::entity::request_objects::RequestObjectAud;
+use actix_web::web;
+use rauthy_common::constants::CLIENT_ASSERTION_MAX_LIFETIME;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::base64_url_no_pad_decode;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use time::OffsetDateTime;
+
+/// The claims of a JWT client assertion (RFC 7523), which is used for the `private_key_jwt` and
+/// `client_secret_jwt` client authentication.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClientAssertion {
+    pub iss: String,
+    pub sub: String,
+    pub aud: RequestObjectAud,
+    pub exp: i64,
+    pub nbf: Option<i64>,
+    pub iat: Option<i64>,
+    pub jti: String,
+}
+
+impl ClientAssertion {
+    /// Returns the `sub` of the given assertion without validating it. This is only used to find
+    /// the client, since the `client_id` is optional when a client assertion is sent.
+    pub fn unverified_sub(token: &str) -> Result<String, ErrorResponse> {
+        Ok(Self::decode_claims(token)?.sub)
+    }
+
+    /// Validates the signature of the given assertion depending on the
+    /// `token_endpoint_auth_method` of the client and checks its claims afterward.
+    /// The `jti` will be remembered to reject any replay of the same assertion.
+    pub async fn validate(
+        data: &web::Data<AppState>,
+        client: &Client,
+        token: &str,
+    ) -> Result<(), ErrorResponse> {
+        let metadata = jwt_simple::token::Token::decode_metadata(token).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Malformed client assertion".to_string(),
+            )
+        })?;
+
+        let is_valid = match client.token_endpoint_auth_method.as_str() {
+            "client_secret_jwt" => {
+                let secret = client.get_secret_cleartext(data)?.ok_or_else(|| {
+                    ErrorResponse::new(
+                        ErrorResponseType::Internal,
+                        format!("'{}' has no secret while being confidential", client.id),
+                    )
+                })?;
+                Self::verify_hmac(secret.as_bytes(), metadata.algorithm(), token)
+            }
+            "private_key_jwt" => {
+                // this rejects unsigned assertions with 'alg: none' as well
+                let alg = JwkKeyPairAlg::from_str(metadata.algorithm()).map_err(|_| {
+                    ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!(
+                            "Unsupported 'alg' for the client assertion: {}",
+                            metadata.algorithm()
+                        ),
+                    )
+                })?;
+                client
+                    .validate_jwt_signature(data, token, &alg, metadata.key_id())
+                    .await?
+            }
+            _ => false,
+        };
+        if !is_valid {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Invalid client assertion signature".to_string(),
+            ));
+        }
+
+        let slf = Self::decode_claims(token)?;
+        slf.validate_claims(
+            &data.issuer,
+            &client.id,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )?;
+        slf.check_replay(data).await
+    }
+
+    /// Remembers the `jti` until the assertion expires. The primary key makes this atomic, so
+    /// concurrent requests with the same assertion will be rejected as well.
+    async fn check_replay(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        let res = sqlx::query!(
+            "insert into client_assertions (client_id, jti, exp) values ($1, $2, $3)",
+            self.sub,
+            self.jti,
+            self.exp,
+        )
+        .execute(&data.db)
+        .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "The client assertion has been used already".to_string(),
+                ))
+            }
+            Err(err) => Err(ErrorResponse::from(err)),
+        }
+    }
+
+    fn decode_claims(token: &str) -> Result<Self, ErrorResponse> {
+        let claims = token.split('.').nth(1).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Malformed client assertion".to_string(),
+            )
+        })?;
+        let slf = serde_json::from_slice::<Self>(&base64_url_no_pad_decode(claims)?)?;
+        Ok(slf)
+    }
+
+    fn validate_claims(
+        &self,
+        issuer: &str,
+        client_id: &str,
+        now: i64,
+    ) -> Result<(), ErrorResponse> {
+        let err = |msg: &str| ErrorResponse::new(ErrorResponseType::Unauthorized, msg.to_string());
+
+        if self.iss != client_id || self.sub != client_id {
+            return Err(err(
+                "The client assertion 'iss' and 'sub' must match the 'client_id'",
+            ));
+        }
+
+        // the issuer itself or any of its endpoints, like the token endpoint, is a valid audience
+        let endpoints = format!("{}/oidc/", issuer);
+        let is_valid_aud = |aud: &String| aud == issuer || aud.starts_with(&endpoints);
+        let is_valid = match &self.aud {
+            RequestObjectAud::Single(aud) => is_valid_aud(aud),
+            RequestObjectAud::Multiple(auds) => auds.iter().any(is_valid_aud),
+        };
+        if !is_valid {
+            return Err(err("The client assertion 'aud' does not match the issuer"));
+        }
+
+        if self.exp < now {
+            return Err(err("The client assertion has expired"));
+        }
+        // the 'jti' is only remembered for this time
+        if self.exp > now + *CLIENT_ASSERTION_MAX_LIFETIME as i64 {
+            return Err(err("The client assertion lifetime is too long"));
+        }
+        if matches!(self.nbf, Some(nbf) if nbf > now) {
+            return Err(err("The client assertion is not valid yet"));
+        }
+        if self.jti.is_empty() {
+            return Err(err("The client assertion 'jti' is missing"));
+        }
+
+        Ok(())
+    }
+
+    fn verify_hmac(secret: &[u8], alg: &str, token: &str) -> bool {
+        let algorithm = match alg {
+            "HS256" => hmac::HMAC_SHA256,
+            "HS384" => hmac::HMAC_SHA384,
+            "HS512" => hmac::HMAC_SHA512,
+            _ => return false,
+        };
+
+        if let Some((message, sig)) = token.rsplit_once('.') {
+            if let Ok(sig) = base64_url_no_pad_decode(sig) {
+                let key = hmac::Key::new(algorithm, secret);
+                return hmac::verify(&key, message.as_bytes(), &sig).is_ok();
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_common::utils::base64_url_no_pad_encode;
+
+    #[test]
+    fn test_verify_hmac() {
+        let secret = b"SuperSafeSecret1337";
+        let header = base64_url_no_pad_encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = base64_url_no_pad_encode(br#"{"iss":"test","sub":"test"}"#);
+        let input = format!("{}.{}", header, claims);
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        let sig = hmac::sign(&key, input.as_bytes());
+        let token = format!("{}.{}", input, base64_url_no_pad_encode(sig.as_ref()));
+
+        assert!(ClientAssertion::verify_hmac(secret, "HS256", &token));
+        assert!(!ClientAssertion::verify_hmac(secret, "HS512", &token));
+        assert!(!ClientAssertion::verify_hmac(
+            b"WrongSecret",
+            "HS256",
+            &token
+        ));
+        assert!(!ClientAssertion::verify_hmac(secret, "none", &token));
+    }
+
+    #[test]
+    fn test_validate_claims() {
+        let issuer = "https://iam.example.com/auth/v1";
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        let assertion = ClientAssertion {
+            iss: "test".to_string(),
+            sub: "test".to_string(),
+            aud: RequestObjectAud::Single(format!("{}/oidc/token", issuer)),
+            exp: now + 60,
+            nbf: None,
+            iat: Some(now),
+            jti: "jti1337".to_string(),
+        };
+        assert!(assertion.validate_claims(issuer, "test", now).is_ok());
+        assert!(assertion.validate_claims(issuer, "other", now).is_err());
+        assert!(assertion
+            .validate_claims("https://other.example.com", "test", now)
+            .is_err());
+        assert!(assertion
+            .validate_claims(issuer, "test", now + 120)
+            .is_err());
+        // the lifetime must not exceed the time the 'jti' is remembered
+        assert!(assertion
+            .validate_claims(issuer, "test", now - 3600)
+            .is_err());
+
+        let assertion = ClientAssertion {
+            aud: RequestObjectAud::Multiple(vec![
+                "https://other.example.com".to_string(),
+                issuer.to_string(),
+            ]),
+            sub: "other".to_string(),
+            ..assertion
+        };
+        assert!(assertion.validate_claims(issuer, "test", now).is_err());
+        let assertion = ClientAssertion {
+            sub: "test".to_string(),
+            ..assertion
+        };
+        assert!(assertion.validate_claims(issuer, "test", now).is_ok());
+    }
+}
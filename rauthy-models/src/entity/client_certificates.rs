@@ -0,0 +1,82 @@
+use actix_web::HttpRequest;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::base64_url_no_pad_encode;
+use ring::digest;
+
+/// The TLS client certificate, which has been presented by the peer during the handshake on the
+/// HTTPS listener with `TLS_CLIENT_AUTH` enabled. It is extracted once for each new connection
+/// and is used for the mutual-TLS client authentication and certificate-bound tokens (RFC 8705).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// The base64 url encoded SHA-256 thumbprint of the DER encoded certificate (`x5t#S256`)
+    pub thumbprint: String,
+    /// The subject DN in its string representation, like `CN=client,O=Example`
+    pub subject_dn: String,
+    /// `true` if the certificate chain is valid for the configured `TLS_CLIENT_AUTH_CA`
+    pub is_ca_verified: bool,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &[u8], is_ca_verified: bool) -> Result<Self, ErrorResponse> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Cannot parse the TLS client certificate: {}", err),
+            )
+        })?;
+
+        Ok(Self {
+            thumbprint: Self::thumbprint(der),
+            subject_dn: Self::normalize_dn(&cert.subject().to_string()),
+            is_ca_verified,
+        })
+    }
+
+    /// Returns the client certificate of the connection the given request has been received on.
+    pub fn from_req(req: &HttpRequest) -> Option<Self> {
+        req.conn_data::<Self>().cloned()
+    }
+
+    /// Removes the whitespace around the separators of the given subject DN to make different
+    /// string representations of the same DN comparable.
+    pub fn normalize_dn(dn: &str) -> String {
+        dn.split(',')
+            .map(|rdn| {
+                rdn.split('=')
+                    .map(|part| part.trim())
+                    .collect::<Vec<&str>>()
+                    .join("=")
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub fn thumbprint(der: &[u8]) -> String {
+        let hash = digest::digest(&digest::SHA256, der);
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_dn() {
+        assert_eq!(
+            ClientCertificate::normalize_dn("CN=client, O=Example Org,C = DE"),
+            "CN=client,O=Example Org,C=DE"
+        );
+        assert_eq!(
+            ClientCertificate::normalize_dn("CN=client"),
+            ClientCertificate::normalize_dn(" CN = client ")
+        );
+    }
+
+    #[test]
+    fn test_thumbprint() {
+        // must be the base64 url encoded SHA-256 without any padding
+        let thumbprint = ClientCertificate::thumbprint(b"test");
+        assert_eq!(thumbprint, "n4bQgYhMfWWaL-qgxVrQFaO_TxsrC4Is0V1sFbDwCgg");
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::OnceLock;
 use std::time::Duration;
@@ -13,6 +14,7 @@ use redhac::{
 };
 use reqwest::header::CONTENT_TYPE;
 use reqwest::{tls, Url};
+use ring::hmac;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row};
 use tracing::{debug, error, warn};
@@ -22,22 +24,31 @@ use validator::Validate;
 use rauthy_common::constants::{
     APPLICATION_JSON, CACHE_NAME_12HR, CACHE_NAME_EPHEMERAL_CLIENTS, ENABLE_EPHEMERAL_CLIENTS,
     EPHEMERAL_CLIENTS_ALLOWED_FLOWS, EPHEMERAL_CLIENTS_ALLOWED_SCOPES, EPHEMERAL_CLIENTS_FORCE_MFA,
-    IDX_CLIENTS, IDX_CLIENT_LOGO, PROXY_MODE, RAUTHY_VERSION,
+    IDX_CLIENTS, IDX_CLIENT_JWKS, IDX_CLIENT_JWKS_REFRESH, IDX_CLIENT_LOGO, PAIRWISE_SUB_SECRET,
+    PROXY_MODE, RAUTHY_VERSION, TLS_CLIENT_AUTH,
 };
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
-use rauthy_common::utils::{cache_entry_client, get_client_ip, get_rand};
+use rauthy_common::utils::{base64_url_no_pad_encode, cache_entry_client, get_client_ip, get_rand};
 use rauthy_common::utils::{decrypt, encrypt};
 
 use crate::app_state::{AppState, DbTxn};
-use crate::entity::jwk::JwkKeyPairAlg;
+use crate::entity::client_assertions::ClientAssertion;
+use crate::entity::client_certificates::ClientCertificate;
+use crate::entity::jwe::{Jwe, JweAlg, JweEnc, JweEncKey};
+use crate::entity::jwk::{JwkKeyPairAlg, JWKS};
+use crate::entity::provisioning::ProvisioningConnector;
 use crate::entity::scopes::Scope;
 use crate::entity::users::User;
-use crate::request::{EphemeralClientRequest, NewClientRequest};
+use crate::request::{ClientCredentials, EphemeralClientRequest, NewClientRequest};
 use crate::ListenScheme;
 
 const RAUTHY_DEFAULT_LOGO: &str = "data:image/svg+xml;base64,PD94bWwgdmVyc2lvbj0iMS4wIiBlbmNvZGluZz0iVVRGLTgiIHN0YW5kYWxvbmU9Im5vIj8+CjwhRE9DVFlQRSBzdmcgUFVCTElDICItLy9XM0MvL0RURCBTVkcgMS4xLy9FTiIgImh0dHA6Ly93d3cudzMub3JnL0dyYXBoaWNzL1NWRy8xLjEvRFREL3N2ZzExLmR0ZCI+Cjxzdmcgd2lkdGg9IjEwMCUiIGhlaWdodD0iMTAwJSIgdmlld0JveD0iMCAwIDUxMiAxMzgiIHZlcnNpb249IjEuMSIgeG1sbnM9Imh0dHA6Ly93d3cudzMub3JnLzIwMDAvc3ZnIiB4bWxuczp4bGluaz0iaHR0cDovL3d3dy53My5vcmcvMTk5OS94bGluayIgeG1sOnNwYWNlPSJwcmVzZXJ2ZSIgeG1sbnM6c2VyaWY9Imh0dHA6Ly93d3cuc2VyaWYuY29tLyIgc3R5bGU9ImZpbGwtcnVsZTpldmVub2RkO2NsaXAtcnVsZTpldmVub2RkO3N0cm9rZS1saW5lY2FwOnJvdW5kO3N0cm9rZS1saW5lam9pbjpyb3VuZDtzdHJva2UtbWl0ZXJsaW1pdDoxLjU7Ij4KICAgIDxnIHRyYW5zZm9ybT0ibWF0cml4KDEsMCwwLDEsMCwtMTEpIj4KICAgICAgICA8ZyB0cmFuc2Zvcm09Im1hdHJpeCgxLDAsMCwxLDAsLTE3NikiPgogICAgICAgICAgICA8ZyB0cmFuc2Zvcm09Im1hdHJpeCgwLjkyMDMyNSwwLDAsMS44NDE1MSw0NS45Mjc5LDI2LjQ1OSkiPgogICAgICAgICAgICAgICAgPHJlY3QgeD0iMjcuNzQxIiB5PSIxNTEuNTciIHdpZHRoPSIyMDAuNTE3IiBoZWlnaHQ9IjEwLjE0OCIgc3R5bGU9ImZpbGw6cmdiKDQsNywxMSk7Ii8+CiAgICAgICAgICAgIDwvZz4KICAgICAgICAgICAgPGcgdHJhbnNmb3JtPSJtYXRyaXgoMS45MzQ3MiwwLDAsMS44MjczMiw4LjM1NjE4LDI4Ljc1MzMpIj4KICAgICAgICAgICAgICAgIDxyZWN0IHg9IjMzLjMwNyIgeT0iOTcuMTUiIHdpZHRoPSI5NC42OTMiIGhlaWdodD0iNTQuNDIiIHN0eWxlPSJmaWxsOnJnYig0LDcsMTEpO3N0cm9rZTpyZ2IoNCw3LDExKTtzdHJva2Utd2lkdGg6MS4wNnB4OyIvPgogICAgICAgICAgICA8L2c+CiAgICAgICAgICAgIDxnIHRyYW5zZm9ybT0ibWF0cml4KDEuODI3MzIsMCwwLDEuODI3MzIsLTE2MC44MjIsNzAuMTgwNikiPgogICAgICAgICAgICAgICAgPGcgdHJhbnNmb3JtPSJtYXRyaXgoNzIsMCwwLDcyLDIyNy4xNzQsMTIzLjQxNykiPgogICAgICAgICAgICAgICAgPC9nPgogICAgICAgICAgICAgICAgPHRleHQgeD0iMTI4Ljk4MnB4IiB5PSIxMjMuNDE3cHgiIHN0eWxlPSJmb250LWZhbWlseTonQ2FsaWJyaS1Cb2xkJywgJ0NhbGlicmknLCBzYW5zLXNlcmlmO2ZvbnQtd2VpZ2h0OjcwMDtmb250LXNpemU6NzJweDtmaWxsOndoaXRlOyI+cjx0c3BhbiB4PSIxNTIuOTk0cHggMTg4LjUzN3B4ICIgeT0iMTIzLjQxN3B4IDEyMy40MTdweCAiPmF1PC90c3Bhbj48L3RleHQ+CiAgICAgICAgICAgIDwvZz4KICAgICAgICAgICAgPGcgdHJhbnNmb3JtPSJtYXRyaXgoMSwwLDAsMS4wMTYxNywtMS40MjEwOWUtMTQsLTUuMjQ0OTIpIj4KICAgICAgICAgICAgICAgIDxwYXRoIGQ9Ik00NDAuOTM2LDMyMi42NDNMNDM5LjIwNCwzMjQuMjY2TDI1NS40ODIsMzI0LjI2NkwyNTUuNDgyLDMwNS43MjFMNDQwLjkzNiwzMDUuNzIxTDQ0MC45MzYsMzIyLjY0M1oiIHN0eWxlPSJmaWxsOnVybCgjX0xpbmVhcjEpOyIvPgogICAgICAgICAgICA8L2c+CiAgICAgICAgICAgIDxnIHRyYW5zZm9ybT0ibWF0cml4KDAuOTIwMTkxLDAsMCwxLjg0MTIxLDQ2LjI0NjQsLTkxLjMzODMpIj4KICAgICAgICAgICAgICAgIDxyZWN0IHg9IjI3Ljc0MSIgeT0iMTUxLjU3IiB3aWR0aD0iMjAwLjUxNyIgaGVpZ2h0PSIxMC4xNDgiIHN0eWxlPSJmaWxsOnVybCgjX0xpbmVhcjIpOyIvPgogICAgICAgICAgICA8L2c+CiAgICAgICAgICAgIDxnIHRyYW5zZm9ybT0ibWF0cml4KDEuOTc1OTgsMCwwLDEuODQ2MTksMTkwLjE4NywyNi4wNjIpIj4KICAgICAgICAgICAgICAgIDxyZWN0IHg9IjMzLjMwNyIgeT0iOTcuMTUiIHdpZHRoPSI5NC42OTMiIGhlaWdodD0iNTQuNDIiIHN0eWxlPSJmaWxsOnJnYig0Myw2NSwxMDcpOyIvPgogICAgICAgICAgICA8L2c+CiAgICAgICAgICAgIDxwYXRoIGQ9Ik00MzkuMjA0LDE4Ny43MzRMNDQwLjU1NywxODkuMDA3TDQ0MC41NTcsMjA2LjI3OUwyNTYsMjA2LjI3OUwyNTYsMTg3LjczNEw0MzkuMjA0LDE4Ny43MzRaIiBzdHlsZT0iZmlsbDpyZ2IoNDMsNjUsMTA3KTsiLz4KICAgICAgICAgICAgPGcgdHJhbnNmb3JtPSJtYXRyaXgoMS44MjczMiwwLDAsMS44MjczMiwtMTU0LjY2MSw3MC4xODA2KSI+CiAgICAgICAgICAgICAgICA8ZyB0cmFuc2Zvcm09Im1hdHJpeCg3MiwwLDAsNzIsMzIzLjA0NSwxMjMuNDE3KSI+CiAgICAgICAgICAgICAgICA8L2c+CiAgICAgICAgICAgICAgICA8dGV4dCB4PSIyMjYuNjQ2cHgiIHk9IjEyMy40MTdweCIgc3R5bGU9ImZvbnQtZmFtaWx5OidDYWxpYnJpLUJvbGQnLCAnQ2FsaWJyaScsIHNhbnMtc2VyaWY7Zm9udC13ZWlnaHQ6NzAwO2ZvbnQtc2l6ZTo3MnB4O2ZpbGw6d2hpdGU7Ij50aDx0c3BhbiB4PSIyODguOTQzcHggIiB5PSIxMjMuNDE3cHggIj55PC90c3Bhbj48L3RleHQ+CiAgICAgICAgICAgIDwvZz4KICAgICAgICAgICAgPGcgdHJhbnNmb3JtPSJtYXRyaXgoMiwwLDAsMiwwLDApIj4KICAgICAgICAgICAgICAgIDxwYXRoIGQ9Ik0yMTkuNjAyLDkzLjg2N0wyNTYsMTI4TDIxOS42MDIsMTYyLjEzM0wyMTkuNjAyLDkzLjg2N1oiIHN0eWxlPSJmaWxsOnJnYig0Myw2NSwxMDcpOyIvPgogICAgICAgICAgICA8L2c+CiAgICAgICAgICAgIDxnIHRyYW5zZm9ybT0ibWF0cml4KDIsMCwwLDEuOTU3MzksMCwzLjk5OTk3KSI+CiAgICAgICAgICAgICAgICA8cGF0aCBkPSJNMzYuMzk4LDkzLjg2N0wwLDkzLjg2N0wzNS45MDgsMTI4LjUyNEwwLDE2My42MTlMMzYuMzk4LDE2My42MTkiIHN0eWxlPSJmaWxsOnJnYig0LDcsMTEpOyIvPgogICAgICAgICAgICA8L2c+CiAgICAgICAgPC9nPgogICAgPC9nPgogICAgPGRlZnM+CiAgICAgICAgPGxpbmVhckdyYWRpZW50IGlkPSJfTGluZWFyMSIgeDE9IjAiIHkxPSIwIiB4Mj0iMSIgeTI9IjAiIGdyYWRpZW50VW5pdHM9InVzZXJTcGFjZU9uVXNlIiBncmFkaWVudFRyYW5zZm9ybT0ibWF0cml4KDE4NS40NTQsMCwwLDE4LjU0NDMsMjU1LjQ4MiwzMTQuOTk0KSI+PHN0b3Agb2Zmc2V0PSIwIiBzdHlsZT0ic3RvcC1jb2xvcjpyZ2IoNCw3LDExKTtzdG9wLW9wYWNpdHk6MSIvPjxzdG9wIG9mZnNldD0iMSIgc3R5bGU9InN0b3AtY29sb3I6cmdiKDQzLDY1LDEwNyk7c3RvcC1vcGFjaXR5OjEiLz48L2xpbmVhckdyYWRpZW50PgogICAgICAgIDxsaW5lYXJHcmFkaWVudCBpZD0iX0xpbmVhcjIiIHgxPSIwIiB5MT0iMCIgeDI9IjEiIHkyPSIwIiBncmFkaWVudFVuaXRzPSJ1c2VyU3BhY2VPblVzZSIgZ3JhZGllbnRUcmFuc2Zvcm09Im1hdHJpeCgyMDAuNTE3LDAsMCwxMC4xNDgzLDI3Ljc0MTQsMTU2LjY0NSkiPjxzdG9wIG9mZnNldD0iMCIgc3R5bGU9InN0b3AtY29sb3I6cmdiKDQsNywxMSk7c3RvcC1vcGFjaXR5OjEiLz48c3RvcCBvZmZzZXQ9IjEiIHN0eWxlPSJzdG9wLWNvbG9yOnJnYig0Myw2NSwxMDcpO3N0b3Atb3BhY2l0eToxIi8+PC9saW5lYXJHcmFkaWVudD4KICAgIDwvZGVmcz4KPC9zdmc+Cg==";
 
 static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+static HTTP_CLIENT_JWKS: OnceLock<reqwest::Client> = OnceLock::new();
+static HTTP_CLIENT_SECTOR: OnceLock<reqwest::Client> = OnceLock::new();
+// the minimal time between two forced refreshes of the JWKS from a clients `jwks_uri`
+const JWKS_REFRESH_COOLDOWN_SECS: i64 = 60;
 
 /**
 # OIDC Client
@@ -63,9 +74,9 @@ pub struct Client {
     pub post_logout_redirect_uris: Option<String>,
     pub allowed_origins: Option<String>,
     pub flows_enabled: String,
-    // Currently supported Algorithms: RS 256, 384, 512 and ES 256, 384, 512
+    // Currently supported Algorithms: RS 256, 384, 512, PS 256, ES 256, 384 and EdDSA
     pub access_token_alg: String,
-    // Currently supported Algorithms: RS 256, 384, 512 and ES 256, 384, 512
+    // Currently supported Algorithms: RS 256, 384, 512, PS 256, ES 256, 384 and EdDSA
     pub id_token_alg: String,
     pub refresh_token: bool,
     pub auth_code_lifetime: i32,
@@ -74,6 +85,44 @@ pub struct Client {
     pub default_scopes: String,
     pub challenge: Option<String>,
     pub force_mfa: bool,
+    // CSV of the audiences this client may request via the token exchange grant
+    pub token_exchange_audiences: Option<String>,
+    // OIDC Back-Channel Logout - logout tokens will be sent here after a user logged out
+    pub backchannel_logout_uri: Option<String>,
+    // OIDC Front-Channel Logout - will be loaded inside an iframe during the logout
+    pub frontchannel_logout_uri: Option<String>,
+    // if set, the authorization_code flow can only be started with a pushed authorization request
+    pub require_par: bool,
+    // JWKS with the public keys of this client as JSON, used to validate signed request objects
+    pub jwks: Option<String>,
+    // the client's JWKS will be fetched from here, if set instead of `jwks`
+    pub jwks_uri: Option<String>,
+    // client_secret_basic | client_secret_post | client_secret_jwt | private_key_jwt |
+    // tls_client_auth | self_signed_tls_client_auth | none
+    pub token_endpoint_auth_method: String,
+    // the expected subject DN of the client certificate for `tls_client_auth`
+    pub tls_client_auth_subject_dn: Option<String>,
+    // CSV of the SHA-256 certificate thumbprints for `self_signed_tls_client_auth`
+    pub tls_client_auth_thumbprints: Option<String>,
+    // if set, ID tokens will be encrypted to a key from the client JWKS: RSA-OAEP-256 | ECDH-ES
+    pub id_token_encrypted_response_alg: Option<String>,
+    // currently only A256GCM
+    pub id_token_encrypted_response_enc: Option<String>,
+    // if set, userinfo responses will be encrypted to a key from the client JWKS
+    pub userinfo_encrypted_response_alg: Option<String>,
+    pub userinfo_encrypted_response_enc: Option<String>,
+    // if set, userinfo responses will be signed JWTs: RS 256, 384, 512, PS 256, ES 256, 384, EdDSA
+    pub userinfo_signed_response_alg: Option<String>,
+    // `pairwise` or `None` for public subjects
+    pub subject_type: Option<String>,
+    // JSON array of redirect URIs, its host is the sector for pairwise subjects
+    pub sector_identifier_uri: Option<String>,
+    // if set, the user must grant the requested scopes on a consent screen before a code is issued
+    pub require_consent: bool,
+    // if set, each refresh token can only be used once and will be rotated with every refresh
+    pub refresh_token_rotation: bool,
+    // CSV of the pre-registered URIs, from which request objects may be fetched
+    pub request_uris: Option<String>,
 }
 
 // CRUD
@@ -91,13 +140,29 @@ impl Client {
             client_req.secret = Some(enc);
         }
         let client = Client::from(client_req);
+        client.insert(data, None).await?;
+        Ok(client)
+    }
 
-        let rows =  sqlx::query!(
+    /// Inserts a new client into the database and adds it to the clients cache index.
+    pub async fn insert(
+        &self,
+        data: &web::Data<AppState>,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let client = self;
+        let q = sqlx::query!(
             r#"insert into clients (id, name, enabled, confidential, secret, secret_kid,
             redirect_uris, post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg,
             id_token_alg, refresh_token, auth_code_lifetime, access_token_lifetime, scopes, default_scopes,
-            challenge, force_mfa)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#,
+            challenge, force_mfa, token_exchange_audiences, backchannel_logout_uri,
+            frontchannel_logout_uri, require_par, jwks, jwks_uri, token_endpoint_auth_method,
+            tls_client_auth_subject_dn, tls_client_auth_thumbprints, id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc, userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc, userinfo_signed_response_alg, subject_type,
+            sector_identifier_uri, require_consent, refresh_token_rotation, request_uris)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+            $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38)"#,
             client.id,
             client.name,
             client.enabled,
@@ -117,10 +182,33 @@ impl Client {
             client.default_scopes,
             client.challenge,
             client.force_mfa,
-        )
-            .execute(&data.db)
-            .await?
-            .rows_affected();
+            client.token_exchange_audiences,
+            client.backchannel_logout_uri,
+            client.frontchannel_logout_uri,
+            client.require_par,
+            client.jwks,
+            client.jwks_uri,
+            client.token_endpoint_auth_method,
+            client.tls_client_auth_subject_dn,
+            client.tls_client_auth_thumbprints,
+            client.id_token_encrypted_response_alg,
+            client.id_token_encrypted_response_enc,
+            client.userinfo_encrypted_response_alg,
+            client.userinfo_encrypted_response_enc,
+            client.userinfo_signed_response_alg,
+            client.subject_type,
+            client.sector_identifier_uri,
+            client.require_consent,
+            client.refresh_token_rotation,
+            client.request_uris,
+        );
+
+        let rows = if let Some(txn) = txn {
+            q.execute(&mut **txn).await?
+        } else {
+            q.execute(&data.db).await?
+        }
+        .rows_affected();
 
         if rows == 0 {
             error!("Error inserting client - no rows affected");
@@ -137,7 +225,7 @@ impl Client {
         )
         .await?;
 
-        Ok(client)
+        Ok(())
     }
 
     // Deletes a client
@@ -168,6 +256,9 @@ impl Client {
         )
         .await?;
 
+        // the connectors have been deleted via cascade
+        ProvisioningConnector::cache_invalidate(data).await?;
+
         Ok(())
     }
 
@@ -269,6 +360,126 @@ impl Client {
         Ok(client)
     }
 
+    /// Returns the public keys of this client, which are either registered directly or will be
+    /// fetched from its `jwks_uri`. Fetched keys are cached. Set `force_refresh` to fetch them
+    /// again, for instance when a `kid` is unknown after a key rotation on the client side.
+    pub async fn find_jwks(
+        &self,
+        data: &web::Data<AppState>,
+        force_refresh: bool,
+    ) -> Result<JWKS, ErrorResponse> {
+        let jwks = self.find_jwks_raw(data, force_refresh).await?;
+        Self::parse_jwks(&jwks)
+    }
+
+    /// Returns the public key of this client, which should be used to encrypt ID tokens or
+    /// userinfo responses with the given `alg`.
+    pub async fn find_enc_key(
+        &self,
+        data: &web::Data<AppState>,
+        alg: &JweAlg,
+    ) -> Result<JweEncKey, ErrorResponse> {
+        let jwks = self.find_jwks_raw(data, false).await?;
+        if let Some(key) = Self::find_enc_key_in(&jwks, alg)? {
+            return Ok(key);
+        }
+
+        // the client may have rotated its encryption keys
+        if self.jwks_uri.is_some() {
+            let jwks = self.find_jwks_raw(data, true).await?;
+            if let Some(key) = Self::find_enc_key_in(&jwks, alg)? {
+                return Ok(key);
+            }
+        }
+
+        Err(ErrorResponse::new(
+            ErrorResponseType::Internal,
+            format!(
+                "No encryption key for '{}' registered for client '{}'",
+                alg.as_str(),
+                self.id
+            ),
+        ))
+    }
+
+    async fn find_jwks_raw(
+        &self,
+        data: &web::Data<AppState>,
+        force_refresh: bool,
+    ) -> Result<String, ErrorResponse> {
+        if let Some(jwks) = &self.jwks {
+            return Ok(jwks.clone());
+        }
+        let uri = self.jwks_uri.as_ref().ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("No JWKS registered for client '{}'", self.id),
+            )
+        })?;
+
+        let idx = format!("{}{}", IDX_CLIENT_JWKS, self.id);
+        // Otherwise, each JWT with an unknown `kid` would make us fetch the `jwks_uri` again.
+        let force_refresh = force_refresh && self.jwks_refresh_allowed(data).await?;
+        if !force_refresh {
+            if let Some(jwks) = cache_get!(
+                String,
+                CACHE_NAME_12HR.to_string(),
+                idx.clone(),
+                &data.caches.ha_cache_config,
+                false
+            )
+            .await?
+            {
+                return Ok(jwks);
+            }
+        }
+
+        let jwks = Self::jwks_from_url(uri).await?;
+        cache_put(
+            CACHE_NAME_12HR.to_string(),
+            idx,
+            &data.caches.ha_cache_config,
+            &jwks,
+        )
+        .await?;
+
+        Ok(jwks)
+    }
+
+    /// Returns `true` and starts a new cooldown, if the last forced refresh of the JWKS has been
+    /// longer ago than `JWKS_REFRESH_COOLDOWN_SECS`.
+    async fn jwks_refresh_allowed(
+        &self,
+        data: &web::Data<AppState>,
+    ) -> Result<bool, ErrorResponse> {
+        let idx = format!("{}{}", IDX_CLIENT_JWKS_REFRESH, self.id);
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+        let last_refresh = cache_get!(
+            i64,
+            CACHE_NAME_12HR.to_string(),
+            idx.clone(),
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?;
+        if matches!(last_refresh, Some(ts) if now - ts < JWKS_REFRESH_COOLDOWN_SECS) {
+            debug!(
+                "Skipping the JWKS refresh for client '{}' during the cooldown",
+                self.id
+            );
+            return Ok(false);
+        }
+
+        cache_put(
+            CACHE_NAME_12HR.to_string(),
+            idx,
+            &data.caches.ha_cache_config,
+            &now,
+        )
+        .await?;
+        Ok(true)
+    }
+
     pub async fn find_logo(data: &web::Data<AppState>, id: &str) -> Result<String, ErrorResponse> {
         let idx = format!("{}{}", IDX_CLIENT_LOGO, id);
         let logo = cache_get!(
@@ -363,7 +574,15 @@ impl Client {
             secret_kid = $5, redirect_uris = $6, post_logout_redirect_uris = $7, allowed_origins = $8,
             flows_enabled = $9, access_token_alg = $10, id_token_alg = $11, refresh_token = $12,
             auth_code_lifetime = $13, access_token_lifetime = $14, scopes = $15, default_scopes = $16,
-            challenge = $17, force_mfa= $18 where id = $19"#,
+            challenge = $17, force_mfa= $18, token_exchange_audiences = $19,
+            backchannel_logout_uri = $20, frontchannel_logout_uri = $21,
+            require_par = $22, jwks = $23, jwks_uri = $24, token_endpoint_auth_method = $25,
+            tls_client_auth_subject_dn = $26, tls_client_auth_thumbprints = $27,
+            id_token_encrypted_response_alg = $28, id_token_encrypted_response_enc = $29,
+            userinfo_encrypted_response_alg = $30, userinfo_encrypted_response_enc = $31,
+            userinfo_signed_response_alg = $32, subject_type = $33, sector_identifier_uri = $34,
+            require_consent = $35, refresh_token_rotation = $36, request_uris = $37
+            where id = $38"#,
             self.name,
             self.enabled,
             self.confidential,
@@ -382,6 +601,25 @@ impl Client {
             self.default_scopes,
             self.challenge,
             self.force_mfa,
+            self.token_exchange_audiences,
+            self.backchannel_logout_uri,
+            self.frontchannel_logout_uri,
+            self.require_par,
+            self.jwks,
+            self.jwks_uri,
+            self.token_endpoint_auth_method,
+            self.tls_client_auth_subject_dn,
+            self.tls_client_auth_thumbprints,
+            self.id_token_encrypted_response_alg,
+            self.id_token_encrypted_response_enc,
+            self.userinfo_encrypted_response_alg,
+            self.userinfo_encrypted_response_enc,
+            self.userinfo_signed_response_alg,
+            self.subject_type,
+            self.sector_identifier_uri,
+            self.require_consent,
+            self.refresh_token_rotation,
+            self.request_uris,
             self.id,
         );
 
@@ -398,6 +636,13 @@ impl Client {
             &self,
         )
         .await?;
+        // the `jwks_uri` may have changed
+        cache_del(
+            CACHE_NAME_12HR.to_string(),
+            format!("{}{}", IDX_CLIENT_JWKS, self.id),
+            &data.caches.ha_cache_config,
+        )
+        .await?;
 
         let mut found_self = false;
         let mut clients = Client::find_all(data)
@@ -499,6 +744,60 @@ impl Client {
         JwkKeyPairAlg::from_str(self.access_token_alg.as_str())
     }
 
+    /// Encrypts the given ID token, if this client has an `id_token_encrypted_response_alg`.
+    /// Returns the signed token unchanged otherwise.
+    pub async fn encrypt_id_token(
+        &self,
+        data: &web::Data<AppState>,
+        id_token: String,
+    ) -> Result<String, ErrorResponse> {
+        let jwe = self
+            .encrypt_jwe(
+                data,
+                &self.id_token_encrypted_response_alg,
+                &self.id_token_encrypted_response_enc,
+                id_token.as_bytes(),
+                Some("JWT"),
+            )
+            .await?;
+        Ok(jwe.unwrap_or(id_token))
+    }
+
+    /// Encrypts the given userinfo, if this client has a `userinfo_encrypted_response_alg`.
+    /// `is_jwt` must be set, if the userinfo has been signed before.
+    pub async fn encrypt_userinfo(
+        &self,
+        data: &web::Data<AppState>,
+        userinfo: &[u8],
+        is_jwt: bool,
+    ) -> Result<Option<String>, ErrorResponse> {
+        self.encrypt_jwe(
+            data,
+            &self.userinfo_encrypted_response_alg,
+            &self.userinfo_encrypted_response_enc,
+            userinfo,
+            is_jwt.then_some("JWT"),
+        )
+        .await
+    }
+
+    async fn encrypt_jwe(
+        &self,
+        data: &web::Data<AppState>,
+        alg: &Option<String>,
+        enc: &Option<String>,
+        payload: &[u8],
+        cty: Option<&str>,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let Some(alg) = alg else {
+            return Ok(None);
+        };
+        let alg = JweAlg::from_str(alg)?;
+        let enc = JweEnc::from_str(enc.as_deref().unwrap_or_default())?;
+        let key = self.find_enc_key(data, &alg).await?;
+        Jwe::encrypt(payload, &key, &alg, &enc, cty).map(Some)
+    }
+
     pub fn get_allowed_origins(&self) -> Option<Vec<String>> {
         self.allowed_origins.as_ref()?;
         let mut origins = Vec::new();
@@ -592,6 +891,102 @@ impl Client {
         self.scopes.replace(',', " ")
     }
 
+    pub fn get_token_exchange_audiences(&self) -> Option<Vec<String>> {
+        self.token_exchange_audiences.as_ref()?;
+        Some(
+            self.token_exchange_audiences
+                .as_ref()
+                .unwrap()
+                .split(',')
+                .map(|a| a.trim().to_string())
+                .collect(),
+        )
+    }
+
+    pub fn get_request_uris(&self) -> Option<Vec<String>> {
+        self.request_uris
+            .as_ref()
+            .map(|uris| uris.split(',').map(|uri| uri.trim().to_string()).collect())
+    }
+
+    pub fn get_tls_client_auth_thumbprints(&self) -> Option<Vec<String>> {
+        self.tls_client_auth_thumbprints.as_ref()?;
+        Some(
+            self.tls_client_auth_thumbprints
+                .as_ref()
+                .unwrap()
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .collect(),
+        )
+    }
+
+    /// Returns the pairwise `sub` for the given user, if this client has the `pairwise`
+    /// subject type. It is an HMAC of the user id and the sector identifier, which makes it
+    /// stable for all clients of the same sector, while it cannot be correlated across sectors.
+    pub fn get_pairwise_sub(&self, user_id: &str) -> Result<Option<String>, ErrorResponse> {
+        if self.subject_type.as_deref() != Some("pairwise") {
+            return Ok(None);
+        }
+        let secret = PAIRWISE_SUB_SECRET.as_ref().ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "PAIRWISE_SUB_SECRET is not set".to_string(),
+            )
+        })?;
+
+        let sector = self.get_sector_identifier()?;
+        Ok(Some(Self::pairwise_sub(secret, &sector, user_id)))
+    }
+
+    fn pairwise_sub(secret: &str, sector: &str, user_id: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let tag = hmac::sign(&key, format!("{} {}", sector, user_id).as_bytes());
+        base64_url_no_pad_encode(tag.as_ref())
+    }
+
+    /// Returns the host of the `sector_identifier_uri`, or of the first redirect URI as a
+    /// fallback.
+    pub fn get_sector_identifier(&self) -> Result<String, ErrorResponse> {
+        let uri = match &self.sector_identifier_uri {
+            Some(uri) => uri.clone(),
+            None => self
+                .get_redirect_uris()
+                .into_iter()
+                .find(|uri| !uri.is_empty())
+                .ok_or_else(|| {
+                    ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!(
+                            "Client '{}' has no redirect URI to derive the sector identifier from",
+                            self.id
+                        ),
+                    )
+                })?,
+        };
+        Self::uri_host(&uri)
+    }
+
+    fn uri_host(uri: &str) -> Result<String, ErrorResponse> {
+        Url::parse(uri)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Cannot extract the host from '{}'", uri),
+                )
+            })
+    }
+
+    /// Offline tokens can only be issued, if `offline_access` is one of the allowed scopes.
+    pub fn allows_offline_access(&self) -> bool {
+        self.scopes
+            .split(',')
+            .chain(self.default_scopes.split(','))
+            .any(|s| s == "offline_access")
+    }
+
     pub fn is_ephemeral(&self) -> bool {
         Url::from_str(&self.id).is_ok()
     }
@@ -628,10 +1023,12 @@ impl Client {
         &self,
         scopes: &Option<Vec<String>>,
     ) -> Result<Vec<String>, ErrorResponse> {
+        // `offline_access` is never added as a default, it must always be requested explicitly
         if scopes.is_none() {
             return Ok(self
                 .default_scopes
                 .split(',')
+                .filter(|s| *s != "offline_access")
                 .map(|s| s.to_string())
                 .collect());
         }
@@ -641,11 +1038,13 @@ impl Client {
 
         // Always add the configured default scopes
         for s in self.default_scopes.split(',') {
-            res.push(s.to_string());
+            if s != "offline_access" {
+                res.push(s.to_string());
+            }
         }
 
         for s in scopes {
-            if self.default_scopes.contains(s) {
+            if s != "offline_access" && self.default_scopes.contains(s) {
                 continue;
             }
 
@@ -696,7 +1095,7 @@ impl Client {
     /// possible without MFA. The force MFA for the Rauthy admin UI is done in
     /// Principal::validate_admin_session() depending on the `ADMIN_FORCE_MFA` config variable.
     pub fn validate_mfa(&self, user: &User) -> Result<(), ErrorResponse> {
-        if &self.id != "rauthy" && self.force_mfa && !user.has_webauthn_enabled() {
+        if &self.id != "rauthy" && self.force_mfa && !user.has_mfa_enabled() {
             Err(ErrorResponse::new(
                 ErrorResponseType::MfaRequired,
                 "MFA is required for this client".to_string(),
@@ -706,6 +1105,241 @@ impl Client {
         }
     }
 
+    /// Parses a JSON Web Key Set given by a client. Only `RSA`, `OKP` and `EC` keys are
+    /// supported. Encryption keys are skipped, they are only used via `find_enc_key()`.
+    pub fn parse_jwks(value: &str) -> Result<JWKS, ErrorResponse> {
+        let keys = Self::parse_jwks_keys(value)?
+            .into_iter()
+            .filter(|key| !JweEncKey::is_enc_key(key))
+            .collect::<Vec<serde_json::Value>>();
+        let jwks =
+            serde_json::from_value::<JWKS>(serde_json::json!({ "keys": keys })).map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Invalid JWKS: {}", err),
+                )
+            })?;
+        Ok(jwks)
+    }
+
+    fn parse_jwks_keys(value: &str) -> Result<Vec<serde_json::Value>, ErrorResponse> {
+        let keys = serde_json::from_str::<serde_json::Value>(value)
+            .ok()
+            .and_then(|mut jwks| match jwks.get_mut("keys").map(|k| k.take()) {
+                Some(serde_json::Value::Array(keys)) => Some(keys),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "Invalid JWKS: expected an object with a 'keys' array".to_string(),
+                )
+            })?;
+        if keys.is_empty() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The JWKS does not contain any keys".to_string(),
+            ));
+        }
+        Ok(keys)
+    }
+
+    fn find_enc_key_in(jwks: &str, alg: &JweAlg) -> Result<Option<JweEncKey>, ErrorResponse> {
+        let key = Self::parse_jwks_keys(jwks)?
+            .into_iter()
+            .filter(JweEncKey::is_enc_key)
+            .filter_map(|key| serde_json::from_value::<JweEncKey>(key).ok())
+            .find(|key| key.supports(alg));
+        Ok(key)
+    }
+
+    /// Validates the public keys of this client, which can be registered either directly via
+    /// `jwks` or by reference via `jwks_uri`, but not both.
+    pub fn validate_jwks(&self) -> Result<(), ErrorResponse> {
+        if self.jwks.is_some() && self.jwks_uri.is_some() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'jwks' and 'jwks_uri' are mutually exclusive".to_string(),
+            ));
+        }
+        if let Some(jwks) = &self.jwks {
+            Self::parse_jwks(jwks)?;
+        }
+        Ok(())
+    }
+
+    /// Validates the settings for encrypted ID tokens and userinfo responses. An `alg` always
+    /// needs an `enc` and a JWKS, which contains a matching encryption key.
+    pub fn validate_jwe(&self) -> Result<(), ErrorResponse> {
+        let values = [
+            (
+                "id_token",
+                &self.id_token_encrypted_response_alg,
+                &self.id_token_encrypted_response_enc,
+            ),
+            (
+                "userinfo",
+                &self.userinfo_encrypted_response_alg,
+                &self.userinfo_encrypted_response_enc,
+            ),
+        ];
+
+        for (typ, alg, enc) in values {
+            match (alg, enc) {
+                (None, None) => {}
+                (None, Some(_)) => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!(
+                            "'{}_encrypted_response_enc' needs '{}_encrypted_response_alg'",
+                            typ, typ
+                        ),
+                    ));
+                }
+                (Some(_), None) => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!(
+                            "'{}_encrypted_response_alg' needs '{}_encrypted_response_enc'",
+                            typ, typ
+                        ),
+                    ));
+                }
+                (Some(alg), Some(enc)) => {
+                    let alg = JweAlg::from_str(alg)?;
+                    JweEnc::from_str(enc)?;
+
+                    if let Some(jwks) = &self.jwks {
+                        if Self::find_enc_key_in(jwks, &alg)?.is_none() {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                format!(
+                                    "The 'jwks' contain no encryption key for '{}'",
+                                    alg.as_str()
+                                ),
+                            ));
+                        }
+                    } else if self.jwks_uri.is_none() {
+                        return Err(ErrorResponse::new(
+                            ErrorResponseType::BadRequest,
+                            format!(
+                                "'{}_encrypted_response_alg' needs either 'jwks' or 'jwks_uri'",
+                                typ
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the settings for pairwise subject identifiers. Without a `sector_identifier_uri`,
+    /// all redirect URIs must share the same host. Otherwise, the JSON array behind it must
+    /// contain all redirect URIs of this client (OIDC Core 1.0, section 8.1).
+    pub async fn validate_subject_type(&self) -> Result<(), ErrorResponse> {
+        let err = |msg: String| Err(ErrorResponse::new(ErrorResponseType::BadRequest, msg));
+
+        match self.subject_type.as_deref() {
+            None | Some("public") => return Ok(()),
+            Some("pairwise") => {}
+            Some(typ) => return err(format!("Unsupported 'subject_type': {}", typ)),
+        }
+        if PAIRWISE_SUB_SECRET.is_none() {
+            return err("Pairwise subject identifiers are not enabled".to_string());
+        }
+
+        let redirect_uris = self.get_redirect_uris();
+        if let Some(uri) = &self.sector_identifier_uri {
+            if !uri.starts_with("https://") {
+                return err("The 'sector_identifier_uri' must use https".to_string());
+            }
+            let sector_uris = Self::sector_uris_from_url(uri).await?;
+            if let Some(missing) = redirect_uris.iter().find(|u| !sector_uris.contains(u)) {
+                return err(format!(
+                    "The redirect URI '{}' is missing at the 'sector_identifier_uri'",
+                    missing
+                ));
+            }
+        } else {
+            let hosts = redirect_uris
+                .iter()
+                .map(|uri| Self::uri_host(uri))
+                .collect::<Result<HashSet<String>, ErrorResponse>>()?;
+            if hosts.len() != 1 {
+                return err(
+                    "Redirect URIs with different hosts need a 'sector_identifier_uri'".to_string(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the signature of a JWT, which has been signed by this client, with the keys from
+    /// its JWKS. If a `jwks_uri` is registered and the `kid` is unknown, the JWKS will be fetched
+    /// again, because the client may have rotated its keys in the meantime. This happens at most
+    /// once per `JWKS_REFRESH_COOLDOWN_SECS` for each client.
+    pub async fn validate_jwt_signature(
+        &self,
+        data: &web::Data<AppState>,
+        token: &str,
+        alg: &JwkKeyPairAlg,
+        kid: Option<&str>,
+    ) -> Result<bool, ErrorResponse> {
+        let mut jwks = self.find_jwks(data, false).await?;
+        if self.jwks_uri.is_some()
+            && kid.is_some()
+            && !jwks.keys.iter().any(|k| k.kid.as_deref() == kid)
+        {
+            jwks = self.find_jwks(data, true).await?;
+        }
+
+        let is_valid = jwks
+            .keys
+            .into_iter()
+            .filter(|key| kid.is_none() || key.kid.as_deref() == kid)
+            .any(|key| key.verify_token_signature_with_alg(alg, token));
+        Ok(is_valid)
+    }
+
+    /// Validates that the authorization request has been pushed before, if this client
+    /// requires pushed authorization requests (RFC 9126).
+    pub fn validate_par(&self, is_par: bool) -> Result<(), ErrorResponse> {
+        if self.require_par && !is_par {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Pushed authorization requests are required for this client".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Request objects will only be fetched from pre-registered `request_uris` (RFC 9101
+    /// Section 10.4.1). Otherwise, anyone could make Rauthy fetch any URL. A fragment, which may
+    /// be added to force a fresh fetch, is ignored for the comparison.
+    pub fn validate_request_uri(&self, request_uri: &str) -> Result<(), ErrorResponse> {
+        let uri = request_uri.split('#').next().unwrap_or_default();
+        let is_registered = self
+            .get_request_uris()
+            .unwrap_or_default()
+            .iter()
+            .any(|registered| registered == uri);
+        if is_registered {
+            Ok(())
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "The 'request_uri' has not been registered for client '{}'",
+                    self.id
+                ),
+            ))
+        }
+    }
+
     // Validates the `Origin` HTTP Header from an incoming request and compares it to the
     // `allowed_origins`. If the Origin is an external one and allowed by the config, it returns
     // the correct `ACCESS_CONTROL_ALLOW_ORIGIN` header which can then be inserted into the
@@ -788,6 +1422,103 @@ impl Client {
         Ok(())
     }
 
+    /// Validates the credentials, which have been sent by this client, depending on its
+    /// `token_endpoint_auth_method`. Public clients do not need any credentials.
+    pub async fn validate_credentials(
+        &self,
+        data: &web::Data<AppState>,
+        credentials: ClientCredentials,
+        req: &HttpRequest,
+    ) -> Result<(), ErrorResponse> {
+        if !self.confidential {
+            return Ok(());
+        }
+
+        let method = self.token_endpoint_auth_method.as_str();
+        let uses_assertion = method == "client_secret_jwt" || method == "private_key_jwt";
+        let uses_cert = method == "tls_client_auth" || method == "self_signed_tls_client_auth";
+        match credentials {
+            // with mutual-TLS, only the `client_id` is sent next to the certificate
+            ClientCredentials::None if uses_cert => {
+                let cert = ClientCertificate::from_req(req).ok_or_else(|| {
+                    ErrorResponse::new(
+                        ErrorResponseType::Unauthorized,
+                        String::from("A TLS client certificate is required for this client"),
+                    )
+                })?;
+                self.validate_client_certificate(&cert).map_err(|err| {
+                    warn!(
+                        "Invalid TLS client certificate for client '{}' from '{}'",
+                        self.id,
+                        get_client_ip(req)
+                    );
+                    err
+                })
+            }
+            ClientCredentials::Assertion(assertion) if uses_assertion => {
+                ClientAssertion::validate(data, self, &assertion)
+                    .await
+                    .map_err(|err| {
+                        warn!(
+                            "Invalid client assertion for client '{}' from '{}': {}",
+                            self.id,
+                            get_client_ip(req),
+                            err.message
+                        );
+                        err
+                    })
+            }
+            ClientCredentials::Secret(secret) if !uses_assertion && !uses_cert => {
+                self.validate_secret(data, &secret, req)
+            }
+            ClientCredentials::None => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                if uses_assertion {
+                    String::from("'client_assertion' is missing")
+                } else {
+                    String::from("'client_secret' is missing")
+                },
+            )),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                format!("'{}' is required for this client", method),
+            )),
+        }
+    }
+
+    /// Validates the TLS client certificate for the mutual-TLS client authentication (RFC 8705).
+    /// With `tls_client_auth`, the certificate chain must be valid for the `TLS_CLIENT_AUTH_CA`
+    /// and the subject DN must match. With `self_signed_tls_client_auth`, the thumbprint must be
+    /// registered.
+    pub fn validate_client_certificate(
+        &self,
+        cert: &ClientCertificate,
+    ) -> Result<(), ErrorResponse> {
+        let is_valid = match self.token_endpoint_auth_method.as_str() {
+            "tls_client_auth" => {
+                cert.is_ca_verified
+                    && self
+                        .tls_client_auth_subject_dn
+                        .as_deref()
+                        .map(|dn| ClientCertificate::normalize_dn(dn) == cert.subject_dn)
+                        .unwrap_or(false)
+            }
+            "self_signed_tls_client_auth" => self
+                .get_tls_client_auth_thumbprints()
+                .map(|thumbprints| thumbprints.contains(&cert.thumbprint))
+                .unwrap_or(false),
+            _ => false,
+        };
+
+        if !is_valid {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                String::from("Invalid TLS client certificate"),
+            ));
+        }
+        Ok(())
+    }
+
     pub fn validate_secret(
         &self,
         state: &web::Data<AppState>,
@@ -829,9 +1560,157 @@ impl Client {
         }
         Ok(())
     }
+
+    /// Returns the thumbprint of the TLS client certificate, if this client uses the mutual-TLS
+    /// client authentication. Issued access tokens will be bound to this certificate.
+    pub fn tls_cert_thumbprint(&self, req: &HttpRequest) -> Option<String> {
+        match self.token_endpoint_auth_method.as_str() {
+            "tls_client_auth" | "self_signed_tls_client_auth" => {
+                ClientCertificate::from_req(req).map(|cert| cert.thumbprint)
+            }
+            _ => None,
+        }
+    }
+
+    /// Validates, that this client is allowed to request tokens for the given `audience` via the
+    /// token exchange grant. The client itself is always an allowed audience.
+    pub fn validate_token_exchange_audience(&self, audience: &str) -> Result<(), ErrorResponse> {
+        if audience == self.id {
+            return Ok(());
+        }
+
+        let is_allowed = self
+            .get_token_exchange_audiences()
+            .map(|auds| auds.iter().any(|aud| aud == audience))
+            .unwrap_or(false);
+        if !is_allowed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                format!(
+                    "Token exchange for audience '{}' is not allowed for this client",
+                    audience
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validates, that the `token_endpoint_auth_method` fits the other values of this client.
+    pub fn validate_token_endpoint_auth_method(&self) -> Result<(), ErrorResponse> {
+        let err = |msg: &str| {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                msg.to_string(),
+            ))
+        };
+
+        match self.token_endpoint_auth_method.as_str() {
+            "none" if self.confidential => err(
+                "'none' is not allowed as 'token_endpoint_auth_method' for confidential clients",
+            ),
+            "none" => Ok(()),
+            _ if !self.confidential => {
+                err("Public clients must use 'none' as 'token_endpoint_auth_method'")
+            }
+            "private_key_jwt" if self.jwks.is_none() && self.jwks_uri.is_none() => {
+                err("'private_key_jwt' needs either 'jwks' or 'jwks_uri'")
+            }
+            "tls_client_auth" | "self_signed_tls_client_auth" if !*TLS_CLIENT_AUTH => {
+                err("TLS client authentication is not enabled")
+            }
+            "tls_client_auth" if self.tls_client_auth_subject_dn.is_none() => {
+                err("'tls_client_auth' needs the 'tls_client_auth_subject_dn'")
+            }
+            "self_signed_tls_client_auth" if self.tls_client_auth_thumbprints.is_none() => {
+                err("'self_signed_tls_client_auth' needs the 'tls_client_auth_thumbprints'")
+            }
+            "client_secret_basic"
+            | "client_secret_post"
+            | "client_secret_jwt"
+            | "private_key_jwt"
+            | "tls_client_auth"
+            | "self_signed_tls_client_auth" => Ok(()),
+            method => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported 'token_endpoint_auth_method': {}", method),
+            )),
+        }
+    }
 }
 
 impl Client {
+    async fn jwks_from_url(uri: &str) -> Result<String, ErrorResponse> {
+        let client = HTTP_CLIENT_JWKS.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} JWKS Resolver", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let res = client.get(uri).send().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Cannot fetch the client JWKS from {}: {:?}", uri, err),
+            )
+        })?;
+        if !res.status().is_success() {
+            let msg = format!("Cannot fetch the client JWKS from {}", uri);
+            error!("{}", msg);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, msg));
+        }
+
+        let body = res.text().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Cannot read the client JWKS from {}: {:?}", uri, err),
+            )
+        })?;
+        Self::parse_jwks(&body)?;
+        Ok(body)
+    }
+
+    async fn sector_uris_from_url(uri: &str) -> Result<Vec<String>, ErrorResponse> {
+        let client = HTTP_CLIENT_SECTOR.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!(
+                    "Rauthy v{} Sector Identifier Resolver",
+                    RAUTHY_VERSION
+                ))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .build()
+                .unwrap()
+        });
+
+        let res = client.get(uri).send().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!(
+                    "Cannot fetch the 'sector_identifier_uri' {}: {:?}",
+                    uri, err
+                ),
+            )
+        })?;
+        if !res.status().is_success() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Cannot fetch the 'sector_identifier_uri' {}", uri),
+            ));
+        }
+
+        res.json::<Vec<String>>().await.map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The 'sector_identifier_uri' must return a JSON array of URIs".to_string(),
+            )
+        })
+    }
+
     async fn ephemeral_from_url(value: &str) -> Result<Self, ErrorResponse> {
         let client = HTTP_CLIENT.get_or_init(|| {
             reqwest::Client::builder()
@@ -927,6 +1806,25 @@ impl From<EphemeralClientRequest> for Client {
             default_scopes: scopes,
             challenge: Some("S256".to_string()),
             force_mfa: *EPHEMERAL_CLIENTS_FORCE_MFA,
+            token_exchange_audiences: None,
+            backchannel_logout_uri: None,
+            frontchannel_logout_uri: None,
+            require_par: false,
+            jwks: None,
+            jwks_uri: None,
+            token_endpoint_auth_method: "none".to_string(),
+            tls_client_auth_subject_dn: None,
+            tls_client_auth_thumbprints: None,
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
+            userinfo_signed_response_alg: None,
+            subject_type: None,
+            sector_identifier_uri: None,
+            require_consent: false,
+            refresh_token_rotation: false,
+            request_uris: None,
         }
     }
 }
@@ -963,6 +1861,25 @@ impl Default for Client {
             default_scopes: "openid".to_string(),
             challenge: Some("S256".to_string()),
             force_mfa: false,
+            token_exchange_audiences: None,
+            backchannel_logout_uri: None,
+            frontchannel_logout_uri: None,
+            require_par: false,
+            jwks: None,
+            jwks_uri: None,
+            token_endpoint_auth_method: "none".to_string(),
+            tls_client_auth_subject_dn: None,
+            tls_client_auth_thumbprints: None,
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
+            userinfo_signed_response_alg: None,
+            subject_type: None,
+            sector_identifier_uri: None,
+            require_consent: false,
+            refresh_token_rotation: false,
+            request_uris: None,
         }
     }
 }
@@ -971,6 +1888,11 @@ impl From<NewClientRequest> for Client {
     fn from(client: NewClientRequest) -> Self {
         let redirect_uris = client.redirect_uris.join(",");
         let post_logout_redirect_uris = client.post_logout_redirect_uris.map(|u| u.join(","));
+        let token_endpoint_auth_method = if client.confidential {
+            "client_secret_basic"
+        } else {
+            "none"
+        };
 
         Self {
             id: client.id,
@@ -979,6 +1901,7 @@ impl From<NewClientRequest> for Client {
             confidential: client.confidential,
             redirect_uris,
             post_logout_redirect_uris,
+            token_endpoint_auth_method: token_endpoint_auth_method.to_string(),
             ..Default::default()
         }
     }
@@ -1071,6 +1994,25 @@ mod tests {
             default_scopes: "openid,email,profile,groups".to_string(),
             challenge: Some("S256,plain".to_string()),
             force_mfa: false,
+            token_exchange_audiences: Some("backend-1,backend-2".to_string()),
+            backchannel_logout_uri: None,
+            frontchannel_logout_uri: None,
+            require_par: false,
+            jwks: None,
+            jwks_uri: None,
+            token_endpoint_auth_method: "none".to_string(),
+            tls_client_auth_subject_dn: None,
+            tls_client_auth_thumbprints: None,
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
+            userinfo_signed_response_alg: None,
+            subject_type: None,
+            sector_identifier_uri: None,
+            require_consent: false,
+            refresh_token_rotation: false,
+            request_uris: None,
         };
 
         assert_eq!(client.get_access_token_alg().unwrap(), JwkKeyPairAlg::EdDSA);
@@ -1153,6 +2095,15 @@ mod tests {
         assert!(client.validate_flow("blabla").is_err());
         assert!(client.validate_flow("").is_err());
 
+        assert_eq!(
+            client.get_token_exchange_audiences(),
+            Some(vec!["backend-1".to_string(), "backend-2".to_string()])
+        );
+        assert_eq!(client.validate_token_exchange_audience("123"), Ok(()));
+        assert_eq!(client.validate_token_exchange_audience("backend-2"), Ok(()));
+        assert!(client.validate_token_exchange_audience("backend").is_err());
+        assert!(client.validate_token_exchange_audience("").is_err());
+
         // validate origin
         let listen_scheme = ListenScheme::Http;
         let pub_url = "localhost:8080";
@@ -1192,6 +2143,209 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_validate_client_certificate() {
+        let cert = ClientCertificate {
+            thumbprint: "n4bQgYhMfWWaL-qgxVrQFaO_TxsrC4Is0V1sFbDwCgg".to_string(),
+            subject_dn: "CN=client,O=Example".to_string(),
+            is_ca_verified: true,
+        };
+
+        let client = Client {
+            confidential: true,
+            token_endpoint_auth_method: "tls_client_auth".to_string(),
+            tls_client_auth_subject_dn: Some("CN=client, O=Example".to_string()),
+            ..Default::default()
+        };
+        assert!(client.validate_client_certificate(&cert).is_ok());
+        let not_verified = ClientCertificate {
+            is_ca_verified: false,
+            ..cert.clone()
+        };
+        assert!(client.validate_client_certificate(&not_verified).is_err());
+
+        let client = Client {
+            token_endpoint_auth_method: "self_signed_tls_client_auth".to_string(),
+            tls_client_auth_subject_dn: None,
+            tls_client_auth_thumbprints: Some(format!("other, {}", cert.thumbprint)),
+            ..client
+        };
+        // the chain does not matter for self-signed certificates
+        assert!(client.validate_client_certificate(&not_verified).is_ok());
+        let other = ClientCertificate {
+            thumbprint: "unknown".to_string(),
+            ..cert
+        };
+        assert!(client.validate_client_certificate(&other).is_err());
+    }
+
+    #[test]
+    fn test_validate_jwe() {
+        let jwks = r#"{"keys": [
+            {"kty": "OKP", "alg": "EdDSA", "crv": "Ed25519", "kid": "sig", "x": "x"},
+            {"kty": "EC", "use": "enc", "crv": "P-256", "kid": "enc", "x": "x", "y": "y"}
+        ]}"#;
+        // encryption keys must not end up in the keys for signature validation
+        let parsed = Client::parse_jwks(jwks).unwrap();
+        assert_eq!(parsed.keys.len(), 1);
+        assert_eq!(parsed.keys[0].kid.as_deref(), Some("sig"));
+
+        let client = Client {
+            jwks: Some(jwks.to_string()),
+            id_token_encrypted_response_alg: Some("ECDH-ES".to_string()),
+            id_token_encrypted_response_enc: Some("A256GCM".to_string()),
+            ..Default::default()
+        };
+        assert!(client.validate_jwe().is_ok());
+
+        let no_enc = Client {
+            id_token_encrypted_response_enc: None,
+            ..client.clone()
+        };
+        assert!(no_enc.validate_jwe().is_err());
+
+        let no_alg = Client {
+            id_token_encrypted_response_alg: None,
+            ..client.clone()
+        };
+        assert!(no_alg.validate_jwe().is_err());
+
+        // there is no RSA encryption key
+        let no_key = Client {
+            userinfo_encrypted_response_alg: Some("RSA-OAEP-256".to_string()),
+            userinfo_encrypted_response_enc: Some("A256GCM".to_string()),
+            ..client.clone()
+        };
+        assert!(no_key.validate_jwe().is_err());
+
+        let no_jwks = Client {
+            jwks: None,
+            ..client
+        };
+        assert!(no_jwks.validate_jwe().is_err());
+    }
+
+    #[test]
+    fn test_sanitize_login_scopes_offline() {
+        let client = Client {
+            scopes: "openid,email,offline_access".to_string(),
+            default_scopes: "openid,offline_access".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            client.sanitize_login_scopes(&None).unwrap(),
+            vec!["openid".to_string()]
+        );
+        assert_eq!(
+            client
+                .sanitize_login_scopes(&Some(vec!["email".to_string()]))
+                .unwrap(),
+            vec!["openid".to_string(), "email".to_string()]
+        );
+        assert_eq!(
+            client
+                .sanitize_login_scopes(&Some(vec!["offline_access".to_string()]))
+                .unwrap(),
+            vec!["openid".to_string(), "offline_access".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_pairwise_sub() {
+        let client = Client {
+            redirect_uris: "https://app.example.com/callback,https://app.example.com/other"
+                .to_string(),
+            ..Default::default()
+        };
+        assert_eq!(client.get_pairwise_sub("user1").unwrap(), None);
+        assert_eq!(client.get_sector_identifier().unwrap(), "app.example.com");
+
+        let client = Client {
+            sector_identifier_uri: Some("https://sector.example.com/uris.json".to_string()),
+            ..client
+        };
+        assert_eq!(
+            client.get_sector_identifier().unwrap(),
+            "sector.example.com"
+        );
+
+        let client = Client {
+            redirect_uris: String::default(),
+            sector_identifier_uri: None,
+            ..client
+        };
+        assert!(client.get_sector_identifier().is_err());
+
+        // the same user must get the same `sub` for all clients of one sector
+        let secret = "pairwise_secret_123";
+        let sub = Client::pairwise_sub(secret, "app.example.com", "user1");
+        assert_eq!(sub.len(), 43);
+        assert_eq!(
+            sub,
+            Client::pairwise_sub(secret, "app.example.com", "user1")
+        );
+
+        // while it must not be possible to correlate it across sectors or users
+        assert_ne!(
+            sub,
+            Client::pairwise_sub(secret, "other.example.com", "user1")
+        );
+        assert_ne!(
+            sub,
+            Client::pairwise_sub(secret, "app.example.com", "user2")
+        );
+        assert_ne!(
+            sub,
+            Client::pairwise_sub("other_secret_456", "app.example.com", "user1")
+        );
+    }
+
+    #[test]
+    fn test_validate_request_uri() {
+        let client = Client {
+            request_uris: Some(
+                "https://app.example.com/request.jwt,https://app.example.com/other.jwt".to_string(),
+            ),
+            ..Default::default()
+        };
+        assert!(client
+            .validate_request_uri("https://app.example.com/request.jwt")
+            .is_ok());
+        assert!(client
+            .validate_request_uri("https://app.example.com/other.jwt#abc123")
+            .is_ok());
+        assert!(client
+            .validate_request_uri("https://app.example.com/request.jwt/")
+            .is_err());
+        assert!(client
+            .validate_request_uri("https://169.254.169.254/latest/meta-data")
+            .is_err());
+
+        let client = Client {
+            request_uris: None,
+            ..client
+        };
+        assert!(client
+            .validate_request_uri("https://app.example.com/request.jwt")
+            .is_err());
+    }
+
+    #[test]
+    fn test_allows_offline_access() {
+        let client = Client {
+            scopes: "openid,email,profile".to_string(),
+            default_scopes: "openid".to_string(),
+            ..Default::default()
+        };
+        assert!(!client.allows_offline_access());
+
+        let client = Client {
+            scopes: "openid,email,offline_access".to_string(),
+            ..client
+        };
+        assert!(client.allows_offline_access());
+    }
+
     #[test]
     fn test_is_origin_external() {
         let pub_url = "localhost:8443";
@@ -0,0 +1,132 @@
+use crate::app_state::{AppState, DbTxn};
+use crate::entity::clients::Client;
+use actix_web::web;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::get_rand;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+/// Additional information for clients, which have been registered via the dynamic client
+/// registration. The client itself is a normal [Client](crate::entity::clients::Client) and
+/// will be deleted together with this entry.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct ClientDyn {
+    pub id: String,
+    pub created: i64,
+    // only the hex encoded SHA256 hash of the registration access token is saved
+    pub registration_token: String,
+    pub token_endpoint_auth_method: String,
+}
+
+// CRUD
+impl ClientDyn {
+    /// Inserts the given client together with its dynamic registration information inside a
+    /// single transaction. Returns the new entity and the cleartext registration access token,
+    /// which cannot be retrieved again later on.
+    pub async fn create(
+        data: &web::Data<AppState>,
+        client: &Client,
+        token_endpoint_auth_method: String,
+    ) -> Result<(Self, String), ErrorResponse> {
+        let token = get_rand(48);
+        let slf = Self {
+            id: client.id.clone(),
+            created: OffsetDateTime::now_utc().unix_timestamp(),
+            registration_token: Self::hash_token(&token),
+            token_endpoint_auth_method,
+        };
+
+        let mut txn = data.db.begin().await?;
+        client.insert(data, Some(&mut txn)).await?;
+        sqlx::query!(
+            r#"insert into clients_dyn (id, created, registration_token, token_endpoint_auth_method)
+            values ($1, $2, $3, $4)"#,
+            slf.id,
+            slf.created,
+            slf.registration_token,
+            slf.token_endpoint_auth_method,
+        )
+        .execute(&mut *txn)
+        .await?;
+        txn.commit().await?;
+
+        Ok((slf, token))
+    }
+
+    pub async fn find(data: &web::Data<AppState>, id: &str) -> Result<Self, ErrorResponse> {
+        sqlx::query_as::<_, Self>("select * from clients_dyn where id = $1")
+            .bind(id)
+            .fetch_optional(&data.db)
+            .await?
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    "Client has not been registered dynamically".to_string(),
+                )
+            })
+    }
+
+    pub async fn save(
+        &self,
+        data: &web::Data<AppState>,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let q = sqlx::query!(
+            r#"update clients_dyn set registration_token = $1, token_endpoint_auth_method = $2
+            where id = $3"#,
+            self.registration_token,
+            self.token_endpoint_auth_method,
+            self.id,
+        );
+
+        if let Some(txn) = txn {
+            q.execute(&mut **txn).await?;
+        } else {
+            q.execute(&data.db).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ClientDyn {
+    /// Comparing the hashes instead of the tokens themselves makes the comparison time
+    /// independent of the token value.
+    pub fn hash_token(token: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, token.as_bytes());
+        hex::encode(hash.as_ref())
+    }
+
+    /// Validates the given registration access token against the saved hash.
+    pub fn validate_token(&self, token: &str) -> Result<(), ErrorResponse> {
+        if Self::hash_token(token) == self.registration_token {
+            Ok(())
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Invalid registration access token".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_token() {
+        let token = get_rand(48);
+        let client_dyn = ClientDyn {
+            id: "dyn".to_string(),
+            created: 0,
+            registration_token: ClientDyn::hash_token(&token),
+            token_endpoint_auth_method: "client_secret_basic".to_string(),
+        };
+
+        assert!(client_dyn.validate_token(&token).is_ok());
+        assert!(client_dyn.validate_token("invalid").is_err());
+    }
+}
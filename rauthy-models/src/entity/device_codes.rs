@@ -0,0 +1,180 @@
+use crate::app_state::AppState;
+use actix_web::web;
+use rand::Rng;
+use rauthy_common::constants::{
+    CACHE_NAME_DEVICE_CODES, DEVICE_GRANT_CODE_LIFETIME, DEVICE_GRANT_POLL_INTERVAL,
+    DEVICE_GRANT_USER_CODE_LENGTH,
+};
+use rauthy_common::error_response::ErrorResponse;
+use rauthy_common::utils::get_rand;
+use redhac::{cache_del, cache_get, cache_get_from, cache_get_value, cache_put};
+use serde::{Deserialize, Serialize};
+use std::ops::Add;
+use time::OffsetDateTime;
+
+// Only consonants to prevent accidental words and easily confused characters
+const USER_CODE_CHARS: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+// Struct for the codes from the 'urn:ietf:params:oauth:grant-type:device_code' flow
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    // always saved in its normalized form without any separators
+    pub user_code: String,
+    pub exp: i64,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    // the current minimal polling interval in seconds, which will be increased with each `slow_down`
+    pub interval: u32,
+    pub last_poll: Option<i64>,
+    // the id of the user, who verified this device
+    pub verified_by: Option<String>,
+    pub is_mfa: bool,
+    pub denied: bool,
+}
+
+// CRUD
+impl DeviceCode {
+    // Deletes a Device Code from the cache
+    pub async fn delete(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_del(
+            CACHE_NAME_DEVICE_CODES.to_string(),
+            Self::cache_idx_user_code(&self.user_code),
+            &data.caches.ha_cache_config,
+        )
+        .await?;
+        cache_del(
+            CACHE_NAME_DEVICE_CODES.to_string(),
+            Self::cache_idx(&self.device_code),
+            &data.caches.ha_cache_config,
+        )
+        .await
+        .map_err(ErrorResponse::from)
+    }
+
+    // Returns a Device Code from the cache
+    pub async fn find(
+        data: &web::Data<AppState>,
+        device_code: &str,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        cache_get!(
+            DeviceCode,
+            CACHE_NAME_DEVICE_CODES.to_string(),
+            Self::cache_idx(device_code),
+            &data.caches.ha_cache_config,
+            true
+        )
+        .await
+        .map_err(ErrorResponse::from)
+    }
+
+    // Returns a Device Code from the cache by the user code, which was entered on the verification
+    // page
+    pub async fn find_by_user_code(
+        data: &web::Data<AppState>,
+        user_code: &str,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        let device_code = cache_get!(
+            String,
+            CACHE_NAME_DEVICE_CODES.to_string(),
+            Self::cache_idx_user_code(&Self::normalize_user_code(user_code)),
+            &data.caches.ha_cache_config,
+            true
+        )
+        .await?;
+
+        match device_code {
+            None => Ok(None),
+            Some(device_code) => Self::find(data, &device_code).await,
+        }
+    }
+
+    // Saves a Device Code
+    pub async fn save(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_put(
+            CACHE_NAME_DEVICE_CODES.to_string(),
+            Self::cache_idx_user_code(&self.user_code),
+            &data.caches.ha_cache_config,
+            &self.device_code,
+        )
+        .await?;
+        cache_put(
+            CACHE_NAME_DEVICE_CODES.to_string(),
+            Self::cache_idx(&self.device_code),
+            &data.caches.ha_cache_config,
+            self,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl DeviceCode {
+    pub fn new(client_id: String, scopes: Vec<String>) -> Self {
+        let exp = OffsetDateTime::now_utc()
+            .add(time::Duration::seconds(*DEVICE_GRANT_CODE_LIFETIME as i64))
+            .unix_timestamp();
+
+        let mut rng = rand::thread_rng();
+        let user_code = (0..*DEVICE_GRANT_USER_CODE_LENGTH)
+            .map(|_| USER_CODE_CHARS[rng.gen_range(0..USER_CODE_CHARS.len())] as char)
+            .collect::<String>();
+
+        Self {
+            device_code: get_rand(64),
+            user_code,
+            exp,
+            client_id,
+            scopes,
+            interval: *DEVICE_GRANT_POLL_INTERVAL,
+            last_poll: None,
+            verified_by: None,
+            is_mfa: false,
+            denied: false,
+        }
+    }
+
+    fn cache_idx(device_code: &str) -> String {
+        format!("device_code_{}", device_code)
+    }
+
+    fn cache_idx_user_code(user_code: &str) -> String {
+        format!("device_user_code_{}", user_code)
+    }
+
+    /// Returns the user code split in half with a `-` for better readability.
+    pub fn user_code_display(&self) -> String {
+        let (a, b) = self.user_code.split_at(self.user_code.len() / 2);
+        format!("{}-{}", a, b)
+    }
+
+    /// Removes any separators and converts the code to uppercase, since users may enter the code
+    /// in any form.
+    pub fn normalize_user_code(user_code: &str) -> String {
+        user_code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_code() {
+        let code = DeviceCode::new("test".to_string(), vec!["openid".to_string()]);
+        assert_eq!(code.user_code.len(), 8);
+        assert!(code.user_code.bytes().all(|b| USER_CODE_CHARS.contains(&b)));
+
+        let display = code.user_code_display();
+        assert_eq!(display.len(), 9);
+        assert_eq!(DeviceCode::normalize_user_code(&display), code.user_code);
+        assert_eq!(
+            DeviceCode::normalize_user_code(&display.to_lowercase()),
+            code.user_code
+        );
+    }
+}
@@ -395,6 +395,7 @@ mod tests {
                 n: None,
                 e: None,
                 x: Some(base64_url_encode(kp.pk.as_slice())),
+                y: None,
             },
             kid: None,
         };
@@ -448,6 +449,7 @@ mod tests {
                 n: Some(base64_url_encode(&n)),
                 e: Some(base64_url_encode(&e)),
                 x: None,
+                y: None,
             },
             kid: None,
         };
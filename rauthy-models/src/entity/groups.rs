@@ -1,4 +1,5 @@
 use crate::app_state::AppState;
+use crate::entity::provisioning::{ProvisioningAction, ProvisioningDelivery};
 use crate::entity::users::User;
 use crate::request::NewGroupRequest;
 use actix_web::web;
@@ -56,6 +57,9 @@ impl Group {
         )
         .await?;
 
+        ProvisioningDelivery::enqueue_group(data, &new_group, ProvisioningAction::Created, None)
+            .await?;
+
         Ok(new_group)
     }
 
@@ -97,6 +101,14 @@ impl Group {
             .execute(&mut *txn)
             .await?;
 
+        ProvisioningDelivery::enqueue_group(
+            data,
+            &group,
+            ProvisioningAction::Deleted,
+            Some(&mut txn),
+        )
+        .await?;
+
         txn.commit().await?;
 
         let groups = Group::find_all(data)
@@ -204,6 +216,14 @@ impl Group {
         .execute(&mut *txn)
         .await?;
 
+        ProvisioningDelivery::enqueue_group(
+            data,
+            &new_group,
+            ProvisioningAction::Updated,
+            Some(&mut txn),
+        )
+        .await?;
+
         txn.commit().await?;
 
         let groups = Group::find_all(data)
@@ -0,0 +1,353 @@
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::{base64_url_no_pad_decode, base64_url_no_pad_encode};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use rsa::sha2::Sha256;
+use rsa::{BigUint, Oaep, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The key management algorithms for encrypted ID tokens and userinfo responses (RFC 7516).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JweAlg {
+    RsaOaep256,
+    EcdhEs,
+}
+
+impl JweAlg {
+    pub fn as_str(&self) -> &str {
+        match self {
+            JweAlg::RsaOaep256 => "RSA-OAEP-256",
+            JweAlg::EcdhEs => "ECDH-ES",
+        }
+    }
+}
+
+impl FromStr for JweAlg {
+    type Err = ErrorResponse;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "RSA-OAEP-256" => Ok(JweAlg::RsaOaep256),
+            "ECDH-ES" => Ok(JweAlg::EcdhEs),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported JWE 'alg': {}", s),
+            )),
+        }
+    }
+}
+
+/// The content encryption algorithms for JWEs. The default `A128CBC-HS256` is not supported,
+/// which means the `enc` must always be given explicitly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JweEnc {
+    A256GCM,
+}
+
+impl JweEnc {
+    pub fn as_str(&self) -> &str {
+        match self {
+            JweEnc::A256GCM => "A256GCM",
+        }
+    }
+}
+
+impl FromStr for JweEnc {
+    type Err = ErrorResponse;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A256GCM" => Ok(JweEnc::A256GCM),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported JWE 'enc': {}", s),
+            )),
+        }
+    }
+}
+
+/// A public encryption key from the JWKS of a client.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct JweEncKey {
+    pub kty: String,
+    #[serde(rename = "use")]
+    pub use_: Option<String>,
+    pub alg: Option<String>,
+    pub kid: Option<String>,
+    pub crv: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+}
+
+impl JweEncKey {
+    /// Returns `true` if the given raw JWK from a client JWKS is meant for encryption only.
+    /// These keys are skipped when the JWKS is parsed for signature validation.
+    pub fn is_enc_key(value: &serde_json::Value) -> bool {
+        if value.get("use").and_then(|u| u.as_str()) == Some("enc") {
+            return true;
+        }
+        value
+            .get("alg")
+            .and_then(|alg| alg.as_str())
+            .map(|alg| JweAlg::from_str(alg).is_ok())
+            .unwrap_or(false)
+    }
+
+    /// Returns `true` if this key can be used with the given `alg`.
+    pub fn supports(&self, alg: &JweAlg) -> bool {
+        if self.use_.as_deref() == Some("sig") {
+            return false;
+        }
+        if matches!(&self.alg, Some(key_alg) if key_alg != alg.as_str()) {
+            return false;
+        }
+        match alg {
+            JweAlg::RsaOaep256 => self.kty == "RSA" && self.n.is_some() && self.e.is_some(),
+            JweAlg::EcdhEs => {
+                self.kty == "EC"
+                    && matches!(self.crv.as_deref(), Some("P-256") | Some("P-384"))
+                    && self.x.is_some()
+                    && self.y.is_some()
+            }
+        }
+    }
+
+    /// Returns the key as an uncompressed SEC1 point: 0x04 | x | y
+    fn ec_point(&self) -> Result<Vec<u8>, ErrorResponse> {
+        let (Some(x), Some(y)) = (&self.x, &self.y) else {
+            return Err(err("The EC key must have 'x' and 'y'"));
+        };
+        let mut point = vec![0x04];
+        point.extend(base64_url_no_pad_decode(x)?);
+        point.extend(base64_url_no_pad_decode(y)?);
+        Ok(point)
+    }
+
+    fn rsa_public_key(&self) -> Result<RsaPublicKey, ErrorResponse> {
+        let (Some(n), Some(e)) = (&self.n, &self.e) else {
+            return Err(err("The RSA key must have 'n' and 'e'"));
+        };
+        RsaPublicKey::new(
+            BigUint::from_bytes_be(&base64_url_no_pad_decode(n)?),
+            BigUint::from_bytes_be(&base64_url_no_pad_decode(e)?),
+        )
+        .map_err(|_| err("Invalid RSA encryption key"))
+    }
+}
+
+/// A JWE in compact serialization (RFC 7516).
+pub struct Jwe;
+
+impl Jwe {
+    /// Encrypts the payload to the given public key. For nested JWTs, like an encrypted ID token,
+    /// the `cty` must be `JWT`.
+    pub fn encrypt(
+        payload: &[u8],
+        key: &JweEncKey,
+        alg: &JweAlg,
+        enc: &JweEnc,
+        cty: Option<&str>,
+    ) -> Result<String, ErrorResponse> {
+        if !key.supports(alg) {
+            return Err(err("The encryption key does not support the JWE 'alg'"));
+        }
+
+        let rng = SystemRandom::new();
+        let mut header = serde_json::json!({
+            "alg": alg.as_str(),
+            "enc": enc.as_str(),
+        });
+        if let Some(kid) = &key.kid {
+            header["kid"] = kid.as_str().into();
+        }
+        if let Some(cty) = cty {
+            header["cty"] = cty.into();
+        }
+
+        let (cek, encrypted_key) = match alg {
+            JweAlg::RsaOaep256 => {
+                let mut cek = [0u8; 32];
+                rng.fill(&mut cek)
+                    .map_err(|_| err("Cannot generate the CEK"))?;
+                let encrypted_key = key
+                    .rsa_public_key()?
+                    .encrypt(&mut rand_core::OsRng, Oaep::new::<Sha256>(), &cek)
+                    .map_err(|_| err("Cannot encrypt the CEK"))?;
+                (cek.to_vec(), encrypted_key)
+            }
+            JweAlg::EcdhEs => {
+                let (crv, curve) = match key.crv.as_deref() {
+                    Some("P-384") => ("P-384", &agreement::ECDH_P384),
+                    _ => ("P-256", &agreement::ECDH_P256),
+                };
+                let private_key = EphemeralPrivateKey::generate(curve, &rng)
+                    .map_err(|_| err("Cannot generate the ephemeral key"))?;
+                let public_key = private_key
+                    .compute_public_key()
+                    .map_err(|_| err("Cannot generate the ephemeral key"))?;
+                let (x, y) = public_key.as_ref()[1..].split_at((public_key.as_ref().len() - 1) / 2);
+                header["epk"] = serde_json::json!({
+                    "kty": "EC",
+                    "crv": crv,
+                    "x": base64_url_no_pad_encode(x),
+                    "y": base64_url_no_pad_encode(y),
+                });
+
+                let peer = UnparsedPublicKey::new(curve, key.ec_point()?);
+                // with direct key agreement, the derived key is the CEK itself
+                let cek = agreement::agree_ephemeral(private_key, &peer, |z| {
+                    Self::concat_kdf(z, enc.as_str(), 256, b"", b"")
+                })
+                .map_err(|_| err("ECDH key agreement failed"))?;
+                (cek, Vec::new())
+            }
+        };
+
+        let protected = base64_url_no_pad_encode(serde_json::to_string(&header)?.as_bytes());
+        let mut iv = [0u8; 12];
+        rng.fill(&mut iv)
+            .map_err(|_| err("Cannot generate the IV"))?;
+
+        let key =
+            LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &cek).map_err(|_| err("Invalid CEK"))?);
+        let mut ciphertext = payload.to_vec();
+        let tag = key
+            .seal_in_place_separate_tag(
+                Nonce::assume_unique_for_key(iv),
+                Aad::from(protected.as_bytes()),
+                &mut ciphertext,
+            )
+            .map_err(|_| err("Cannot encrypt the JWE payload"))?;
+
+        Ok(format!(
+            "{}.{}.{}.{}.{}",
+            protected,
+            base64_url_no_pad_encode(&encrypted_key),
+            base64_url_no_pad_encode(&iv),
+            base64_url_no_pad_encode(&ciphertext),
+            base64_url_no_pad_encode(tag.as_ref()),
+        ))
+    }
+
+    /// The Concat KDF from NIST SP 800-56A like defined for ECDH-ES in RFC 7518, section 4.6.
+    /// Only a single round is needed, since the key length is never bigger than 256 bits.
+    fn concat_kdf(z: &[u8], alg_id: &str, key_bits: u32, apu: &[u8], apv: &[u8]) -> Vec<u8> {
+        let with_len = |value: &[u8]| {
+            let mut res = (value.len() as u32).to_be_bytes().to_vec();
+            res.extend_from_slice(value);
+            res
+        };
+
+        let mut input = 1u32.to_be_bytes().to_vec();
+        input.extend_from_slice(z);
+        input.extend(with_len(alg_id.as_bytes()));
+        input.extend(with_len(apu));
+        input.extend(with_len(apv));
+        input.extend_from_slice(&key_bits.to_be_bytes());
+
+        let hash = digest::digest(&digest::SHA256, &input);
+        hash.as_ref()[..(key_bits / 8) as usize].to_vec()
+    }
+}
+
+fn err(msg: &str) -> ErrorResponse {
+    ErrorResponse::new(ErrorResponseType::Internal, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_kdf() {
+        // test vector from RFC 7518, Appendix C
+        let z = [
+            158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49,
+            110, 163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+        ];
+        let key = Jwe::concat_kdf(&z, "A128GCM", 128, b"Alice", b"Bob");
+        assert_eq!(base64_url_no_pad_encode(&key), "VqqN6vgjbSBcIijNcacQGg");
+    }
+
+    #[test]
+    fn test_encrypt_ecdh_es() {
+        let rng = SystemRandom::new();
+        let recipient = EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng).unwrap();
+        let recipient_pub = recipient.compute_public_key().unwrap();
+        let (x, y) = recipient_pub.as_ref()[1..].split_at(32);
+        let key = JweEncKey {
+            kty: "EC".to_string(),
+            use_: Some("enc".to_string()),
+            crv: Some("P-256".to_string()),
+            kid: Some("enc1".to_string()),
+            x: Some(base64_url_no_pad_encode(x)),
+            y: Some(base64_url_no_pad_encode(y)),
+            ..Default::default()
+        };
+        assert!(key.supports(&JweAlg::EcdhEs));
+        assert!(!key.supports(&JweAlg::RsaOaep256));
+
+        let jwe = Jwe::encrypt(
+            b"{\"sub\":\"test\"}",
+            &key,
+            &JweAlg::EcdhEs,
+            &JweEnc::A256GCM,
+            None,
+        )
+        .unwrap();
+        let parts = jwe.split('.').collect::<Vec<&str>>();
+        assert_eq!(parts.len(), 5);
+        assert!(parts[1].is_empty());
+
+        // decrypt it again as the recipient
+        let header = serde_json::from_slice::<serde_json::Value>(
+            &base64_url_no_pad_decode(parts[0]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(header["kid"], "enc1");
+        let epk = JweEncKey {
+            kty: "EC".to_string(),
+            x: header["epk"]["x"].as_str().map(String::from),
+            y: header["epk"]["y"].as_str().map(String::from),
+            ..Default::default()
+        };
+        let peer = UnparsedPublicKey::new(&agreement::ECDH_P256, epk.ec_point().unwrap());
+        let cek = agreement::agree_ephemeral(recipient, &peer, |z| {
+            Jwe::concat_kdf(z, "A256GCM", 256, b"", b"")
+        })
+        .unwrap();
+
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &cek).unwrap());
+        let iv: [u8; 12] = base64_url_no_pad_decode(parts[2])
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let mut in_out = base64_url_no_pad_decode(parts[3]).unwrap();
+        in_out.extend(base64_url_no_pad_decode(parts[4]).unwrap());
+        let plain = key
+            .open_in_place(
+                Nonce::assume_unique_for_key(iv),
+                Aad::from(parts[0].as_bytes()),
+                &mut in_out,
+            )
+            .unwrap();
+        assert_eq!(plain, b"{\"sub\":\"test\"}".as_slice());
+    }
+
+    #[test]
+    fn test_is_enc_key() {
+        let key = serde_json::json!({"kty": "RSA", "use": "enc", "n": "n", "e": "AQAB"});
+        assert!(JweEncKey::is_enc_key(&key));
+        let key = serde_json::json!({"kty": "RSA", "alg": "RSA-OAEP-256", "n": "n", "e": "AQAB"});
+        assert!(JweEncKey::is_enc_key(&key));
+        let key = serde_json::json!({"kty": "RSA", "alg": "RS256", "n": "n", "e": "AQAB"});
+        assert!(!JweEncKey::is_enc_key(&key));
+        let key = serde_json::json!({"kty": "OKP", "crv": "Ed25519", "x": "x"});
+        assert!(!JweEncKey::is_enc_key(&key));
+    }
+}
@@ -1,12 +1,16 @@
 use crate::app_state::{AppState, DbPool};
 use actix_web::web;
 use jwt_simple::algorithms;
+use p256::ecdsa::signature::Signer;
 use rauthy_common::constants::{CACHE_NAME_12HR, IDX_JWKS, IDX_JWK_KID, IDX_JWK_LATEST};
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
-use rauthy_common::utils::decrypt;
-use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_decode};
+use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_decode, base64_url_no_pad_encode};
+use rauthy_common::utils::{decrypt, encrypt, get_rand};
 use redhac::{cache_get, cache_get_from, cache_get_value, cache_put};
-use rsa::BigUint;
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::sha2::{Digest, Sha256, Sha384, Sha512};
+use rsa::{BigUint, Pkcs1v15Sign, Pss};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::sqlite::SqliteRow;
@@ -38,6 +42,24 @@ macro_rules! sign_jwt {
                         .unwrap();
                 key.with_key_id(&$key_pair.kid).sign($claims)
             }
+            JwkKeyPairAlg::PS256 => {
+                let key =
+                    jwt_simple::algorithms::PS256KeyPair::from_der($key_pair.bytes.as_slice())
+                        .unwrap();
+                key.with_key_id(&$key_pair.kid).sign($claims)
+            }
+            JwkKeyPairAlg::ES256 => {
+                let key =
+                    jwt_simple::algorithms::ES256KeyPair::from_der($key_pair.bytes.as_slice())
+                        .unwrap();
+                key.with_key_id(&$key_pair.kid).sign($claims)
+            }
+            JwkKeyPairAlg::ES384 => {
+                let key =
+                    jwt_simple::algorithms::ES384KeyPair::from_der($key_pair.bytes.as_slice())
+                        .unwrap();
+                key.with_key_id(&$key_pair.kid).sign($claims)
+            }
             JwkKeyPairAlg::EdDSA => {
                 let key =
                     jwt_simple::algorithms::Ed25519KeyPair::from_der($key_pair.bytes.as_slice())
@@ -79,6 +101,27 @@ macro_rules! validate_jwt {
                 key.public_key()
                     .verify_token::<$type>($token, Some($options))
             }
+            JwkKeyPairAlg::PS256 => {
+                let key =
+                    jwt_simple::algorithms::PS256KeyPair::from_der($key_pair.bytes.as_slice())
+                        .unwrap();
+                key.public_key()
+                    .verify_token::<$type>($token, Some($options))
+            }
+            JwkKeyPairAlg::ES256 => {
+                let key =
+                    jwt_simple::algorithms::ES256KeyPair::from_der($key_pair.bytes.as_slice())
+                        .unwrap();
+                key.public_key()
+                    .verify_token::<$type>($token, Some($options))
+            }
+            JwkKeyPairAlg::ES384 => {
+                let key =
+                    jwt_simple::algorithms::ES384KeyPair::from_der($key_pair.bytes.as_slice())
+                        .unwrap();
+                key.public_key()
+                    .verify_token::<$type>($token, Some($options))
+            }
             JwkKeyPairAlg::EdDSA => {
                 let key =
                     jwt_simple::algorithms::Ed25519KeyPair::from_der($key_pair.bytes.as_slice())
@@ -143,6 +186,41 @@ impl Jwk {
             jwk,
         }
     }
+
+    /// Generates a new key pair for the given algorithm. The key pair will be encrypted with the
+    /// given `enc_key` right away and is ready to be saved afterward.
+    pub async fn generate(
+        alg: JwkKeyPairAlg,
+        enc_key_id: &str,
+        enc_key: &[u8],
+    ) -> Result<Self, ErrorResponse> {
+        let signature = alg.clone();
+        // the RSA key generation can take quite a few seconds for bigger keys
+        let der = web::block(move || match alg {
+            JwkKeyPairAlg::RS256 => algorithms::RS256KeyPair::generate(2048)?.to_der(),
+            JwkKeyPairAlg::RS384 => algorithms::RS384KeyPair::generate(3072)?.to_der(),
+            JwkKeyPairAlg::RS512 => algorithms::RS512KeyPair::generate(4096)?.to_der(),
+            JwkKeyPairAlg::PS256 => algorithms::PS256KeyPair::generate(2048)?.to_der(),
+            JwkKeyPairAlg::ES256 => algorithms::ES256KeyPair::generate().to_der(),
+            JwkKeyPairAlg::ES384 => algorithms::ES384KeyPair::generate().to_der(),
+            JwkKeyPairAlg::EdDSA => Ok(algorithms::Ed25519KeyPair::generate().to_der()),
+        })
+        .await?
+        .map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Error generating a new {} JWK: {}", signature.as_str(), err),
+            )
+        })?;
+
+        Ok(Self {
+            kid: get_rand(24),
+            created_at: time::OffsetDateTime::now_utc().unix_timestamp(),
+            signature,
+            enc_key_id: enc_key_id.to_string(),
+            jwk: encrypt(der.as_slice(), enc_key)?,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -206,11 +284,12 @@ impl JWKS {
 pub struct JWKSPublicKey {
     pub kty: JwkKeyPairType,
     pub alg: Option<JwkKeyPairAlg>,
-    pub crv: Option<String>, // Ed25519
+    pub crv: Option<String>, // Ed25519, P-256, P-384
     pub kid: Option<String>,
     pub n: Option<String>, // RSA
     pub e: Option<String>, // RSA
-    pub x: Option<String>, // OKP
+    pub x: Option<String>, // OKP, EC
+    pub y: Option<String>, // EC
 }
 
 impl JWKSPublicKey {
@@ -260,6 +339,17 @@ impl JWKSPublicKey {
         }
     }
 
+    pub fn y(&self) -> Result<Vec<u8>, ErrorResponse> {
+        if let Some(y) = &self.y {
+            Ok(base64_url_no_pad_decode(y)?)
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "No 'y' in JwkKeyPublicKey".to_string(),
+            ))
+        }
+    }
+
     pub fn from_key_pair(key_pair: &JwkKeyPair) -> Self {
         let get_rsa = |kid: String, comp: algorithms::RSAPublicKeyComponents| JWKSPublicKey {
             kty: JwkKeyPairType::RSA,
@@ -269,6 +359,7 @@ impl JWKSPublicKey {
             n: Some(base64_url_encode(&comp.n)),
             e: Some(base64_url_encode(&comp.e)),
             x: None,
+            y: None,
         };
 
         let get_ed25519 = |kid: String, x: String| JWKSPublicKey {
@@ -279,6 +370,22 @@ impl JWKSPublicKey {
             n: None,
             e: None,
             x: Some(x),
+            y: None,
+        };
+
+        // the public key is given as an uncompressed point: 0x04 | x | y
+        let get_ec = |kid: String, crv: &str, point: Vec<u8>| {
+            let (x, y) = point[1..].split_at((point.len() - 1) / 2);
+            JWKSPublicKey {
+                kty: JwkKeyPairType::EC,
+                alg: Some(key_pair.typ.clone()),
+                crv: Some(crv.to_string()),
+                kid: Some(kid),
+                n: None,
+                e: None,
+                x: Some(base64_url_encode(x)),
+                y: Some(base64_url_encode(y)),
+            }
         };
 
         match key_pair.typ {
@@ -297,6 +404,21 @@ impl JWKSPublicKey {
                 let comp = kp.public_key().to_components();
                 get_rsa(key_pair.kid.clone(), comp)
             }
+            JwkKeyPairAlg::PS256 => {
+                let kp = algorithms::PS256KeyPair::from_der(&key_pair.bytes).unwrap();
+                let comp = kp.public_key().to_components();
+                get_rsa(key_pair.kid.clone(), comp)
+            }
+            JwkKeyPairAlg::ES256 => {
+                let kp = algorithms::ES256KeyPair::from_der(&key_pair.bytes).unwrap();
+                let point = kp.public_key().to_bytes_uncompressed();
+                get_ec(key_pair.kid.clone(), "P-256", point)
+            }
+            JwkKeyPairAlg::ES384 => {
+                let kp = algorithms::ES384KeyPair::from_der(&key_pair.bytes).unwrap();
+                let point = kp.public_key().to_bytes_uncompressed();
+                get_ec(key_pair.kid.clone(), "P-384", point)
+            }
             JwkKeyPairAlg::EdDSA => {
                 let kp = algorithms::Ed25519KeyPair::from_der(&key_pair.bytes).unwrap();
                 let x = base64_url_encode(&kp.public_key().to_bytes());
@@ -345,6 +467,27 @@ impl JWKSPublicKey {
                     x
                 )
             }
+
+            JwkKeyPairType::EC => {
+                if self.crv.is_none() || self.x.is_none() || self.y.is_none() {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::Internal,
+                        "Incorrect format for EC JWK: crv / x / y missing".to_string(),
+                    ));
+                }
+
+                // mandatory keys for EC are in order: crv, kty, x, y
+                let crv = self.crv.as_deref().unwrap();
+                let x = self.x.as_deref().unwrap();
+                let y = self.y.as_deref().unwrap();
+                format!(
+                    "{{\"crv\":\"{}\",\"kty\":\"{}\",\"x\":\"{}\",\"y\":\"{}\"}}",
+                    crv,
+                    self.kty.as_str(),
+                    x,
+                    y
+                )
+            }
         };
 
         let hash = hmac_sha256::Hash::hash(s.as_bytes());
@@ -362,10 +505,13 @@ impl JWKSPublicKey {
             Some(alg) => {
                 match self.kty {
                     JwkKeyPairType::RSA => {
-                        if alg == &JwkKeyPairAlg::EdDSA {
+                        if matches!(
+                            alg,
+                            JwkKeyPairAlg::EdDSA | JwkKeyPairAlg::ES256 | JwkKeyPairAlg::ES384
+                        ) {
                             return Err(ErrorResponse::new(
                                 ErrorResponseType::BadRequest,
-                                "RSA kty cannot have EdDSA alg".to_string(),
+                                format!("RSA kty cannot have {} alg", alg.as_str()),
                             ));
                         }
 
@@ -376,10 +522,10 @@ impl JWKSPublicKey {
                             ));
                         }
 
-                        if self.x.is_some() {
+                        if self.x.is_some() || self.y.is_some() {
                             return Err(ErrorResponse::new(
                                 ErrorResponseType::BadRequest,
-                                "RSA key cannot have 'x' public key component".to_string(),
+                                "RSA key cannot have 'x' or 'y' public key components".to_string(),
                             ));
                         }
                     }
@@ -422,6 +568,40 @@ impl JWKSPublicKey {
                             ));
                         }
                     }
+
+                    JwkKeyPairType::EC => {
+                        let crv = match alg {
+                            JwkKeyPairAlg::ES256 => "P-256",
+                            JwkKeyPairAlg::ES384 => "P-384",
+                            _ => {
+                                return Err(ErrorResponse::new(
+                                    ErrorResponseType::BadRequest,
+                                    "EC kty must have ES256 or ES384 alg".to_string(),
+                                ));
+                            }
+                        };
+
+                        if self.crv.as_deref() != Some(crv) {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                format!("EC key with {} alg must have '{}' crv", alg.as_str(), crv),
+                            ));
+                        }
+
+                        if self.n.is_some() || self.e.is_some() {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "EC key cannot have 'n' or 'e' public key components".to_string(),
+                            ));
+                        }
+
+                        if self.x.is_none() || self.y.is_none() {
+                            return Err(ErrorResponse::new(
+                                ErrorResponseType::BadRequest,
+                                "EC key must have 'x' and 'y' public key components".to_string(),
+                            ));
+                        }
+                    }
                 }
 
                 Ok(())
@@ -467,6 +647,21 @@ impl JwkKeyPair {
                 typ: JwkKeyPairAlg::RS512,
                 bytes: jwk_decrypted,
             },
+            JwkKeyPairAlg::PS256 => JwkKeyPair {
+                kid,
+                typ: JwkKeyPairAlg::PS256,
+                bytes: jwk_decrypted,
+            },
+            JwkKeyPairAlg::ES256 => JwkKeyPair {
+                kid,
+                typ: JwkKeyPairAlg::ES256,
+                bytes: jwk_decrypted,
+            },
+            JwkKeyPairAlg::ES384 => JwkKeyPair {
+                kid,
+                typ: JwkKeyPairAlg::ES384,
+                bytes: jwk_decrypted,
+            },
             JwkKeyPairAlg::EdDSA => JwkKeyPair {
                 kid,
                 typ: JwkKeyPairAlg::EdDSA,
@@ -576,12 +771,103 @@ impl JwkKeyPair {
             ))
         }
     }
+
+    /// Signs the given claims with a custom `typ` header, like for instance
+    /// `token-introspection+jwt` (RFC 9701). The `sign_jwt!` macro cannot be used in these
+    /// cases, because it always sets the `typ` to `JWT`.
+    pub fn sign_with_typ<T: Serialize>(
+        &self,
+        claims: &T,
+        typ: &str,
+    ) -> Result<String, ErrorResponse> {
+        let header = serde_json::json!({
+            "alg": self.typ.as_str(),
+            "kid": self.kid,
+            "typ": typ,
+        });
+        let header = serde_json::to_vec(&header)?;
+        let payload = serde_json::to_vec(claims)?;
+        let input = format!(
+            "{}.{}",
+            base64_url_no_pad_encode(&header),
+            base64_url_no_pad_encode(&payload),
+        );
+
+        let err = || {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "Error signing JWT Token".to_string(),
+            )
+        };
+
+        let signature = match self.typ {
+            JwkKeyPairAlg::RS256 | JwkKeyPairAlg::RS384 | JwkKeyPairAlg::RS512 => {
+                let key = rsa::RsaPrivateKey::from_pkcs8_der(self.bytes.as_slice())
+                    .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_der(self.bytes.as_slice()))
+                    .map_err(|_| err())?;
+                match self.typ {
+                    JwkKeyPairAlg::RS256 => key.sign(
+                        Pkcs1v15Sign::new::<Sha256>(),
+                        &Sha256::digest(input.as_bytes()),
+                    ),
+                    JwkKeyPairAlg::RS384 => key.sign(
+                        Pkcs1v15Sign::new::<Sha384>(),
+                        &Sha384::digest(input.as_bytes()),
+                    ),
+                    _ => key.sign(
+                        Pkcs1v15Sign::new::<Sha512>(),
+                        &Sha512::digest(input.as_bytes()),
+                    ),
+                }
+                .map_err(|_| err())?
+            }
+            JwkKeyPairAlg::PS256 => {
+                let key = rsa::RsaPrivateKey::from_pkcs8_der(self.bytes.as_slice())
+                    .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_der(self.bytes.as_slice()))
+                    .map_err(|_| err())?;
+                key.sign_with_rng(
+                    &mut rand_core::OsRng,
+                    Pss::new::<Sha256>(),
+                    &Sha256::digest(input.as_bytes()),
+                )
+                .map_err(|_| err())?
+            }
+            JwkKeyPairAlg::ES256 => {
+                let key = p256::ecdsa::SigningKey::from_pkcs8_der(self.bytes.as_slice())
+                    .map_err(|_| err())?;
+                let sig: p256::ecdsa::Signature = key.sign(input.as_bytes());
+                sig.to_vec()
+            }
+            JwkKeyPairAlg::ES384 => {
+                let key = p384::ecdsa::SigningKey::from_pkcs8_der(self.bytes.as_slice())
+                    .map_err(|_| err())?;
+                let sig: p384::ecdsa::Signature = key.sign(input.as_bytes());
+                sig.to_vec()
+            }
+            JwkKeyPairAlg::EdDSA => {
+                let key = algorithms::Ed25519KeyPair::from_der(self.bytes.as_slice())
+                    .map_err(|_| err())?;
+                let kp =
+                    ed25519_compact::KeyPair::from_slice(&key.to_bytes()).map_err(|_| err())?;
+                kp.sk
+                    .sign(input.as_bytes(), Some(ed25519_compact::Noise::generate()))
+                    .to_vec()
+            }
+        };
+
+        Ok(format!(
+            "{}.{}",
+            input,
+            base64_url_no_pad_encode(&signature)
+        ))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum JwkKeyPairType {
     RSA,
     OKP,
+    EC,
 }
 
 impl Default for JwkKeyPairType {
@@ -595,6 +881,7 @@ impl JwkKeyPairType {
         match self {
             JwkKeyPairType::RSA => "RSA",
             JwkKeyPairType::OKP => "OKP",
+            JwkKeyPairType::EC => "EC",
         }
     }
 }
@@ -604,6 +891,9 @@ pub enum JwkKeyPairAlg {
     RS256,
     RS384,
     RS512,
+    PS256,
+    ES256,
+    ES384,
     EdDSA,
 }
 
@@ -619,6 +909,9 @@ impl From<String> for JwkKeyPairAlg {
             "RS256" => JwkKeyPairAlg::RS256,
             "RS384" => JwkKeyPairAlg::RS384,
             "RS512" => JwkKeyPairAlg::RS512,
+            "PS256" => JwkKeyPairAlg::PS256,
+            "ES256" => JwkKeyPairAlg::ES256,
+            "ES384" => JwkKeyPairAlg::ES384,
             "EdDSA" => JwkKeyPairAlg::EdDSA,
             _ => unreachable!(),
         }
@@ -642,11 +935,27 @@ impl FromRow<'_, PgRow> for JwkKeyPairAlg {
 }
 
 impl JwkKeyPairAlg {
+    /// All algorithms Rauthy maintains its own signing keys for.
+    pub fn all() -> [Self; 7] {
+        [
+            Self::RS256,
+            Self::RS384,
+            Self::RS512,
+            Self::PS256,
+            Self::ES256,
+            Self::ES384,
+            Self::EdDSA,
+        ]
+    }
+
     pub fn as_str(&self) -> &str {
         match self {
             JwkKeyPairAlg::RS256 => "RS256",
             JwkKeyPairAlg::RS384 => "RS384",
             JwkKeyPairAlg::RS512 => "RS512",
+            JwkKeyPairAlg::PS256 => "PS256",
+            JwkKeyPairAlg::ES256 => "ES256",
+            JwkKeyPairAlg::ES384 => "ES384",
             JwkKeyPairAlg::EdDSA => "EdDSA",
         }
     }
@@ -666,6 +975,9 @@ impl FromStr for JwkKeyPairAlg {
             "RS256" => Ok(JwkKeyPairAlg::RS256),
             "RS384" => Ok(JwkKeyPairAlg::RS384),
             "RS512" => Ok(JwkKeyPairAlg::RS512),
+            "PS256" => Ok(JwkKeyPairAlg::PS256),
+            "ES256" => Ok(JwkKeyPairAlg::ES256),
+            "ES384" => Ok(JwkKeyPairAlg::ES384),
             "EdDSA" => Ok(JwkKeyPairAlg::EdDSA),
             _ => Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -693,6 +1005,7 @@ mod tests {
             n: Some("0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.fingerprint().unwrap();
         assert_eq!(tp.as_str(), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
 
@@ -704,6 +1017,7 @@ mod tests {
             n: Some("0OJuIbD0k90-Xod2cnqcGWu0xP4Z3Eyfi3CXBxdzlEwFHSNat6Vjts2g5Uzbdvmgm2ys-UWUaCcw2zPEbn25dtcv0MVK26J71OV0Q38yB701SniEJqLXf3OehSR7lfd9HNasZF_-2u6oJMwvKLe10qlSGYLzeUCWIV4LDPDv7lxsWFx0WntgLlHpKfVmYuvW_AQ1Q8XSO53K4Xk3n84zzAXvCUyW8Z4tmE4tc3ibriHH63AYpKbB8oDR-zhbIoGHtZnDdRo02JvS11KNINLdmMOE2zre7hPgXVbgnYS9qbpz4nsc4sPCiGclM2c2faSkwyxI60Ng6272e3fIEkBTKtYidoaG00tM1j42kD-b7bNjWJIsY92F15SdRA4stpic2KcAnyphNrLeDMKd_c-h3PC22eR-a8pb5nE1VvDSagn9g8WE3TSMEJxEmAgVcOcldSV9EDpSz4uk2CqRdytwAZOnRDEwehnRQiLNiwgyNEygLAcaVWDR8ym8ARRLWCRL".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.fingerprint().unwrap();
         assert_eq!(tp.as_str(), "EunK2QL42BZ2Eb4urUxXiFFomdjus4UtGB9qJ8Vnjtw");
 
@@ -715,6 +1029,7 @@ mod tests {
             n: Some("1UjNug4a3OEo8saHbM14jhEqpgRHvjMaQ0lB_1rRuK4yMNPLxhdes8PcMXfEuCOYrC4jxkeVb31QgM5OFwxRtyBT-T1SmiWCtXX2beFtRrvZcGYQrd_LooKLrcjww-P8atQBBYKgf82e9aqb5I-4BFYTBdDQ5lQKQtZDwiU-lUVYP103SphHQMkkWLKsC7oFcthN2m8IliQnJ3-XeqgYt9dc6AszDEjNTDZMeC-HWwRXI9JGYjIgNIZj_u0n6UgaqhdjR1sEHxRGI_t6xQX_L9zRecdDM6-e_lNxIaeROZJ2FU-t9GmZZWyyDWUHk7tk4dS1cU5CdtwvL75dXMHsmwyTs8QK9YUvCWmLeCp6JNPOpCalwyW8YcqJphINhKgonsMinxWLPlO4jtSXKzrpGDLxOF_8xVMW3gNmnIWuUY0_29p7-DzdVm44GEYhQRNNX7yh850uYpwoi42fFvXa5wXm6Hy5QHh_Aqv3tTZgG2f20xCKOzzGzWB28BdJJa9EPu2WLrxaPbn8Qi536979UvMhlZsnUc4fW3TSy20coMb1NIatZaJCDu-uQuGFz7FHBFWjJV6fjF7gqiNqu8cZTeOedGjMitdCnMtOjCz8SASphF12_opWTvtFjq0IMNo4kR8zgZQ24Kt2o2qDhH7fYJI1cLj0RBGDCUU3AlozG_U".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.fingerprint().unwrap();
         assert_eq!(tp.as_str(), "rSJa_34h-WFCVMoSG7ORvEvxhF45iCvcm1FRZlxSRio");
 
@@ -726,6 +1041,7 @@ mod tests {
             n: None,
             e: None,
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .fingerprint()
         .unwrap();
@@ -743,6 +1059,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self().unwrap();
 
         JWKSPublicKey {
@@ -753,6 +1070,7 @@ mod tests {
             n: Some("0OJuIbD0k90-Xod2cnqcGWu0xP4Z3Eyfi3CXBxdzlEwFHSNat6Vjts2g5Uzbdvmgm2ys-UWUaCcw2zPEbn25dtcv0MVK26J71OV0Q38yB701SniEJqLXf3OehSR7lfd9HNasZF_-2u6oJMwvKLe10qlSGYLzeUCWIV4LDPDv7lxsWFx0WntgLlHpKfVmYuvW_AQ1Q8XSO53K4Xk3n84zzAXvCUyW8Z4tmE4tc3ibriHH63AYpKbB8oDR-zhbIoGHtZnDdRo02JvS11KNINLdmMOE2zre7hPgXVbgnYS9qbpz4nsc4sPCiGclM2c2faSkwyxI60Ng6272e3fIEkBTKtYidoaG00tM1j42kD-b7bNjWJIsY92F15SdRA4stpic2KcAnyphNrLeDMKd_c-h3PC22eR-a8pb5nE1VvDSagn9g8WE3TSMEJxEmAgVcOcldSV9EDpSz4uk2CqRdytwAZOnRDEwehnRQiLNiwgyNEygLAcaVWDR8ym8ARRLWCRL".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self().unwrap();
 
         JWKSPublicKey {
@@ -763,6 +1081,7 @@ mod tests {
             n: Some("1UjNug4a3OEo8saHbM14jhEqpgRHvjMaQ0lB_1rRuK4yMNPLxhdes8PcMXfEuCOYrC4jxkeVb31QgM5OFwxRtyBT-T1SmiWCtXX2beFtRrvZcGYQrd_LooKLrcjww-P8atQBBYKgf82e9aqb5I-4BFYTBdDQ5lQKQtZDwiU-lUVYP103SphHQMkkWLKsC7oFcthN2m8IliQnJ3-XeqgYt9dc6AszDEjNTDZMeC-HWwRXI9JGYjIgNIZj_u0n6UgaqhdjR1sEHxRGI_t6xQX_L9zRecdDM6-e_lNxIaeROZJ2FU-t9GmZZWyyDWUHk7tk4dS1cU5CdtwvL75dXMHsmwyTs8QK9YUvCWmLeCp6JNPOpCalwyW8YcqJphINhKgonsMinxWLPlO4jtSXKzrpGDLxOF_8xVMW3gNmnIWuUY0_29p7-DzdVm44GEYhQRNNX7yh850uYpwoi42fFvXa5wXm6Hy5QHh_Aqv3tTZgG2f20xCKOzzGzWB28BdJJa9EPu2WLrxaPbn8Qi536979UvMhlZsnUc4fW3TSy20coMb1NIatZaJCDu-uQuGFz7FHBFWjJV6fjF7gqiNqu8cZTeOedGjMitdCnMtOjCz8SASphF12_opWTvtFjq0IMNo4kR8zgZQ24Kt2o2qDhH7fYJI1cLj0RBGDCUU3AlozG_U".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self().unwrap();
 
         JWKSPublicKey {
@@ -773,6 +1092,7 @@ mod tests {
             n: None,
             e: None,
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .validate_self()
         .unwrap();
@@ -786,6 +1106,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self();
         assert!(key.is_err());
 
@@ -797,6 +1118,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self();
         assert!(key.is_err());
 
@@ -808,6 +1130,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }.validate_self();
         assert!(key.is_err());
 
@@ -819,6 +1142,7 @@ mod tests {
             n: None,
             e: Some("AQAB".to_string()),
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -831,6 +1155,7 @@ mod tests {
             n: Some("r5Xn8yuwc7ekL5NLFnBw76cRUiYbIQqNgPq6XYw6_Mgle3BSJ-UTKTWjGLDoTSlFC7k2xCZNOt8pqix2R_qoGwlNo8kYXlgMpAEo00rSKoG1RO1PMj1M_--swijR8l1bnb-VfIPgT_kM3zv7RLPLEEjYHMuT7N5liFVq1Xh-So8i3X1UeWGHyJPHjF5koB_XO1vleYQCZQeGFaomJgrFJsxdmtFueJaMEMQ1-mPwuPjvSwOtMMAu0nO9DJm3-xwkygPqGmEbbDHLeEO1dEOlDdEYlYle5Pa70FGinCBqaAl7lDaJ1umAvpcLBUHtFOM7VBmt-xUjzOU7VDPareR6Ww".to_string()),
             e: None,
             x: None,
+            y: None,
         }
             .validate_self();
         assert!(key.is_err());
@@ -843,6 +1168,7 @@ mod tests {
             n: None,
             e: None,
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -855,6 +1181,7 @@ mod tests {
             n: None,
             e: None,
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -867,6 +1194,7 @@ mod tests {
             n: Some("n".to_string()),
             e: None,
             x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -879,6 +1207,7 @@ mod tests {
             n: Some("n".to_string()),
             e: None,
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -891,6 +1220,65 @@ mod tests {
             n: None,
             e: Some("e".to_string()),
             x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
+        }
+        .validate_self();
+        assert!(key.is_err());
+
+        let key = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::ES256),
+            crv: Some("P-256".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some("f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU".to_string()),
+            y: Some("x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0".to_string()),
+        };
+        key.validate_self().unwrap();
+
+        let key = JWKSPublicKey {
+            crv: Some("P-384".to_string()),
+            ..key
+        }
+        .validate_self();
+        assert!(key.is_err());
+
+        let key = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::ES384),
+            crv: Some("P-384".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some("f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU".to_string()),
+            y: None,
+        }
+        .validate_self();
+        assert!(key.is_err());
+
+        let key = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::EdDSA),
+            crv: Some("Ed25519".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some("suwfa9fyMHqS0yOh9T-Bsdkji0naFVRRGZFBNrGX_RQ".to_string()),
+            y: None,
+        }
+        .validate_self();
+        assert!(key.is_err());
+
+        let key = JWKSPublicKey {
+            kty: JwkKeyPairType::RSA,
+            alg: Some(JwkKeyPairAlg::ES256),
+            crv: None,
+            kid: None,
+            n: Some("n".to_string()),
+            e: Some("AQAB".to_string()),
+            x: None,
+            y: None,
         }
         .validate_self();
         assert!(key.is_err());
@@ -926,6 +1314,7 @@ mod tests {
             n: None,
             e: None,
             x: Some(x),
+            y: None,
         };
         jwk.validate_token_signature(&signed_token).unwrap();
 
@@ -943,6 +1332,7 @@ mod tests {
             n: Some(n),
             e: Some(e),
             x: None,
+            y: None,
         };
         jwk.validate_token_signature(&signed_token).unwrap();
 
@@ -960,6 +1350,7 @@ mod tests {
             n: Some(n),
             e: Some(e),
             x: None,
+            y: None,
         };
         jwk.validate_token_signature(&signed_token).unwrap();
 
@@ -977,6 +1368,57 @@ mod tests {
             n: Some(n),
             e: Some(e),
             x: None,
+            y: None,
+        };
+        jwk.validate_token_signature(&signed_token).unwrap();
+
+        // PS256
+        let kp = PS256KeyPair::generate(2048).unwrap();
+        let signed_token = kp.sign(claims.clone()).unwrap();
+        let comp = kp.public_key().to_components();
+        let n = base64_url_encode(&comp.n);
+        let e = base64_url_encode(&comp.e);
+        let jwk = JWKSPublicKey {
+            kty: JwkKeyPairType::RSA,
+            alg: Some(JwkKeyPairAlg::PS256),
+            crv: None,
+            kid: None,
+            n: Some(n),
+            e: Some(e),
+            x: None,
+            y: None,
+        };
+        jwk.validate_token_signature(&signed_token).unwrap();
+
+        // ES256
+        let kp = ES256KeyPair::generate();
+        let signed_token = kp.sign(claims.clone()).unwrap();
+        let point = kp.public_key().to_bytes_uncompressed();
+        let jwk = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::ES256),
+            crv: Some("P-256".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some(base64_url_encode(&point[1..33])),
+            y: Some(base64_url_encode(&point[33..])),
+        };
+        jwk.validate_token_signature(&signed_token).unwrap();
+
+        // ES384
+        let kp = ES384KeyPair::generate();
+        let signed_token = kp.sign(claims.clone()).unwrap();
+        let point = kp.public_key().to_bytes_uncompressed();
+        let jwk = JWKSPublicKey {
+            kty: JwkKeyPairType::EC,
+            alg: Some(JwkKeyPairAlg::ES384),
+            crv: Some("P-384".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some(base64_url_encode(&point[1..49])),
+            y: Some(base64_url_encode(&point[49..])),
         };
         jwk.validate_token_signature(&signed_token).unwrap();
     }
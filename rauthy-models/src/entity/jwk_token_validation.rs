@@ -2,10 +2,11 @@
 // which cannot handle some stuff from the `rsa` crate at the time of writing
 
 use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg};
+use p256::ecdsa::signature::Verifier;
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_common::utils::base64_url_no_pad_decode;
 use rsa::sha2::{Sha256, Sha384, Sha512};
-use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use rsa::{Pkcs1v15Sign, Pss, RsaPublicKey};
 use tracing::warn;
 
 impl JWKSPublicKey {
@@ -23,7 +24,7 @@ impl JWKSPublicKey {
         })?;
         // TODO this can be made more efficient without creating a new String -> only &[u8] needed
         let message = format!("{}.{}", header, claims);
-        let sig_bytes = base64_url_no_pad_decode(sig_str).unwrap();
+        let sig_bytes = base64_url_no_pad_decode(sig_str)?;
 
         match self.alg()? {
             JwkKeyPairAlg::RS256 => {
@@ -62,11 +63,53 @@ impl JWKSPublicKey {
                 }
             }
 
+            JwkKeyPairAlg::PS256 => {
+                if let Ok(rsa_pk) = RsaPublicKey::new(self.n()?, self.e()?) {
+                    let hash = hmac_sha256::Hash::hash(message.as_bytes());
+                    if rsa_pk
+                        .verify(Pss::new::<Sha256>(), hash.as_slice(), &sig_bytes)
+                        .is_ok()
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+
+            JwkKeyPairAlg::ES256 => {
+                // uncompressed SEC1 point: 0x04 | x | y
+                let mut point = vec![0x04];
+                point.extend(self.x()?);
+                point.extend(self.y()?);
+                if let (Ok(pubkey), Ok(signature)) = (
+                    p256::ecdsa::VerifyingKey::from_sec1_bytes(&point),
+                    p256::ecdsa::Signature::from_slice(sig_bytes.as_slice()),
+                ) {
+                    if pubkey.verify(message.as_bytes(), &signature).is_ok() {
+                        return Ok(());
+                    }
+                }
+            }
+
+            JwkKeyPairAlg::ES384 => {
+                let mut point = vec![0x04];
+                point.extend(self.x()?);
+                point.extend(self.y()?);
+                if let (Ok(pubkey), Ok(signature)) = (
+                    p384::ecdsa::VerifyingKey::from_sec1_bytes(&point),
+                    p384::ecdsa::Signature::from_slice(sig_bytes.as_slice()),
+                ) {
+                    if pubkey.verify(message.as_bytes(), &signature).is_ok() {
+                        return Ok(());
+                    }
+                }
+            }
+
             JwkKeyPairAlg::EdDSA => {
                 let x = self.x()?;
-                if let Ok(pubkey) = ed25519_compact::PublicKey::from_slice(x.as_slice()) {
-                    let signature =
-                        ed25519_compact::Signature::from_slice(sig_bytes.as_slice()).unwrap();
+                if let (Ok(pubkey), Ok(signature)) = (
+                    ed25519_compact::PublicKey::from_slice(x.as_slice()),
+                    ed25519_compact::Signature::from_slice(sig_bytes.as_slice()),
+                ) {
                     if pubkey.verify(message, &signature).is_ok() {
                         return Ok(());
                     }
@@ -80,4 +123,57 @@ impl JWKSPublicKey {
             "Invalid JWT Token".to_string(),
         ))
     }
+
+    /// Validates the token signature for the `alg` from the token header. The `alg` is optional
+    /// for keys inside a client's JWKS. If it is given, it must match.
+    pub fn verify_token_signature_with_alg(mut self, alg: &JwkKeyPairAlg, token: &str) -> bool {
+        match &self.alg {
+            Some(key_alg) if key_alg != alg => return false,
+            Some(_) => {}
+            None => self.alg = Some(alg.clone()),
+        }
+        self.validate_self().is_ok() && self.validate_token_signature(token).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::jwk::JwkKeyPairType;
+    use ed25519_compact::Noise;
+    use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_encode};
+
+    #[test]
+    fn test_verify_token_signature_with_alg() {
+        let kp = ed25519_compact::KeyPair::generate();
+        let header = base64_url_no_pad_encode(br#"{"alg":"EdDSA","typ":"oauth-authz-req+jwt"}"#);
+        let claims = base64_url_no_pad_encode(br#"{"iss":"test","response_type":"code"}"#);
+        let input = format!("{}.{}", header, claims);
+        let sig = kp.sk.sign(&input, Some(Noise::generate()));
+        let token = format!("{}.{}", input, base64_url_no_pad_encode(sig.as_ref()));
+
+        let key = JWKSPublicKey {
+            kty: JwkKeyPairType::OKP,
+            alg: None,
+            crv: Some("Ed25519".to_string()),
+            kid: None,
+            n: None,
+            e: None,
+            x: Some(base64_url_encode(kp.pk.as_slice())),
+            y: None,
+        };
+        assert!(key
+            .clone()
+            .verify_token_signature_with_alg(&JwkKeyPairAlg::EdDSA, &token));
+        assert!(!key
+            .clone()
+            .verify_token_signature_with_alg(&JwkKeyPairAlg::RS256, &token));
+
+        let other = ed25519_compact::KeyPair::generate();
+        let key_other = JWKSPublicKey {
+            x: Some(base64_url_encode(other.pk.as_slice())),
+            ..key
+        };
+        assert!(!key_other.verify_token_signature_with_alg(&JwkKeyPairAlg::EdDSA, &token));
+    }
 }
@@ -4,23 +4,38 @@ use sqlx::query;
 pub mod api_keys;
 pub mod app_version;
 pub mod auth_codes;
+pub mod client_assertions;
+pub mod client_certificates;
 pub mod clients;
+pub mod clients_dyn;
 pub mod colors;
 pub mod config;
 pub mod db_version;
+pub mod device_codes;
 pub mod dpop_proof;
 pub mod groups;
+pub mod jwe;
 pub mod jwk;
 pub mod jwk_token_validation;
 pub mod magic_links;
+pub mod pairwise_subjects;
 pub mod password;
 pub mod pow;
 pub mod principal;
+pub mod provisioning;
+pub mod pushed_auth_requests;
+pub mod recovery_codes;
 pub mod refresh_tokens;
+pub mod request_objects;
+pub mod revoked_tokens;
 pub mod roles;
 pub mod scopes;
+pub mod session_clients;
 pub mod sessions;
+pub mod totp;
+pub mod upstream_providers;
 pub mod user_attr;
+pub mod user_consents;
 pub mod users;
 pub mod webauthn;
 pub mod webids;
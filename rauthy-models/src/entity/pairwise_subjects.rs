@@ -0,0 +1,96 @@
+use crate::app_state::AppState;
+use actix_web::web;
+use rauthy_common::constants::{CACHE_NAME_12HR, IDX_PAIRWISE_SUBJECT};
+use rauthy_common::error_response::ErrorResponse;
+use redhac::{cache_get, cache_get_from, cache_get_value, cache_insert, AckLevel};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Maps a pairwise `sub` back to the user it has been issued for.
+///
+/// Pairwise subjects are an HMAC, which cannot be reversed. Tokens for pairwise clients do not
+/// contain the global user id, so this mapping is needed to find the user for the userinfo
+/// endpoint or the token exchange.
+#[derive(Debug, PartialEq, Eq, FromRow, Serialize, Deserialize)]
+pub struct PairwiseSubject {
+    pub sub: String,
+    pub user_id: String,
+}
+
+// CRUD
+impl PairwiseSubject {
+    /// Saves the mapping, if it does not exist yet. Since it can never change, it is only
+    /// written on a cache miss and not with each issued token.
+    pub async fn create(
+        data: &web::Data<AppState>,
+        sub: &str,
+        user_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        if Self::find_cached(data, sub).await?.is_some() {
+            return Ok(());
+        }
+
+        #[cfg(feature = "sqlite")]
+        let q = sqlx::query!(
+            "insert or ignore into pairwise_subjects (sub, user_id) values ($1, $2)",
+            sub,
+            user_id
+        );
+        #[cfg(not(feature = "sqlite"))]
+        let q = sqlx::query!(
+            r#"insert into pairwise_subjects (sub, user_id) values ($1, $2)
+            on conflict(sub) do nothing"#,
+            sub,
+            user_id
+        );
+        q.execute(&data.db).await?;
+
+        Self::cache(data, sub, user_id).await
+    }
+
+    pub async fn find_user_id(
+        data: &web::Data<AppState>,
+        sub: &str,
+    ) -> Result<String, ErrorResponse> {
+        if let Some(user_id) = Self::find_cached(data, sub).await? {
+            return Ok(user_id);
+        }
+
+        let res = sqlx::query!("select user_id from pairwise_subjects where sub = $1", sub)
+            .fetch_one(&data.db)
+            .await?;
+        Self::cache(data, sub, &res.user_id).await?;
+        Ok(res.user_id)
+    }
+
+    async fn cache(
+        data: &web::Data<AppState>,
+        sub: &str,
+        user_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        cache_insert(
+            CACHE_NAME_12HR.to_string(),
+            format!("{}{}", IDX_PAIRWISE_SUBJECT, sub),
+            &data.caches.ha_cache_config,
+            &user_id,
+            AckLevel::Leader,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn find_cached(
+        data: &web::Data<AppState>,
+        sub: &str,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let user_id = cache_get!(
+            String,
+            CACHE_NAME_12HR.to_string(),
+            format!("{}{}", IDX_PAIRWISE_SUBJECT, sub),
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?;
+        Ok(user_id)
+    }
+}
@@ -0,0 +1,1483 @@
+use crate::app_state::{AppState, DbTxn};
+use crate::entity::clients::Client;
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use crate::entity::user_attr::UserAttrValueEntity;
+use crate::entity::users::User;
+use crate::request::ProvisioningConnectorRequest;
+use crate::scim::{ScimUser, SCIM_SCHEMA_GROUP, SCIM_SCHEMA_RAUTHY_USER, SCIM_SCHEMA_USER};
+use actix_web::web;
+use rauthy_common::constants::{
+    APPLICATION_JSON, APPLICATION_SCIM_JSON, CACHE_NAME_12HR, IDX_PROVISIONING_CONNECTORS,
+    PROVISIONING_MAX_ATTEMPTS, RAUTHY_VERSION,
+};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::{decrypt, encrypt, new_store_id};
+use redhac::{cache_get, cache_get_from, cache_get_value, cache_insert, cache_remove, AckLevel};
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{tls, Method, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, Type};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// The first retry happens after 30 seconds and the backoff doubles with each failed attempt,
+// up to a maximum of 1 hour.
+const RETRY_BACKOFF_BASE_MILLIS: i64 = 30_000;
+const RETRY_BACKOFF_MAX_MILLIS: i64 = 3_600_000;
+// The limit per connector for a single run. Everything above will simply be picked up by the
+// next one.
+const PROCESS_BATCH_SIZE: i64 = 1000;
+
+/// The protocol a [ProvisioningConnector] uses to talk to the downstream app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "varchar")]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProvisioningConnectorType {
+    Scim,
+    Webhook,
+}
+
+impl ProvisioningConnectorType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Scim => "scim",
+            Self::Webhook => "webhook",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "varchar")]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProvisioningResource {
+    User,
+    Group,
+    Role,
+}
+
+impl ProvisioningResource {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::User => "user",
+            Self::Group => "group",
+            Self::Role => "role",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "varchar")]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProvisioningAction {
+    Created,
+    Updated,
+    Disabled,
+    Deleted,
+}
+
+impl ProvisioningAction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Disabled => "disabled",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type, ToSchema)]
+#[sqlx(type_name = "varchar")]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ProvisioningStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+impl ProvisioningStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/**
+# Outbound Provisioning Connector
+
+Pushes changes of users, groups and roles to a downstream app, which belongs to a client.
+
+- `scim`: The `endpoint` is the base URL of a SCIM 2.0 API. Users and groups will be created,
+  replaced and deleted downstream. The `secret` will be sent as a `Bearer` token.
+- `webhook`: Every change will be POSTed as a JSON event to the `endpoint`. If a `secret` is set,
+  the body will be signed with an HMAC-SHA256 in the `X-Rauthy-Signature` header.
+
+Changes are not sent out directly, but go through a persistent queue of
+[ProvisioningDelivery]s, which will be retried with an exponential backoff.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct ProvisioningConnector {
+    pub id: String,
+    pub client_id: String,
+    pub typ: ProvisioningConnectorType,
+    pub endpoint: String,
+    // The secret is saved as encrypted bytes, exactly like `Client.secret`, and can only be
+    // decrypted with the key referenced by `secret_kid`.
+    pub secret: Option<Vec<u8>>,
+    pub secret_kid: Option<String>,
+    pub enabled: bool,
+    pub created_at: i64,
+}
+
+// CRUD
+impl ProvisioningConnector {
+    pub async fn create(
+        data: &web::Data<AppState>,
+        client_id: String,
+        req: ProvisioningConnectorRequest,
+    ) -> Result<Self, ErrorResponse> {
+        // return a proper error instead of a foreign key violation
+        Client::find(data, client_id.clone()).await?;
+
+        let (secret, secret_kid) = match req.secret.as_deref() {
+            Some(secret) => Self::encrypt_secret(data, secret)?,
+            None => (None, None),
+        };
+        let connector = Self {
+            id: new_store_id(),
+            client_id,
+            typ: req.typ,
+            endpoint: req.endpoint,
+            secret,
+            secret_kid,
+            enabled: req.enabled,
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+
+        sqlx::query!(
+            r#"insert into provisioning_connectors
+            (id, client_id, typ, endpoint, secret, secret_kid, enabled, created_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+            connector.id,
+            connector.client_id,
+            connector.typ.as_str(),
+            connector.endpoint,
+            connector.secret,
+            connector.secret_kid,
+            connector.enabled,
+            connector.created_at,
+        )
+        .execute(&data.db)
+        .await?;
+
+        Self::cache_invalidate(data).await?;
+
+        Ok(connector)
+    }
+
+    pub async fn delete(
+        data: &web::Data<AppState>,
+        client_id: &str,
+        id: &str,
+    ) -> Result<(), ErrorResponse> {
+        let connector = Self::find(data, client_id, id).await?;
+
+        // all queued deliveries and downstream ids will be deleted via cascade
+        sqlx::query!(
+            "delete from provisioning_connectors where id = $1",
+            connector.id
+        )
+        .execute(&data.db)
+        .await?;
+
+        Self::cache_invalidate(data).await?;
+
+        Ok(())
+    }
+
+    pub async fn find(
+        data: &web::Data<AppState>,
+        client_id: &str,
+        id: &str,
+    ) -> Result<Self, ErrorResponse> {
+        Self::find_all(data)
+            .await?
+            .into_iter()
+            .find(|c| c.id == id && c.client_id == client_id)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    format!("Provisioning connector '{}' does not exist", id),
+                )
+            })
+    }
+
+    pub async fn find_all(data: &web::Data<AppState>) -> Result<Vec<Self>, ErrorResponse> {
+        let connectors = cache_get!(
+            Vec<ProvisioningConnector>,
+            CACHE_NAME_12HR.to_string(),
+            IDX_PROVISIONING_CONNECTORS.to_string(),
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?;
+        if let Some(connectors) = connectors {
+            return Ok(connectors);
+        }
+
+        let connectors =
+            sqlx::query_as::<_, Self>("select * from provisioning_connectors order by created_at")
+                .fetch_all(&data.db)
+                .await?;
+
+        cache_insert(
+            CACHE_NAME_12HR.to_string(),
+            IDX_PROVISIONING_CONNECTORS.to_string(),
+            &data.caches.ha_cache_config,
+            &connectors,
+            AckLevel::Leader,
+        )
+        .await?;
+
+        Ok(connectors)
+    }
+
+    pub async fn find_for_client(
+        data: &web::Data<AppState>,
+        client_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let connectors = Self::find_all(data)
+            .await?
+            .into_iter()
+            .filter(|c| c.client_id == client_id)
+            .collect();
+        Ok(connectors)
+    }
+
+    pub async fn update(
+        data: &web::Data<AppState>,
+        client_id: &str,
+        id: &str,
+        req: ProvisioningConnectorRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let current = Self::find(data, client_id, id).await?;
+
+        let (secret, secret_kid) = match req.secret.as_deref() {
+            Some(secret) => Self::encrypt_secret(data, secret)?,
+            None => (current.secret, current.secret_kid),
+        };
+        let connector = Self {
+            id: current.id,
+            client_id: current.client_id,
+            typ: req.typ,
+            endpoint: req.endpoint,
+            secret,
+            secret_kid,
+            enabled: req.enabled,
+            created_at: current.created_at,
+        };
+
+        let mut txn = data.db.begin().await?;
+
+        sqlx::query!(
+            r#"update provisioning_connectors
+            set typ = $1, endpoint = $2, secret = $3, secret_kid = $4, enabled = $5
+            where id = $6"#,
+            connector.typ.as_str(),
+            connector.endpoint,
+            connector.secret,
+            connector.secret_kid,
+            connector.enabled,
+            connector.id,
+        )
+        .execute(&mut *txn)
+        .await?;
+
+        // the ids of the already provisioned resources are meaningless for another downstream app
+        if current.typ != connector.typ || current.endpoint != connector.endpoint {
+            sqlx::query!(
+                "delete from provisioning_resources where connector_id = $1",
+                connector.id
+            )
+            .execute(&mut *txn)
+            .await?;
+        }
+
+        txn.commit().await?;
+
+        Self::cache_invalidate(data).await?;
+
+        Ok(connector)
+    }
+
+    pub async fn cache_invalidate(data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_remove(
+            CACHE_NAME_12HR.to_string(),
+            IDX_PROVISIONING_CONNECTORS.to_string(),
+            &data.caches.ha_cache_config,
+            AckLevel::Quorum,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl ProvisioningConnector {
+    pub fn get_secret_cleartext(
+        &self,
+        data: &web::Data<AppState>,
+    ) -> Result<Option<String>, ErrorResponse> {
+        if let Some(secret) = self.secret.as_ref() {
+            let kid = self.secret_kid.as_ref().unwrap_or(&data.enc_key_active);
+            let key = data.enc_keys.get(kid).ok_or_else(|| {
+                error!("Cannot get encryption key {} from config", kid);
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "Cannot decrypt provisioning connector secret".to_string(),
+                )
+            })?;
+            let bytes = decrypt(secret, key)?;
+            Ok(Some(String::from_utf8_lossy(&bytes).to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn enabled(data: &web::Data<AppState>) -> Result<Vec<Self>, ErrorResponse> {
+        let connectors = Self::find_all(data)
+            .await?
+            .into_iter()
+            .filter(|c| c.enabled)
+            .collect();
+        Ok(connectors)
+    }
+
+    fn client() -> &'static reqwest::Client {
+        HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Provisioning Client", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        })
+    }
+
+    async fn deliver(
+        &self,
+        data: &web::Data<AppState>,
+        delivery: &ProvisioningDelivery,
+    ) -> Result<(), ErrorResponse> {
+        match self.typ {
+            ProvisioningConnectorType::Scim => self.deliver_scim(data, delivery).await,
+            ProvisioningConnectorType::Webhook => self.deliver_webhook(data, delivery).await,
+        }
+    }
+
+    async fn deliver_webhook(
+        &self,
+        data: &web::Data<AppState>,
+        delivery: &ProvisioningDelivery,
+    ) -> Result<(), ErrorResponse> {
+        let body = json!({
+            "id": delivery.id,
+            "event": format!(
+                "{}.{}",
+                delivery.resource_typ.as_str(),
+                delivery.action.as_str()
+            ),
+            "timestamp": delivery.created_at,
+            "data": serde_json::from_str::<Value>(&delivery.payload)?,
+        })
+        .to_string();
+
+        let mut req = Self::client()
+            .post(&self.endpoint)
+            .header(CONTENT_TYPE, APPLICATION_JSON)
+            .header("X-Rauthy-Delivery", &delivery.id);
+        if let Some(secret) = self.get_secret_cleartext(data)? {
+            let ts = OffsetDateTime::now_utc().unix_timestamp();
+            req = req.header(
+                "X-Rauthy-Signature",
+                Self::webhook_signature(&secret, ts, &body),
+            );
+        }
+
+        let res = req.body(body).send().await?;
+        Self::check_response(res).await?;
+        Ok(())
+    }
+
+    // The timestamp is part of the signature to give the receiver a way to reject replays.
+    // The format is `t={unix_ts},v1={hex(HMAC-SHA256(secret, "{unix_ts}.{body}"))}`.
+    fn webhook_signature(secret: &str, ts: i64, body: &str) -> String {
+        let mac = hmac_sha256::HMAC::mac(format!("{}.{}", ts, body), secret.as_bytes());
+        format!("t={},v1={}", ts, hex::encode(mac))
+    }
+
+    async fn deliver_scim(
+        &self,
+        data: &web::Data<AppState>,
+        delivery: &ProvisioningDelivery,
+    ) -> Result<(), ErrorResponse> {
+        let typ = delivery.resource_typ;
+        let path = match typ {
+            ProvisioningResource::User => "Users",
+            ProvisioningResource::Group => "Groups",
+            // SCIM has no roles resource - they are provisioned as part of the users
+            ProvisioningResource::Role => return Ok(()),
+        };
+        let remote_id =
+            ProvisionedResource::find_remote_id(data, &self.id, typ, &delivery.resource_id).await?;
+
+        if delivery.action == ProvisioningAction::Deleted {
+            if let Some(remote_id) = remote_id {
+                let res = self
+                    .scim_request(data, Method::DELETE, &format!("{}/{}", path, remote_id))?
+                    .send()
+                    .await?;
+                if res.status() != StatusCode::NOT_FOUND {
+                    Self::check_response(res).await?;
+                }
+                ProvisionedResource::delete(data, &self.id, typ, &delivery.resource_id).await?;
+            }
+            return Ok(());
+        }
+
+        let (mut body, group_ids) = match typ {
+            ProvisioningResource::User => Self::scim_user_body(delivery)?,
+            _ => match self.scim_group_body(data, &delivery.resource_id).await? {
+                Some(body) => (body, Vec::default()),
+                None => {
+                    debug!(
+                        "Group {} does not exist anymore - skipping SCIM delivery",
+                        delivery.resource_id
+                    );
+                    return Ok(());
+                }
+            },
+        };
+
+        if let Some(remote_id) = remote_id {
+            body["id"] = json!(remote_id);
+            let res = self
+                .scim_request(data, Method::PUT, &format!("{}/{}", path, remote_id))?
+                .body(body.to_string())
+                .send()
+                .await?;
+            if res.status() != StatusCode::NOT_FOUND {
+                Self::check_response(res).await?;
+                return Ok(());
+            }
+
+            // the resource has been deleted downstream in the meantime -> create it again
+            if let Some(obj) = body.as_object_mut() {
+                obj.remove("id");
+            }
+        }
+
+        let res = self
+            .scim_request(data, Method::POST, path)?
+            .body(body.to_string())
+            .send()
+            .await?;
+        let remote_id = if res.status() == StatusCode::CONFLICT {
+            // The resource already exists downstream, for instance because it has been created
+            // before the connector was configured. In this case, link and replace it.
+            let remote_id = self.scim_find_existing(data, typ, &body).await?;
+            body["id"] = json!(remote_id);
+            let res = self
+                .scim_request(data, Method::PUT, &format!("{}/{}", path, remote_id))?
+                .body(body.to_string())
+                .send()
+                .await?;
+            Self::check_response(res).await?;
+            remote_id
+        } else {
+            let value = Self::check_response(res).await?.json::<Value>().await?;
+            value
+                .get("id")
+                .and_then(|id| id.as_str())
+                .map(String::from)
+                .ok_or_else(|| {
+                    ErrorResponse::new(
+                        ErrorResponseType::Connection,
+                        "The SCIM response does not contain an 'id'".to_string(),
+                    )
+                })?
+        };
+        ProvisionedResource::save(data, &self.id, typ, &delivery.resource_id, &remote_id).await?;
+
+        // The members of a group can only reference users, which exist downstream. A new user
+        // must therefore be added to its groups afterwards.
+        if !group_ids.is_empty() {
+            let groups = Group::find_all(data).await?;
+            for group in groups.iter().filter(|g| group_ids.contains(&g.id)) {
+                ProvisioningDelivery::enqueue_group_sync(data, &self.id, group, None).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the downstream id of an already existing resource with the same unique name.
+    async fn scim_find_existing(
+        &self,
+        data: &web::Data<AppState>,
+        typ: ProvisioningResource,
+        body: &Value,
+    ) -> Result<String, ErrorResponse> {
+        let (path, attr) = if typ == ProvisioningResource::User {
+            ("Users", "userName")
+        } else {
+            ("Groups", "displayName")
+        };
+        let value = body
+            .get(attr)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .replace('"', "\\\"");
+
+        let res = self
+            .scim_request(data, Method::GET, path)?
+            .query(&[("filter", format!("{} eq \"{}\"", attr, value))])
+            .send()
+            .await?;
+        let list = Self::check_response(res).await?.json::<Value>().await?;
+        list.get("Resources")
+            .and_then(|r| r.as_array())
+            .and_then(|r| r.first())
+            .and_then(|r| r.get("id"))
+            .and_then(|id| id.as_str())
+            .map(String::from)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    format!(
+                        "The SCIM resource with {} '{}' conflicts downstream, but cannot be found",
+                        attr, value
+                    ),
+                )
+            })
+    }
+
+    // Builds the downstream user from the snapshot in the delivery and returns it together with
+    // the ids of its groups.
+    fn scim_user_body(
+        delivery: &ProvisioningDelivery,
+    ) -> Result<(Value, Vec<String>), ErrorResponse> {
+        let user = serde_json::from_str::<ScimUser>(&delivery.payload)?;
+        let group_ids = user.groups.iter().map(|g| g.value.clone()).collect();
+
+        let mut body = serde_json::to_value(ScimUser {
+            schemas: if user
+                .attributes
+                .as_ref()
+                .map(|a| a.is_empty())
+                .unwrap_or(true)
+            {
+                vec![SCIM_SCHEMA_USER.to_string()]
+            } else {
+                user.schemas
+            },
+            id: None,
+            // groups are read-only and will be synced via the group members
+            groups: Vec::default(),
+            attributes: user.attributes.filter(|a| !a.is_empty()),
+            meta: None,
+            ..user
+        })?;
+        body["externalId"] = json!(delivery.resource_id);
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("groups");
+        }
+
+        Ok((body, group_ids))
+    }
+
+    // Builds the downstream group from the current state, since the members must reference the
+    // downstream ids of the users. Returns `None` if the group does not exist anymore.
+    async fn scim_group_body(
+        &self,
+        data: &web::Data<AppState>,
+        group_id: &str,
+    ) -> Result<Option<Value>, ErrorResponse> {
+        let group = match Group::find_all(data)
+            .await?
+            .into_iter()
+            .find(|g| g.id == group_id)
+        {
+            Some(group) => group,
+            None => return Ok(None),
+        };
+
+        let remote_ids =
+            ProvisionedResource::find_remote_ids(data, &self.id, ProvisioningResource::User)
+                .await?;
+        let members = User::find_all(data)
+            .await?
+            .into_iter()
+            .filter(|u| u.get_groups().contains(&group.name))
+            .filter_map(|u| {
+                remote_ids
+                    .get(&u.id)
+                    .map(|id| json!({ "value": id, "display": u.email }))
+            })
+            .collect::<Vec<Value>>();
+
+        Ok(Some(json!({
+            "schemas": [SCIM_SCHEMA_GROUP],
+            "externalId": group.id,
+            "displayName": group.name,
+            "members": members,
+        })))
+    }
+
+    fn scim_request(
+        &self,
+        data: &web::Data<AppState>,
+        method: Method,
+        path: &str,
+    ) -> Result<RequestBuilder, ErrorResponse> {
+        let url = format!("{}/{}", self.endpoint.trim_end_matches('/'), path);
+        let mut req = Self::client()
+            .request(method, url)
+            .header(ACCEPT, APPLICATION_SCIM_JSON)
+            .header(CONTENT_TYPE, APPLICATION_SCIM_JSON);
+        if let Some(token) = self.get_secret_cleartext(data)? {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        Ok(req)
+    }
+
+    async fn check_response(res: reqwest::Response) -> Result<reqwest::Response, ErrorResponse> {
+        if res.status().is_success() {
+            return Ok(res);
+        }
+
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        // Only an unavailable downstream app is a connection error, which holds back all
+        // other deliveries for the connector. Anything else only concerns this single delivery.
+        let typ = if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            ErrorResponseType::Connection
+        } else {
+            ErrorResponseType::BadRequest
+        };
+        Err(ErrorResponse::new(
+            typ,
+            format!(
+                "Downstream responded with {}: {}",
+                status,
+                body.chars().take(256).collect::<String>()
+            ),
+        ))
+    }
+
+    fn encrypt_secret(
+        data: &web::Data<AppState>,
+        secret: &str,
+    ) -> Result<(Option<Vec<u8>>, Option<String>), ErrorResponse> {
+        if secret.is_empty() {
+            return Ok((None, None));
+        }
+
+        let key = data
+            .enc_keys
+            .get(&data.enc_key_active)
+            .expect("Encryption Key config is broken");
+        let enc = encrypt(secret.as_bytes(), key)?;
+        Ok((Some(enc), Some(data.enc_key_active.clone())))
+    }
+}
+
+/// A single queued change for a [ProvisioningConnector].
+///
+/// All timestamps are unix millis to keep the order of changes, which happen within the same
+/// second. The `payload` is a snapshot of the resource at the time of the change.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct ProvisioningDelivery {
+    pub id: String,
+    pub connector_id: String,
+    pub resource_typ: ProvisioningResource,
+    pub resource_id: String,
+    pub action: ProvisioningAction,
+    pub payload: String,
+    pub status: ProvisioningStatus,
+    pub attempts: i64,
+    pub next_attempt: i64,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub delivered_at: Option<i64>,
+}
+
+// CRUD
+impl ProvisioningDelivery {
+    /// Returns the latest deliveries for a connector, newest first.
+    pub async fn find_for_connector(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>(
+            r#"select * from provisioning_deliveries where connector_id = $1
+            order by created_at desc limit 500"#,
+        )
+        .bind(connector_id)
+        .fetch_all(&data.db)
+        .await?;
+        Ok(res)
+    }
+
+    /// Re-queues a failed delivery for the next run.
+    pub async fn retry(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+        id: &str,
+    ) -> Result<Self, ErrorResponse> {
+        let mut delivery = sqlx::query_as::<_, Self>(
+            "select * from provisioning_deliveries where id = $1 and connector_id = $2",
+        )
+        .bind(id)
+        .bind(connector_id)
+        .fetch_one(&data.db)
+        .await?;
+
+        if delivery.status != ProvisioningStatus::Failed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Only failed deliveries can be retried".to_string(),
+            ));
+        }
+
+        delivery.status = ProvisioningStatus::Pending;
+        delivery.attempts = 0;
+        delivery.next_attempt = now_millis();
+        delivery.save(data).await?;
+
+        Ok(delivery)
+    }
+
+    async fn insert(
+        &self,
+        data: &web::Data<AppState>,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let q = sqlx::query!(
+            r#"insert into provisioning_deliveries
+            (id, connector_id, resource_typ, resource_id, action, payload, status, attempts,
+            next_attempt, last_error, created_at, delivered_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+            self.id,
+            self.connector_id,
+            self.resource_typ.as_str(),
+            self.resource_id,
+            self.action.as_str(),
+            self.payload,
+            self.status.as_str(),
+            self.attempts,
+            self.next_attempt,
+            self.last_error,
+            self.created_at,
+            self.delivered_at,
+        );
+
+        if let Some(txn) = txn {
+            q.execute(&mut **txn).await?;
+        } else {
+            q.execute(&data.db).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn save(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        sqlx::query!(
+            r#"update provisioning_deliveries
+            set status = $1, attempts = $2, next_attempt = $3, last_error = $4, delivered_at = $5
+            where id = $6"#,
+            self.status.as_str(),
+            self.attempts,
+            self.next_attempt,
+            self.last_error,
+            self.delivered_at,
+            self.id,
+        )
+        .execute(&data.db)
+        .await?;
+        Ok(())
+    }
+}
+
+impl ProvisioningDelivery {
+    fn new(
+        connector_id: &str,
+        resource_typ: ProvisioningResource,
+        resource_id: &str,
+        action: ProvisioningAction,
+        payload: String,
+    ) -> Self {
+        let now = now_millis();
+        Self {
+            id: new_store_id(),
+            connector_id: connector_id.to_string(),
+            resource_typ,
+            resource_id: resource_id.to_string(),
+            action,
+            payload,
+            status: ProvisioningStatus::Pending,
+            attempts: 0,
+            next_attempt: now,
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+        }
+    }
+
+    /// Queues a change of the given user for all enabled connectors.
+    pub async fn enqueue_user(
+        data: &web::Data<AppState>,
+        user: &User,
+        action: ProvisioningAction,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let connectors = ProvisioningConnector::enabled(data).await?;
+        if connectors.is_empty() {
+            return Ok(());
+        }
+
+        let mut txn = txn;
+        let payload = Self::user_payload(data, user).await?;
+        for connector in &connectors {
+            Self::new(
+                &connector.id,
+                ProvisioningResource::User,
+                &user.id,
+                action,
+                payload.clone(),
+            )
+            .insert(data, txn.as_deref_mut())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Queues an update of the given user after its custom attributes have changed. The new
+    /// `attrs` must be given, because they have only been written inside the `txn` so far.
+    pub async fn enqueue_user_attrs(
+        data: &web::Data<AppState>,
+        user: &User,
+        attrs: Vec<UserAttrValueEntity>,
+        txn: &mut DbTxn<'_>,
+    ) -> Result<(), ErrorResponse> {
+        let connectors = ProvisioningConnector::enabled(data).await?;
+        if connectors.is_empty() {
+            return Ok(());
+        }
+
+        let payload = Self::user_payload_with_attrs(data, user, attrs).await?;
+        for connector in &connectors {
+            Self::new(
+                &connector.id,
+                ProvisioningResource::User,
+                &user.id,
+                ProvisioningAction::Updated,
+                payload.clone(),
+            )
+            .insert(data, Some(&mut *txn))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Queues an update of the given user, if any of its provisioned values have changed, and a
+    /// sync for every group on SCIM connectors, the user has joined or left.
+    ///
+    /// Most updates of a user, like a new `last_login`, do not concern any downstream app.
+    pub async fn enqueue_user_update(
+        data: &web::Data<AppState>,
+        before: &User,
+        user: &User,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let action = match Self::user_update_action(before, user) {
+            Some(action) => action,
+            None => return Ok(()),
+        };
+
+        let connectors = ProvisioningConnector::enabled(data).await?;
+        if connectors.is_empty() {
+            return Ok(());
+        }
+
+        let mut txn = txn;
+        let payload = Self::user_payload(data, user).await?;
+        for connector in &connectors {
+            Self::new(
+                &connector.id,
+                ProvisioningResource::User,
+                &user.id,
+                action,
+                payload.clone(),
+            )
+            .insert(data, txn.as_deref_mut())
+            .await?;
+        }
+
+        let groups_before = before.get_groups().into_iter().collect::<HashSet<String>>();
+        let groups_after = user.get_groups().into_iter().collect::<HashSet<String>>();
+        let changed = groups_before
+            .symmetric_difference(&groups_after)
+            .collect::<Vec<&String>>();
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        let groups = Group::find_all(data).await?;
+        for connector in connectors
+            .iter()
+            .filter(|c| c.typ == ProvisioningConnectorType::Scim)
+        {
+            for group in groups.iter().filter(|g| changed.contains(&&g.name)) {
+                Self::enqueue_group_sync(data, &connector.id, group, txn.as_deref_mut()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the action for a user update, or `None` if no provisioned value has changed.
+    fn user_update_action(before: &User, user: &User) -> Option<ProvisioningAction> {
+        if before.enabled && !user.enabled {
+            Some(ProvisioningAction::Disabled)
+        } else if before.email != user.email
+            || before.given_name != user.given_name
+            || before.family_name != user.family_name
+            || before.roles != user.roles
+            || before.groups != user.groups
+            || before.enabled != user.enabled
+            || before.language != user.language
+        {
+            Some(ProvisioningAction::Updated)
+        } else {
+            None
+        }
+    }
+
+    /// Queues a change of the given group for all enabled connectors.
+    pub async fn enqueue_group(
+        data: &web::Data<AppState>,
+        group: &Group,
+        action: ProvisioningAction,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let connectors = ProvisioningConnector::enabled(data).await?;
+
+        let mut txn = txn;
+        let payload = serde_json::to_string(group)?;
+        for connector in &connectors {
+            if connector.typ == ProvisioningConnectorType::Scim
+                && action != ProvisioningAction::Deleted
+            {
+                Self::enqueue_group_sync(data, &connector.id, group, txn.as_deref_mut()).await?;
+            } else {
+                Self::new(
+                    &connector.id,
+                    ProvisioningResource::Group,
+                    &group.id,
+                    action,
+                    payload.clone(),
+                )
+                .insert(data, txn.as_deref_mut())
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Queues a change of the given role for all enabled webhook connectors.
+    pub async fn enqueue_role(
+        data: &web::Data<AppState>,
+        role: &Role,
+        action: ProvisioningAction,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let connectors = ProvisioningConnector::enabled(data).await?;
+
+        let mut txn = txn;
+        let payload = serde_json::to_string(role)?;
+        for connector in connectors
+            .iter()
+            .filter(|c| c.typ == ProvisioningConnectorType::Webhook)
+        {
+            Self::new(
+                &connector.id,
+                ProvisioningResource::Role,
+                &role.id,
+                action,
+                payload.clone(),
+            )
+            .insert(data, txn.as_deref_mut())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    // A SCIM group will be built from its current state at delivery time. This makes a sync,
+    // which has not been tried yet, cover all changes queued after it.
+    async fn enqueue_group_sync(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+        group: &Group,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let mut txn = txn;
+        let q = sqlx::query!(
+            r#"select id from provisioning_deliveries
+            where connector_id = $1 and resource_typ = $2 and resource_id = $3 and action = $4
+            and status = $5 and attempts = 0"#,
+            connector_id,
+            ProvisioningResource::Group.as_str(),
+            group.id,
+            ProvisioningAction::Updated.as_str(),
+            ProvisioningStatus::Pending.as_str(),
+        );
+        let queued = if let Some(txn) = txn.as_deref_mut() {
+            q.fetch_optional(&mut **txn).await?
+        } else {
+            q.fetch_optional(&data.db).await?
+        };
+        if queued.is_some() {
+            return Ok(());
+        }
+
+        Self::new(
+            connector_id,
+            ProvisioningResource::Group,
+            &group.id,
+            ProvisioningAction::Updated,
+            serde_json::to_string(group)?,
+        )
+        .insert(data, txn)
+        .await
+    }
+
+    async fn user_payload(
+        data: &web::Data<AppState>,
+        user: &User,
+    ) -> Result<String, ErrorResponse> {
+        let attrs = UserAttrValueEntity::find_for_user(data, &user.id).await?;
+        Self::user_payload_with_attrs(data, user, attrs).await
+    }
+
+    async fn user_payload_with_attrs(
+        data: &web::Data<AppState>,
+        user: &User,
+        attrs: Vec<UserAttrValueEntity>,
+    ) -> Result<String, ErrorResponse> {
+        let groups = Group::find_all(data).await?;
+        let scim_user = ScimUser::from_user(user.clone(), &groups, attrs, &data.issuer);
+        Ok(serde_json::to_string(&scim_user)?)
+    }
+
+    /// Sends out all due deliveries in the order they have been queued.
+    ///
+    /// A delivery, which is not due yet or fails, blocks all later ones for the same resource to
+    /// never apply changes downstream in the wrong order. Each connector gets its own batch and
+    /// a connector, which cannot be reached, will be skipped for the rest of the run, so a
+    /// single broken downstream app can never hold back all others. Must only run on a single
+    /// instance.
+    pub async fn process_due(data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        let now = now_millis();
+        let deliveries = sqlx::query_as::<_, Self>(
+            r#"select * from (
+                select d.*, row_number() over (
+                    partition by d.connector_id order by d.created_at, d.id
+                ) as batch_pos
+                from provisioning_deliveries d
+                where d.status = $1 and d.next_attempt <= $2
+                and not exists (
+                    select 1 from provisioning_deliveries p
+                    where p.connector_id = d.connector_id and p.resource_typ = d.resource_typ
+                    and p.resource_id = d.resource_id and p.status = $3 and p.next_attempt > $4
+                    and (p.created_at < d.created_at or (p.created_at = d.created_at and p.id < d.id))
+                )
+            ) due
+            where batch_pos <= $5
+            order by created_at, id"#,
+        )
+        .bind(ProvisioningStatus::Pending.as_str())
+        .bind(now)
+        .bind(ProvisioningStatus::Pending.as_str())
+        .bind(now)
+        .bind(PROCESS_BATCH_SIZE)
+        .fetch_all(&data.db)
+        .await?;
+        if deliveries.is_empty() {
+            return Ok(());
+        }
+
+        let connectors = ProvisioningConnector::enabled(data).await?;
+        let mut skip = DeliverySkip::default();
+
+        for mut delivery in deliveries {
+            if skip.contains(&delivery) {
+                continue;
+            }
+
+            // deliveries for disabled connectors stay queued until they are enabled again
+            let connector = match connectors.iter().find(|c| c.id == delivery.connector_id) {
+                Some(connector) => connector,
+                None => continue,
+            };
+
+            delivery.attempts += 1;
+            match connector.deliver(data, &delivery).await {
+                Ok(_) => {
+                    delivery.status = ProvisioningStatus::Delivered;
+                    delivery.delivered_at = Some(now_millis());
+                }
+                Err(err) => {
+                    skip.insert(&delivery, &err);
+
+                    if delivery.attempts >= *PROVISIONING_MAX_ATTEMPTS {
+                        error!(
+                            "Provisioning delivery {} for connector {} failed after {} attempts: {}",
+                            delivery.id, connector.id, delivery.attempts, err.message
+                        );
+                        delivery.status = ProvisioningStatus::Failed;
+                    } else {
+                        debug!(
+                            "Provisioning delivery {} for connector {} failed: {}",
+                            delivery.id, connector.id, err.message
+                        );
+                        delivery.next_attempt = now_millis() + retry_backoff(delivery.attempts);
+                    }
+                    delivery.last_error = Some(err.message);
+                }
+            }
+            delivery.save(data).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps track of the deliveries, which must be skipped for the rest of a
+/// [ProvisioningDelivery::process_due] run after a failure.
+#[derive(Debug, Default)]
+struct DeliverySkip {
+    // (connector_id, resource_typ, resource_id)
+    resources: HashSet<(String, ProvisioningResource, String)>,
+    connectors: HashSet<String>,
+}
+
+impl DeliverySkip {
+    fn contains(&self, delivery: &ProvisioningDelivery) -> bool {
+        self.connectors.contains(&delivery.connector_id)
+            || self.resources.contains(&(
+                delivery.connector_id.clone(),
+                delivery.resource_typ,
+                delivery.resource_id.clone(),
+            ))
+    }
+
+    fn insert(&mut self, delivery: &ProvisioningDelivery, err: &ErrorResponse) {
+        if err.error == ErrorResponseType::Connection {
+            self.connectors.insert(delivery.connector_id.clone());
+        } else {
+            self.resources.insert((
+                delivery.connector_id.clone(),
+                delivery.resource_typ,
+                delivery.resource_id.clone(),
+            ));
+        }
+    }
+}
+
+// The backoff before the next attempt, after the given number of failed attempts.
+fn retry_backoff(attempts: i64) -> i64 {
+    RETRY_BACKOFF_BASE_MILLIS
+        .saturating_mul(1i64 << (attempts - 1).clamp(0, 20))
+        .min(RETRY_BACKOFF_MAX_MILLIS)
+}
+
+/// The downstream id of a resource, which has been provisioned via a SCIM connector
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct ProvisionedResource {
+    pub connector_id: String,
+    pub resource_typ: ProvisioningResource,
+    pub resource_id: String,
+    pub remote_id: String,
+}
+
+impl ProvisionedResource {
+    async fn delete(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+        resource_typ: ProvisioningResource,
+        resource_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        sqlx::query!(
+            r#"delete from provisioning_resources
+            where connector_id = $1 and resource_typ = $2 and resource_id = $3"#,
+            connector_id,
+            resource_typ.as_str(),
+            resource_id,
+        )
+        .execute(&data.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn find_remote_id(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+        resource_typ: ProvisioningResource,
+        resource_id: &str,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>(
+            r#"select * from provisioning_resources
+            where connector_id = $1 and resource_typ = $2 and resource_id = $3"#,
+        )
+        .bind(connector_id)
+        .bind(resource_typ.as_str())
+        .bind(resource_id)
+        .fetch_optional(&data.db)
+        .await?;
+        Ok(res.map(|r| r.remote_id))
+    }
+
+    // Returns a mapping from the local to the downstream ids.
+    async fn find_remote_ids(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+        resource_typ: ProvisioningResource,
+    ) -> Result<HashMap<String, String>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>(
+            "select * from provisioning_resources where connector_id = $1 and resource_typ = $2",
+        )
+        .bind(connector_id)
+        .bind(resource_typ.as_str())
+        .fetch_all(&data.db)
+        .await?
+        .into_iter()
+        .map(|r| (r.resource_id, r.remote_id))
+        .collect();
+        Ok(res)
+    }
+
+    async fn save(
+        data: &web::Data<AppState>,
+        connector_id: &str,
+        resource_typ: ProvisioningResource,
+        resource_id: &str,
+        remote_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        #[cfg(feature = "sqlite")]
+        let q = sqlx::query!(
+            r#"insert or replace into provisioning_resources
+            (connector_id, resource_typ, resource_id, remote_id)
+            values ($1, $2, $3, $4)"#,
+            connector_id,
+            resource_typ.as_str(),
+            resource_id,
+            remote_id,
+        );
+
+        #[cfg(not(feature = "sqlite"))]
+        let q = sqlx::query!(
+            r#"insert into provisioning_resources
+            (connector_id, resource_typ, resource_id, remote_id)
+            values ($1, $2, $3, $4)
+            on conflict(connector_id, resource_typ, resource_id) do update set remote_id = $4"#,
+            connector_id,
+            resource_typ.as_str(),
+            resource_id,
+            remote_id,
+        );
+
+        q.execute(&data.db).await?;
+        Ok(())
+    }
+}
+
+fn now_millis() -> i64 {
+    (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::Language;
+    use pretty_assertions::assert_eq;
+
+    fn delivery(connector_id: &str, resource_id: &str) -> ProvisioningDelivery {
+        ProvisioningDelivery::new(
+            connector_id,
+            ProvisioningResource::User,
+            resource_id,
+            ProvisioningAction::Updated,
+            "{}".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        assert_eq!(retry_backoff(0), RETRY_BACKOFF_BASE_MILLIS);
+        assert_eq!(retry_backoff(1), 30_000);
+        assert_eq!(retry_backoff(2), 60_000);
+        assert_eq!(retry_backoff(3), 120_000);
+        assert_eq!(retry_backoff(7), 1_920_000);
+        assert_eq!(retry_backoff(8), RETRY_BACKOFF_MAX_MILLIS);
+        assert_eq!(retry_backoff(i64::MAX), RETRY_BACKOFF_MAX_MILLIS);
+    }
+
+    #[test]
+    fn test_delivery_skip() {
+        let mut skip = DeliverySkip::default();
+        let user_a = delivery("connector1", "a");
+        let user_a_later = delivery("connector1", "a");
+        let user_b = delivery("connector1", "b");
+        let other_connector = delivery("connector2", "a");
+        assert!(!skip.contains(&user_a));
+
+        // a rejected delivery only holds back the later ones for the same resource
+        skip.insert(
+            &user_a,
+            &ErrorResponse::new(ErrorResponseType::BadRequest, "rejected".to_string()),
+        );
+        assert!(skip.contains(&user_a_later));
+        assert!(!skip.contains(&user_b));
+        assert!(!skip.contains(&other_connector));
+        let mut group_a = delivery("connector1", "a");
+        group_a.resource_typ = ProvisioningResource::Group;
+        assert!(!skip.contains(&group_a));
+
+        // an unreachable downstream app holds back the whole connector, but no other one
+        skip.insert(
+            &user_b,
+            &ErrorResponse::new(ErrorResponseType::Connection, "timeout".to_string()),
+        );
+        assert!(skip.contains(&delivery("connector1", "c")));
+        assert!(skip.contains(&group_a));
+        assert!(!skip.contains(&other_connector));
+    }
+
+    #[test]
+    fn test_webhook_signature() {
+        let sig = ProvisioningConnector::webhook_signature(
+            "SuperSecret1337",
+            1700000000,
+            r#"{"id":"abc"}"#,
+        );
+        assert_eq!(
+            sig,
+            "t=1700000000,v1=8d766f7b3c84498c495387c265c80366c1073c78b0cdd542e91a0eec38426dad"
+        );
+
+        // the timestamp is part of the signed content
+        let sig_other_ts = ProvisioningConnector::webhook_signature(
+            "SuperSecret1337",
+            1700000001,
+            r#"{"id":"abc"}"#,
+        );
+        assert_ne!(&sig[16..], &sig_other_ts[16..]);
+    }
+
+    #[test]
+    fn test_user_update_action() {
+        let before = User {
+            email: "admin@localhost.de".to_string(),
+            given_name: "Admin".to_string(),
+            family_name: "Rauthy".to_string(),
+            roles: "admin".to_string(),
+            groups: Some("admin".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ProvisioningDelivery::user_update_action(&before, &before),
+            None
+        );
+
+        // values, which are never provisioned
+        let user = User {
+            password: Some("$argon2id$".to_string()),
+            last_login: Some(1700000000),
+            failed_login_attempts: Some(1),
+            email_verified: true,
+            ..before.clone()
+        };
+        assert_eq!(
+            ProvisioningDelivery::user_update_action(&before, &user),
+            None
+        );
+
+        for user in [
+            User {
+                email: "other@localhost.de".to_string(),
+                ..before.clone()
+            },
+            User {
+                given_name: "Other".to_string(),
+                ..before.clone()
+            },
+            User {
+                family_name: "Other".to_string(),
+                ..before.clone()
+            },
+            User {
+                roles: "admin,user".to_string(),
+                ..before.clone()
+            },
+            User {
+                groups: None,
+                ..before.clone()
+            },
+            User {
+                language: Language::De,
+                ..before.clone()
+            },
+        ] {
+            assert_eq!(
+                ProvisioningDelivery::user_update_action(&before, &user),
+                Some(ProvisioningAction::Updated)
+            );
+        }
+
+        let disabled = User {
+            enabled: false,
+            // a disable always wins over other changes
+            email: "other@localhost.de".to_string(),
+            ..before.clone()
+        };
+        assert_eq!(
+            ProvisioningDelivery::user_update_action(&before, &disabled),
+            Some(ProvisioningAction::Disabled)
+        );
+        assert_eq!(
+            ProvisioningDelivery::user_update_action(&disabled, &before),
+            Some(ProvisioningAction::Updated)
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::app_state::AppState;
+use crate::request::{AuthRequest, LoginRefreshRequest, LoginRequest, ParRequest};
+use actix_web::web;
+use rauthy_common::constants::{CACHE_NAME_PAR, PAR_REQUEST_URI_PREFIX};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::get_rand;
+use redhac::{cache_del, cache_get, cache_get_from, cache_get_value, cache_put};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+/// A validated Pushed Authorization Request (RFC 9126). It lives in the cache until the
+/// `request_uri` expires or an authorization code has been issued for it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PushedAuthRequest {
+    pub id: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub claims: Option<String>,
+    pub acr_values: Option<String>,
+    pub prompt: Option<String>,
+    pub max_age: Option<i64>,
+    pub login_hint: Option<String>,
+}
+
+// CRUD
+impl PushedAuthRequest {
+    pub async fn delete(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_del(
+            CACHE_NAME_PAR.to_string(),
+            Self::cache_idx(&self.id),
+            &data.caches.ha_cache_config,
+        )
+        .await
+        .map_err(ErrorResponse::from)
+    }
+
+    /// Returns the pushed request for the given `request_uri`, which must have been pushed by
+    /// the client with the given `client_id`.
+    pub async fn find_by_request_uri(
+        data: &web::Data<AppState>,
+        client_id: &str,
+        request_uri: &str,
+    ) -> Result<Self, ErrorResponse> {
+        let id = request_uri
+            .strip_prefix(PAR_REQUEST_URI_PREFIX)
+            .ok_or_else(Self::err_invalid_request_uri)?;
+
+        let slf = cache_get!(
+            PushedAuthRequest,
+            CACHE_NAME_PAR.to_string(),
+            Self::cache_idx(id),
+            &data.caches.ha_cache_config,
+            true
+        )
+        .await?
+        .ok_or_else(Self::err_invalid_request_uri)?;
+
+        if slf.client_id != client_id {
+            return Err(Self::err_invalid_request_uri());
+        }
+        Ok(slf)
+    }
+
+    pub async fn save(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_put(
+            CACHE_NAME_PAR.to_string(),
+            Self::cache_idx(&self.id),
+            &data.caches.ha_cache_config,
+            self,
+        )
+        .await
+        .map_err(ErrorResponse::from)
+    }
+}
+
+impl PushedAuthRequest {
+    /// The given request must have been validated against the client already.
+    pub fn new(client_id: String, req: ParRequest, scopes: Vec<String>) -> Self {
+        Self {
+            id: get_rand(48),
+            client_id,
+            redirect_uri: req.redirect_uri,
+            scopes,
+            state: req.state,
+            nonce: req.nonce,
+            code_challenge: req.code_challenge,
+            code_challenge_method: req.code_challenge_method,
+            claims: req.claims,
+            acr_values: req.acr_values,
+            prompt: req.prompt,
+            max_age: req.max_age,
+            login_hint: req.login_hint,
+        }
+    }
+
+    fn cache_idx(id: &str) -> String {
+        format!("par_{}", id)
+    }
+
+    fn err_invalid_request_uri() -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("Invalid or expired 'request_uri'"),
+        )
+    }
+
+    pub fn request_uri(&self) -> String {
+        format!("{}{}", PAR_REQUEST_URI_PREFIX, self.id)
+    }
+
+    /// Returns the URI of the authorization endpoint, which references this request.
+    pub fn authorize_uri(&self, issuer: &str) -> String {
+        let mut url = Url::parse(&format!("{}/oidc/authorize", issuer))
+            .expect("the issuer to always be a valid URL");
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.client_id)
+            .append_pair("request_uri", &self.request_uri());
+        url.to_string()
+    }
+
+    /// Overwrites all authorization params of the given login request with the pushed ones.
+    pub fn apply_to_login(&self, req: &mut LoginRequest) {
+        req.redirect_uri = self.redirect_uri.clone();
+        req.scopes = Some(self.scopes.clone());
+        req.state = self.state.clone();
+        req.nonce = self.nonce.clone();
+        req.code_challenge = self.code_challenge.clone();
+        req.code_challenge_method = self.code_challenge_method.clone();
+        req.claims = self.claims.clone();
+        req.acr_values = self.acr_values.clone();
+        req.prompt = self.prompt.clone();
+    }
+
+    /// Overwrites all authorization params of the given login refresh request with the pushed ones.
+    pub fn apply_to_login_refresh(&self, req: &mut LoginRefreshRequest) {
+        req.redirect_uri = self.redirect_uri.clone();
+        req.scopes = Some(self.scopes.clone());
+        req.state = self.state.clone();
+        req.nonce = self.nonce.clone();
+        req.code_challenge = self.code_challenge.clone();
+        req.code_challenge_method = self.code_challenge_method.clone();
+        req.claims = self.claims.clone();
+        req.acr_values = self.acr_values.clone();
+        req.prompt = self.prompt.clone();
+        req.max_age = self.max_age;
+    }
+}
+
+impl From<PushedAuthRequest> for AuthRequest {
+    fn from(par: PushedAuthRequest) -> Self {
+        Self {
+            client_id: par.client_id,
+            redirect_uri: par.redirect_uri,
+            response_type: "code".to_string(),
+            scope: par.scopes.join(" "),
+            state: par.state,
+            nonce: par.nonce,
+            code_challenge: par.code_challenge,
+            code_challenge_method: par.code_challenge_method,
+            claims: par.claims,
+            acr_values: par.acr_values,
+            prompt: par.prompt,
+            max_age: par.max_age,
+            login_hint: par.login_hint,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_uri() {
+        let par = PushedAuthRequest {
+            id: get_rand(48),
+            client_id: "test".to_string(),
+            redirect_uri: "http://localhost:3000/callback".to_string(),
+            scopes: vec!["openid".to_string(), "email".to_string()],
+            state: Some("state123".to_string()),
+            nonce: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            claims: None,
+            acr_values: None,
+            prompt: Some("none".to_string()),
+            max_age: Some(300),
+            login_hint: None,
+        };
+
+        let request_uri = par.request_uri();
+        assert!(request_uri.starts_with("urn:ietf:params:oauth:request_uri:"));
+        assert!(request_uri.ends_with(&par.id));
+
+        assert_eq!(
+            par.authorize_uri("https://iam.example.com/auth/v1"),
+            format!(
+                "https://iam.example.com/auth/v1/oidc/authorize?client_id=test&request_uri=urn%3Aietf%3Aparams%3Aoauth%3Arequest_uri%3A{}",
+                par.id
+            )
+        );
+
+        let auth_req = AuthRequest::from(par);
+        assert_eq!(auth_req.response_type, "code");
+        assert_eq!(auth_req.scope, "openid email");
+        assert!(auth_req.has_prompt("none"));
+        assert!(!auth_req.has_prompt("login"));
+        assert_eq!(auth_req.max_age, Some(300));
+        assert_eq!(
+            auth_req.error_redirect_uri("login_required", "No session"),
+            "http://localhost:3000/callback?error=login_required&error_description=No+session&state=state123"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::app_state::{AppState, DbTxn};
+use actix_web::web;
+use futures_util::future::join_all;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::password_hasher::{ComparePasswords, HashPassword};
+use rauthy_common::utils::get_rand;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use tracing::warn;
+
+const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_ID_LEN: usize = 8;
+const RECOVERY_CODE_SECRET_LEN: usize = 16;
+
+/// A one-time recovery code, which can replace the MFA step during a login if a user lost
+/// access to all passkeys or the authenticator app. The plain code has the format
+/// `{id}-{secret}`. The `id` is only used for the lookup, while the secret is stored as an
+/// argon2id hash, just like a password.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct UserRecoveryCode {
+    pub user_id: String,
+    pub id: String,
+    pub hash: String,
+    pub created_at: i64,
+}
+
+// CRUD
+impl UserRecoveryCode {
+    /// Replaces all existing codes of the user with a new set and returns the plain codes,
+    /// which must be shown to the user exactly once.
+    pub async fn generate(
+        data: &web::Data<AppState>,
+        user_id: &str,
+    ) -> Result<Vec<String>, ErrorResponse> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let secrets = (0..RECOVERY_CODES_COUNT)
+            .map(|_| {
+                (
+                    get_rand(RECOVERY_CODE_ID_LEN),
+                    get_rand(RECOVERY_CODE_SECRET_LEN),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        // the password hasher limits the amount of concurrent hashes on its own
+        let hashes = join_all(
+            secrets
+                .iter()
+                .map(|(_, secret)| HashPassword::hash_password(secret.clone())),
+        )
+        .await;
+
+        let mut codes = Vec::with_capacity(RECOVERY_CODES_COUNT);
+        let mut entities = Vec::with_capacity(RECOVERY_CODES_COUNT);
+        for ((id, secret), hash) in secrets.into_iter().zip(hashes) {
+            entities.push(Self {
+                user_id: user_id.to_string(),
+                id: id.clone(),
+                hash: hash?,
+                created_at: now,
+            });
+            codes.push(format!("{}-{}", id, secret));
+        }
+
+        let mut txn = data.db.begin().await?;
+        Self::delete_all(data, user_id, Some(&mut txn)).await?;
+        for entity in entities {
+            sqlx::query!(
+                r#"insert into user_recovery_codes (user_id, id, hash, created_at)
+                values ($1, $2, $3, $4)"#,
+                entity.user_id,
+                entity.id,
+                entity.hash,
+                entity.created_at,
+            )
+            .execute(&mut *txn)
+            .await?;
+        }
+        txn.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// Deletes all codes of the user, which must happen when the last MFA method is removed.
+    pub async fn delete_all(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        txn: Option<&mut DbTxn<'_>>,
+    ) -> Result<(), ErrorResponse> {
+        let q = sqlx::query!(
+            "delete from user_recovery_codes where user_id = $1",
+            user_id
+        );
+        if let Some(txn) = txn {
+            q.execute(&mut **txn).await?;
+        } else {
+            q.execute(&data.db).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn find_for_user(
+        data: &web::Data<AppState>,
+        user_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>("select * from user_recovery_codes where user_id = $1")
+            .bind(user_id)
+            .fetch_all(&data.db)
+            .await?;
+        Ok(res)
+    }
+
+    /// Validates the given plain code and deletes it, so it can never be used again.
+    pub async fn redeem(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        code: &str,
+    ) -> Result<(), ErrorResponse> {
+        let codes = Self::find_for_user(data, user_id).await?;
+        let (slf, secret) = Self::find_candidate(&codes, code).ok_or_else(Self::err_invalid)?;
+        if !ComparePasswords::is_match(secret.to_string(), slf.hash.clone()).await? {
+            return Err(Self::err_invalid());
+        }
+
+        // the delete decides about the success to not accept the same code twice with
+        // concurrent requests
+        let res = sqlx::query!(
+            "delete from user_recovery_codes where user_id = $1 and id = $2",
+            user_id,
+            slf.id,
+        )
+        .execute(&data.db)
+        .await?;
+        if res.rows_affected() == 0 {
+            warn!("Recovery code replay for user {}", user_id);
+            return Err(Self::err_invalid());
+        }
+
+        Ok(())
+    }
+}
+
+impl UserRecoveryCode {
+    fn err_invalid() -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Invalid recovery code".to_string(),
+        )
+    }
+
+    /// Returns the still existing code with the id of the given plain code together with its
+    /// secret, which must still be verified against the hash.
+    fn find_candidate<'a, 'b>(codes: &'a [Self], code: &'b str) -> Option<(&'a Self, &'b str)> {
+        let (id, secret) = Self::split_code(code)?;
+        codes.iter().find(|c| c.id == id).map(|c| (c, secret))
+    }
+
+    /// Splits a plain code into its id and secret, if it has the expected format.
+    fn split_code(code: &str) -> Option<(&str, &str)> {
+        let (id, secret) = code.trim().split_once('-')?;
+        let is_valid =
+            |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_alphanumeric());
+        if is_valid(id, RECOVERY_CODE_ID_LEN) && is_valid(secret, RECOVERY_CODE_SECRET_LEN) {
+            Some((id, secret))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(id: &str) -> UserRecoveryCode {
+        UserRecoveryCode {
+            user_id: "user1".to_string(),
+            id: id.to_string(),
+            hash: format!("$argon2id$v=19$m=32768,t=3,p=2${}", id),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_split_code() {
+        assert_eq!(
+            UserRecoveryCode::split_code("abcd1234-abcdefgh12345678"),
+            Some(("abcd1234", "abcdefgh12345678"))
+        );
+        assert_eq!(
+            UserRecoveryCode::split_code(" abcd1234-abcdefgh12345678\n"),
+            Some(("abcd1234", "abcdefgh12345678"))
+        );
+
+        for malformed in [
+            "",
+            "-",
+            "abcd1234",
+            "abcd1234abcdefgh12345678",
+            "abcd1234-",
+            "-abcdefgh12345678",
+            "abcd123-abcdefgh12345678",
+            "abcd1234-abcdefgh1234567",
+            "abcd1234-abcdefgh123456789",
+            "abcd1234-abcdefgh-2345678",
+            "abcd12$4-abcdefgh12345678",
+            "abcd1234-abcdefgh1234567ä",
+        ] {
+            assert_eq!(
+                UserRecoveryCode::split_code(malformed),
+                None,
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_candidate() {
+        let codes = vec![code("abcd1234"), code("efgh5678")];
+
+        let found = UserRecoveryCode::find_candidate(&codes, "abcd1234-abcdefgh12345678");
+        assert_eq!(found, Some((&codes[0], "abcdefgh12345678")));
+        let found = UserRecoveryCode::find_candidate(&codes, " efgh5678-12345678abcdefgh\n");
+        assert_eq!(found, Some((&codes[1], "12345678abcdefgh")));
+
+        // malformed
+        assert!(UserRecoveryCode::find_candidate(&codes, "abcd1234abcdefgh12345678").is_none());
+        assert!(UserRecoveryCode::find_candidate(&codes, "abcd1234-abcdefgh1234567").is_none());
+        // unknown id
+        assert!(UserRecoveryCode::find_candidate(&codes, "xxxx1234-abcdefgh12345678").is_none());
+    }
+
+    #[test]
+    fn test_single_use() {
+        let plain = "abcd1234-abcdefgh12345678";
+        let mut codes = vec![code("abcd1234"), code("efgh5678")];
+
+        let (used, _) = UserRecoveryCode::find_candidate(&codes, plain).unwrap();
+        let used_id = used.id.clone();
+        // simulates the delete in `redeem`
+        codes.retain(|c| c.id != used_id);
+
+        assert!(UserRecoveryCode::find_candidate(&codes, plain).is_none());
+        assert!(UserRecoveryCode::find_candidate(&codes, "efgh5678-12345678abcdefgh").is_some());
+    }
+}
@@ -14,32 +14,25 @@ pub struct RefreshToken {
     pub exp: i64,
     pub scope: Option<String>,
     pub is_mfa: bool,
+    pub client_id: Option<String>,
+    // all rotated refresh tokens, which originate from the same login, share the same family
+    pub family_id: Option<String>,
+    // the session the login has been done with, if any
+    pub session_id: Option<String>,
+    // only set for clients with `refresh_token_rotation`, when the token has been used once
+    pub used: bool,
+    // offline tokens have been issued with the `offline_access` scope and live independently
+    // of the session with the much longer `OFFLINE_TOKEN_LIFETIME`
+    pub offline: bool,
+    // the creation of the very first token of this family
+    pub created_at: Option<i64>,
+    pub last_used: Option<i64>,
+    // the IP of the client, which has been issued this token
+    pub ip: Option<String>,
 }
 
 // CRUD
 impl RefreshToken {
-    pub async fn create(
-        data: &web::Data<AppState>,
-        id: String,
-        user_id: String,
-        nbf: OffsetDateTime,
-        exp: OffsetDateTime,
-        scope: Option<String>,
-        is_mfa: bool,
-    ) -> Result<Self, ErrorResponse> {
-        let rt = Self {
-            id,
-            user_id,
-            nbf: nbf.unix_timestamp(),
-            exp: exp.unix_timestamp(),
-            scope,
-            is_mfa,
-        };
-
-        rt.save(data).await?;
-        Ok(rt)
-    }
-
     pub async fn delete(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
         sqlx::query!("delete from refresh_tokens where id = $1", self.id)
             .execute(&data.db)
@@ -103,26 +96,48 @@ impl RefreshToken {
     pub async fn save(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
         #[cfg(feature = "sqlite")]
         let q = sqlx::query!(
-            r#"insert or replace into refresh_tokens (id, user_id, nbf, exp, scope, is_mfa)
-                values ($1, $2, $3, $4, $5, $6)"#,
+            r#"insert or replace into refresh_tokens
+                (id, user_id, nbf, exp, scope, is_mfa, client_id, family_id, session_id, used,
+                offline, created_at, last_used, ip)
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
             self.id,
             self.user_id,
             self.nbf,
             self.exp,
             self.scope,
             self.is_mfa,
+            self.client_id,
+            self.family_id,
+            self.session_id,
+            self.used,
+            self.offline,
+            self.created_at,
+            self.last_used,
+            self.ip,
         );
         #[cfg(not(feature = "sqlite"))]
         let q = sqlx::query!(
-            r#"insert into refresh_tokens (id, user_id, nbf, exp, scope, is_mfa)
-                values ($1, $2, $3, $4, $5, $6)
-                on conflict(id) do update set user_id = $2, nbf = $3, exp = $4, scope = $5"#,
+            r#"insert into refresh_tokens
+                (id, user_id, nbf, exp, scope, is_mfa, client_id, family_id, session_id, used,
+                offline, created_at, last_used, ip)
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                on conflict(id) do update set user_id = $2, nbf = $3, exp = $4, scope = $5,
+                is_mfa = $6, client_id = $7, family_id = $8, session_id = $9, used = $10,
+                offline = $11, last_used = $13, ip = $14"#,
             self.id,
             self.user_id,
             self.nbf,
             self.exp,
             self.scope,
             self.is_mfa,
+            self.client_id,
+            self.family_id,
+            self.session_id,
+            self.used,
+            self.offline,
+            self.created_at,
+            self.last_used,
+            self.ip,
         );
 
         q.execute(&data.db).await?;
@@ -142,4 +157,98 @@ impl RefreshToken {
 
         Ok(())
     }
+
+    /// Deletes all refresh tokens of the same family. Tokens without a family have been issued
+    /// before the rotation existed and will only delete themselves.
+    pub async fn delete_family(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        if let Some(family_id) = &self.family_id {
+            sqlx::query!("delete from refresh_tokens where family_id = $1", family_id)
+                .execute(&data.db)
+                .await?;
+        } else {
+            self.delete(data).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks this token as used. Returns `false`, if it has already been used before, which
+    /// will also be the case for concurrent requests with the same token.
+    pub async fn mark_used(&mut self, data: &web::Data<AppState>) -> Result<bool, ErrorResponse> {
+        let res = sqlx::query!(
+            "update refresh_tokens set used = true where id = $1 and used = false",
+            self.id
+        )
+        .execute(&data.db)
+        .await?;
+        self.used = true;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Deletes a single offline token family of the user. Interactive sessions are not affected.
+    pub async fn delete_offline_family(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        family_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        let res = sqlx::query!(
+            "delete from refresh_tokens where user_id = $1 and family_id = $2 and offline = true",
+            user_id,
+            family_id
+        )
+        .execute(&data.db)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Offline token does not exist".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns all currently valid offline tokens of the user. Without rotation, the previous
+    /// token of a family may still be valid for the grace time, which makes it possible to find
+    /// multiple tokens for the same family.
+    pub async fn find_offline_for_user(
+        data: &web::Data<AppState>,
+        user_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let res = sqlx::query_as!(
+            Self,
+            r#"select * from refresh_tokens
+            where user_id = $1 and offline = true and used = false and exp > $2"#,
+            user_id,
+            now
+        )
+        .fetch_all(&data.db)
+        .await?;
+
+        Ok(res)
+    }
+
+    /// Deletes all refresh tokens, which have been issued to the given client for this user.
+    ///
+    /// Tokens from before the `client_id` has been tracked cannot be mapped to any client, and the
+    /// column cannot be backfilled, since only the signature of a token is saved. These will be
+    /// deleted as well to make sure, that no token for this client survives.
+    pub async fn delete_for_user_client(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        client_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        sqlx::query!(
+            r#"delete from refresh_tokens
+            where user_id = $1 and (client_id = $2 or client_id is null)"#,
+            user_id,
+            client_id
+        )
+        .execute(&data.db)
+        .await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,347 @@
+use crate::app_state::AppState;
+use crate::entity::clients::Client;
+use crate::entity::jwk::JwkKeyPairAlg;
+use crate::request::{default_scope, AuthRequestRef, ParRequest};
+use actix_web::web;
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::base64_url_no_pad_decode;
+use reqwest::tls;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// request objects passed by reference will be rejected, if they are bigger than this
+const REQUEST_OBJECT_MAX_BYTES: usize = 64 * 1024;
+
+/// The claims of a signed request object (RFC 9101). Only the authorization params which are
+/// supported by Rauthy will be extracted, all others are ignored.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RequestObject {
+    pub iss: Option<String>,
+    pub aud: Option<RequestObjectAud>,
+    pub exp: Option<i64>,
+    pub nbf: Option<i64>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub response_type: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    /// Inside a request object, `claims` is a JSON object instead of a string.
+    pub claims: Option<serde_json::Value>,
+    pub acr_values: Option<String>,
+    pub prompt: Option<String>,
+    pub max_age: Option<i64>,
+    pub login_hint: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum RequestObjectAud {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl RequestObject {
+    /// Fetches a request object, which has been passed by reference via the `request_uri`.
+    /// Only the `request_uris`, which have been registered for the client, will be fetched.
+    pub async fn fetch(client: &Client, request_uri: &str) -> Result<String, ErrorResponse> {
+        client.validate_request_uri(request_uri)?;
+        if !request_uri.starts_with("https://") {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Only 'https' is supported for the 'request_uri'".to_string(),
+            ));
+        }
+
+        let http_client = HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!(
+                    "Rauthy v{} Request Object Resolver",
+                    RAUTHY_VERSION
+                ))
+                .min_tls_version(tls::Version::TLS_1_2)
+                // a registered uri must not be able to redirect us somewhere else
+                .redirect(reqwest::redirect::Policy::none())
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let err = |msg: String| ErrorResponse::new(ErrorResponseType::BadRequest, msg);
+        let mut res = http_client
+            .get(request_uri)
+            .send()
+            .await
+            .map_err(|e| err(format!("Cannot fetch the 'request_uri': {}", e)))?;
+        if !res.status().is_success() {
+            return Err(err(format!(
+                "Cannot fetch the 'request_uri': HTTP {}",
+                res.status()
+            )));
+        }
+
+        // the size must be checked before the whole body is in memory
+        let err_too_big = || err("The request object is too big".to_string());
+        if res.content_length().unwrap_or_default() > REQUEST_OBJECT_MAX_BYTES as u64 {
+            return Err(err_too_big());
+        }
+        let mut body = Vec::with_capacity(1024);
+        while let Some(chunk) = res
+            .chunk()
+            .await
+            .map_err(|e| err(format!("Cannot read the request object: {}", e)))?
+        {
+            if body.len() + chunk.len() > REQUEST_OBJECT_MAX_BYTES {
+                return Err(err_too_big());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8(body)
+            .map_err(|_| err("The request object is not valid UTF-8".to_string()))?;
+        Ok(body.trim().to_string())
+    }
+
+    /// Validates the signature of the given request object against the public keys of the
+    /// client and returns its claims after checking `iss`, `aud`, `exp`, `nbf` and `client_id`.
+    pub async fn validate(
+        data: &web::Data<AppState>,
+        client: &Client,
+        token: &str,
+    ) -> Result<Self, ErrorResponse> {
+        let metadata = jwt_simple::token::Token::decode_metadata(token).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Malformed request object".to_string(),
+            )
+        })?;
+        // this rejects unsigned request objects with 'alg: none' as well
+        let alg = JwkKeyPairAlg::from_str(metadata.algorithm()).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Unsupported 'alg' for the request object: {}",
+                    metadata.algorithm()
+                ),
+            )
+        })?;
+        if !client
+            .validate_jwt_signature(data, token, &alg, metadata.key_id())
+            .await?
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Invalid request object signature".to_string(),
+            ));
+        }
+
+        let claims = token.split('.').nth(1).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Malformed request object".to_string(),
+            )
+        })?;
+        let slf = serde_json::from_slice::<Self>(&base64_url_no_pad_decode(claims)?)?;
+        slf.validate_claims(
+            &data.issuer,
+            &client.id,
+            OffsetDateTime::now_utc().unix_timestamp(),
+        )?;
+
+        Ok(slf)
+    }
+
+    /// Merges the params from this request object with the additional query params.
+    /// Params inside the request object always take precedence. The `response_type` and
+    /// `client_id` must match, if they are given twice.
+    pub fn merge(self, query: AuthRequestRef) -> Result<ParRequest, ErrorResponse> {
+        if let (Some(obj), Some(q)) = (&self.response_type, &query.response_type) {
+            if obj != q {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "'response_type' does not match the request object".to_string(),
+                ));
+            }
+        }
+
+        let redirect_uri = self.redirect_uri.or(query.redirect_uri).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'redirect_uri' is missing".to_string(),
+            )
+        })?;
+        let response_type = self.response_type.or(query.response_type).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'response_type' is missing".to_string(),
+            )
+        })?;
+
+        Ok(ParRequest {
+            client_id: None,
+            client_secret: None,
+            client_assertion: None,
+            client_assertion_type: None,
+            redirect_uri,
+            response_type,
+            scope: self.scope.or(query.scope).unwrap_or_else(default_scope),
+            state: self.state.or(query.state),
+            nonce: self.nonce.or(query.nonce),
+            code_challenge: self.code_challenge.or(query.code_challenge),
+            code_challenge_method: self.code_challenge_method.or(query.code_challenge_method),
+            claims: self
+                .claims
+                .map(|claims| claims.to_string())
+                .or(query.claims),
+            acr_values: self.acr_values.or(query.acr_values),
+            prompt: self.prompt.or(query.prompt),
+            max_age: self.max_age.or(query.max_age),
+            login_hint: self.login_hint.or(query.login_hint),
+        })
+    }
+
+    fn validate_claims(
+        &self,
+        issuer: &str,
+        client_id: &str,
+        now: i64,
+    ) -> Result<(), ErrorResponse> {
+        let err = |msg: &str| ErrorResponse::new(ErrorResponseType::BadRequest, msg.to_string());
+
+        if matches!(&self.iss, Some(iss) if iss != client_id) {
+            return Err(err(
+                "The request object 'iss' does not match the 'client_id'",
+            ));
+        }
+        if matches!(&self.client_id, Some(id) if id != client_id) {
+            return Err(err("The request object 'client_id' does not match"));
+        }
+        if let Some(aud) = &self.aud {
+            let is_valid = match aud {
+                RequestObjectAud::Single(aud) => aud == issuer,
+                RequestObjectAud::Multiple(auds) => auds.iter().any(|aud| aud == issuer),
+            };
+            if !is_valid {
+                return Err(err("The request object 'aud' does not match the issuer"));
+            }
+        }
+        if matches!(self.exp, Some(exp) if exp < now) {
+            return Err(err("The request object has expired"));
+        }
+        if matches!(self.nbf, Some(nbf) if nbf > now) {
+            return Err(err("The request object is not valid yet"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_claims() {
+        let issuer = "https://iam.example.com/auth/v1";
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+
+        let obj = RequestObject {
+            iss: Some("test".to_string()),
+            aud: Some(RequestObjectAud::Single(issuer.to_string())),
+            exp: Some(now + 60),
+            nbf: Some(now - 60),
+            client_id: Some("test".to_string()),
+            ..Default::default()
+        };
+        assert!(obj.validate_claims(issuer, "test", now).is_ok());
+        assert!(obj.validate_claims(issuer, "other", now).is_err());
+        assert!(obj
+            .validate_claims("https://other.example.com", "test", now)
+            .is_err());
+        assert!(obj.validate_claims(issuer, "test", now + 120).is_err());
+        assert!(obj.validate_claims(issuer, "test", now - 120).is_err());
+
+        let obj = RequestObject {
+            aud: Some(RequestObjectAud::Multiple(vec![
+                "https://other.example.com".to_string(),
+                issuer.to_string(),
+            ])),
+            ..Default::default()
+        };
+        assert!(obj.validate_claims(issuer, "test", now).is_ok());
+    }
+
+    #[test]
+    fn test_merge() {
+        let query = AuthRequestRef {
+            client_id: "test".to_string(),
+            request: Some("ey...".to_string()),
+            request_uri: None,
+            redirect_uri: Some("https://app.example.com/query".to_string()),
+            response_type: Some("code".to_string()),
+            scope: None,
+            state: Some("query".to_string()),
+            nonce: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            claims: None,
+            acr_values: Some("mfa".to_string()),
+            prompt: Some("login".to_string()),
+            max_age: None,
+            login_hint: None,
+        };
+        let obj = RequestObject {
+            redirect_uri: Some("https://app.example.com/callback".to_string()),
+            state: Some("object".to_string()),
+            nonce: Some("nonce".to_string()),
+            claims: Some(serde_json::json!({ "id_token": { "email": null } })),
+            ..Default::default()
+        };
+
+        let merged = obj.merge(query).unwrap();
+        assert_eq!(merged.redirect_uri, "https://app.example.com/callback");
+        assert_eq!(merged.response_type, "code");
+        assert_eq!(merged.scope, "openid");
+        assert_eq!(merged.state.as_deref(), Some("object"));
+        assert_eq!(merged.nonce.as_deref(), Some("nonce"));
+        assert_eq!(
+            merged.claims.as_deref(),
+            Some(r#"{"id_token":{"email":null}}"#)
+        );
+        assert_eq!(merged.acr_values.as_deref(), Some("mfa"));
+        assert_eq!(merged.prompt.as_deref(), Some("login"));
+
+        let query = AuthRequestRef {
+            client_id: "test".to_string(),
+            request: Some("ey...".to_string()),
+            request_uri: None,
+            redirect_uri: None,
+            response_type: Some("token".to_string()),
+            scope: None,
+            state: None,
+            nonce: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            claims: None,
+            acr_values: None,
+            prompt: None,
+            max_age: None,
+            login_hint: None,
+        };
+        let obj = RequestObject {
+            redirect_uri: Some("https://app.example.com/callback".to_string()),
+            response_type: Some("code".to_string()),
+            ..Default::default()
+        };
+        assert!(obj.merge(query).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::app_state::AppState;
+use actix_web::web;
+use rauthy_common::constants::{
+    CACHE_NAME_REVOKED_TOKENS, IDX_NOT_REVOKED_TOKEN, IDX_REVOKED_TOKEN,
+};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use redhac::{cache_get, cache_get_from, cache_get_value, cache_insert, cache_put, AckLevel};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// An entry in the revocation list for access tokens (RFC 7009).
+///
+/// Only the `jti` of the token is saved. Entries can be cleaned up as soon as the token itself
+/// has expired.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct RevokedToken {
+    pub jti: String,
+    pub exp: i64,
+}
+
+// CRUD
+impl RevokedToken {
+    pub async fn create(
+        data: &web::Data<AppState>,
+        jti: String,
+        exp: i64,
+    ) -> Result<(), ErrorResponse> {
+        #[cfg(feature = "sqlite")]
+        let q = sqlx::query!(
+            "insert or ignore into revoked_tokens (jti, exp) values ($1, $2)",
+            jti,
+            exp
+        );
+        #[cfg(not(feature = "sqlite"))]
+        let q = sqlx::query!(
+            "insert into revoked_tokens (jti, exp) values ($1, $2) on conflict(jti) do nothing",
+            jti,
+            exp
+        );
+        q.execute(&data.db).await?;
+
+        // The revoked entries are checked before the not revoked ones and will never be
+        // overwritten, which makes it safe to cache both of them.
+        cache_insert(
+            CACHE_NAME_REVOKED_TOKENS.to_string(),
+            format!("{}{}", IDX_REVOKED_TOKEN, jti),
+            &data.caches.ha_cache_config,
+            &true,
+            AckLevel::Quorum,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl RevokedToken {
+    /// Returns an error, if the token with the given `jti` has been revoked.
+    pub async fn check_not_revoked(
+        data: &web::Data<AppState>,
+        jti: &str,
+    ) -> Result<(), ErrorResponse> {
+        let idx_revoked = format!("{}{}", IDX_REVOKED_TOKEN, jti);
+        let revoked = cache_get!(
+            bool,
+            CACHE_NAME_REVOKED_TOKENS.to_string(),
+            idx_revoked.clone(),
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?
+        .is_some();
+        if revoked {
+            return Err(Self::err_revoked());
+        }
+
+        let idx_not_revoked = format!("{}{}", IDX_NOT_REVOKED_TOKEN, jti);
+        let not_revoked = cache_get!(
+            bool,
+            CACHE_NAME_REVOKED_TOKENS.to_string(),
+            idx_not_revoked.clone(),
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?
+        .is_some();
+        if not_revoked {
+            return Ok(());
+        }
+
+        let revoked = sqlx::query!("select jti from revoked_tokens where jti = $1", jti)
+            .fetch_optional(&data.db)
+            .await?
+            .is_some();
+
+        let idx = if revoked {
+            idx_revoked
+        } else {
+            idx_not_revoked
+        };
+        cache_put(
+            CACHE_NAME_REVOKED_TOKENS.to_string(),
+            idx,
+            &data.caches.ha_cache_config,
+            &true,
+        )
+        .await?;
+
+        if revoked {
+            Err(Self::err_revoked())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn err_revoked() -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Token has been revoked".to_string(),
+        )
+    }
+}
@@ -1,4 +1,5 @@
 use crate::app_state::AppState;
+use crate::entity::provisioning::{ProvisioningAction, ProvisioningDelivery};
 use crate::entity::users::User;
 use crate::request::NewRoleRequest;
 use actix_web::web;
@@ -55,6 +56,9 @@ impl Role {
         )
         .await?;
 
+        ProvisioningDelivery::enqueue_role(data, &new_role, ProvisioningAction::Created, None)
+            .await?;
+
         Ok(new_role)
     }
 
@@ -104,6 +108,14 @@ impl Role {
             .execute(&mut *txn)
             .await?;
 
+        ProvisioningDelivery::enqueue_role(
+            data,
+            &role,
+            ProvisioningAction::Deleted,
+            Some(&mut txn),
+        )
+        .await?;
+
         txn.commit().await?;
         // DATA_STORE.del(Cf::Roles, role.id.clone()).await?;
 
@@ -207,6 +219,14 @@ impl Role {
         .execute(&mut *txn)
         .await?;
 
+        ProvisioningDelivery::enqueue_role(
+            data,
+            &new_role,
+            ProvisioningAction::Updated,
+            Some(&mut txn),
+        )
+        .await?;
+
         txn.commit().await?;
 
         let roles = Role::find_all(data)
@@ -378,7 +378,11 @@ impl Scope {
     /// Note: `groups` is not a default scope, but it will be handled like one for performance
     /// and efficiency reasons.
     pub fn is_custom(scope: &str) -> bool {
-        scope != "openid" && scope != "profile" && scope != "email" && scope != "groups"
+        scope != "openid"
+            && scope != "profile"
+            && scope != "email"
+            && scope != "groups"
+            && scope != "offline_access"
     }
 }
 
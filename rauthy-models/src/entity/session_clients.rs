@@ -0,0 +1,311 @@
+use crate::app_state::AppState;
+use crate::entity::clients::Client;
+use crate::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use crate::entity::sessions::Session;
+use crate::events::event::Event;
+use crate::JwtLogoutClaims;
+use actix_web::web;
+use rauthy_common::constants::{BACKCHANNEL_LOGOUT_EVENT, RAUTHY_VERSION, TOKEN_LOGOUT_JWT_TYP};
+use rauthy_common::error_response::ErrorResponse;
+use rauthy_common::utils::get_rand;
+use reqwest::{tls, Url};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{debug, error, warn};
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// max attempts for the delivery of a single logout token with an exponential backoff
+const LOGOUT_TOKEN_DELIVERY_ATTEMPTS: u32 = 5;
+const LOGOUT_TOKEN_LIFETIME: i64 = 120;
+
+/// Tracks which clients a session has been logged into. This is needed to know which clients
+/// must be notified (OIDC Back- and Front-Channel Logout) when the session ends.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct SessionClient {
+    pub session_id: String,
+    pub client_id: String,
+    pub created: i64,
+}
+
+// CRUD
+impl SessionClient {
+    pub async fn create(
+        data: &web::Data<AppState>,
+        session_id: &str,
+        client_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        #[cfg(feature = "sqlite")]
+        let q = sqlx::query!(
+            r#"insert or ignore into session_clients (session_id, client_id, created)
+            values ($1, $2, $3)"#,
+            session_id,
+            client_id,
+            now,
+        );
+        #[cfg(not(feature = "sqlite"))]
+        let q = sqlx::query!(
+            r#"insert into session_clients (session_id, client_id, created)
+            values ($1, $2, $3) on conflict(session_id, client_id) do nothing"#,
+            session_id,
+            client_id,
+            now,
+        );
+
+        q.execute(&data.db).await?;
+        Ok(())
+    }
+
+    pub async fn find_for_session(
+        data: &web::Data<AppState>,
+        session_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>("select * from session_clients where session_id = $1")
+            .bind(session_id)
+            .fetch_all(&data.db)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn delete_for_session(
+        data: &web::Data<AppState>,
+        session_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        sqlx::query!(
+            "delete from session_clients where session_id = $1",
+            session_id
+        )
+        .execute(&data.db)
+        .await?;
+        Ok(())
+    }
+}
+
+impl SessionClient {
+    /// Returns all existing clients the given session has been logged into.
+    pub async fn find_clients(
+        data: &web::Data<AppState>,
+        session_id: &str,
+    ) -> Result<Vec<Client>, ErrorResponse> {
+        let mut clients = Vec::default();
+        for sc in Self::find_for_session(data, session_id).await? {
+            // the client may have been deleted in the meantime
+            if let Ok(client) = Client::find(data, sc.client_id).await {
+                clients.push(client);
+            }
+        }
+        Ok(clients)
+    }
+
+    /// Returns all existing clients the given session has been logged into and removes the
+    /// tracking entries, since they are not needed anymore after a logout.
+    pub async fn take_clients(
+        data: &web::Data<AppState>,
+        session_id: &str,
+    ) -> Result<Vec<Client>, ErrorResponse> {
+        let clients = Self::find_clients(data, session_id).await?;
+        Self::delete_for_session(data, session_id).await?;
+        Ok(clients)
+    }
+
+    /// Sends a logout token to each of the given clients with a `backchannel_logout_uri`.
+    /// The delivery happens in the background and will be retried a few times.
+    /// Failed deliveries will be emitted as an [Event].
+    pub async fn backchannel_logout(
+        data: &web::Data<AppState>,
+        session: &Session,
+        clients: &[Client],
+    ) -> Result<(), ErrorResponse> {
+        let user_id = match &session.user_id {
+            Some(uid) => uid,
+            None => return Ok(()),
+        };
+
+        let sid = Session::oidc_sid(&session.id);
+        for client in clients {
+            let uri = match &client.backchannel_logout_uri {
+                Some(uri) => uri.clone(),
+                None => continue,
+            };
+
+            let token = Self::build_logout_token(data, client, user_id, &sid).await?;
+            tokio::spawn(Self::send_logout_token(
+                data.tx_events.clone(),
+                client.id.clone(),
+                uri,
+                token,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the `frontchannel_logout_uri` of each of the given clients with the `iss` and
+    /// `sid` query params appended. These must be loaded inside iframes by the user agent.
+    pub fn frontchannel_logout_uris(
+        issuer: &str,
+        session: &Session,
+        clients: &[Client],
+    ) -> Vec<String> {
+        let sid = Session::oidc_sid(&session.id);
+        clients
+            .iter()
+            .filter_map(|c| c.frontchannel_logout_uri.as_ref())
+            .filter_map(|uri| match Url::parse(uri) {
+                Ok(mut url) => {
+                    url.query_pairs_mut()
+                        .append_pair("iss", issuer)
+                        .append_pair("sid", &sid);
+                    Some(url.to_string())
+                }
+                Err(err) => {
+                    warn!("Invalid Front-Channel Logout URI '{}': {}", uri, err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the origins of the Front-Channel Logout URIs of the given clients, which are
+    /// needed for the `frame-src` of the CSP on the logout page.
+    pub fn frontchannel_logout_origins(clients: &[Client]) -> Vec<String> {
+        let mut origins = clients
+            .iter()
+            .filter_map(|c| c.frontchannel_logout_uri.as_ref())
+            .filter_map(|uri| Url::parse(uri).ok())
+            .map(|url| url.origin().ascii_serialization())
+            .collect::<Vec<String>>();
+        origins.sort();
+        origins.dedup();
+        origins
+    }
+
+    async fn build_logout_token(
+        data: &web::Data<AppState>,
+        client: &Client,
+        user_id: &str,
+        sid: &str,
+    ) -> Result<String, ErrorResponse> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let claims = JwtLogoutClaims {
+            iss: data.issuer.clone(),
+            sub: user_id.to_string(),
+            aud: client.id.clone(),
+            iat: now,
+            exp: now + LOGOUT_TOKEN_LIFETIME,
+            jti: get_rand(24),
+            events: HashMap::from([(
+                BACKCHANNEL_LOGOUT_EVENT.to_string(),
+                serde_json::Value::Object(Default::default()),
+            )]),
+            sid: sid.to_string(),
+        };
+
+        // logout tokens are signed in the same way as ID tokens
+        let key_pair_type = JwkKeyPairAlg::from_str(&client.id_token_alg)?;
+        let kp = JwkKeyPair::find_latest(data, &client.id_token_alg, key_pair_type).await?;
+        kp.sign_with_typ(&claims, TOKEN_LOGOUT_JWT_TYP)
+    }
+
+    async fn send_logout_token(
+        tx_events: flume::Sender<Event>,
+        client_id: String,
+        uri: String,
+        token: String,
+    ) {
+        let mut delay = Duration::from_secs(1);
+        let mut last_err = String::default();
+
+        for attempt in 1..=LOGOUT_TOKEN_DELIVERY_ATTEMPTS {
+            let res = Self::http_client()
+                .post(&uri)
+                .form(&[("logout_token", &token)])
+                .send()
+                .await;
+
+            match res {
+                Ok(resp) if resp.status().is_success() => {
+                    debug!("Back-Channel Logout for client '{}' successful", client_id);
+                    return;
+                }
+                Ok(resp) => last_err = format!("HTTP {}", resp.status()),
+                Err(err) => last_err = err.to_string(),
+            }
+
+            if attempt < LOGOUT_TOKEN_DELIVERY_ATTEMPTS {
+                warn!(
+                    "Back-Channel Logout for client '{}' failed: {} - retrying in {} s",
+                    client_id,
+                    last_err,
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+
+        error!(
+            "Back-Channel Logout for client '{}' failed finally: {}",
+            client_id, last_err
+        );
+        // the error is logged inside `send()` already
+        let _ = Event::backchannel_logout_failed(client_id, last_err)
+            .send(&tx_events)
+            .await;
+    }
+
+    fn http_client() -> &'static reqwest::Client {
+        HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Back-Channel Logout", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frontchannel_logout() {
+        let clients = vec![
+            Client {
+                frontchannel_logout_uri: Some("https://app.example.com/logout?a=b".to_string()),
+                ..Default::default()
+            },
+            Client {
+                frontchannel_logout_uri: Some("https://app.example.com/other".to_string()),
+                ..Default::default()
+            },
+            Client::default(),
+        ];
+
+        let origins = SessionClient::frontchannel_logout_origins(&clients);
+        assert_eq!(origins, vec!["https://app.example.com".to_string()]);
+
+        let session = Session::new(60, None);
+        let sid = Session::oidc_sid(&session.id);
+        let uris =
+            SessionClient::frontchannel_logout_uris("https://iam.example.com", &session, &clients);
+        assert_eq!(uris.len(), 2);
+        assert_eq!(
+            uris[0],
+            format!(
+                "https://app.example.com/logout?a=b&iss=https%3A%2F%2Fiam.example.com&sid={}",
+                sid
+            )
+        );
+    }
+}
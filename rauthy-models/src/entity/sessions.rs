@@ -9,6 +9,7 @@ use rauthy_common::constants::{
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_common::utils::get_rand;
 use redhac::{cache_get, cache_get_from, cache_get_value, cache_insert, cache_remove, AckLevel};
+use ring::digest;
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
 use sqlx::sqlite::SqliteRow;
@@ -32,6 +33,8 @@ pub struct Session {
     pub exp: i64,
     pub last_seen: i64,
     pub remote_ip: Option<String>, // TODO should we maybe force a linked remote_ip all the time?
+    // the last time the user actively authenticated for this session
+    pub auth_time: Option<i64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
@@ -222,8 +225,9 @@ impl Session {
         #[cfg(feature = "sqlite")]
         let q = sqlx::query!(
             r#"insert or replace into
-            sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+            sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip,
+            auth_time)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#,
             self.id,
             self.csrf_token,
             self.user_id,
@@ -234,15 +238,17 @@ impl Session {
             self.exp,
             self.last_seen,
             self.remote_ip,
+            self.auth_time,
         );
 
         #[cfg(not(feature = "sqlite"))]
         let q = sqlx::query!(
             r#"insert into
-            sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip,
+            auth_time)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             on conflict(id) do update set user_id = $3, roles = $4, groups = $5, is_mfa = $6,
-            state = $7, exp = $8, last_seen = $9, remote_ip = $10"#,
+            state = $7, exp = $8, last_seen = $9, remote_ip = $10, auth_time = $11"#,
             self.id,
             self.csrf_token,
             self.user_id,
@@ -253,6 +259,7 @@ impl Session {
             self.exp,
             self.last_seen,
             self.remote_ip,
+            self.auth_time,
         );
 
         q.execute(&data.db).await?;
@@ -290,6 +297,7 @@ impl Session {
                 .unix_timestamp(),
             last_seen: now.unix_timestamp(),
             remote_ip,
+            auth_time: None,
         }
     }
 
@@ -297,6 +305,13 @@ impl Session {
         format!("{}{}", IDX_SESSION, id)
     }
 
+    /// Returns the `sid` claim for ID and logout tokens for the given session id.
+    /// The session id itself must never be exposed, since it is the value of the session cookie.
+    pub fn oidc_sid(session_id: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, session_id.as_bytes());
+        hex::encode(hash.as_ref())
+    }
+
     /// exp_in will be the time in seconds when the session will expire
     pub fn try_new(
         user: &User,
@@ -344,6 +359,7 @@ impl Session {
             exp,
             last_seen: now.unix_timestamp(),
             remote_ip,
+            auth_time: None,
         })
     }
 
@@ -451,6 +467,18 @@ impl Session {
         true
     }
 
+    /// Returns `true`, if the last active authentication is older than the given `max_age` in
+    /// seconds. Sessions without a known `auth_time` are always treated as expired in that case.
+    pub fn is_auth_expired(&self, max_age: Option<i64>) -> bool {
+        match (max_age, self.auth_time) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(max_age), Some(auth_time)) => {
+                OffsetDateTime::now_utc().unix_timestamp() - auth_time > max_age
+            }
+        }
+    }
+
     pub fn groups_as_vec(&self) -> Result<Vec<&str>, ErrorResponse> {
         if self.groups.is_none() {
             return Ok(Vec::default());
@@ -537,3 +565,26 @@ pub fn get_header_value<'a>(
     })?;
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_auth_expired() {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut session = Session::new(3600, None);
+
+        // without a known `auth_time`, only a missing `max_age` is fine
+        assert!(!session.is_auth_expired(None));
+        assert!(session.is_auth_expired(Some(300)));
+
+        session.auth_time = Some(now - 60);
+        assert!(!session.is_auth_expired(None));
+        assert!(!session.is_auth_expired(Some(300)));
+        assert!(session.is_auth_expired(Some(30)));
+        // `max_age=0` always forces a new authentication
+        session.auth_time = Some(now - 1);
+        assert!(session.is_auth_expired(Some(0)));
+    }
+}
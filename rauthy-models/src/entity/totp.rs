@@ -0,0 +1,294 @@
+use crate::app_state::AppState;
+use crate::entity::recovery_codes::UserRecoveryCode;
+use crate::entity::users::User;
+use actix_web::web;
+use rand::RngCore;
+use rauthy_common::constants::{TOTP_ISSUER, TOTP_SKEW};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::{decrypt, encrypt};
+use reqwest::Url;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use tracing::{error, warn};
+
+const TOTP_DIGITS: u32 = 6;
+const TOTP_PERIOD: i64 = 30;
+const TOTP_SECRET_LEN: usize = 20;
+
+/// The TOTP (RFC 6238) secret of a user. The secret is encrypted with the active `ENC_KEYS`
+/// entry. As long as `users.totp_enabled` is not set, this is a pending enrollment, which still
+/// needs to be confirmed with a first valid code.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct UserTotp {
+    pub user_id: String,
+    pub secret: Vec<u8>,
+    pub enc_key_id: String,
+    // the last accepted time step to prevent the replay of already used codes
+    pub last_step: Option<i64>,
+    pub created_at: i64,
+}
+
+// CRUD
+impl UserTotp {
+    /// Creates a new pending enrollment and returns the plain secret, which must be shown to
+    /// the user exactly once. An already existing pending enrollment will be replaced.
+    pub async fn create(
+        data: &web::Data<AppState>,
+        user_id: String,
+    ) -> Result<(Self, Vec<u8>), ErrorResponse> {
+        let mut plain = vec![0u8; TOTP_SECRET_LEN];
+        rand::thread_rng().fill_bytes(&mut plain);
+
+        let key = data
+            .enc_keys
+            .get(&data.enc_key_active)
+            .expect("Encryption Key config is broken");
+        let slf = Self {
+            user_id,
+            secret: encrypt(&plain, key)?,
+            enc_key_id: data.enc_key_active.clone(),
+            last_step: None,
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+
+        #[cfg(feature = "sqlite")]
+        let q = sqlx::query!(
+            r#"insert or replace into user_totps
+            (user_id, secret, enc_key_id, last_step, created_at)
+            values ($1, $2, $3, $4, $5)"#,
+            slf.user_id,
+            slf.secret,
+            slf.enc_key_id,
+            slf.last_step,
+            slf.created_at,
+        );
+        #[cfg(not(feature = "sqlite"))]
+        let q = sqlx::query!(
+            r#"insert into user_totps (user_id, secret, enc_key_id, last_step, created_at)
+            values ($1, $2, $3, $4, $5)
+            on conflict(user_id) do update
+            set secret = $2, enc_key_id = $3, last_step = $4, created_at = $5"#,
+            slf.user_id,
+            slf.secret,
+            slf.enc_key_id,
+            slf.last_step,
+            slf.created_at,
+        );
+        q.execute(&data.db).await?;
+
+        Ok((slf, plain))
+    }
+
+    /// Deletes the TOTP secret and disables TOTP for the user.
+    pub async fn delete(data: &web::Data<AppState>, user_id: String) -> Result<(), ErrorResponse> {
+        let mut user = User::find(data, user_id).await?;
+
+        let mut txn = data.db.begin().await?;
+        sqlx::query!("delete from user_totps where user_id = $1", user.id)
+            .execute(&mut *txn)
+            .await?;
+        if user.totp_enabled {
+            user.totp_enabled = false;
+            user.save(data, None, Some(&mut txn)).await?;
+        }
+        if !user.has_mfa_enabled() {
+            UserRecoveryCode::delete_all(data, &user.id, Some(&mut txn)).await?;
+        }
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find(data: &web::Data<AppState>, user_id: &str) -> Result<Self, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>("select * from user_totps where user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&data.db)
+            .await?;
+        res.ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "No TOTP has been registered for this user".to_string(),
+            )
+        })
+    }
+}
+
+impl UserTotp {
+    /// Builds the `otpauth://` URI, which will be encoded as a QR code for the authenticator app.
+    pub fn provisioning_uri(email: &str, secret: &[u8]) -> String {
+        let mut url = Url::parse("otpauth://totp/").expect("static otpauth URI to be valid");
+        url.path_segments_mut()
+            .expect("otpauth URI to have a path")
+            .pop_if_empty()
+            .push(&format!("{}:{}", *TOTP_ISSUER, email));
+        url.query_pairs_mut()
+            .append_pair("secret", &base32_encode(secret))
+            .append_pair("issuer", &TOTP_ISSUER)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &TOTP_DIGITS.to_string())
+            .append_pair("period", &TOTP_PERIOD.to_string());
+        url.to_string()
+    }
+
+    pub fn secret_b32(secret: &[u8]) -> String {
+        base32_encode(secret)
+    }
+
+    /// Validates the given code with a tolerance of `TOTP_SKEW` time steps in both directions.
+    /// Each time step can only be used once, which makes sure that a code cannot be replayed.
+    pub async fn verify(
+        &self,
+        data: &web::Data<AppState>,
+        code: &str,
+    ) -> Result<(), ErrorResponse> {
+        let key = data.enc_keys.get(&self.enc_key_id).ok_or_else(|| {
+            error!("Cannot get encryption key {} from config", self.enc_key_id);
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "Internal Error with the ENC_KEYS config".to_string(),
+            )
+        })?;
+        let secret = decrypt(&self.secret, key)?;
+
+        let now = OffsetDateTime::now_utc().unix_timestamp() / TOTP_PERIOD;
+        let skew = *TOTP_SKEW as i64;
+        let step = (now - skew..=now + skew)
+            .find(|step| constant_time_eq(&totp(&secret, *step), code))
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "Invalid TOTP code".to_string(),
+                )
+            })?;
+
+        // the update is conditional to not accept the same code twice with concurrent requests
+        let res = sqlx::query!(
+            r#"update user_totps set last_step = $1
+            where user_id = $2 and (last_step is null or last_step < $1)"#,
+            step,
+            self.user_id,
+        )
+        .execute(&data.db)
+        .await?;
+        if res.rows_affected() == 0 {
+            warn!("TOTP code replay for user {}", self.user_id);
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "This TOTP code has already been used".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Computes the TOTP code for the given time step (RFC 4226 / RFC 6238 with HMAC-SHA1).
+fn totp(secret: &[u8], step: i64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let tag = hmac::sign(&key, &step.to_be_bytes());
+    let hash = tag.as_ref();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        bin % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    )
+}
+
+#[inline]
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// RFC 4648 base32 without padding, which is what authenticator apps expect for the secret.
+///
+/// None of the existing dependencies provides a base32 encoding. The encoding is checked against
+/// all test vectors from RFC 4648 instead.
+fn base32_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut res = String::with_capacity((input.len() * 8 + 4) / 5);
+    let mut buf: u32 = 0;
+    let mut bits = 0;
+    for b in input {
+        buf = (buf << 8) | *b as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            res.push(ALPHABET[((buf >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        res.push(ALPHABET[((buf << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_rfc6238() {
+        // test vectors from RFC 6238 Appendix B, truncated to 6 digits
+        let secret = b"12345678901234567890";
+        assert_eq!(totp(secret, 59 / TOTP_PERIOD), "287082");
+        assert_eq!(totp(secret, 1111111109 / TOTP_PERIOD), "081804");
+        assert_eq!(totp(secret, 1234567890 / TOTP_PERIOD), "005924");
+        assert_eq!(totp(secret, 2000000000 / TOTP_PERIOD), "279037");
+    }
+
+    #[test]
+    fn test_base32_encode() {
+        // test vectors from RFC 4648 section 10 without the padding
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"fo"), "MZXQ");
+        assert_eq!(base32_encode(b"foo"), "MZXW6");
+        assert_eq!(base32_encode(b"foob"), "MZXW6YQ");
+        assert_eq!(base32_encode(b"fooba"), "MZXW6YTB");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(
+            base32_encode(b"12345678901234567890"),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+        assert_eq!(base32_encode(&[0xff; 5]), "77777777");
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = UserTotp::provisioning_uri("admin@localhost.de", b"12345678901234567890");
+        let url = Url::parse(&uri).unwrap();
+        assert_eq!(url.scheme(), "otpauth");
+        assert_eq!(url.host_str(), Some("totp"));
+        assert_eq!(
+            url.path(),
+            format!("/{}:admin@localhost.de", TOTP_ISSUER.replace(' ', "%20"))
+        );
+
+        let params = url.query_pairs().collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            vec![
+                ("secret".into(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".into()),
+                ("issuer".into(), TOTP_ISSUER.as_str().into()),
+                ("algorithm".into(), "SHA1".into()),
+                ("digits".into(), "6".into()),
+                ("period".into(), "30".into()),
+            ]
+        );
+    }
+}
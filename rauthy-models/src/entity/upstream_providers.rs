@@ -0,0 +1,928 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use actix_web::web;
+use redhac::{cache_del, cache_get, cache_get_from, cache_get_value, cache_insert, cache_put};
+use redhac::{cache_remove, AckLevel};
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use reqwest::{tls, Url};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use tracing::error;
+
+use rauthy_common::constants::{
+    APPLICATION_JSON, CACHE_NAME_12HR, CACHE_NAME_UPSTREAM_AUTH, IDX_UPSTREAM_PROVIDERS,
+    RAUTHY_VERSION,
+};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::{decrypt, encrypt, get_rand, new_store_id};
+
+use crate::app_state::AppState;
+use crate::request::{UpstreamAuthRequest, UpstreamProviderRequest};
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/**
+# Upstream Identity Provider
+
+An external OIDC / OAuth2 provider users can log in with instead of a local password or passkey.
+Rauthy acts as a confidential (or public, if no secret is set) client against this provider.
+
+The user is identified by the claims from the upstream `userinfo_endpoint`. The `claim_*` values
+map the names of the upstream claims to the local user values.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct UpstreamProvider {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub client_id: String,
+    // The client secret is saved as encrypted bytes, exactly like `Client.secret`, and can only
+    // be decrypted with the key referenced by `secret_kid`.
+    pub secret: Option<Vec<u8>>,
+    pub secret_kid: Option<String>,
+    // space separated, like it is sent upstream
+    pub scope: String,
+    pub use_pkce: bool,
+    pub claim_sub: String,
+    pub claim_email: String,
+    pub claim_email_verified: Option<String>,
+    pub claim_given_name: Option<String>,
+    pub claim_family_name: Option<String>,
+    pub auto_link: bool,
+    pub auto_provision: bool,
+}
+
+// CRUD
+impl UpstreamProvider {
+    pub async fn create(
+        data: &web::Data<AppState>,
+        req: UpstreamProviderRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let mut provider = Self::from_req(data, req).await?;
+        provider.id = new_store_id();
+        provider.insert(data).await?;
+        Ok(provider)
+    }
+
+    pub async fn delete(data: &web::Data<AppState>, id: &str) -> Result<(), ErrorResponse> {
+        sqlx::query!("delete from upstream_providers where id = $1", id)
+            .execute(&data.db)
+            .await?;
+
+        Self::cache_invalidate(data).await?;
+
+        Ok(())
+    }
+
+    pub async fn find(data: &web::Data<AppState>, id: &str) -> Result<Self, ErrorResponse> {
+        Self::find_all(data)
+            .await?
+            .into_iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    format!("Upstream provider '{}' does not exist", id),
+                )
+            })
+    }
+
+    pub async fn find_all(data: &web::Data<AppState>) -> Result<Vec<Self>, ErrorResponse> {
+        let providers = cache_get!(
+            Vec<UpstreamProvider>,
+            CACHE_NAME_12HR.to_string(),
+            IDX_UPSTREAM_PROVIDERS.to_string(),
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?;
+        if let Some(providers) = providers {
+            return Ok(providers);
+        }
+
+        let providers = sqlx::query_as::<_, Self>("select * from upstream_providers")
+            .fetch_all(&data.db)
+            .await?;
+
+        cache_insert(
+            CACHE_NAME_12HR.to_string(),
+            IDX_UPSTREAM_PROVIDERS.to_string(),
+            &data.caches.ha_cache_config,
+            &providers,
+            AckLevel::Leader,
+        )
+        .await?;
+
+        Ok(providers)
+    }
+
+    pub async fn update(
+        data: &web::Data<AppState>,
+        id: String,
+        req: UpstreamProviderRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let current = Self::find(data, &id).await?;
+
+        let keep_secret = req.client_secret.is_none();
+        let mut provider = Self::from_req(data, req).await?;
+        provider.id = id;
+        if keep_secret {
+            provider.secret = current.secret;
+            provider.secret_kid = current.secret_kid;
+        }
+
+        sqlx::query!(
+            r#"update upstream_providers set name = $1, enabled = $2, issuer = $3,
+            authorization_endpoint = $4, token_endpoint = $5, userinfo_endpoint = $6,
+            client_id = $7, secret = $8, secret_kid = $9, scope = $10, use_pkce = $11,
+            claim_sub = $12, claim_email = $13, claim_email_verified = $14, claim_given_name = $15,
+            claim_family_name = $16, auto_link = $17, auto_provision = $18
+            where id = $19"#,
+            provider.name,
+            provider.enabled,
+            provider.issuer,
+            provider.authorization_endpoint,
+            provider.token_endpoint,
+            provider.userinfo_endpoint,
+            provider.client_id,
+            provider.secret,
+            provider.secret_kid,
+            provider.scope,
+            provider.use_pkce,
+            provider.claim_sub,
+            provider.claim_email,
+            provider.claim_email_verified,
+            provider.claim_given_name,
+            provider.claim_family_name,
+            provider.auto_link,
+            provider.auto_provision,
+            provider.id,
+        )
+        .execute(&data.db)
+        .await?;
+
+        Self::cache_invalidate(data).await?;
+
+        Ok(provider)
+    }
+
+    async fn insert(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        sqlx::query!(
+            r#"insert into upstream_providers (id, name, enabled, issuer, authorization_endpoint,
+            token_endpoint, userinfo_endpoint, client_id, secret, secret_kid, scope, use_pkce,
+            claim_sub, claim_email, claim_email_verified, claim_given_name, claim_family_name,
+            auto_link, auto_provision)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18, $19)"#,
+            self.id,
+            self.name,
+            self.enabled,
+            self.issuer,
+            self.authorization_endpoint,
+            self.token_endpoint,
+            self.userinfo_endpoint,
+            self.client_id,
+            self.secret,
+            self.secret_kid,
+            self.scope,
+            self.use_pkce,
+            self.claim_sub,
+            self.claim_email,
+            self.claim_email_verified,
+            self.claim_given_name,
+            self.claim_family_name,
+            self.auto_link,
+            self.auto_provision,
+        )
+        .execute(&data.db)
+        .await?;
+
+        Self::cache_invalidate(data).await?;
+
+        Ok(())
+    }
+
+    async fn cache_invalidate(data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_remove(
+            CACHE_NAME_12HR.to_string(),
+            IDX_UPSTREAM_PROVIDERS.to_string(),
+            &data.caches.ha_cache_config,
+            AckLevel::Quorum,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl UpstreamProvider {
+    /// Builds the URL the user agent will be redirected to for the login at the upstream provider.
+    pub fn authorization_url(
+        &self,
+        redirect_uri: &str,
+        state: &str,
+        code_challenge: Option<&str>,
+        force_login: bool,
+        max_age: Option<i64>,
+    ) -> Result<String, ErrorResponse> {
+        let mut url = Url::parse(&self.authorization_endpoint).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Invalid upstream authorization_endpoint: {}", err),
+            )
+        })?;
+
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("client_id", &self.client_id)
+                .append_pair("redirect_uri", redirect_uri)
+                .append_pair("response_type", "code")
+                .append_pair("scope", &self.scope)
+                .append_pair("state", state);
+            if let Some(challenge) = code_challenge {
+                query
+                    .append_pair("code_challenge", challenge)
+                    .append_pair("code_challenge_method", "S256");
+            }
+            // the upstream provider does the actual authentication and must enforce these
+            if force_login {
+                query.append_pair("prompt", "login");
+            }
+            if let Some(max_age) = max_age {
+                query.append_pair("max_age", &max_age.to_string());
+            }
+        }
+
+        Ok(url.to_string())
+    }
+
+    /// Returns the URI the upstream provider must redirect back to after the login.
+    pub fn callback_uri(data: &web::Data<AppState>) -> String {
+        format!("{}/oidc/authorize/upstream/callback", data.issuer)
+    }
+
+    /// Exchanges the `code` at the upstream `token_endpoint` and fetches the users claims from
+    /// the upstream `userinfo_endpoint` afterward.
+    pub async fn fetch_claims(
+        &self,
+        secret: Option<String>,
+        code: &str,
+        redirect_uri: &str,
+        code_verifier: Option<&str>,
+    ) -> Result<UpstreamClaims, ErrorResponse> {
+        let client = Self::http_client();
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", self.client_id.as_str()),
+        ];
+        if let Some(secret) = secret.as_deref() {
+            form.push(("client_secret", secret));
+        }
+        if let Some(verifier) = code_verifier {
+            form.push(("code_verifier", verifier));
+        }
+
+        let res = client
+            .post(&self.token_endpoint)
+            .header(ACCEPT, APPLICATION_JSON)
+            .form(&form)
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            error!(
+                "Upstream token request to {} failed with {}: {}",
+                self.token_endpoint, status, body
+            );
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Upstream provider did not accept the authorization code".to_string(),
+            ));
+        }
+        let ts = res.json::<UpstreamTokenResponse>().await?;
+
+        let res = client
+            .get(&self.userinfo_endpoint)
+            .header(ACCEPT, APPLICATION_JSON)
+            .header(AUTHORIZATION, format!("Bearer {}", ts.access_token))
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            let msg = format!(
+                "Cannot fetch upstream userinfo from {}: {}",
+                self.userinfo_endpoint,
+                res.status()
+            );
+            error!("{}", msg);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, msg));
+        }
+        let value = res.json::<serde_json::Value>().await?;
+
+        self.map_claims(&value)
+    }
+
+    pub fn get_secret_cleartext(
+        &self,
+        data: &web::Data<AppState>,
+    ) -> Result<Option<String>, ErrorResponse> {
+        if let Some(secret) = self.secret.as_ref() {
+            let kid = self.secret_kid.as_ref().unwrap_or(&data.enc_key_active);
+            let key = data.enc_keys.get(kid).ok_or_else(|| {
+                error!("Cannot get encryption key {} from config", kid);
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "Cannot decrypt upstream provider secret".to_string(),
+                )
+            })?;
+            let bytes = decrypt(secret, key)?;
+            Ok(Some(String::from_utf8_lossy(&bytes).to_string()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Maps the upstream userinfo claims to [UpstreamClaims] with the configured `claim_*` values.
+    pub fn map_claims(&self, value: &serde_json::Value) -> Result<UpstreamClaims, ErrorResponse> {
+        let sub = match value.get(&self.claim_sub) {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Number(n)) => n.to_string(),
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Upstream claim '{}' is missing", self.claim_sub),
+                ))
+            }
+        };
+
+        let email = value
+            .get(&self.claim_email)
+            .and_then(|v| v.as_str())
+            .map(|e| e.to_lowercase())
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Upstream claim '{}' is missing", self.claim_email),
+                )
+            })?;
+
+        let email_verified = self
+            .claim_email_verified
+            .as_ref()
+            .and_then(|claim| value.get(claim))
+            .map(|v| match v {
+                serde_json::Value::Bool(b) => *b,
+                serde_json::Value::String(s) => s == "true",
+                _ => false,
+            })
+            .unwrap_or(false);
+
+        let get_str = |claim: &Option<String>| {
+            claim
+                .as_ref()
+                .and_then(|c| value.get(c))
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+        };
+
+        Ok(UpstreamClaims {
+            sub,
+            email,
+            email_verified,
+            given_name: get_str(&self.claim_given_name),
+            family_name: get_str(&self.claim_family_name),
+        })
+    }
+
+    fn http_client() -> &'static reqwest::Client {
+        HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Upstream Auth Client", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        })
+    }
+
+    async fn from_req(
+        data: &web::Data<AppState>,
+        req: UpstreamProviderRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let issuer = req.issuer.trim_end_matches('/').to_string();
+
+        let (authorization_endpoint, token_endpoint, userinfo_endpoint) = match (
+            req.authorization_endpoint,
+            req.token_endpoint,
+            req.userinfo_endpoint,
+        ) {
+            (Some(auth), Some(token), Some(userinfo)) => (auth, token, userinfo),
+            (auth, token, userinfo) => {
+                let discovery = UpstreamDiscovery::fetch(&issuer).await?;
+                (
+                    auth.unwrap_or(discovery.authorization_endpoint),
+                    token.unwrap_or(discovery.token_endpoint),
+                    userinfo.or(discovery.userinfo_endpoint).ok_or_else(|| {
+                        ErrorResponse::new(
+                            ErrorResponseType::BadRequest,
+                            "The upstream provider does not publish a 'userinfo_endpoint'"
+                                .to_string(),
+                        )
+                    })?,
+                )
+            }
+        };
+
+        let (secret, secret_kid) = match req.client_secret {
+            Some(secret) if !secret.is_empty() => {
+                let (enc, kid) = Self::encrypt_secret(data, &secret)?;
+                (Some(enc), Some(kid))
+            }
+            _ => (None, None),
+        };
+
+        Ok(Self {
+            id: String::default(),
+            name: req.name,
+            enabled: req.enabled,
+            issuer,
+            authorization_endpoint,
+            token_endpoint,
+            userinfo_endpoint,
+            client_id: req.client_id,
+            secret,
+            secret_kid,
+            scope: req.scope,
+            use_pkce: req.use_pkce,
+            claim_sub: req.claim_sub,
+            claim_email: req.claim_email,
+            claim_email_verified: req.claim_email_verified,
+            claim_given_name: req.claim_given_name,
+            claim_family_name: req.claim_family_name,
+            auto_link: req.auto_link,
+            auto_provision: req.auto_provision,
+        })
+    }
+
+    fn encrypt_secret(
+        data: &web::Data<AppState>,
+        secret: &str,
+    ) -> Result<(Vec<u8>, String), ErrorResponse> {
+        let key = data
+            .enc_keys
+            .get(&data.enc_key_active)
+            .expect("Encryption Key config is broken");
+        let enc = encrypt(secret.as_bytes(), key)?;
+        Ok((enc, data.enc_key_active.clone()))
+    }
+}
+
+/// The mapped user information from an upstream provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamClaims {
+    pub sub: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpstreamDiscovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+impl UpstreamDiscovery {
+    async fn fetch(issuer: &str) -> Result<Self, ErrorResponse> {
+        let url = format!("{}/.well-known/openid-configuration", issuer);
+        let res = UpstreamProvider::http_client()
+            .get(&url)
+            .header(ACCEPT, APPLICATION_JSON)
+            .send()
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Cannot fetch upstream discovery from {}: {:?}", url, err),
+                )
+            })?;
+
+        if !res.status().is_success() {
+            let msg = format!("Cannot fetch upstream discovery from {}", url);
+            error!("{}", msg);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, msg));
+        }
+
+        res.json::<Self>().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Cannot deserialize upstream discovery from {}: {:?}",
+                    url, err
+                ),
+            )
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UpstreamTokenResponse {
+    access_token: String,
+}
+
+/// Links a local user to the `sub` of an upstream provider
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct UpstreamProviderLink {
+    pub provider_id: String,
+    pub upstream_sub: String,
+    pub user_id: String,
+    pub created_at: i64,
+}
+
+impl UpstreamProviderLink {
+    pub async fn create(
+        data: &web::Data<AppState>,
+        provider_id: String,
+        upstream_sub: String,
+        user_id: String,
+    ) -> Result<Self, ErrorResponse> {
+        let link = Self {
+            provider_id,
+            upstream_sub,
+            user_id,
+            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+
+        sqlx::query!(
+            r#"insert into upstream_provider_links (provider_id, upstream_sub, user_id, created_at)
+            values ($1, $2, $3, $4)"#,
+            link.provider_id,
+            link.upstream_sub,
+            link.user_id,
+            link.created_at,
+        )
+        .execute(&data.db)
+        .await?;
+
+        Ok(link)
+    }
+
+    pub async fn find(
+        data: &web::Data<AppState>,
+        provider_id: &str,
+        upstream_sub: &str,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>(
+            "select * from upstream_provider_links where provider_id = $1 and upstream_sub = $2",
+        )
+        .bind(provider_id)
+        .bind(upstream_sub)
+        .fetch_optional(&data.db)
+        .await?;
+        Ok(res)
+    }
+}
+
+// Saves the original downstream authorization request while the user is logging in upstream.
+// The `id` is sent upstream as the `state` parameter.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpstreamAuthState {
+    pub id: String,
+    pub provider_id: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub claims: Option<String>,
+    pub acr_values: Option<String>,
+    pub prompt: Option<String>,
+    pub max_age: Option<i64>,
+    pub upstream_verifier: Option<String>,
+}
+
+// CRUD
+impl UpstreamAuthState {
+    pub async fn delete(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_del(
+            CACHE_NAME_UPSTREAM_AUTH.to_string(),
+            self.id.clone(),
+            &data.caches.ha_cache_config,
+        )
+        .await
+        .map_err(ErrorResponse::from)
+    }
+
+    pub async fn find(
+        data: &web::Data<AppState>,
+        id: String,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        cache_get!(
+            UpstreamAuthState,
+            CACHE_NAME_UPSTREAM_AUTH.to_string(),
+            id,
+            &data.caches.ha_cache_config,
+            true
+        )
+        .await
+        .map_err(ErrorResponse::from)
+    }
+
+    pub async fn save(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_put(
+            CACHE_NAME_UPSTREAM_AUTH.to_string(),
+            self.id.clone(),
+            &data.caches.ha_cache_config,
+            self,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+impl UpstreamAuthState {
+    pub fn new(req: UpstreamAuthRequest, use_pkce: bool) -> Self {
+        Self {
+            id: get_rand(48),
+            provider_id: req.provider_id,
+            client_id: req.client_id,
+            redirect_uri: req.redirect_uri,
+            scope: req.scope,
+            state: req.state,
+            nonce: req.nonce,
+            code_challenge: req.code_challenge,
+            code_challenge_method: req.code_challenge_method,
+            claims: req.claims,
+            acr_values: req.acr_values,
+            prompt: req.prompt,
+            max_age: req.max_age,
+            upstream_verifier: if use_pkce { Some(get_rand(64)) } else { None },
+        }
+    }
+
+    /// Returns `true`, if the space-delimited `prompt` param contains the given value.
+    pub fn has_prompt(&self, value: &str) -> bool {
+        self.prompt
+            .as_deref()
+            .map(|prompt| prompt.split_whitespace().any(|p| p == value))
+            .unwrap_or(false)
+    }
+
+    /// Returns the location for the redirect back to the downstream client after a successful
+    /// login with the given authorization `code`.
+    pub fn code_redirect_uri(&self, code: &str) -> String {
+        match Url::parse(&self.redirect_uri) {
+            Ok(mut url) => {
+                url.query_pairs_mut().append_pair("code", code);
+                if let Some(state) = &self.state {
+                    url.query_pairs_mut().append_pair("state", state);
+                }
+                url.to_string()
+            }
+            // cannot happen for an already validated `redirect_uri`
+            Err(_) => format!("{}?code={}", self.redirect_uri, code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::thread::JoinHandle;
+
+    use actix_web::{App, HttpResponse, HttpServer};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn provider() -> UpstreamProvider {
+        UpstreamProvider {
+            id: "upstream123".to_string(),
+            name: "Upstream".to_string(),
+            enabled: true,
+            issuer: "http://127.0.0.1:10081".to_string(),
+            authorization_endpoint: "http://127.0.0.1:10081/authorize".to_string(),
+            token_endpoint: "http://127.0.0.1:10081/token".to_string(),
+            userinfo_endpoint: "http://127.0.0.1:10081/userinfo".to_string(),
+            client_id: "rauthy".to_string(),
+            secret: None,
+            secret_kid: None,
+            scope: "openid email profile".to_string(),
+            use_pkce: true,
+            claim_sub: "sub".to_string(),
+            claim_email: "mail".to_string(),
+            claim_email_verified: Some("email_verified".to_string()),
+            claim_given_name: Some("given_name".to_string()),
+            claim_family_name: None,
+            auto_link: false,
+            auto_provision: true,
+        }
+    }
+
+    #[test]
+    fn test_authorization_url() {
+        let url = provider()
+            .authorization_url(
+                "https://localhost:8443/auth/v1/oidc/authorize/upstream/callback",
+                "state123",
+                Some("challenge123"),
+                false,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            url,
+            "http://127.0.0.1:10081/authorize?client_id=rauthy&redirect_uri=https%3A%2F%2Flocalhost%3A8443%2Fauth%2Fv1%2Foidc%2Fauthorize%2Fupstream%2Fcallback&response_type=code&scope=openid+email+profile&state=state123&code_challenge=challenge123&code_challenge_method=S256"
+        );
+
+        let url = provider()
+            .authorization_url(
+                "https://localhost:8443/auth/v1/oidc/authorize/upstream/callback",
+                "state123",
+                None,
+                true,
+                Some(300),
+            )
+            .unwrap();
+        assert!(url.ends_with("&state=state123&prompt=login&max_age=300"));
+    }
+
+    #[test]
+    fn test_map_claims() {
+        let provider = provider();
+
+        let value = serde_json::json!({
+            "sub": 1337,
+            "mail": "Upstream@Localhost.de",
+            "email_verified": "true",
+            "given_name": "Up",
+            "family_name": "Stream",
+        });
+        let claims = provider.map_claims(&value).unwrap();
+        assert_eq!(
+            claims,
+            UpstreamClaims {
+                sub: "1337".to_string(),
+                email: "upstream@localhost.de".to_string(),
+                email_verified: true,
+                given_name: Some("Up".to_string()),
+                // no mapping configured
+                family_name: None,
+            }
+        );
+
+        // the email is mandatory
+        let value = serde_json::json!({ "sub": "1337", "email": "upstream@localhost.de" });
+        assert!(provider.map_claims(&value).is_err());
+    }
+
+    #[test]
+    fn test_code_redirect_uri() {
+        let mut state = UpstreamAuthState::new(
+            UpstreamAuthRequest {
+                provider_id: "provider".to_string(),
+                client_id: "client".to_string(),
+                redirect_uri: "https://app.localhost/callback?tenant=1".to_string(),
+                scope: "openid".to_string(),
+                state: Some("a b&c=d".to_string()),
+                nonce: None,
+                code_challenge: None,
+                code_challenge_method: None,
+                claims: None,
+                acr_values: None,
+                prompt: Some("login consent".to_string()),
+                max_age: None,
+            },
+            false,
+        );
+        assert!(state.has_prompt("consent"));
+        assert!(!state.has_prompt("none"));
+        assert_eq!(
+            state.code_redirect_uri("abc"),
+            "https://app.localhost/callback?tenant=1&code=abc&state=a+b%26c%3Dd"
+        );
+
+        state.state = None;
+        state.redirect_uri = "https://app.localhost/callback".to_string();
+        assert_eq!(
+            state.code_redirect_uri("abc"),
+            "https://app.localhost/callback?code=abc"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_claims() {
+        let handle = serve_upstream_provider();
+
+        // make sure the http server starts and keeps running
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!handle.is_finished());
+
+        let provider = provider();
+        let claims = provider
+            .fetch_claims(
+                Some("secret123".to_string()),
+                "code123",
+                "https://localhost:8443/auth/v1/oidc/authorize/upstream/callback",
+                Some("verifier123"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(claims.sub.as_str(), "upstream-sub-123");
+        assert_eq!(claims.email.as_str(), "upstream@localhost.de");
+        assert!(claims.email_verified);
+
+        // a wrong code must be rejected by the upstream token endpoint
+        let res = provider
+            .fetch_claims(
+                Some("secret123".to_string()),
+                "invalid",
+                "https://localhost:8443/auth/v1/oidc/authorize/upstream/callback",
+                Some("verifier123"),
+            )
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[derive(Deserialize)]
+    struct MockTokenRequest {
+        grant_type: String,
+        code: String,
+        client_id: String,
+        client_secret: Option<String>,
+        code_verifier: Option<String>,
+    }
+
+    fn serve_upstream_provider() -> JoinHandle<()> {
+        thread::spawn(move || {
+            let actix_system = actix_web::rt::System::new();
+            actix_system.block_on(async {
+                HttpServer::new(|| {
+                    App::new()
+                        .route(
+                            "/token",
+                            web::post().to(|req: web::Form<MockTokenRequest>| async move {
+                                if req.grant_type != "authorization_code"
+                                    || req.code != "code123"
+                                    || req.client_id != "rauthy"
+                                    || req.client_secret.as_deref() != Some("secret123")
+                                    || req.code_verifier.as_deref() != Some("verifier123")
+                                {
+                                    return HttpResponse::BadRequest()
+                                        .content_type(APPLICATION_JSON)
+                                        .body(r#"{"error": "invalid_grant"}"#);
+                                }
+                                HttpResponse::Ok().content_type(APPLICATION_JSON).body(
+                                    r#"{
+                                      "access_token": "upstream-access-token",
+                                      "token_type": "Bearer",
+                                      "expires_in": 60
+                                    }"#,
+                                )
+                            }),
+                        )
+                        .route(
+                            "/userinfo",
+                            web::get().to(|req: actix_web::HttpRequest| async move {
+                                let auth = req
+                                    .headers()
+                                    .get(AUTHORIZATION)
+                                    .and_then(|h| h.to_str().ok());
+                                if auth != Some("Bearer upstream-access-token") {
+                                    return HttpResponse::Unauthorized().finish();
+                                }
+                                HttpResponse::Ok().content_type(APPLICATION_JSON).body(
+                                    r#"{
+                                      "sub": "upstream-sub-123",
+                                      "mail": "upstream@localhost.de",
+                                      "email_verified": true,
+                                      "given_name": "Up"
+                                    }"#,
+                                )
+                            }),
+                        )
+                })
+                .bind(("127.0.0.1", 10081))
+                .expect("port 10081 to be free for testing")
+                .run()
+                .await
+                .expect("upstream provider test http server to start")
+            })
+        })
+    }
+}
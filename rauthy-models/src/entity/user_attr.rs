@@ -1,4 +1,5 @@
 use crate::app_state::{AppState, DbTxn};
+use crate::entity::provisioning::ProvisioningDelivery;
 use crate::entity::scopes::Scope;
 use crate::entity::users::User;
 use crate::request::{UserAttrConfigRequest, UserAttrValuesUpdateRequest};
@@ -9,7 +10,7 @@ use redhac::{cache_get, cache_get_from, cache_get_value, cache_insert, cache_rem
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::FromRow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use utoipa::ToSchema;
 
 // Additional custom attributes for users. These can be set for every user and then mapped to a
@@ -443,14 +444,33 @@ impl UserAttrValueEntity {
         Ok(res)
     }
 
+    /// Returns the values of all users grouped by the user id, which avoids a query per user
+    /// when all users are needed anyway.
+    pub async fn find_all_by_user(
+        data: &web::Data<AppState>,
+    ) -> Result<HashMap<String, Vec<Self>>, ErrorResponse> {
+        let values = sqlx::query_as!(Self, "select * from user_attr_values")
+            .fetch_all(&data.db)
+            .await?;
+
+        let mut res: HashMap<String, Vec<Self>> = HashMap::new();
+        for value in values {
+            res.entry(value.user_id.clone()).or_default().push(value);
+        }
+        Ok(res)
+    }
+
     pub async fn update_for_user(
         data: &web::Data<AppState>,
         user_id: &str,
         req_data: UserAttrValuesUpdateRequest,
     ) -> Result<Vec<Self>, ErrorResponse> {
-        // Not necessary for the operation and correctness, but look up the user first and return
-        // an error, if it does not exist at all, for a better user experience.
-        User::exists(data, user_id.to_string()).await?;
+        // The user is needed for the provisioning anyway. Looking it up first returns an error,
+        // if it does not exist at all, for a better user experience.
+        let user = User::find(data, user_id.to_string()).await?;
+
+        // the values and the provisioning of the update must never get out of sync
+        let mut txn = data.db.begin().await?;
 
         for value in req_data.values {
             let del = if let Some(s) = value.value.as_str() {
@@ -465,7 +485,7 @@ impl UserAttrValueEntity {
                     user_id,
                     value.key,
                 )
-                .execute(&data.db)
+                .execute(&mut *txn)
                 .await?;
             } else {
                 let v = serde_json::to_vec(&value.value).unwrap();
@@ -489,7 +509,7 @@ impl UserAttrValueEntity {
                     v,
                 );
 
-                q.execute(&data.db).await?;
+                q.execute(&mut *txn).await?;
             }
         }
 
@@ -499,9 +519,12 @@ impl UserAttrValueEntity {
             "select * from user_attr_values where user_id = $1",
             user_id
         )
-        .fetch_all(&data.db)
+        .fetch_all(&mut *txn)
         .await?;
 
+        ProvisioningDelivery::enqueue_user_attrs(data, &user, res.clone(), &mut txn).await?;
+        txn.commit().await?;
+
         let idx = Self::cache_idx(user_id);
         cache_insert(
             CACHE_NAME_12HR.to_string(),
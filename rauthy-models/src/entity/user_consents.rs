@@ -0,0 +1,285 @@
+use crate::app_state::AppState;
+use crate::entity::refresh_tokens::RefreshToken;
+use actix_web::web;
+use rauthy_common::constants::CACHE_NAME_CONSENT;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::get_rand;
+use redhac::{cache_get, cache_get_from, cache_get_value, cache_insert, cache_remove, AckLevel};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+/// The scopes a user has granted to a client, which requires a consent. As long as a consent
+/// covers all requested scopes, the consent screen will be skipped on following logins.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct UserConsent {
+    pub user_id: String,
+    pub client_id: String,
+    // CSV of the granted scopes
+    pub scopes: String,
+    pub created_at: i64,
+    pub last_update: i64,
+}
+
+// CRUD
+impl UserConsent {
+    /// Adds the given scopes to an already existing consent or creates a new one.
+    pub async fn grant(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        client_id: &str,
+        scopes: &[String],
+    ) -> Result<Self, ErrorResponse> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let consent = match Self::find(data, user_id, client_id).await? {
+            Some(mut consent) => {
+                let mut granted = consent.get_scopes();
+                for scope in scopes {
+                    if !granted.contains(scope) {
+                        granted.push(scope.clone());
+                    }
+                }
+                consent.scopes = granted.join(",");
+                consent.last_update = now;
+                consent
+            }
+            None => Self {
+                user_id: user_id.to_string(),
+                client_id: client_id.to_string(),
+                scopes: scopes.join(","),
+                created_at: now,
+                last_update: now,
+            },
+        };
+
+        #[cfg(feature = "sqlite")]
+        let q = sqlx::query!(
+            r#"insert or replace into user_consents
+            (user_id, client_id, scopes, created_at, last_update)
+            values ($1, $2, $3, $4, $5)"#,
+            consent.user_id,
+            consent.client_id,
+            consent.scopes,
+            consent.created_at,
+            consent.last_update,
+        );
+        #[cfg(not(feature = "sqlite"))]
+        let q = sqlx::query!(
+            r#"insert into user_consents (user_id, client_id, scopes, created_at, last_update)
+            values ($1, $2, $3, $4, $5)
+            on conflict(user_id, client_id) do update set scopes = $3, last_update = $5"#,
+            consent.user_id,
+            consent.client_id,
+            consent.scopes,
+            consent.created_at,
+            consent.last_update,
+        );
+
+        q.execute(&data.db).await?;
+
+        Ok(consent)
+    }
+
+    pub async fn find(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        client_id: &str,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>(
+            "select * from user_consents where user_id = $1 and client_id = $2",
+        )
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_optional(&data.db)
+        .await?;
+        Ok(res)
+    }
+
+    pub async fn find_for_user(
+        data: &web::Data<AppState>,
+        user_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let res = sqlx::query_as::<_, Self>("select * from user_consents where user_id = $1")
+            .bind(user_id)
+            .fetch_all(&data.db)
+            .await?;
+        Ok(res)
+    }
+
+    /// Revokes the consent and deletes all refresh tokens, which have been issued with it.
+    pub async fn revoke(
+        data: &web::Data<AppState>,
+        user_id: &str,
+        client_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        let res = sqlx::query!(
+            "delete from user_consents where user_id = $1 and client_id = $2",
+            user_id,
+            client_id,
+        )
+        .execute(&data.db)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Consent does not exist".to_string(),
+            ));
+        }
+
+        RefreshToken::delete_for_user_client(data, user_id, client_id).await
+    }
+}
+
+impl UserConsent {
+    /// Returns `true` if all the given scopes have already been granted.
+    pub fn covers(&self, scopes: &[String]) -> bool {
+        let granted = self.get_scopes();
+        scopes.iter().all(|s| granted.contains(s))
+    }
+
+    pub fn get_scopes(&self) -> Vec<String> {
+        self.scopes
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// A pending consent during the authorization code flow. The redirect with the already issued
+/// code is held back until the user granted the requested scopes on the consent page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsentReq {
+    pub id: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub client_id: String,
+    pub code: String,
+    pub scopes: Vec<String>,
+    // the redirect with the code after the consent has been given
+    pub header_loc: String,
+    // the redirect with `error=access_denied` if the user denies the request
+    pub header_loc_denied: String,
+}
+
+// CRUD
+impl ConsentReq {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        user_id: String,
+        session_id: String,
+        client_id: String,
+        code: String,
+        scopes: Vec<String>,
+        header_loc: String,
+        header_loc_denied: String,
+    ) -> Self {
+        Self {
+            id: get_rand(48),
+            user_id,
+            session_id,
+            client_id,
+            code,
+            scopes,
+            header_loc,
+            header_loc_denied,
+        }
+    }
+
+    pub async fn delete(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_remove(
+            CACHE_NAME_CONSENT.to_string(),
+            self.id.clone(),
+            &data.caches.ha_cache_config,
+            AckLevel::Quorum,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn find(data: &web::Data<AppState>, id: String) -> Result<Self, ErrorResponse> {
+        let res = cache_get!(
+            ConsentReq,
+            CACHE_NAME_CONSENT.to_string(),
+            id,
+            &data.caches.ha_cache_config,
+            false
+        )
+        .await?;
+
+        match res {
+            None => Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Consent Request not found".to_string(),
+            )),
+            Some(res) => Ok(res),
+        }
+    }
+
+    pub async fn save(&self, data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+        cache_insert(
+            CACHE_NAME_CONSENT.to_string(),
+            self.id.clone(),
+            &data.caches.ha_cache_config,
+            &self,
+            AckLevel::Quorum,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The internal consent page, where the user will be redirected to instead of the client.
+    pub fn consent_uri(&self) -> String {
+        format!("/auth/v1/oidc/consent?id={}", self.id)
+    }
+
+    /// The redirect back to the client with `error=access_denied`, if the user denies the request.
+    pub fn denied_uri(redirect_uri: &str, state: Option<&str>) -> String {
+        match Url::parse(redirect_uri) {
+            Ok(mut url) => {
+                url.query_pairs_mut().append_pair("error", "access_denied");
+                if let Some(state) = state {
+                    url.query_pairs_mut().append_pair("state", state);
+                }
+                url.to_string()
+            }
+            // cannot happen for an already validated `redirect_uri`
+            Err(_) => format!("{}?error=access_denied", redirect_uri),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consent_covers() {
+        let consent = UserConsent {
+            user_id: "user".to_string(),
+            client_id: "client".to_string(),
+            scopes: "openid,email".to_string(),
+            created_at: 0,
+            last_update: 0,
+        };
+
+        assert!(consent.covers(&["openid".to_string()]));
+        assert!(consent.covers(&["openid".to_string(), "email".to_string()]));
+        assert!(!consent.covers(&["openid".to_string(), "profile".to_string()]));
+        assert!(consent.covers(&[]));
+    }
+
+    #[test]
+    fn test_denied_uri() {
+        assert_eq!(
+            ConsentReq::denied_uri("https://app.example.com/callback", None),
+            "https://app.example.com/callback?error=access_denied"
+        );
+        assert_eq!(
+            ConsentReq::denied_uri("https://app.example.com/callback?tenant=1", Some("a b&c=d")),
+            "https://app.example.com/callback?tenant=1&error=access_denied&state=a+b%26c%3Dd"
+        );
+    }
+}
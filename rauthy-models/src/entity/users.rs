@@ -6,6 +6,7 @@ use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
 use crate::entity::password::PasswordPolicy;
 use crate::entity::password::RecentPasswordsEntity;
 use crate::entity::pow::Pow;
+use crate::entity::provisioning::{ProvisioningAction, ProvisioningDelivery};
 use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::roles::Role;
 use crate::entity::sessions::Session;
@@ -16,6 +17,7 @@ use crate::real_ip_from_req;
 use crate::request::{
     NewUserRegistrationRequest, NewUserRequest, UpdateUserRequest, UpdateUserSelfRequest,
 };
+use crate::scim::filter::SqlFilter;
 use crate::templates::UserEmailChangeConfirmHtml;
 use actix_web::{web, HttpRequest};
 use argon2::PasswordHash;
@@ -61,6 +63,7 @@ pub struct User {
     pub language: Language,
     pub webauthn_user_id: Option<String>,
     pub user_expires: Option<i64>,
+    pub totp_enabled: bool,
 }
 
 // CRUD
@@ -108,6 +111,9 @@ impl User {
         )
         .await?;
 
+        ProvisioningDelivery::enqueue_user(data, &new_user, ProvisioningAction::Created, None)
+            .await?;
+
         Ok(new_user)
     }
 
@@ -181,6 +187,8 @@ impl User {
         Session::invalidate_for_user(data, &self.id).await?;
         RefreshToken::invalidate_for_user(data, &self.id).await?;
 
+        ProvisioningDelivery::enqueue_user(data, self, ProvisioningAction::Deleted, None).await?;
+
         Ok(())
     }
 
@@ -301,6 +309,20 @@ impl User {
         Ok(res)
     }
 
+    /// Returns all users matching the condition, which has been translated from a SCIM filter.
+    pub async fn find_by_scim_filter(
+        data: &web::Data<AppState>,
+        filter: &SqlFilter,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let sql = format!("select * from users where {}", filter.clause);
+        let mut query = sqlx::query_as::<_, Self>(&sql);
+        for param in &filter.params {
+            query = query.bind(param);
+        }
+        let res = query.fetch_all(&data.db).await?;
+        Ok(res)
+    }
+
     pub async fn find_expired(data: &web::Data<AppState>) -> Result<Vec<Self>, ErrorResponse> {
         let now = OffsetDateTime::now_utc()
             .add(time::Duration::seconds(10))
@@ -331,8 +353,8 @@ impl User {
             email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6,
             enabled = $7, email_verified = $8, password_expires = $9, last_login = $10,
             last_failed_login = $11, failed_login_attempts = $12, language = $13,
-            webauthn_user_id = $14, user_expires = $15
-            where id = $16"#,
+            webauthn_user_id = $14, user_expires = $15, totp_enabled = $16
+            where id = $17"#,
         )
         .bind(&self.email)
         .bind(&self.given_name)
@@ -349,14 +371,36 @@ impl User {
         .bind(lang)
         .bind(self.webauthn_user_id.clone())
         .bind(self.user_expires)
+        .bind(self.totp_enabled)
         .bind(&self.id);
 
-        if let Some(txn) = txn {
+        // The state before the update decides, if the change must be provisioned downstream.
+        // It must be fetched before the query, because it could be read from the DB.
+        let mut before = None;
+        let users = User::find_all(data)
+            .await?
+            .into_iter()
+            .map(|u| {
+                if u.id == self.id {
+                    before = Some(u);
+                    self.clone()
+                } else {
+                    u
+                }
+            })
+            .collect::<Vec<Self>>();
+
+        let mut txn = txn;
+        if let Some(txn) = txn.as_deref_mut() {
             q.execute(&mut **txn).await?;
         } else {
             q.execute(&data.db).await?;
         }
 
+        if let Some(before) = before.as_ref() {
+            ProvisioningDelivery::enqueue_user_update(data, before, self, txn).await?;
+        }
+
         // invalidate all possibly existing sessions and refresh tokens, if the user has been disabled
         if !self.enabled {
             Session::invalidate_for_user(data, &self.id).await?;
@@ -364,17 +408,6 @@ impl User {
         }
 
         // TODO think about a good way to catch a possibly failing transaction -> cache invalidation
-        let users = User::find_all(data)
-            .await?
-            .into_iter()
-            .map(|mut u| {
-                if u.id == self.id {
-                    u = self.clone();
-                }
-                u
-            })
-            .collect::<Vec<Self>>();
-
         if let Some(email) = old_email {
             let idx = format!("{}_{}", IDX_USERS, email);
             cache_del(
@@ -974,6 +1007,12 @@ impl User {
         self.webauthn_user_id.is_some()
     }
 
+    /// Returns `true`, if the user has any second factor, either a passkey or TOTP.
+    #[inline]
+    pub fn has_mfa_enabled(&self) -> bool {
+        self.has_webauthn_enabled() || self.totp_enabled
+    }
+
     pub fn is_argon2_uptodate(&self, params: &Argon2Params) -> Result<bool, ErrorResponse> {
         if self.password.is_none() {
             error!(
@@ -1155,6 +1194,7 @@ impl Default for User {
             language: Language::En,
             webauthn_user_id: None,
             user_expires: None,
+            totp_enabled: false,
         }
     }
 }
@@ -1190,6 +1230,7 @@ mod tests {
                     .sub(::time::Duration::seconds(2))
                     .unix_timestamp(),
             ),
+            totp_enabled: false,
         };
         let session = Session::try_new(&user, 1, None);
         assert!(session.is_err());
@@ -1246,6 +1287,7 @@ mod tests {
             language: Language::En,
             webauthn_user_id: None,
             user_expires: None,
+            totp_enabled: false,
         };
 
         // enabled
@@ -536,6 +536,8 @@ impl WebauthnAdditionalData {
 pub struct WebauthnLoginReq {
     pub code: String,
     pub user_id: String,
+    // the already issued authorization code, which is held back until the MFA step is done
+    pub auth_code_id: String,
     pub header_loc: String,
     pub header_origin: Option<String>,
 }
@@ -1,7 +1,12 @@
 use crate::app_state::AppState;
+use crate::entity::jwe::{JweAlg, JweEnc};
 use crate::entity::scopes::Scope;
+use crate::JwtAmrValue;
 use actix_web::web;
-use rauthy_common::constants::CACHE_NAME_12HR;
+use rauthy_common::constants::{
+    CACHE_NAME_12HR, ENABLE_DYN_CLIENT_REG, GRANT_TYPE_DEVICE_CODE, GRANT_TYPE_TOKEN_EXCHANGE,
+    PAIRWISE_SUB_SECRET, TLS_CLIENT_AUTH,
+};
 use rauthy_common::error_response::ErrorResponse;
 use redhac::{cache_get, cache_get_from, cache_get_value, cache_put};
 use serde::{Deserialize, Serialize};
@@ -13,21 +18,43 @@ pub struct WellKnown {
     pub issuer: String,
     pub authorization_endpoint: String,
     pub token_endpoint: String,
+    pub device_authorization_endpoint: String,
     pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub pushed_authorization_request_endpoint: String,
     pub userinfo_endpoint: String,
     pub end_session_endpoint: String,
     pub jwks_uri: String,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // pub registration_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_endpoint: Option<String>,
     // pub check_session_iframe: String,
     pub grant_types_supported: Vec<String>,
     pub response_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
     pub id_token_signing_alg_values_supported: Vec<String>,
+    pub id_token_encryption_alg_values_supported: Vec<String>,
+    pub id_token_encryption_enc_values_supported: Vec<String>,
+    pub userinfo_signing_alg_values_supported: Vec<String>,
+    pub userinfo_encryption_alg_values_supported: Vec<String>,
+    pub userinfo_encryption_enc_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
     pub token_endpoint_auth_signing_alg_values_supported: Vec<String>,
     pub claims_supported: Vec<String>,
+    pub claims_parameter_supported: bool,
+    pub acr_values_supported: Vec<String>,
     pub scopes_supported: Vec<String>,
     pub code_challenge_methods_supported: Vec<String>,
     pub dpop_signing_alg_values_supported: Vec<String>,
+    pub request_object_signing_alg_values_supported: Vec<String>,
+    pub request_parameter_supported: bool,
+    pub request_uri_parameter_supported: bool,
+    pub require_request_uri_registration: bool,
+    pub backchannel_logout_supported: bool,
+    pub backchannel_logout_session_supported: bool,
+    pub frontchannel_logout_supported: bool,
+    pub frontchannel_logout_session_supported: bool,
+    pub require_pushed_authorization_requests: bool,
+    pub tls_client_certificate_bound_access_tokens: bool,
 }
 
 const IDX: &str = ".well-known";
@@ -92,33 +119,74 @@ impl WellKnown {
     pub fn new(issuer: &str, scopes_supported: Vec<String>) -> Self {
         let authorization_endpoint = format!("{}/oidc/authorize", issuer);
         let token_endpoint = format!("{}/oidc/token", issuer);
-        let introspection_endpoint = format!("{}/oidc/tokenInfo", issuer);
+        let device_authorization_endpoint = format!("{}/oidc/device", issuer);
+        let introspection_endpoint = format!("{}/oidc/introspect", issuer);
+        let revocation_endpoint = format!("{}/oidc/revoke", issuer);
+        let pushed_authorization_request_endpoint = format!("{}/oidc/par", issuer);
         let userinfo_endpoint = format!("{}/oidc/userinfo", issuer);
         let end_session_endpoint = format!("{}/oidc/userinfo", issuer);
         let jwks_uri = format!("{}/oidc/certs", issuer);
+        let registration_endpoint = if *ENABLE_DYN_CLIENT_REG {
+            Some(format!("{}/oidc/register", issuer))
+        } else {
+            None
+        };
         let grant_types_supported = vec![
             "authorization_code".to_string(),
             "client_credentials".to_string(),
             "password".to_string(),
             "refresh_token".to_string(),
+            GRANT_TYPE_TOKEN_EXCHANGE.to_string(),
+            GRANT_TYPE_DEVICE_CODE.to_string(),
         ];
         let response_types_supported = vec!["code".to_string()];
+        let mut subject_types_supported = vec!["public".to_string()];
+        if PAIRWISE_SUB_SECRET.is_some() {
+            subject_types_supported.push("pairwise".to_string());
+        }
         let id_token_signing_alg_values_supported = vec![
             "RS256".to_string(),
             "RS384".to_string(),
             "RS512".to_string(),
+            "PS256".to_string(),
+            "ES256".to_string(),
+            "ES384".to_string(),
             "EdDSA".to_string(),
         ];
+        let userinfo_signing_alg_values_supported = id_token_signing_alg_values_supported.clone();
+        let encryption_alg_values_supported = vec![
+            JweAlg::RsaOaep256.as_str().to_string(),
+            JweAlg::EcdhEs.as_str().to_string(),
+        ];
+        let encryption_enc_values_supported = vec![JweEnc::A256GCM.as_str().to_string()];
+        let mut token_endpoint_auth_methods_supported = vec![
+            "client_secret_basic".to_string(),
+            "client_secret_post".to_string(),
+            "client_secret_jwt".to_string(),
+            "private_key_jwt".to_string(),
+        ];
+        if *TLS_CLIENT_AUTH {
+            token_endpoint_auth_methods_supported.push("tls_client_auth".to_string());
+            token_endpoint_auth_methods_supported.push("self_signed_tls_client_auth".to_string());
+        }
         let token_endpoint_auth_signing_alg_values_supported = vec![
             "RS256".to_string(),
             "RS384".to_string(),
             "RS512".to_string(),
+            "PS256".to_string(),
+            "ES256".to_string(),
+            "ES384".to_string(),
             "EdDSA".to_string(),
+            "HS256".to_string(),
+            "HS384".to_string(),
+            "HS512".to_string(),
         ];
         let claims_supported = vec![
             "iss".to_string(),
             "azp".to_string(),
             "amr".to_string(),
+            "acr".to_string(),
+            "auth_time".to_string(),
             "sub".to_string(),
             "preferred_username".to_string(),
             "email".to_string(),
@@ -128,6 +196,7 @@ impl WellKnown {
             "roles".to_string(),
             "groups".to_string(),
             "custom".to_string(),
+            "sid".to_string(),
         ];
         // TODO to not confuse users when static clients will not be able to use the scope,
         // `webid` should be added manually in the UI to make it fully work for ephemeral as
@@ -135,11 +204,24 @@ impl WellKnown {
         // if *ENABLE_WEB_ID {
         //     claims_supported.push("webid".to_string());
         // }
+        let acr_values_supported = vec![JwtAmrValue::Pwd.to_string(), JwtAmrValue::Mfa.to_string()];
         let code_challenge_methods_supported = vec!["plain".to_string(), "S256".to_string()];
         let dpop_signing_alg_values_supported = vec![
             "RS256".to_string(),
             "RS384".to_string(),
             "RS512".to_string(),
+            "PS256".to_string(),
+            "ES256".to_string(),
+            "ES384".to_string(),
+            "EdDSA".to_string(),
+        ];
+        let request_object_signing_alg_values_supported = vec![
+            "RS256".to_string(),
+            "RS384".to_string(),
+            "RS512".to_string(),
+            "PS256".to_string(),
+            "ES256".to_string(),
+            "ES384".to_string(),
             "EdDSA".to_string(),
         ];
 
@@ -147,18 +229,42 @@ impl WellKnown {
             issuer: String::from(issuer),
             authorization_endpoint,
             token_endpoint,
+            device_authorization_endpoint,
             introspection_endpoint,
+            revocation_endpoint,
+            pushed_authorization_request_endpoint,
             userinfo_endpoint,
             end_session_endpoint,
             jwks_uri,
+            registration_endpoint,
             grant_types_supported,
             response_types_supported,
+            subject_types_supported,
             id_token_signing_alg_values_supported,
+            id_token_encryption_alg_values_supported: encryption_alg_values_supported.clone(),
+            id_token_encryption_enc_values_supported: encryption_enc_values_supported.clone(),
+            userinfo_signing_alg_values_supported,
+            userinfo_encryption_alg_values_supported: encryption_alg_values_supported,
+            userinfo_encryption_enc_values_supported: encryption_enc_values_supported,
+            token_endpoint_auth_methods_supported,
             token_endpoint_auth_signing_alg_values_supported,
             claims_supported,
+            claims_parameter_supported: true,
+            acr_values_supported,
             scopes_supported,
             code_challenge_methods_supported,
             dpop_signing_alg_values_supported,
+            request_object_signing_alg_values_supported,
+            request_parameter_supported: true,
+            request_uri_parameter_supported: true,
+            require_request_uri_registration: true,
+            backchannel_logout_supported: true,
+            backchannel_logout_session_supported: true,
+            frontchannel_logout_supported: true,
+            frontchannel_logout_session_supported: true,
+            // can only be enforced per client
+            require_pushed_authorization_requests: false,
+            tls_client_certificate_bound_access_tokens: *TLS_CLIENT_AUTH,
         }
     }
 }
@@ -4,8 +4,9 @@ use crate::events::{
     EVENT_LEVEL_FAILED_LOGINS_20, EVENT_LEVEL_FAILED_LOGINS_25, EVENT_LEVEL_FAILED_LOGINS_7,
     EVENT_LEVEL_IP_BLACKLISTED, EVENT_LEVEL_JWKS_ROTATE, EVENT_LEVEL_NEW_RAUTHY_ADMIN,
     EVENT_LEVEL_NEW_RAUTHY_VERSION, EVENT_LEVEL_NEW_USER, EVENT_LEVEL_RAUTHY_HEALTHY,
-    EVENT_LEVEL_RAUTHY_START, EVENT_LEVEL_RAUTHY_UNHEALTHY, EVENT_LEVEL_SECRETS_MIGRATED,
-    EVENT_LEVEL_USER_EMAIL_CHANGE, EVENT_LEVEL_USER_PASSWORD_RESET,
+    EVENT_LEVEL_RAUTHY_START, EVENT_LEVEL_RAUTHY_UNHEALTHY, EVENT_LEVEL_REFRESH_TOKEN_REUSE,
+    EVENT_LEVEL_SECRETS_MIGRATED, EVENT_LEVEL_USER_EMAIL_CHANGE, EVENT_LEVEL_USER_PASSWORD_RESET,
+    EVENT_LEVEL_USER_RECOVERY_CODE_USED,
 };
 use chrono::{DateTime, NaiveDateTime, Timelike, Utc};
 use rauthy_common::constants::EMAIL_SUB_PREFIX;
@@ -123,6 +124,7 @@ impl EventLevel {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
+    BackchannelLogoutFailed,
     InvalidLogins,
     IpBlacklisted,
     IpBlacklistRemoved,
@@ -134,15 +136,18 @@ pub enum EventType {
     RauthyStarted,
     RauthyHealthy,
     RauthyUnhealthy,
+    RefreshTokenReuse,
     SecretsMigrated,
     UserEmailChange,
     UserPasswordReset,
+    UserRecoveryCodeUsed,
     Test,
 }
 
 impl Display for EventType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            EventType::BackchannelLogoutFailed => write!(f, "Back-Channel Logout failed"),
             EventType::InvalidLogins => write!(f, "Invalid logins"),
             EventType::IpBlacklisted => write!(f, "IP blacklisted"),
             EventType::IpBlacklistRemoved => write!(f, "IP blacklist removed"),
@@ -154,9 +159,11 @@ impl Display for EventType {
             EventType::RauthyStarted => write!(f, "Rauthy has been restarted"),
             EventType::RauthyHealthy => write!(f, "Rauthy is healthy"),
             EventType::RauthyUnhealthy => write!(f, "Rauthy is unhealthy"),
+            EventType::RefreshTokenReuse => write!(f, "Refresh Token reuse detected"),
             EventType::SecretsMigrated => write!(f, "Secrets have been migrated"),
             EventType::UserEmailChange => write!(f, "User's E-Mail has been changed"),
             EventType::UserPasswordReset => write!(f, "User has reset its password"),
+            EventType::UserRecoveryCodeUsed => write!(f, "User has used a recovery code"),
             EventType::Test => write!(f, "TEST"),
         }
     }
@@ -165,6 +172,7 @@ impl Display for EventType {
 impl EventType {
     pub fn as_str(&self) -> &str {
         match self {
+            Self::BackchannelLogoutFailed => "BackchannelLogoutFailed",
             Self::InvalidLogins => "InvalidLogins",
             Self::IpBlacklisted => "IpBlacklisted",
             Self::IpBlacklistRemoved => "IpBlacklistRemoved",
@@ -176,9 +184,11 @@ impl EventType {
             Self::RauthyStarted => "RauthyRestarted",
             Self::RauthyHealthy => "RauthyHealthy",
             Self::RauthyUnhealthy => "RauthyUnhealthy",
+            Self::RefreshTokenReuse => "RefreshTokenReuse",
             Self::SecretsMigrated => "SecretsMigrated",
             Self::UserEmailChange => "UserEmailChange",
             Self::UserPasswordReset => "UserPasswordReset",
+            Self::UserRecoveryCodeUsed => "UserRecoveryCodeUsed",
             Self::Test => "TEST",
         }
     }
@@ -200,6 +210,9 @@ impl EventType {
             EventType::UserEmailChange => 12,
             EventType::UserPasswordReset => 13,
             EventType::Test => 14,
+            EventType::BackchannelLogoutFailed => 15,
+            EventType::UserRecoveryCodeUsed => 16,
+            EventType::RefreshTokenReuse => 17,
         }
     }
 }
@@ -207,6 +220,7 @@ impl EventType {
 impl From<String> for EventType {
     fn from(value: String) -> Self {
         match value.as_str() {
+            "BackchannelLogoutFailed" => Self::BackchannelLogoutFailed,
             "InvalidLogins" => Self::InvalidLogins,
             "IpBlacklisted" => Self::IpBlacklisted,
             "IpBlacklistRemoved" => Self::IpBlacklistRemoved,
@@ -218,9 +232,11 @@ impl From<String> for EventType {
             "RauthyRestarted" => Self::RauthyStarted,
             "RauthyHealthy" => Self::RauthyHealthy,
             "RauthyUnhealthy" => Self::RauthyUnhealthy,
+            "RefreshTokenReuse" => Self::RefreshTokenReuse,
             "SecretsMigrated" => Self::SecretsMigrated,
             "UserEmailChange" => Self::UserEmailChange,
             "UserPasswordReset" => Self::UserPasswordReset,
+            "UserRecoveryCodeUsed" => Self::UserRecoveryCodeUsed,
             "TEST" => Self::Test,
             // just return test to never panic
             _ => Self::Test,
@@ -252,6 +268,9 @@ impl From<i64> for EventType {
             12 => EventType::UserEmailChange,
             13 => EventType::UserPasswordReset,
             14 => EventType::Test,
+            15 => EventType::BackchannelLogoutFailed,
+            16 => EventType::UserRecoveryCodeUsed,
+            17 => EventType::RefreshTokenReuse,
             _ => EventType::Test,
         }
     }
@@ -282,6 +301,7 @@ impl From<&Event> for Notification {
         let row_1 = format!("{} {}", d.format("%Y/%m/%d %H:%M:%S"), value.typ);
 
         let row_2 = match value.typ {
+            EventType::BackchannelLogoutFailed => value.text.clone(),
             EventType::InvalidLogins => Some(format!(
                 "{} invalid logins from IP: `{}`",
                 value.data.unwrap_or_default(),
@@ -319,9 +339,11 @@ impl From<&Event> for Notification {
             EventType::RauthyStarted => value.text.clone(),
             EventType::RauthyHealthy => value.text.clone(),
             EventType::RauthyUnhealthy => value.text.clone(),
+            EventType::RefreshTokenReuse => value.text.clone(),
             EventType::SecretsMigrated => value.ip.clone(),
             EventType::UserEmailChange => value.text.clone(),
             EventType::UserPasswordReset => value.text.clone(),
+            EventType::UserRecoveryCodeUsed => value.text.clone(),
             EventType::Test => value.text.clone(),
         };
 
@@ -430,6 +452,16 @@ impl Event {
         )
     }
 
+    pub fn backchannel_logout_failed(client_id: String, err: String) -> Self {
+        Self::new(
+            EventLevel::Warning,
+            EventType::BackchannelLogoutFailed,
+            None,
+            None,
+            Some(format!("Client '{}': {}", client_id, err)),
+        )
+    }
+
     pub fn brute_force(ip: String) -> Self {
         Self::new(
             EventLevel::Critical,
@@ -586,8 +618,29 @@ impl Event {
         )
     }
 
+    pub fn user_recovery_code_used(text: String, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_RECOVERY_CODE_USED.get().cloned().unwrap(),
+            EventType::UserRecoveryCodeUsed,
+            ip,
+            None,
+            Some(text),
+        )
+    }
+
+    pub fn refresh_token_reuse(text: String, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_REFRESH_TOKEN_REUSE.get().cloned().unwrap(),
+            EventType::RefreshTokenReuse,
+            ip,
+            None,
+            Some(text),
+        )
+    }
+
     pub fn fmt_data(&self) -> String {
         match self.typ {
+            EventType::BackchannelLogoutFailed => self.text.clone().unwrap_or_default(),
             EventType::InvalidLogins => format!("Counter: {}", self.data.unwrap_or_default()),
             EventType::IpBlacklisted => {
                 let d =
@@ -612,6 +665,12 @@ impl Event {
             EventType::RauthyStarted => self.text.clone().unwrap(),
             EventType::RauthyHealthy => self.text.clone().unwrap(),
             EventType::RauthyUnhealthy => self.text.clone().unwrap(),
+            EventType::RefreshTokenReuse => {
+                format!(
+                    "Refresh Token reuse - {}",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
             EventType::SecretsMigrated => String::default(),
             EventType::UserEmailChange => {
                 format!("User E-Mail: {}", self.text.as_deref().unwrap_or_default())
@@ -622,6 +681,12 @@ impl Event {
                     self.text.as_deref().unwrap_or_default()
                 )
             }
+            EventType::UserRecoveryCodeUsed => {
+                format!(
+                    "User {} has used a recovery code",
+                    self.text.as_deref().unwrap_or_default()
+                )
+            }
             EventType::Test => {
                 format!("Test Message: {}", self.text.as_deref().unwrap_or_default())
             }
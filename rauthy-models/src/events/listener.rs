@@ -190,6 +190,7 @@ impl EventListener {
                     let evt = serde_json::from_str::<Event>(&event)
                         .expect("Event to deserialize correctly");
                     match evt.typ {
+                        EventType::BackchannelLogoutFailed => {}
                         EventType::InvalidLogins => {
                             tx_ip_blacklist
                                 .send_async(IpBlacklistReq::LoginFailedSet(IpLoginFailedSet {
@@ -225,9 +226,11 @@ impl EventListener {
                         EventType::RauthyStarted => {}
                         EventType::RauthyHealthy => {}
                         EventType::RauthyUnhealthy => {}
+                        EventType::RefreshTokenReuse => {}
                         EventType::SecretsMigrated => {}
                         EventType::UserEmailChange => {}
                         EventType::UserPasswordReset => {}
+                        EventType::UserRecoveryCodeUsed => {}
                         EventType::Test => {}
                     }
 
@@ -14,6 +14,7 @@ pub static EVENT_PERSIST_LEVEL: OnceLock<i16> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_USER: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_USER_EMAIL_CHANGE: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_USER_PASSWORD_RESET: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_RECOVERY_CODE_USED: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_RAUTHY_ADMIN: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_RAUTHY_VERSION: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_JWKS_ROTATE: OnceLock<EventLevel> = OnceLock::new();
@@ -21,6 +22,7 @@ pub static EVENT_LEVEL_SECRETS_MIGRATED: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_START: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_HEALTHY: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_UNHEALTHY: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_REFRESH_TOKEN_REUSE: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_IP_BLACKLISTED: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_25: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_20: OnceLock<EventLevel> = OnceLock::new();
@@ -49,6 +51,12 @@ pub fn init_event_vars() -> Result<(), ErrorResponse> {
             EventLevel::Notice,
         ))
         .unwrap();
+    EVENT_LEVEL_USER_RECOVERY_CODE_USED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_RECOVERY_CODE_USED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
     EVENT_LEVEL_NEW_RAUTHY_ADMIN
         .set(map_env_var_level(
             "EVENT_LEVEL_RAUTHY_ADMIN",
@@ -91,6 +99,12 @@ pub fn init_event_vars() -> Result<(), ErrorResponse> {
             EventLevel::Critical,
         ))
         .unwrap();
+    EVENT_LEVEL_REFRESH_TOKEN_REUSE
+        .set(map_env_var_level(
+            "EVENT_LEVEL_REFRESH_TOKEN_REUSE",
+            EventLevel::Warning,
+        ))
+        .unwrap();
     EVENT_LEVEL_IP_BLACKLISTED
         .set(map_env_var_level(
             "EVENT_LEVEL_IP_BLACKLISTED",
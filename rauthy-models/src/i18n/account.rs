@@ -13,6 +13,8 @@ pub struct I18nAccount<'a> {
     acc_type_passkey_text_3: &'a str,
     cancel: &'a str,
     change_password: &'a str,
+    consents_desc: &'a str,
+    consents_none: &'a str,
     convert_account: &'a str,
     convert_account_p_1: &'a str,
     created: &'a str,
@@ -28,14 +30,19 @@ pub struct I18nAccount<'a> {
     key: &'a str,
     key_unique: &'a str,
     last_login: &'a str,
+    last_used: &'a str,
     mfa: I18nAccountMfa<'a>,
     mfa_activated: &'a str,
     nav_info: &'a str,
     nav_edit: &'a str,
     nav_mfa: &'a str,
     nav_password: &'a str,
+    nav_consents: &'a str,
     nav_logout: &'a str,
+    nav_offline: &'a str,
     never: &'a str,
+    offline_desc: &'a str,
+    offline_none: &'a str,
     password_confirm: &'a str,
     password_curr: &'a str,
     password_curr_req: &'a str,
@@ -45,6 +52,7 @@ pub struct I18nAccount<'a> {
     password_expiry: &'a str,
     password_policy: I18nPasswordPolicy<'a>,
     password_policy_follow: &'a str,
+    revoke: &'a str,
     roles: &'a str,
     save: &'a str,
     user: &'a str,
@@ -85,6 +93,10 @@ least once."#,
             acc_type_passkey_text_3: "Do you want to convert your account and add a password?",
             cancel: "Cancel",
             change_password: "Change Password",
+            consents_desc: r#"These applications have access to your account with the listed
+scopes. If you revoke a consent, the application will lose its access and you will be asked
+again with the next login."#,
+            consents_none: "You have not granted any consents yet.",
             convert_account: "Convert Account",
             convert_account_p_1: r#"You can convert your account to a Passkey-Only account.
 This conversion deletes your password and you can and must only ever login with your registered
@@ -106,14 +118,20 @@ confirmed, your new address will be updated."#,
             key: "Key",
             key_unique: "Key must be unique",
             last_login: "Last Login",
+            last_used: "last used",
             mfa: I18nAccountMfa::build_en(),
             mfa_activated: "MFA activated",
             nav_info: "Info",
             nav_edit: "Edit",
             nav_mfa: "MFA",
             nav_password: "Password",
+            nav_consents: "Consents",
             nav_logout: "Logout",
+            nav_offline: "Offline",
             never: "Never",
+            offline_desc: r#"These applications have been granted offline access. They can
+access your account even while you are logged out, until you revoke their access."#,
+            offline_none: "No application has offline access to your account.",
             password_confirm: "Confirm Password",
             password_curr: "Current Password",
             password_curr_req: "Current password is required",
@@ -123,6 +141,7 @@ confirmed, your new address will be updated."#,
             password_expiry: "Password expiry",
             password_policy: I18nPasswordPolicy::build_en(),
             password_policy_follow: "You must follow the password policy",
+            revoke: "Revoke",
             roles: "Roles",
             save: "Save",
             user: "User",
@@ -153,6 +172,10 @@ einmalige zusätzliche Verifizierung des Passwortes nicht mehr möglich sein wir
                 "Soll dieser Account gewandelt und ein Passwort hinzugefügt werden?",
             cancel: "Abbrechen",
             change_password: "Passwort wechseln",
+            consents_desc: r#"Diese Anwendungen haben mit den aufgelisteten Scopes Zugriff auf
+Ihren Account. Wird eine Zustimmung widerrufen, verliert die Anwendung ihren Zugriff und
+beim nächsten Login wird erneut nachgefragt."#,
+            consents_none: "Es wurden noch keine Zustimmungen erteilt.",
             convert_account: "Account Umwandeln",
             convert_account_p_1: r#"Dieser Account kann in einen Passkey-Only Account umgewandelt
 werden. Diese Umwandling löscht das Passwort und erlaubt den alleinigen Login mit den registrieren
@@ -173,14 +196,20 @@ enthaltenen Link bestätigt werden. Nach der Bestätigung wird die neue Adresse
             key: "Schlüssel",
             key_unique: "Schlüssel muss einzigartig sein",
             last_login: "Letzter Login",
+            last_used: "zuletzt genutzt",
             mfa: I18nAccountMfa::build_de(),
             mfa_activated: "MFA aktiviert",
             nav_info: "Info",
             nav_edit: "Editieren",
             nav_mfa: "MFA",
             nav_password: "Passwort",
+            nav_consents: "Zustimmungen",
             nav_logout: "Logout",
+            nav_offline: "Offline",
             never: "Niemals",
+            offline_desc: r#"Diese Anwendungen haben Offline-Zugriff erhalten. Sie können auch
+dann auf Ihren Account zugreifen, wenn Sie abgemeldet sind, bis der Zugriff widerrufen wird."#,
+            offline_none: "Keine Anwendung hat Offline-Zugriff auf Ihren Account.",
             password_confirm: "Passwort bestätigen",
             password_curr: "Derzeitiges Passwort",
             password_curr_req: "Derzeitiges Passwort ist notwendig",
@@ -190,6 +219,7 @@ enthaltenen Link bestätigt werden. Nach der Bestätigung wird die neue Adresse
             password_expiry: "Passwort Ablauf",
             password_policy: I18nPasswordPolicy::build_de(),
             password_policy_follow: "Befolgen Sie die Passwort Regeln",
+            revoke: "Widerrufen",
             roles: "Rollen",
             save: "Speichern",
             user: "Benutzer",
@@ -225,9 +255,18 @@ pub struct I18nAccountMfa<'a> {
     registerd_keys: &'a str,
     passkey_name: &'a str,
     passkey_name_err: &'a str,
+    recovery_codes: &'a str,
+    recovery_codes_desc: &'a str,
+    recovery_codes_generate: &'a str,
+    recovery_codes_new: &'a str,
     test: &'a str,
     test_error: &'a str,
     test_success: &'a str,
+    totp: &'a str,
+    totp_active: &'a str,
+    totp_desc: &'a str,
+    totp_scan: &'a str,
+    totp_setup: &'a str,
 }
 
 impl SsrJson for I18nAccountMfa<'_> {
@@ -263,9 +302,22 @@ impl I18nAccountMfa<'_> {
             registerd_keys: "Registered Keys",
             passkey_name: "Passkey Name",
             passkey_name_err: "2 - 32 non-special characters",
+            recovery_codes: "Recovery Codes",
+            recovery_codes_desc: "If you lose access to your MFA devices, each recovery code can \
+            be used once instead. Generating new codes invalidates all existing ones.",
+            recovery_codes_generate: "Generate New Codes",
+            recovery_codes_new: "Store these recovery codes in a safe place. They will not be \
+            shown again.",
             test: "Test",
             test_error: "Error starting the Test",
             test_success: "Test successful",
+            totp: "Authenticator App (TOTP)",
+            totp_active: "An authenticator app is active as your second factor.",
+            totp_desc: "If you cannot use a passkey, you can use an authenticator app as your \
+            second factor instead.",
+            totp_scan: "Open the link or add the secret below to your authenticator app and \
+            confirm it with the first code.",
+            totp_setup: "Set Up",
         }
     }
 
@@ -288,9 +340,23 @@ impl I18nAccountMfa<'_> {
             registerd_keys: "Registrierte Keys",
             passkey_name: "Passkey Name",
             passkey_name_err: "2 - 32 Buchstaben, keine Sonderzeichen",
+            recovery_codes: "Wiederherstellungscodes",
+            recovery_codes_desc: "Sollten Sie den Zugang zu Ihren MFA Geräten verlieren, kann \
+            stattdessen jeder Wiederherstellungscode einmalig verwendet werden. Das Erzeugen neuer \
+            Codes macht alle bestehenden ungültig.",
+            recovery_codes_generate: "Neue Codes Erzeugen",
+            recovery_codes_new: "Bewahren Sie diese Wiederherstellungscodes an einem sicheren Ort \
+            auf. Sie werden nicht erneut angezeigt.",
             test: "Test",
             test_error: "Fehler beim Starten des Tests",
             test_success: "Test erfolgreich",
+            totp: "Authenticator App (TOTP)",
+            totp_active: "Eine Authenticator App ist als zweiter Faktor aktiv.",
+            totp_desc: "Sollten Sie keinen Passkey nutzen können, kann stattdessen eine \
+            Authenticator App als zweiter Faktor verwendet werden.",
+            totp_scan: "Öffnen Sie den Link oder fügen Sie das untenstehende Secret Ihrer \
+            Authenticator App hinzu und bestätigen Sie es mit dem ersten Code.",
+            totp_setup: "Einrichten",
         }
     }
 }
@@ -20,7 +20,10 @@ pub struct I18nAuthorize<'a> {
     password_request: &'a str,
     password_required: &'a str,
     provide_mfa: &'a str,
+    provide_recovery_code: &'a str,
+    provide_totp: &'a str,
     request_expires: &'a str,
+    use_recovery_code: &'a str,
 }
 
 impl SsrJson for I18nAuthorize<'_> {
@@ -55,7 +58,10 @@ To get access, you need to log in to your account and add at least one additiona
             password_request: "Request",
             password_required: "Password is required",
             provide_mfa: "Please login with your MFA device",
+            provide_recovery_code: "Please enter one of your recovery codes",
+            provide_totp: "Please enter the code from your authenticator app",
             request_expires: "Request expires",
+            use_recovery_code: "Lost your MFA device? Use a recovery code",
         }
     }
 
@@ -78,7 +84,10 @@ hinzufügen."#,
             password_request: "Anfordern",
             password_required: "Password ist notwendig",
             provide_mfa: "Bitte stellen Sie Ihr MFA Gerät zur Verfügung",
+            provide_recovery_code: "Bitte geben Sie einen Ihrer Wiederherstellungscodes ein",
+            provide_totp: "Bitte geben Sie den Code aus Ihrer Authenticator App ein",
             request_expires: "Anfrage läuft ab",
+            use_recovery_code: "MFA Gerät verloren? Wiederherstellungscode verwenden",
         }
     }
 }
@@ -0,0 +1,48 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct I18nConsent<'a> {
+    accept: &'a str,
+    deny: &'a str,
+    desc: &'a str,
+    expired: &'a str,
+    title: &'a str,
+}
+
+impl SsrJson for I18nConsent<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nConsent<'_> {
+    fn build_en() -> Self {
+        Self {
+            accept: "Accept",
+            deny: "Deny",
+            desc: "The application requests access to the following information:",
+            expired: "This request has expired. Please start the login again.",
+            title: "Consent",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            accept: "Akzeptieren",
+            deny: "Ablehnen",
+            desc: "Die Anwendung möchte auf die folgenden Informationen zugreifen:",
+            expired: "Diese Anfrage ist abgelaufen. Bitte starten Sie den Login erneut.",
+            title: "Zustimmung",
+        }
+    }
+}
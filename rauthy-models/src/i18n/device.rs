@@ -0,0 +1,59 @@
+use crate::i18n::SsrJson;
+use crate::language::Language;
+use serde::Serialize;
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct I18nDevice<'a> {
+    accept: &'a str,
+    accepted: &'a str,
+    deny: &'a str,
+    denied: &'a str,
+    desc: &'a str,
+    invalid_input: &'a str,
+    title: &'a str,
+    user_code: &'a str,
+}
+
+impl SsrJson for I18nDevice<'_> {
+    fn build(lang: &Language) -> Self {
+        match lang {
+            Language::En => Self::build_en(),
+            Language::De => Self::build_de(),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl I18nDevice<'_> {
+    fn build_en() -> Self {
+        Self {
+            accept: "Accept",
+            accepted: "The device has been authorized. You can close this window now.",
+            deny: "Deny",
+            denied: "The device has been denied. You can close this window now.",
+            desc: r#"Please enter the code shown on your device.
+Only accept the request, if you started the login on this device yourself."#,
+            invalid_input: "Invalid input",
+            title: "Device Authorization",
+            user_code: "User Code",
+        }
+    }
+
+    fn build_de() -> Self {
+        Self {
+            accept: "Akzeptieren",
+            accepted: "Das Gerät wurde autorisiert. Dieses Fenster kann nun geschlossen werden.",
+            deny: "Ablehnen",
+            denied: "Das Gerät wurde abgelehnt. Dieses Fenster kann nun geschlossen werden.",
+            desc: r#"Bitte geben Sie den Code ein, welcher auf Ihrem Gerät angezeigt wird.
+Akzeptieren Sie die Anfrage nur, wenn Sie den Login auf diesem Gerät selbst gestartet haben."#,
+            invalid_input: "Ungültige Eingabe",
+            title: "Geräte Autorisierung",
+            user_code: "Benutzer Code",
+        }
+    }
+}
@@ -22,11 +22,13 @@ pub mod language;
 pub mod migration;
 pub mod request;
 pub mod response;
+pub mod scim;
 pub mod templates;
 
 pub enum AuthStep {
     LoggedIn(AuthStepLoggedIn),
     AwaitWebauthn(AuthStepAwaitWebauthn),
+    AwaitTotp(AuthStepAwaitTotp),
 }
 
 pub struct AuthStepLoggedIn {
@@ -48,6 +50,15 @@ pub struct AuthStepAwaitWebauthn {
     pub session: Session,
 }
 
+pub struct AuthStepAwaitTotp {
+    pub has_password_been_hashed: bool,
+    pub code: String,
+    pub header_csrf: (HeaderName, HeaderValue),
+    pub header_origin: Option<(HeaderName, HeaderValue)>,
+    pub user_id: String,
+    pub exp: u64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RequestId {
     pub id: String,
@@ -78,11 +89,42 @@ pub struct JwtCommonClaims {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scope: Option<String>,
     pub cnf: Option<JktClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
 }
 
+/// The `cnf` claim of sender-constrained tokens
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct JktClaim {
-    pub jkt: String,
+    /// The JWK thumbprint of the DPoP key (RFC 9449)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jkt: Option<String>,
+    /// The SHA-256 thumbprint of the TLS client certificate (RFC 8705)
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+}
+
+impl JktClaim {
+    /// Returns the `cnf` claim, if the token should be bound to either a DPoP key, a TLS client
+    /// certificate, or both.
+    pub fn new(dpop_fingerprint: Option<String>, cert_thumbprint: Option<String>) -> Option<Self> {
+        if dpop_fingerprint.is_none() && cert_thumbprint.is_none() {
+            return None;
+        }
+        Some(Self {
+            jkt: dpop_fingerprint,
+            x5t_s256: cert_thumbprint,
+        })
+    }
+}
+
+/// The `act` claim for delegated tokens from the token exchange grant (RFC 8693).
+/// Prior actors of a delegation chain are nested inside.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JwtActClaim {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<JwtActClaim>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -103,7 +145,13 @@ pub struct JwtAccessClaims {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cnf: Option<JktClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<JwtActClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub custom: Option<HashMap<String, serde_json::Value>>,
+    /// Custom user attributes, which have been requested for the userinfo endpoint via the
+    /// `claims` param during the authorization.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_claims: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -111,7 +159,12 @@ pub struct JwtIdClaims {
     pub azp: String,
     pub typ: JwtTokenType,
     pub amr: Vec<String>,
-    pub preferred_username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -129,6 +182,21 @@ pub struct JwtIdClaims {
     pub custom: Option<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub webid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+/// The claims of a logout token for the OIDC Back-Channel Logout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtLogoutClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+    pub jti: String,
+    pub events: HashMap<String, serde_json::Value>,
+    pub sid: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -163,6 +231,7 @@ impl JwtTokenType {
 #[serde(rename_all(serialize = "lowercase"))]
 pub enum JwtAmrValue {
     Pwd,
+    Otp,
     Mfa,
 }
 
@@ -172,6 +241,7 @@ impl FromStr for JwtAmrValue {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let slf = match s {
             "pwd" => Self::Pwd,
+            "otp" => Self::Otp,
             "mfa" => Self::Mfa,
             _ => {
                 return Err(ErrorResponse::new(
@@ -188,6 +258,7 @@ impl ToString for JwtAmrValue {
     fn to_string(&self) -> String {
         let s = match self {
             Self::Pwd => "pwd",
+            Self::Otp => "otp",
             Self::Mfa => "mfa",
         };
         s.to_string()
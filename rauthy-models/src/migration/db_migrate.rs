@@ -1,33 +1,39 @@
-use actix_web::web;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
-use jwt_simple::algorithms::{
-    Ed25519KeyPair, EdDSAKeyPairLike, RS256KeyPair, RS384KeyPair, RS512KeyPair, RSAKeyPairLike,
-};
 use rand_core::OsRng;
 use sqlx::Row;
-use time::OffsetDateTime;
 use tracing::{debug, info};
 
 use rauthy_common::constants::{ADMIN_FORCE_MFA, DB_TYPE, DEV_MODE};
 use rauthy_common::error_response::ErrorResponse;
-use rauthy_common::utils::{encrypt, get_rand};
+use rauthy_common::utils::get_rand;
 use rauthy_common::DbType;
 
 use crate::app_state::DbPool;
 use crate::entity::api_keys::ApiKeyEntity;
 use crate::entity::clients::Client;
+use crate::entity::clients_dyn::ClientDyn;
 use crate::entity::colors::ColorEntity;
 use crate::entity::config::ConfigEntity;
 use crate::entity::groups::Group;
 use crate::entity::jwk::{Jwk, JwkKeyPairAlg};
 use crate::entity::magic_links::MagicLink;
+use crate::entity::pairwise_subjects::PairwiseSubject;
 use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::provisioning::{
+    ProvisionedResource, ProvisioningConnector, ProvisioningDelivery,
+};
+use crate::entity::recovery_codes::UserRecoveryCode;
 use crate::entity::refresh_tokens::RefreshToken;
+use crate::entity::revoked_tokens::RevokedToken;
 use crate::entity::roles::Role;
 use crate::entity::scopes::Scope;
+use crate::entity::session_clients::SessionClient;
 use crate::entity::sessions::Session;
+use crate::entity::totp::UserTotp;
+use crate::entity::upstream_providers::{UpstreamProvider, UpstreamProviderLink};
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_consents::UserConsent;
 use crate::entity::users::User;
 use crate::entity::webauthn::PasskeyEntity;
 
@@ -63,6 +69,25 @@ pub async fn anti_lockout(db: &DbPool, issuer: &str) -> Result<(), ErrorResponse
         default_scopes: "openid".to_string(),
         challenge: Some("S256".to_string()),
         force_mfa: *ADMIN_FORCE_MFA,
+        token_exchange_audiences: None,
+        backchannel_logout_uri: None,
+        frontchannel_logout_uri: None,
+        require_par: false,
+        jwks: None,
+        jwks_uri: None,
+        token_endpoint_auth_method: "none".to_string(),
+        tls_client_auth_subject_dn: None,
+        tls_client_auth_thumbprints: None,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+        require_consent: false,
+        refresh_token_rotation: false,
+        request_uris: None,
     };
 
     #[cfg(feature = "sqlite")]
@@ -192,70 +217,11 @@ pub async fn migrate_init_prod(
 
         // generate JWKs
         info!("Generating new JWKs - this might take a few seconds");
-        let mut entities = Vec::with_capacity(4);
-
-        // RSA256
-        let jwk_plain = web::block(|| {
-            RS256KeyPair::generate(2048)
-                .unwrap()
-                .with_key_id(&get_rand(24))
-        })
-        .await?;
-        let jwk = encrypt(jwk_plain.to_der().unwrap().as_slice(), enc_key)?;
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::RS256,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
-
-        // RS384
-        let jwk_plain = web::block(|| {
-            RS384KeyPair::generate(3072)
-                .unwrap()
-                .with_key_id(&get_rand(24))
-        })
-        .await?;
-        let jwk = encrypt(jwk_plain.to_der().unwrap().as_slice(), enc_key)?;
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::RS384,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
-
-        // RSA512
-        let jwk_plain = web::block(|| {
-            RS512KeyPair::generate(4096)
-                .unwrap()
-                .with_key_id(&get_rand(24))
-        })
-        .await?;
-        let jwk = encrypt(jwk_plain.to_der().unwrap().as_slice(), enc_key)?;
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::RS512,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
-
-        // Ed25519
-        let jwk_plain =
-            web::block(|| Ed25519KeyPair::generate().with_key_id(&get_rand(24))).await?;
-        let jwk = encrypt(jwk_plain.to_der().as_slice(), enc_key)?;
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::EdDSA,
-            enc_key_id: enc_key_active,
-            jwk,
-        });
-
-        for e in entities {
-            e.save(db).await?;
+        for alg in JwkKeyPairAlg::all() {
+            Jwk::generate(alg, &enc_key_active, enc_key)
+                .await?
+                .save(db)
+                .await?;
         }
 
         info!("Production database initialized successfully");
@@ -264,6 +230,30 @@ pub async fn migrate_init_prod(
     Ok(())
 }
 
+/// Generates a JWK for each signing algorithm, which does not have a key yet. This is needed
+/// for existing databases after support for a new algorithm has been added.
+pub async fn migrate_missing_jwks(
+    db: &DbPool,
+    enc_key_active: &str,
+    enc_key: &[u8],
+) -> Result<(), ErrorResponse> {
+    let jwks = sqlx::query_as::<_, Jwk>("select * from jwks")
+        .fetch_all(db)
+        .await?;
+
+    for alg in JwkKeyPairAlg::all() {
+        if !jwks.iter().any(|jwk| jwk.signature == alg) {
+            info!("Generating missing JWK for {}", alg.as_str());
+            Jwk::generate(alg, enc_key_active, enc_key)
+                .await?
+                .save(db)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Migrates `MIGRATE_DB_FROM` to `DATABASE_URL`
 pub async fn migrate_from_sqlite(
     db_from: sqlx::SqlitePool,
@@ -317,8 +307,9 @@ pub async fn migrate_from_sqlite(
             r#"insert into users
             (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
             password_expires, created_at, last_login, last_failed_login, failed_login_attempts,
-            language, webauthn_user_id, user_expires)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)"#,
+            language, webauthn_user_id, user_expires, totp_enabled)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18)"#,
         )
         .bind(b.id)
         .bind(b.email)
@@ -337,6 +328,7 @@ pub async fn migrate_from_sqlite(
         .bind(b.language)
         .bind(b.webauthn_user_id)
         .bind(b.user_expires)
+        .bind(b.totp_enabled)
         .execute(db_to)
         .await?;
     }
@@ -374,8 +366,14 @@ pub async fn migrate_from_sqlite(
             r#"insert into clients (id, name, enabled, confidential, secret, secret_kid,
             redirect_uris, post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg,
             id_token_alg, refresh_token, auth_code_lifetime, access_token_lifetime, scopes, default_scopes,
-            challenge, force_mfa)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#)
+            challenge, force_mfa, token_exchange_audiences, backchannel_logout_uri,
+            frontchannel_logout_uri, require_par, jwks, jwks_uri, token_endpoint_auth_method,
+            tls_client_auth_subject_dn, tls_client_auth_thumbprints, id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc, userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc, userinfo_signed_response_alg, subject_type,
+            sector_identifier_uri, require_consent, refresh_token_rotation, request_uris)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+            $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38)"#)
             .bind(&b.id)
             .bind(&b.name)
             .bind(b.enabled)
@@ -395,6 +393,25 @@ pub async fn migrate_from_sqlite(
             .bind(&b.default_scopes)
             .bind(&b.challenge)
             .bind(b.force_mfa)
+            .bind(&b.token_exchange_audiences)
+            .bind(&b.backchannel_logout_uri)
+            .bind(&b.frontchannel_logout_uri)
+            .bind(b.require_par)
+            .bind(&b.jwks)
+            .bind(&b.jwks_uri)
+            .bind(&b.token_endpoint_auth_method)
+            .bind(&b.tls_client_auth_subject_dn)
+            .bind(&b.tls_client_auth_thumbprints)
+            .bind(&b.id_token_encrypted_response_alg)
+            .bind(&b.id_token_encrypted_response_enc)
+            .bind(&b.userinfo_encrypted_response_alg)
+            .bind(&b.userinfo_encrypted_response_enc)
+            .bind(&b.userinfo_signed_response_alg)
+            .bind(&b.subject_type)
+            .bind(&b.sector_identifier_uri)
+            .bind(b.require_consent)
+            .bind(b.refresh_token_rotation)
+            .bind(&b.request_uris)
             .execute(db_to)
             .await?;
     }
@@ -502,16 +519,41 @@ pub async fn migrate_from_sqlite(
         .await?;
     for b in before {
         sqlx::query(
-            r#"insert into refresh_tokens (id, user_id, nbf, exp, scope)
-            values ($1, $2, $3, $4, $5)"#,
+            r#"insert into refresh_tokens
+            (id, user_id, nbf, exp, scope, client_id, family_id, session_id, used, offline,
+            created_at, last_used, ip)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
         )
         .bind(&b.id)
         .bind(&b.user_id)
         .bind(b.nbf)
         .bind(b.exp)
         .bind(&b.scope)
+        .bind(&b.client_id)
+        .bind(&b.family_id)
+        .bind(&b.session_id)
+        .bind(b.used)
+        .bind(b.offline)
+        .bind(b.created_at)
+        .bind(b.last_used)
+        .bind(&b.ip)
+        .execute(db_to)
+        .await?;
+    }
+
+    // REVOKED TOKENS
+    let before = sqlx::query_as::<_, RevokedToken>("select * from revoked_tokens")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from revoked_tokens")
         .execute(db_to)
         .await?;
+    for b in before {
+        sqlx::query("insert into revoked_tokens (jti, exp) values ($1, $2)")
+            .bind(b.jti)
+            .bind(b.exp)
+            .execute(db_to)
+            .await?;
     }
 
     // ROLES
@@ -602,6 +644,24 @@ pub async fn migrate_from_sqlite(
         .await?;
     }
 
+    // SESSION CLIENTS
+    let before = sqlx::query_as::<_, SessionClient>("select * from session_clients")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from session_clients")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            "insert into session_clients (session_id, client_id, created) values ($1, $2, $3)",
+        )
+        .bind(b.session_id)
+        .bind(b.client_id)
+        .bind(b.created)
+        .execute(db_to)
+        .await?;
+    }
+
     // RECENT PASSWORDS
     let before = sqlx::query_as::<_, RecentPasswordsEntity>("select * from recent_passwords")
         .fetch_all(&db_from)
@@ -617,6 +677,230 @@ pub async fn migrate_from_sqlite(
             .await?;
     }
 
+    // UPSTREAM PROVIDERS
+    let before = sqlx::query_as::<_, UpstreamProvider>("select * from upstream_providers")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from upstream_providers")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into upstream_providers (id, name, enabled, issuer, authorization_endpoint,
+            token_endpoint, userinfo_endpoint, client_id, secret, secret_kid, scope, use_pkce,
+            claim_sub, claim_email, claim_email_verified, claim_given_name, claim_family_name,
+            auto_link, auto_provision)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18, $19)"#,
+        )
+        .bind(b.id)
+        .bind(b.name)
+        .bind(b.enabled)
+        .bind(b.issuer)
+        .bind(b.authorization_endpoint)
+        .bind(b.token_endpoint)
+        .bind(b.userinfo_endpoint)
+        .bind(b.client_id)
+        .bind(b.secret)
+        .bind(b.secret_kid)
+        .bind(b.scope)
+        .bind(b.use_pkce)
+        .bind(b.claim_sub)
+        .bind(b.claim_email)
+        .bind(b.claim_email_verified)
+        .bind(b.claim_given_name)
+        .bind(b.claim_family_name)
+        .bind(b.auto_link)
+        .bind(b.auto_provision)
+        .execute(db_to)
+        .await?;
+    }
+
+    // UPSTREAM PROVIDER LINKS
+    let before = sqlx::query_as::<_, UpstreamProviderLink>("select * from upstream_provider_links")
+        .fetch_all(&db_from)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into upstream_provider_links (provider_id, upstream_sub, user_id, created_at)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.provider_id)
+        .bind(b.upstream_sub)
+        .bind(b.user_id)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // CLIENTS DYN
+    let before = sqlx::query_as::<_, ClientDyn>("select * from clients_dyn")
+        .fetch_all(&db_from)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into clients_dyn (id, created, registration_token, token_endpoint_auth_method)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.id)
+        .bind(b.created)
+        .bind(b.registration_token)
+        .bind(b.token_endpoint_auth_method)
+        .execute(db_to)
+        .await?;
+    }
+
+    // USER CONSENTS
+    let before = sqlx::query_as::<_, UserConsent>("select * from user_consents")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from user_consents")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into user_consents (user_id, client_id, scopes, created_at, last_update)
+            values ($1, $2, $3, $4, $5)"#,
+        )
+        .bind(b.user_id)
+        .bind(b.client_id)
+        .bind(b.scopes)
+        .bind(b.created_at)
+        .bind(b.last_update)
+        .execute(db_to)
+        .await?;
+    }
+
+    // USER TOTPS
+    let before = sqlx::query_as::<_, UserTotp>("select * from user_totps")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from user_totps").execute(db_to).await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into user_totps (user_id, secret, enc_key_id, last_step, created_at)
+            values ($1, $2, $3, $4, $5)"#,
+        )
+        .bind(b.user_id)
+        .bind(b.secret)
+        .bind(b.enc_key_id)
+        .bind(b.last_step)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // USER RECOVERY CODES
+    let before = sqlx::query_as::<_, UserRecoveryCode>("select * from user_recovery_codes")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from user_recovery_codes")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into user_recovery_codes (user_id, id, hash, created_at)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.user_id)
+        .bind(b.id)
+        .bind(b.hash)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PROVISIONING CONNECTORS
+    let before =
+        sqlx::query_as::<_, ProvisioningConnector>("select * from provisioning_connectors")
+            .fetch_all(&db_from)
+            .await?;
+    sqlx::query("delete from provisioning_connectors")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into provisioning_connectors
+            (id, client_id, typ, endpoint, secret, secret_kid, enabled, created_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        )
+        .bind(b.id)
+        .bind(b.client_id)
+        .bind(b.typ)
+        .bind(b.endpoint)
+        .bind(b.secret)
+        .bind(b.secret_kid)
+        .bind(b.enabled)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PROVISIONING DELIVERIES
+    let before = sqlx::query_as::<_, ProvisioningDelivery>("select * from provisioning_deliveries")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from provisioning_deliveries")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into provisioning_deliveries
+            (id, connector_id, resource_typ, resource_id, action, payload, status, attempts,
+            next_attempt, last_error, created_at, delivered_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+        )
+        .bind(b.id)
+        .bind(b.connector_id)
+        .bind(b.resource_typ)
+        .bind(b.resource_id)
+        .bind(b.action)
+        .bind(b.payload)
+        .bind(b.status)
+        .bind(b.attempts)
+        .bind(b.next_attempt)
+        .bind(b.last_error)
+        .bind(b.created_at)
+        .bind(b.delivered_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PROVISIONING RESOURCES
+    let before = sqlx::query_as::<_, ProvisionedResource>("select * from provisioning_resources")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from provisioning_resources")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into provisioning_resources (connector_id, resource_typ, resource_id, remote_id)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.connector_id)
+        .bind(b.resource_typ)
+        .bind(b.resource_id)
+        .bind(b.remote_id)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PAIRWISE SUBJECTS
+    let before = sqlx::query_as::<_, PairwiseSubject>("select * from pairwise_subjects")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from pairwise_subjects")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query("insert into pairwise_subjects (sub, user_id) values ($1, $2)")
+            .bind(b.sub)
+            .bind(b.user_id)
+            .execute(db_to)
+            .await?;
+    }
+
     // // PASSKEYS
     // let before = sqlx::query_as::<_, PasskeyEntity>("select * from passkeys")
     //     .fetch_all(&db_from)
@@ -691,8 +975,9 @@ pub async fn migrate_from_postgres(
             r#"insert into users
             (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
             password_expires, created_at, last_login, last_failed_login, failed_login_attempts,
-            language, webauthn_user_id, user_expires)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)"#,
+            language, webauthn_user_id, user_expires, totp_enabled)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18)"#,
         )
         .bind(b.id)
         .bind(b.email)
@@ -711,6 +996,7 @@ pub async fn migrate_from_postgres(
         .bind(b.language)
         .bind(b.webauthn_user_id)
         .bind(b.user_expires)
+        .bind(b.totp_enabled)
         .execute(db_to)
         .await?;
     }
@@ -748,8 +1034,14 @@ pub async fn migrate_from_postgres(
             r#"insert into clients (id, name, enabled, confidential, secret, secret_kid,
             redirect_uris, post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg,
             id_token_alg, refresh_token, auth_code_lifetime, access_token_lifetime, scopes, default_scopes,
-            challenge, force_mfa)
-            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#)
+            challenge, force_mfa, token_exchange_audiences, backchannel_logout_uri,
+            frontchannel_logout_uri, require_par, jwks, jwks_uri, token_endpoint_auth_method,
+            tls_client_auth_subject_dn, tls_client_auth_thumbprints, id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc, userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc, userinfo_signed_response_alg, subject_type,
+            sector_identifier_uri, require_consent, refresh_token_rotation, request_uris)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+            $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38)"#)
             .bind(&b.id)
             .bind(&b.name)
             .bind(b.enabled)
@@ -769,6 +1061,25 @@ pub async fn migrate_from_postgres(
             .bind(&b.default_scopes)
             .bind(&b.challenge)
             .bind(b.force_mfa)
+            .bind(&b.token_exchange_audiences)
+            .bind(&b.backchannel_logout_uri)
+            .bind(&b.frontchannel_logout_uri)
+            .bind(b.require_par)
+            .bind(&b.jwks)
+            .bind(&b.jwks_uri)
+            .bind(&b.token_endpoint_auth_method)
+            .bind(&b.tls_client_auth_subject_dn)
+            .bind(&b.tls_client_auth_thumbprints)
+            .bind(&b.id_token_encrypted_response_alg)
+            .bind(&b.id_token_encrypted_response_enc)
+            .bind(&b.userinfo_encrypted_response_alg)
+            .bind(&b.userinfo_encrypted_response_enc)
+            .bind(&b.userinfo_signed_response_alg)
+            .bind(&b.subject_type)
+            .bind(&b.sector_identifier_uri)
+            .bind(b.require_consent)
+            .bind(b.refresh_token_rotation)
+            .bind(&b.request_uris)
             .execute(db_to)
             .await?;
     }
@@ -876,16 +1187,41 @@ pub async fn migrate_from_postgres(
         .await?;
     for b in before {
         sqlx::query(
-            r#"insert into refresh_tokens (id, user_id, nbf, exp, scope)
-            values ($1, $2, $3, $4, $5)"#,
+            r#"insert into refresh_tokens
+            (id, user_id, nbf, exp, scope, client_id, family_id, session_id, used, offline,
+            created_at, last_used, ip)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)"#,
         )
         .bind(&b.id)
         .bind(&b.user_id)
         .bind(b.nbf)
         .bind(b.exp)
         .bind(&b.scope)
+        .bind(&b.client_id)
+        .bind(&b.family_id)
+        .bind(&b.session_id)
+        .bind(b.used)
+        .bind(b.offline)
+        .bind(b.created_at)
+        .bind(b.last_used)
+        .bind(&b.ip)
+        .execute(db_to)
+        .await?;
+    }
+
+    // REVOKED TOKENS
+    let before = sqlx::query_as::<_, RevokedToken>("select * from rauthy.revoked_tokens")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from revoked_tokens")
         .execute(db_to)
         .await?;
+    for b in before {
+        sqlx::query("insert into revoked_tokens (jti, exp) values ($1, $2)")
+            .bind(b.jti)
+            .bind(b.exp)
+            .execute(db_to)
+            .await?;
     }
 
     // ROLES
@@ -976,6 +1312,24 @@ pub async fn migrate_from_postgres(
         .await?;
     }
 
+    // SESSION CLIENTS
+    let before = sqlx::query_as::<_, SessionClient>("select * from rauthy.session_clients")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from session_clients")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            "insert into session_clients (session_id, client_id, created) values ($1, $2, $3)",
+        )
+        .bind(b.session_id)
+        .bind(b.client_id)
+        .bind(b.created)
+        .execute(db_to)
+        .await?;
+    }
+
     // RECENT PASSWORDS
     let before =
         sqlx::query_as::<_, RecentPasswordsEntity>("select * from rauthy.recent_passwords")
@@ -992,6 +1346,233 @@ pub async fn migrate_from_postgres(
             .await?;
     }
 
+    // UPSTREAM PROVIDERS
+    let before = sqlx::query_as::<_, UpstreamProvider>("select * from rauthy.upstream_providers")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from upstream_providers")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into upstream_providers (id, name, enabled, issuer, authorization_endpoint,
+            token_endpoint, userinfo_endpoint, client_id, secret, secret_kid, scope, use_pkce,
+            claim_sub, claim_email, claim_email_verified, claim_given_name, claim_family_name,
+            auto_link, auto_provision)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18, $19)"#,
+        )
+        .bind(b.id)
+        .bind(b.name)
+        .bind(b.enabled)
+        .bind(b.issuer)
+        .bind(b.authorization_endpoint)
+        .bind(b.token_endpoint)
+        .bind(b.userinfo_endpoint)
+        .bind(b.client_id)
+        .bind(b.secret)
+        .bind(b.secret_kid)
+        .bind(b.scope)
+        .bind(b.use_pkce)
+        .bind(b.claim_sub)
+        .bind(b.claim_email)
+        .bind(b.claim_email_verified)
+        .bind(b.claim_given_name)
+        .bind(b.claim_family_name)
+        .bind(b.auto_link)
+        .bind(b.auto_provision)
+        .execute(db_to)
+        .await?;
+    }
+
+    // UPSTREAM PROVIDER LINKS
+    let before =
+        sqlx::query_as::<_, UpstreamProviderLink>("select * from rauthy.upstream_provider_links")
+            .fetch_all(&db_from)
+            .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into upstream_provider_links (provider_id, upstream_sub, user_id, created_at)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.provider_id)
+        .bind(b.upstream_sub)
+        .bind(b.user_id)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // CLIENTS DYN
+    let before = sqlx::query_as::<_, ClientDyn>("select * from rauthy.clients_dyn")
+        .fetch_all(&db_from)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into clients_dyn (id, created, registration_token, token_endpoint_auth_method)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.id)
+        .bind(b.created)
+        .bind(b.registration_token)
+        .bind(b.token_endpoint_auth_method)
+        .execute(db_to)
+        .await?;
+    }
+
+    // USER CONSENTS
+    let before = sqlx::query_as::<_, UserConsent>("select * from rauthy.user_consents")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from user_consents")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into user_consents (user_id, client_id, scopes, created_at, last_update)
+            values ($1, $2, $3, $4, $5)"#,
+        )
+        .bind(b.user_id)
+        .bind(b.client_id)
+        .bind(b.scopes)
+        .bind(b.created_at)
+        .bind(b.last_update)
+        .execute(db_to)
+        .await?;
+    }
+
+    // USER TOTPS
+    let before = sqlx::query_as::<_, UserTotp>("select * from rauthy.user_totps")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from user_totps").execute(db_to).await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into user_totps (user_id, secret, enc_key_id, last_step, created_at)
+            values ($1, $2, $3, $4, $5)"#,
+        )
+        .bind(b.user_id)
+        .bind(b.secret)
+        .bind(b.enc_key_id)
+        .bind(b.last_step)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // USER RECOVERY CODES
+    let before = sqlx::query_as::<_, UserRecoveryCode>("select * from rauthy.user_recovery_codes")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from user_recovery_codes")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into user_recovery_codes (user_id, id, hash, created_at)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.user_id)
+        .bind(b.id)
+        .bind(b.hash)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PROVISIONING CONNECTORS
+    let before =
+        sqlx::query_as::<_, ProvisioningConnector>("select * from rauthy.provisioning_connectors")
+            .fetch_all(&db_from)
+            .await?;
+    sqlx::query("delete from provisioning_connectors")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into provisioning_connectors
+            (id, client_id, typ, endpoint, secret, secret_kid, enabled, created_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        )
+        .bind(b.id)
+        .bind(b.client_id)
+        .bind(b.typ)
+        .bind(b.endpoint)
+        .bind(b.secret)
+        .bind(b.secret_kid)
+        .bind(b.enabled)
+        .bind(b.created_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PROVISIONING DELIVERIES
+    let before =
+        sqlx::query_as::<_, ProvisioningDelivery>("select * from rauthy.provisioning_deliveries")
+            .fetch_all(&db_from)
+            .await?;
+    sqlx::query("delete from provisioning_deliveries")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into provisioning_deliveries
+            (id, connector_id, resource_typ, resource_id, action, payload, status, attempts,
+            next_attempt, last_error, created_at, delivered_at)
+            values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+        )
+        .bind(b.id)
+        .bind(b.connector_id)
+        .bind(b.resource_typ)
+        .bind(b.resource_id)
+        .bind(b.action)
+        .bind(b.payload)
+        .bind(b.status)
+        .bind(b.attempts)
+        .bind(b.next_attempt)
+        .bind(b.last_error)
+        .bind(b.created_at)
+        .bind(b.delivered_at)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PROVISIONING RESOURCES
+    let before =
+        sqlx::query_as::<_, ProvisionedResource>("select * from rauthy.provisioning_resources")
+            .fetch_all(&db_from)
+            .await?;
+    sqlx::query("delete from provisioning_resources")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query(
+            r#"insert into provisioning_resources (connector_id, resource_typ, resource_id, remote_id)
+            values ($1, $2, $3, $4)"#,
+        )
+        .bind(b.connector_id)
+        .bind(b.resource_typ)
+        .bind(b.resource_id)
+        .bind(b.remote_id)
+        .execute(db_to)
+        .await?;
+    }
+
+    // PAIRWISE SUBJECTS
+    let before = sqlx::query_as::<_, PairwiseSubject>("select * from rauthy.pairwise_subjects")
+        .fetch_all(&db_from)
+        .await?;
+    sqlx::query("delete from pairwise_subjects")
+        .execute(db_to)
+        .await?;
+    for b in before {
+        sqlx::query("insert into pairwise_subjects (sub, user_id) values ($1, $2)")
+            .bind(b.sub)
+            .bind(b.user_id)
+            .execute(db_to)
+            .await?;
+    }
+
     // // PASSKEYS
     // let before = sqlx::query_as::<_, PasskeyEntity>("select * from passkeys")
     //     .fetch_all(&db_from)
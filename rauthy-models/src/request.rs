@@ -1,19 +1,25 @@
 use crate::entity::api_keys::ApiKeyAccess;
+use crate::entity::client_assertions::ClientAssertion;
 use crate::entity::jwk::JwkKeyPairAlg;
+use crate::entity::provisioning::ProvisioningConnectorType;
 use crate::events::event::EventLevel;
 use crate::language::Language;
 use actix_web::http::header;
 use actix_web::HttpRequest;
 use css_color::Srgb;
 use rauthy_common::constants::{
-    RE_ALNUM, RE_ALNUM_24, RE_ALNUM_48, RE_ALNUM_64, RE_ALNUM_SPACE, RE_API_KEY, RE_APP_ID,
-    RE_ATTR, RE_ATTR_DESC, RE_CHALLENGE, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_CODE_CHALLENGE,
-    RE_CODE_VERIFIER, RE_FLOWS, RE_GRANT_TYPES, RE_GROUPS, RE_LOWERCASE, RE_LOWERCASE_SPACE,
-    RE_MFA_CODE, RE_URI, RE_USER_NAME,
+    CLIENT_ASSERTION_TYPE_JWT_BEARER, RE_ALNUM, RE_ALNUM_24, RE_ALNUM_48, RE_ALNUM_64,
+    RE_ALNUM_SPACE, RE_API_KEY, RE_APP_ID, RE_ATTR, RE_ATTR_DESC, RE_CERT_THUMBPRINT, RE_CHALLENGE,
+    RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_CODE_CHALLENGE, RE_CODE_VERIFIER, RE_FLOWS,
+    RE_GRANT_TYPES, RE_GROUPS, RE_JWE_ALG, RE_JWE_ENC, RE_LOWERCASE, RE_LOWERCASE_SPACE,
+    RE_MFA_CODE, RE_RECOVERY_CODE, RE_SUBJECT_DN, RE_SUBJECT_TYPE, RE_TOKEN_68,
+    RE_TOKEN_ENDPOINT_AUTH_METHOD, RE_TOTP, RE_URI, RE_USER_CODE, RE_USER_NAME,
 };
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_common::utils::base64_decode;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::net::Ipv4Addr;
 use std::str::FromStr;
 use utoipa::{IntoParams, ToSchema};
@@ -60,7 +66,7 @@ pub struct IpBlacklistRequest {
     pub exp: i64,
 }
 
-#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema, IntoParams)]
 pub struct AuthRequest {
     /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
     #[validate(regex(
@@ -83,10 +89,247 @@ pub struct AuthRequest {
     pub state: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub nonce: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub code_challenge: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub code_challenge_method: Option<String>,
+    /// The OIDC `claims` request parameter as a JSON object
+    ///
+    /// Validation: `ClaimsRequest` JSON, max 4096 characters
+    #[validate(custom(function = "validate_claims_request"))]
+    pub claims: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub prompt: Option<String>,
+    /// Validation: `>= 0`
+    #[validate(range(min = 0))]
+    pub max_age: Option<i64>,
+    /// Validation: max 256 characters
+    #[validate(length(max = 256))]
+    pub login_hint: Option<String>,
+}
+
+impl AuthRequest {
+    /// Returns `true`, if the space-delimited `prompt` param contains the given value.
+    pub fn has_prompt(&self, value: &str) -> bool {
+        self.prompt
+            .as_deref()
+            .map(|prompt| prompt.split_whitespace().any(|p| p == value))
+            .unwrap_or(false)
+    }
+
+    /// Returns the location for an error redirect back to the client, which is needed for
+    /// authorization errors after the `redirect_uri` has been validated, like for `prompt=none`.
+    pub fn error_redirect_uri(&self, error: &str, description: &str) -> String {
+        match Url::parse(&self.redirect_uri) {
+            Ok(mut url) => {
+                url.query_pairs_mut()
+                    .append_pair("error", error)
+                    .append_pair("error_description", description);
+                if let Some(state) = &self.state {
+                    url.query_pairs_mut().append_pair("state", state);
+                }
+                url.to_string()
+            }
+            // cannot happen for an already validated `redirect_uri`
+            Err(_) => format!("{}?error={}", self.redirect_uri, error),
+        }
+    }
+}
+
+impl From<AuthRequest> for LoginRefreshRequest {
+    fn from(req: AuthRequest) -> Self {
+        Self {
+            client_id: req.client_id,
+            redirect_uri: req.redirect_uri,
+            scopes: Some(req.scope.split(' ').map(String::from).collect()),
+            state: req.state,
+            nonce: req.nonce,
+            code_challenge: req.code_challenge,
+            code_challenge_method: req.code_challenge_method,
+            claims: req.claims,
+            acr_values: req.acr_values,
+            prompt: req.prompt,
+            max_age: req.max_age,
+            request_uri: None,
+        }
+    }
+}
+
+/// The authorization request params, if they are passed by reference. This is either the
+/// `request_uri` from a pushed authorization request (RFC 9126) on its own, or a signed request
+/// object (RFC 9101) via `request` or `request_uri`. The params inside a request object take
+/// precedence and the additional query params will only be used, if they are missing there.
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+#[validate(schema(function = "validate_auth_request_ref"))]
+pub struct AuthRequestRef {
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: String,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub request: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub request_uri: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub redirect_uri: Option<String>,
+    /// Validation: `[a-z0-9-_/]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE", code = "[a-z0-9-_/]{2,128}"))]
+    pub response_type: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub scope: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub state: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub nonce: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub code_challenge: Option<String>,
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub code_challenge_method: Option<String>,
+    /// The OIDC `claims` request parameter as a JSON object
+    ///
+    /// Validation: `ClaimsRequest` JSON, max 4096 characters
+    #[validate(custom(function = "validate_claims_request"))]
+    pub claims: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub prompt: Option<String>,
+    /// Validation: `>= 0`
+    #[validate(range(min = 0))]
+    pub max_age: Option<i64>,
+    /// Validation: max 256 characters
+    #[validate(length(max = 256))]
+    pub login_hint: Option<String>,
+}
+
+/// The OIDC `claims` request parameter, which asks for specific claims to be returned inside
+/// the ID token or from the userinfo endpoint, in addition to the ones mapped from the scopes.
+/// Claims which are neither a standard one nor a configured custom user attribute are ignored.
+/// Custom user attributes can only be requested, if one of the granted scopes maps them.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClaimsRequest {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub id_token: HashMap<String, Option<ClaimsRequestMember>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub userinfo: HashMap<String, Option<ClaimsRequestMember>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClaimsRequestMember {
+    #[serde(default)]
+    pub essential: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<serde_json::Value>>,
+}
+
+impl ClaimsRequest {
+    /// All claims, which are managed by Rauthy itself and can therefore never be mapped from a
+    /// custom user attribute.
+    const REGISTERED: [&'static str; 24] = [
+        "iss",
+        "sub",
+        "aud",
+        "exp",
+        "nbf",
+        "iat",
+        "jti",
+        "azp",
+        "typ",
+        "nonce",
+        "acr",
+        "amr",
+        "auth_time",
+        "sid",
+        "cnf",
+        "name",
+        "preferred_username",
+        "email",
+        "email_verified",
+        "given_name",
+        "family_name",
+        "webid",
+        "roles",
+        "groups",
+    ];
+
+    pub fn parse(value: &str) -> Result<Self, ErrorResponse> {
+        serde_json::from_str::<Self>(value).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid 'claims' parameter: {}", err),
+            )
+        })
+    }
+
+    /// Returns the requested `acr` values for the ID token, no matter if they are essential.
+    pub fn acr_values(&self) -> Vec<String> {
+        let Some(Some(acr)) = self.id_token.get("acr") else {
+            return Vec::default();
+        };
+        acr.value
+            .iter()
+            .chain(acr.values.iter().flatten())
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect()
+    }
+
+    pub fn is_custom(name: &str) -> bool {
+        !Self::REGISTERED.contains(&name)
+    }
+
+    /// Returns the names of all requested custom claims for the ID token.
+    pub fn custom_id_token(&self) -> Vec<&str> {
+        Self::custom_names(&self.id_token)
+    }
+
+    /// Returns the names of all requested custom claims for the userinfo endpoint.
+    pub fn custom_userinfo(&self) -> Vec<&str> {
+        Self::custom_names(&self.userinfo)
+    }
+
+    /// Removes all requested custom claims, which are not mapped by any of the granted scopes
+    /// via `attr_include_id` or `attr_include_access`.
+    pub fn retain_granted(&mut self, granted: &HashSet<&str>) {
+        let is_granted = |name: &String, _: &mut Option<ClaimsRequestMember>| {
+            !Self::is_custom(name) || granted.contains(name.as_str())
+        };
+        self.id_token.retain(is_granted);
+        self.userinfo.retain(is_granted);
+    }
+
+    fn custom_names(claims: &HashMap<String, Option<ClaimsRequestMember>>) -> Vec<&str> {
+        claims
+            .keys()
+            .map(String::as_str)
+            .filter(|name| Self::is_custom(name))
+            .collect()
+    }
+
+    /// Returns `true` if the given claim has been requested for the ID token.
+    pub fn wants_id_token(&self, name: &str) -> bool {
+        self.id_token.contains_key(name)
+    }
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -138,6 +381,153 @@ impl ColorsRequest {
     }
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct ConsentParams {
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ConsentRequest {
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub id: String,
+    /// If `true`, the requested scopes will not be granted and the client receives an
+    /// `access_denied` error
+    pub deny: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct DeviceRequest {
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub client_secret: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub client_assertion: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub client_assertion_type: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub scope: Option<String>,
+}
+
+impl DeviceRequest {
+    // by RFC, the client auth can be either sent inside the POST body, or as an Authorization header
+    pub fn try_get_client_credentials(
+        &self,
+        req: &HttpRequest,
+    ) -> Result<(String, ClientCredentials), ErrorResponse> {
+        client_credentials_from_req(
+            req,
+            &self.client_id,
+            &self.client_secret,
+            &self.client_assertion,
+            &self.client_assertion_type,
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct DeviceVerifyRequest {
+    /// Validation: `^[BCDFGHJKLMNPQRSTVWXZbcdfghjklmnpqrstvwxz-]{4,32}$`
+    #[validate(regex(
+        path = "RE_USER_CODE",
+        code = "^[BCDFGHJKLMNPQRSTVWXZbcdfghjklmnpqrstvwxz-]{4,32}$"
+    ))]
+    pub user_code: String,
+    /// If `true`, the authorization for the device will be denied
+    pub deny: Option<bool>,
+    /// Must be `true` to grant the requested scopes, if the client requires a consent, which
+    /// does not cover all of them yet
+    pub consent: Option<bool>,
+}
+
+/// Client metadata for the dynamic client registration (RFC 7591 / RFC 7592)
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct DynClientRequest {
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub redirect_uris: Vec<String>,
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub post_logout_redirect_uris: Option<Vec<String>>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub backchannel_logout_uri: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub frontchannel_logout_uri: Option<String>,
+    /// The public keys of the client as a JSON Web Key Set, mutually exclusive with `jwks_uri`
+    pub jwks: Option<serde_json::Value>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub jwks_uri: Option<String>,
+    /// Validation: `Vec<^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$>`
+    #[validate(custom(function = "validate_vec_grant_type"))]
+    pub grant_types: Option<Vec<String>>,
+    /// Only `code` is supported
+    pub response_types: Option<Vec<String>>,
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{2,128}`
+    #[validate(regex(path = "RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ÿ-\\s]{2,128}"))]
+    pub client_name: Option<String>,
+    /// Validation: `^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt|tls_client_auth|self_signed_tls_client_auth|none)$`
+    #[validate(regex(
+        path = "RE_TOKEN_ENDPOINT_AUTH_METHOD",
+        code = "^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt|tls_client_auth|self_signed_tls_client_auth|none)$"
+    ))]
+    pub token_endpoint_auth_method: Option<String>,
+    /// The expected subject DN of the client certificate for `tls_client_auth`
+    ///
+    /// Validation: `^[a-zA-Z0-9À-ÿ_.,=+@:/'\s-]{2,512}$`
+    #[validate(regex(path = "RE_SUBJECT_DN", code = "^[a-zA-Z0-9À-ÿ_.,=+@:/'\\s-]{2,512}$"))]
+    pub tls_client_auth_subject_dn: Option<String>,
+    /// Validation: `^(RSA-OAEP-256|ECDH-ES)$`
+    #[validate(regex(path = "RE_JWE_ALG", code = "^(RSA-OAEP-256|ECDH-ES)$"))]
+    pub id_token_encrypted_response_alg: Option<String>,
+    /// Validation: `^(A256GCM)$`
+    #[validate(regex(path = "RE_JWE_ENC", code = "^(A256GCM)$"))]
+    pub id_token_encrypted_response_enc: Option<String>,
+    /// Validation: `^(RSA-OAEP-256|ECDH-ES)$`
+    #[validate(regex(path = "RE_JWE_ALG", code = "^(RSA-OAEP-256|ECDH-ES)$"))]
+    pub userinfo_encrypted_response_alg: Option<String>,
+    /// Validation: `^(A256GCM)$`
+    #[validate(regex(path = "RE_JWE_ENC", code = "^(A256GCM)$"))]
+    pub userinfo_encrypted_response_enc: Option<String>,
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
+    pub userinfo_signed_response_alg: Option<JwkKeyPairAlg>,
+    /// Validation: `^(public|pairwise)$`
+    #[validate(regex(path = "RE_SUBJECT_TYPE", code = "^(public|pairwise)$"))]
+    pub subject_type: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub sector_identifier_uri: Option<String>,
+    /// The only URIs, from which request objects may be fetched via `request_uri`
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub request_uris: Option<Vec<String>>,
+    /// Validation: `^[a-z0-9-_/\s]{2,128}$`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub scope: Option<String>,
+    /// Validation: `60 <= default_max_age <= 86400`
+    #[validate(range(min = 60, max = 86400))]
+    pub default_max_age: Option<i32>,
+
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
+    pub access_token_signed_response_alg: Option<JwkKeyPairAlg>,
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
+    pub id_token_signed_response_alg: Option<JwkKeyPairAlg>,
+}
+
 #[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
 pub struct EncKeyMigrateRequest {
     #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
@@ -159,7 +549,7 @@ pub struct EphemeralClientRequest {
     /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
     #[validate(custom(function = "validate_vec_uri"))]
     pub post_logout_redirect_uris: Option<Vec<String>>,
-    /// Validation: `Vec<^(authorization_code|client_credentials|password|refresh_token)$>`
+    /// Validation: `Vec<^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$>`
     #[validate(custom(function = "validate_vec_grant_type"))]
     pub grant_types: Vec<String>,
     /// Validation: `60 <= access_token_lifetime <= 86400`
@@ -170,9 +560,9 @@ pub struct EphemeralClientRequest {
     pub scope: Option<String>,
     pub require_auth_time: Option<bool>,
 
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
     pub access_token_signed_response_alg: Option<JwkKeyPairAlg>,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
     pub id_token_signed_response_alg: Option<JwkKeyPairAlg>,
 }
 
@@ -184,7 +574,7 @@ pub struct EventsListenParams {
     pub level: Option<EventLevel>,
 }
 
-fn default_scope() -> String {
+pub(crate) fn default_scope() -> String {
     String::from("openid")
 }
 
@@ -198,6 +588,8 @@ pub struct I18nRequest {
 pub enum I18nContent {
     Account,
     Authorize,
+    Consent,
+    Device,
     EmailChangeConfirm,
     Error,
     Index,
@@ -206,6 +598,68 @@ pub enum I18nContent {
     Register,
 }
 
+/// Token Introspection Request as defined in RFC 7662
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct IntrospectionRequest {
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub token: String,
+    /// Validation: `^[a-z0-9-_/]{2,128}$`
+    #[validate(regex(path = "RE_LOWERCASE", code = "^[a-z0-9-_/]{2,128}$"))]
+    pub token_type_hint: Option<String>,
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub client_secret: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub client_assertion: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub client_assertion_type: Option<String>,
+}
+
+impl IntrospectionRequest {
+    /// The client auth can be either sent inside the POST body, as a basic Authorization header,
+    /// or as a JWT client assertion.
+    /// Returns `None`, if the request does not contain any client credentials at all.
+    pub fn try_get_client_credentials(
+        &self,
+        req: &HttpRequest,
+    ) -> Option<Result<(String, ClientCredentials), ErrorResponse>> {
+        let has_basic_auth = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.starts_with("Basic "))
+            .unwrap_or(false);
+
+        if has_basic_auth || self.client_assertion.is_some() {
+            Some(client_credentials_from_req(
+                req,
+                &self.client_id,
+                &self.client_secret,
+                &self.client_assertion,
+                &self.client_assertion_type,
+            ))
+        } else {
+            // a possibly existing `Authorization: Bearer` header must not be parsed as basic auth
+            self.client_id.as_ref().map(|id| {
+                let creds = match &self.client_secret {
+                    Some(secret) => ClientCredentials::Secret(secret.clone()),
+                    None => ClientCredentials::None,
+                };
+                Ok((id.clone(), creds))
+            })
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
 pub struct LoginRequest {
     /// Validation: `email`
@@ -221,7 +675,10 @@ pub struct LoginRequest {
     ))]
     pub client_id: String,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    ///
+    /// Can be omitted, if a `request_uri` from a pushed authorization request is given
     #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    #[serde(default)]
     pub redirect_uri: String,
     /// Validation: `Vec<^[a-zA-Z0-9\\s]$>`
     #[validate(custom(function = "validate_vec_scope"))]
@@ -238,6 +695,23 @@ pub struct LoginRequest {
     /// Validation: `[a-zA-Z0-9-._~]{43,128}`
     #[validate(regex(path = "RE_CODE_CHALLENGE", code = "[a-zA-Z0-9-._~]{43,128}"))]
     pub code_challenge_method: Option<String>,
+    /// The OIDC `claims` request parameter as a JSON object
+    ///
+    /// Validation: `ClaimsRequest` JSON, max 4096 characters
+    #[validate(custom(function = "validate_claims_request"))]
+    pub claims: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub prompt: Option<String>,
+    /// The `request_uri` from a pushed authorization request. If given, all other authorization
+    /// params will be taken from the pushed request.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub request_uri: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -249,7 +723,10 @@ pub struct LoginRefreshRequest {
     ))]
     pub client_id: String,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    ///
+    /// Can be omitted, if a `request_uri` from a pushed authorization request is given
     #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    #[serde(default)]
     pub redirect_uri: String,
     /// Validation: `Vec<^[a-zA-Z0-9\\s]$>`
     #[validate(custom(function = "validate_vec_scope"))]
@@ -266,6 +743,28 @@ pub struct LoginRefreshRequest {
     /// Validation: `[a-zA-Z0-9-._~]{43,128}`
     #[validate(regex(path = "RE_CODE_CHALLENGE", code = "[a-zA-Z0-9-._~]{43,128}"))]
     pub code_challenge_method: Option<String>,
+    /// The OIDC `claims` request parameter as a JSON object
+    ///
+    /// Validation: `ClaimsRequest` JSON, max 4096 characters
+    #[validate(custom(function = "validate_claims_request"))]
+    pub claims: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub prompt: Option<String>,
+    /// The session will only be used, if the last active authentication is not older than this.
+    ///
+    /// Validation: `>= 0`
+    #[validate(range(min = 0))]
+    pub max_age: Option<i64>,
+    /// The `request_uri` from a pushed authorization request. If given, all other authorization
+    /// params will be taken from the pushed request.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub request_uri: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
@@ -279,6 +778,33 @@ pub struct LogoutRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub state: Option<String>,
+    /// Can be given instead of the `id_token_hint` to validate the `post_logout_redirect_uri`
+    ///
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: Option<String>,
+}
+
+impl LogoutRequest {
+    /// Returns the redirect location to the already validated `post_logout_redirect_uri` with
+    /// the properly encoded `state` appended, if one has been given.
+    pub fn post_logout_location(&self, target: &str) -> String {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return target.to_string(),
+        };
+        match Url::parse(target) {
+            Ok(mut url) => {
+                url.query_pairs_mut().append_pair("state", state);
+                url.to_string()
+            }
+            // cannot happen for an already validated `post_logout_redirect_uri`
+            Err(_) => target.to_string(),
+        }
+    }
 }
 
 #[derive(Deserialize, Validate, ToSchema)]
@@ -327,6 +853,81 @@ pub struct NewGroupRequest {
     pub group: String,
 }
 
+/// Pushed Authorization Request as defined in RFC 9126
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ParRequest {
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub client_secret: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub client_assertion: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub client_assertion_type: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub redirect_uri: String,
+    /// Validation: `[a-z0-9-_/]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE", code = "[a-z0-9-_/]{2,128}"))]
+    pub response_type: String,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    #[serde(default = "default_scope")]
+    pub scope: String,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub state: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub nonce: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub code_challenge: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub code_challenge_method: Option<String>,
+    /// The OIDC `claims` request parameter as a JSON object
+    ///
+    /// Validation: `ClaimsRequest` JSON, max 4096 characters
+    #[validate(custom(function = "validate_claims_request"))]
+    pub claims: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub prompt: Option<String>,
+    /// Validation: `>= 0`
+    #[validate(range(min = 0))]
+    pub max_age: Option<i64>,
+    /// Validation: max 256 characters
+    #[validate(length(max = 256))]
+    pub login_hint: Option<String>,
+}
+
+impl ParRequest {
+    // by RFC, the client auth can be either sent inside the POST body, or as an Authorization header
+    pub fn try_get_client_credentials(
+        &self,
+        req: &HttpRequest,
+    ) -> Result<(String, ClientCredentials), ErrorResponse> {
+        client_credentials_from_req(
+            req,
+            &self.client_id,
+            &self.client_secret,
+            &self.client_assertion,
+            &self.client_assertion_type,
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct PasswordHashTimesRequest {
     #[validate(range(min = 500))]
@@ -380,6 +981,37 @@ pub struct PasswordResetRequest {
     pub mfa_code: Option<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ProvisioningConnectorRequest {
+    pub typ: ProvisioningConnectorType,
+    /// For `scim`, the base URL of the downstream SCIM 2.0 API like
+    /// `https://app.example.com/scim/v2`. For `webhook`, the URL the events will be POSTed to.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub endpoint: String,
+    /// For `scim`, the `Bearer` token for the downstream API. For `webhook`, the key for the
+    /// `X-Rauthy-Signature` HMAC. The secret will only be updated if it is given. An empty string
+    /// removes it.
+    ///
+    /// Validation: `max length 256`
+    #[validate(length(max = 256))]
+    pub secret: Option<String>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RecoveryCodeAuthRequest {
+    /// The `code` from the login response, which asked for the MFA step
+    ///
+    /// Validation: `[a-zA-Z0-9]{48}`
+    #[validate(regex(path = "RE_ALNUM_48", code = "[a-zA-Z0-9]{48}"))]
+    pub code: String,
+    /// Validation: `[a-zA-Z0-9]{8}-[a-zA-Z0-9]{16}`
+    #[validate(regex(path = "RE_RECOVERY_CODE", code = "[a-zA-Z0-9]{8}-[a-zA-Z0-9]{16}"))]
+    pub recovery_code: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct RequestResetRequest {
     /// Validation: `email`
@@ -387,6 +1019,48 @@ pub struct RequestResetRequest {
     pub email: String,
 }
 
+/// Token Revocation Request as defined in RFC 7009
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RevokeRequest {
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub token: String,
+    /// Validation: `^[a-z0-9-_/]{2,128}$`
+    #[validate(regex(path = "RE_LOWERCASE", code = "^[a-z0-9-_/]{2,128}$"))]
+    pub token_type_hint: Option<String>,
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub client_secret: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub client_assertion: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub client_assertion_type: Option<String>,
+}
+
+impl RevokeRequest {
+    // by RFC, the client auth can be either sent inside the POST body, or as an Authorization header
+    pub fn try_get_client_credentials(
+        &self,
+        req: &HttpRequest,
+    ) -> Result<(String, ClientCredentials), ErrorResponse> {
+        client_credentials_from_req(
+            req,
+            &self.client_id,
+            &self.client_secret,
+            &self.client_assertion,
+            &self.client_assertion_type,
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct NewUserRequest {
     /// Validation: `email`
@@ -469,8 +1143,11 @@ pub struct ScopeRequest {
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct TokenRequest {
-    /// Validation: `^[a-z0-9-_/]{2,128}$`
-    #[validate(regex(path = "RE_LOWERCASE", code = "^[a-z0-9-_/]{2,128}$"))]
+    /// Validation: `^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$`
+    #[validate(regex(
+        path = "RE_GRANT_TYPES",
+        code = "^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$"
+    ))]
     pub grant_type: String,
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
@@ -487,6 +1164,12 @@ pub struct TokenRequest {
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub client_secret: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub client_assertion: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub client_assertion_type: Option<String>,
     /// Validation: `[a-zA-Z0-9-\\._~+/=]+`
     #[validate(regex(path = "RE_CODE_VERIFIER", code = "[a-zA-Z0-9-\\._~+/=]+"))]
     pub code_verifier: Option<String>,
@@ -497,40 +1180,117 @@ pub struct TokenRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub refresh_token: Option<String>,
+    /// Validation: `Vec<^[a-z0-9-_/\s]{2,128}$>`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub scope: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub audience: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub subject_token: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub subject_token_type: Option<String>,
+    /// Validation: `[a-zA-Z0-9-._~+/]+=*`
+    #[validate(regex(path = "RE_TOKEN_68", code = "[a-zA-Z0-9-._~+/]+=*"))]
+    pub actor_token: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub actor_token_type: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub requested_token_type: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub device_code: Option<String>,
 }
 
 impl TokenRequest {
     // by RFC, the client auth can be either sent inside the POST body, or as an Authorization header
-    pub fn try_get_client_id_secret(
+    pub fn try_get_client_credentials(
         &self,
         req: &HttpRequest,
-    ) -> Result<(String, Option<String>), ErrorResponse> {
-        let auth_header = req.headers().get(header::AUTHORIZATION).map(|h| {
-            let (_, b64) = h
-                .to_str()
-                .unwrap_or_default()
-                .split_once(' ')
-                .unwrap_or(("", ""));
-            b64
-        });
+    ) -> Result<(String, ClientCredentials), ErrorResponse> {
+        client_credentials_from_req(
+            req,
+            &self.client_id,
+            &self.client_secret,
+            &self.client_assertion,
+            &self.client_assertion_type,
+        )
+    }
+}
 
-        if let Some(header) = auth_header {
-            let decoded = String::from_utf8(base64_decode(header)?)?;
-            match decoded.split_once(':') {
-                None => Err(ErrorResponse::new(
-                    ErrorResponseType::BadRequest,
-                    "Bad Authorization header".to_string(),
-                )),
-                Some((client_id, client_secret)) => {
-                    Ok((client_id.to_string(), Some(client_secret.to_string())))
-                }
-            }
-        } else {
-            Ok((
-                self.client_id.clone().unwrap_or_default(),
-                self.client_secret.clone(),
-            ))
+/// The credentials a client has sent to authenticate itself at one of the OAuth endpoints.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientCredentials {
+    /// `client_secret_basic` or `client_secret_post`
+    Secret(String),
+    /// `private_key_jwt` or `client_secret_jwt` (RFC 7523)
+    Assertion(String),
+    None,
+}
+
+fn client_credentials_from_req(
+    req: &HttpRequest,
+    client_id: &Option<String>,
+    client_secret: &Option<String>,
+    client_assertion: &Option<String>,
+    client_assertion_type: &Option<String>,
+) -> Result<(String, ClientCredentials), ErrorResponse> {
+    if let Some(assertion) = client_assertion {
+        if client_assertion_type.as_deref() != Some(CLIENT_ASSERTION_TYPE_JWT_BEARER) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("invalid_client".to_string()),
+                "Unsupported 'client_assertion_type'".to_string(),
+            ));
+        }
+        if client_secret.is_some() || req.headers().contains_key(header::AUTHORIZATION) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("invalid_request".to_string()),
+                "Only a single client authentication method must be used".to_string(),
+            ));
+        }
+
+        // the 'client_id' is optional and will be taken from the 'sub' of the assertion
+        let sub = ClientAssertion::unverified_sub(assertion)?;
+        if matches!(client_id, Some(id) if id != &sub) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("invalid_client".to_string()),
+                "The 'client_id' does not match the client assertion".to_string(),
+            ));
         }
+        return Ok((sub, ClientCredentials::Assertion(assertion.clone())));
+    }
+
+    let auth_header = req.headers().get(header::AUTHORIZATION).map(|h| {
+        let (_, b64) = h
+            .to_str()
+            .unwrap_or_default()
+            .split_once(' ')
+            .unwrap_or(("", ""));
+        b64
+    });
+
+    if let Some(header) = auth_header {
+        let decoded = String::from_utf8(base64_decode(header)?)?;
+        match decoded.split_once(':') {
+            None => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Bad Authorization header".to_string(),
+            )),
+            Some((client_id, client_secret)) => Ok((
+                client_id.to_string(),
+                ClientCredentials::Secret(client_secret.to_string()),
+            )),
+        }
+    } else {
+        let creds = match client_secret {
+            Some(secret) => ClientCredentials::Secret(secret.clone()),
+            None => ClientCredentials::None,
+        };
+        Ok((client_id.clone().unwrap_or_default(), creds))
     }
 }
 
@@ -539,6 +1299,33 @@ pub struct TokenValidationRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub token: String,
+    /// The SHA-256 thumbprint of the TLS client certificate the token has been presented with.
+    /// Certificate bound tokens (RFC 8705) are only valid, if this matches their `cnf.x5t#S256`.
+    ///
+    /// Validation: `^[a-zA-Z0-9-_]{43}$`
+    #[validate(regex(path = "RE_CERT_THUMBPRINT", code = "^[a-zA-Z0-9-_]{43}$"))]
+    pub x5t_s256: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct TotpAuthRequest {
+    /// The `code` from the login response, which asked for the TOTP
+    ///
+    /// Validation: `[a-zA-Z0-9]{48}`
+    #[validate(regex(path = "RE_ALNUM_48", code = "[a-zA-Z0-9]{48}"))]
+    pub code: String,
+    /// Validation: `[0-9]{6}`
+    #[validate(regex(path = "RE_TOTP", code = "[0-9]{6}"))]
+    pub totp: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct TotpRegFinishRequest {
+    /// The first code from the authenticator app to confirm the enrollment
+    ///
+    /// Validation: `[0-9]{6}`
+    #[validate(regex(path = "RE_TOTP", code = "[0-9]{6}"))]
+    pub totp: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -560,12 +1347,12 @@ pub struct UpdateClientRequest {
     #[validate(custom(function = "validate_vec_uri"))]
     pub allowed_origins: Option<Vec<String>>,
     pub enabled: bool,
-    /// Validation: `Vec<^(authorization_code|client_credentials|password|refresh_token)$>`
+    /// Validation: `Vec<^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$>`
     #[validate(custom(function = "validate_vec_flows"))]
     pub flows_enabled: Vec<String>,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
     pub access_token_alg: JwkKeyPairAlg,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
     pub id_token_alg: JwkKeyPairAlg,
     pub refresh_token: bool,
     /// Validation: `10 <= auth_code_lifetime <= 300`
@@ -584,6 +1371,69 @@ pub struct UpdateClientRequest {
     #[validate(custom(function = "validate_vec_challenge"))]
     pub challenges: Option<Vec<String>>,
     pub force_mfa: bool,
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub token_exchange_audiences: Option<Vec<String>>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub backchannel_logout_uri: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub frontchannel_logout_uri: Option<String>,
+    #[serde(default)]
+    pub require_par: bool,
+    /// If set, users must grant the requested scopes on a consent screen
+    #[serde(default)]
+    pub require_consent: bool,
+    /// If set, each refresh token can only be used once and a reuse revokes the whole family
+    #[serde(default)]
+    pub refresh_token_rotation: bool,
+    /// The public keys of the client as a JSON Web Key Set, mutually exclusive with `jwks_uri`
+    pub jwks: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub jwks_uri: Option<String>,
+    /// Validation: `^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt|tls_client_auth|self_signed_tls_client_auth|none)$`
+    #[validate(regex(
+        path = "RE_TOKEN_ENDPOINT_AUTH_METHOD",
+        code = "^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt|tls_client_auth|self_signed_tls_client_auth|none)$"
+    ))]
+    pub token_endpoint_auth_method: Option<String>,
+    /// The expected subject DN of the client certificate for `tls_client_auth`
+    ///
+    /// Validation: `^[a-zA-Z0-9À-ÿ_.,=+@:/'\s-]{2,512}$`
+    #[validate(regex(path = "RE_SUBJECT_DN", code = "^[a-zA-Z0-9À-ÿ_.,=+@:/'\\s-]{2,512}$"))]
+    pub tls_client_auth_subject_dn: Option<String>,
+    /// The SHA-256 thumbprints of the client certificates for `self_signed_tls_client_auth`
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9-_]{43}$>`
+    #[validate(custom(function = "validate_vec_cert_thumbprint"))]
+    pub tls_client_auth_thumbprints: Option<Vec<String>>,
+    /// Validation: `^(RSA-OAEP-256|ECDH-ES)$`
+    #[validate(regex(path = "RE_JWE_ALG", code = "^(RSA-OAEP-256|ECDH-ES)$"))]
+    pub id_token_encrypted_response_alg: Option<String>,
+    /// Validation: `^(A256GCM)$`
+    #[validate(regex(path = "RE_JWE_ENC", code = "^(A256GCM)$"))]
+    pub id_token_encrypted_response_enc: Option<String>,
+    /// Validation: `^(RSA-OAEP-256|ECDH-ES)$`
+    #[validate(regex(path = "RE_JWE_ALG", code = "^(RSA-OAEP-256|ECDH-ES)$"))]
+    pub userinfo_encrypted_response_alg: Option<String>,
+    /// Validation: `^(A256GCM)$`
+    #[validate(regex(path = "RE_JWE_ENC", code = "^(A256GCM)$"))]
+    pub userinfo_encrypted_response_enc: Option<String>,
+    /// Validation: `^(RS256|RS384|RS512|PS256|ES256|ES384|EdDSA)$`
+    pub userinfo_signed_response_alg: Option<JwkKeyPairAlg>,
+    /// Validation: `^(public|pairwise)$`
+    #[validate(regex(path = "RE_SUBJECT_TYPE", code = "^(public|pairwise)$"))]
+    pub subject_type: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub sector_identifier_uri: Option<String>,
+    /// The only URIs, from which request objects may be fetched via `request_uri`
+    ///
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub request_uris: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -632,6 +1482,123 @@ pub struct UpdateUserSelfRequest {
     pub password_new: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UpstreamAuthRequest {
+    /// Validation: `^[a-zA-Z0-9]+$`
+    #[validate(regex(path = "RE_ALNUM", code = "^[a-zA-Z0-9]+$"))]
+    pub provider_id: String,
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: String,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub redirect_uri: String,
+    /// Validation: `[a-zA-Z0-9À-ÿ-s]{2,128}`
+    #[validate(regex(path = "RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ÿ-s]{2,128}"))]
+    #[serde(default = "default_scope")]
+    pub scope: String,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub state: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub nonce: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub code_challenge: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub code_challenge_method: Option<String>,
+    /// The OIDC `claims` request parameter as a JSON object
+    ///
+    /// Validation: `ClaimsRequest` JSON, max 4096 characters
+    #[validate(custom(function = "validate_claims_request"))]
+    pub claims: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-z0-9-_/\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub prompt: Option<String>,
+    /// Validation: `>= 0`
+    #[validate(range(min = 0))]
+    pub max_age: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UpstreamCallbackRequest {
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub code: Option<String>,
+    /// Validation: `^[a-zA-Z0-9]+$`
+    #[validate(regex(path = "RE_ALNUM", code = "^[a-zA-Z0-9]+$"))]
+    pub state: String,
+    /// Validation: `^[a-z0-9-_/]{2,128}$`
+    #[validate(regex(path = "RE_LOWERCASE", code = "^[a-z0-9-_/]{2,128}$"))]
+    pub error: Option<String>,
+    /// Validation: `max length 256`
+    #[validate(length(max = 256))]
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct UpstreamProviderRequest {
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{2,128}`
+    #[validate(regex(path = "RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ÿ-\\s]{2,128}"))]
+    pub name: String,
+    pub enabled: bool,
+    /// The issuer URL of the upstream provider. If any of the endpoints are not given, they will
+    /// be looked up via `{issuer}/.well-known/openid-configuration`.
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub issuer: String,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub authorization_endpoint: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub token_endpoint: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub userinfo_endpoint: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub client_id: String,
+    /// The secret will only be updated if it is given. Leave it empty for public upstream clients.
+    ///
+    /// Validation: `max length 256`
+    #[validate(length(max = 256))]
+    pub client_secret: Option<String>,
+    /// Validation: `[a-z0-9-_/\\s]{2,128}`
+    #[validate(regex(path = "RE_LOWERCASE_SPACE", code = "[a-z0-9-_/\\s]{2,128}"))]
+    pub scope: String,
+    pub use_pkce: bool,
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub claim_sub: String,
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub claim_email: String,
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub claim_email_verified: Option<String>,
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub claim_given_name: Option<String>,
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub claim_family_name: Option<String>,
+    /// If `true`, an already existing local user with the same, upstream-verified email will be
+    /// linked to the upstream account on first login.
+    pub auto_link: bool,
+    /// If `true`, a new local user will be created on first login, if none can be found.
+    pub auto_provision: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UserAttrConfigRequest {
     /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
@@ -716,6 +1683,34 @@ pub enum WhoamiRequestParam {
 
 // validation helpers
 
+fn validate_auth_request_ref(value: &AuthRequestRef) -> Result<(), ValidationError> {
+    if value.request.is_some() && value.request_uri.is_some() {
+        return Err(ValidationError::new(
+            "'request' and 'request_uri' are mutually exclusive",
+        ));
+    }
+    if value.request.is_none() && value.request_uri.is_none() {
+        return Err(ValidationError::new(
+            "'request' or 'request_uri' is missing",
+        ));
+    }
+    Ok(())
+}
+
+fn validate_claims_request(value: &str) -> Result<(), ValidationError> {
+    if value.len() > 4096 {
+        return Err(ValidationError::new(
+            "'claims' must not exceed 4096 characters",
+        ));
+    }
+    if ClaimsRequest::parse(value).is_err() {
+        return Err(ValidationError::new(
+            "'claims' is not a valid claims request",
+        ));
+    }
+    Ok(())
+}
+
 fn validate_vec_attr(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
 
@@ -735,6 +1730,19 @@ fn validate_vec_attr(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+fn validate_vec_cert_thumbprint(value: &[String]) -> Result<(), ValidationError> {
+    let mut err = None;
+    value.iter().for_each(|v| {
+        if !RE_CERT_THUMBPRINT.is_match(v) {
+            err = Some("^[a-zA-Z0-9-_]{43}$");
+        }
+    });
+    if let Some(e) = err {
+        return Err(ValidationError::new(e));
+    }
+    Ok(())
+}
+
 fn validate_vec_challenge(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
 
@@ -762,7 +1770,7 @@ fn validate_vec_flows(value: &[String]) -> Result<(), ValidationError> {
     } else {
         value.iter().for_each(|v| {
             if !RE_FLOWS.is_match(v) {
-                err = Some("^(authorization_code|client_credentials|password|refresh_token)$");
+                err = Some("^(authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code)$");
             }
         });
     }
@@ -803,7 +1811,7 @@ fn validate_vec_grant_type(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
     value.iter().for_each(|v| {
         if !RE_GRANT_TYPES.is_match(v) {
-            err = Some("authorization_code|client_credentials|password|refresh_token");
+            err = Some("authorization_code|client_credentials|password|refresh_token|urn:ietf:params:oauth:grant-type:token-exchange|urn:ietf:params:oauth:grant-type:device_code");
         }
     });
     if let Some(e) = err {
@@ -840,8 +1848,9 @@ fn validate_vec_scope(value: &[String]) -> Result<(), ValidationError> {
 
 #[cfg(test)]
 mod tests {
-    use crate::request::ColorsRequest;
+    use crate::request::{AuthRequest, ClaimsRequest, ColorsRequest, LogoutRequest};
     use css_color::Srgb;
+    use std::collections::HashSet;
     use std::str::FromStr;
 
     #[test]
@@ -868,4 +1877,107 @@ mod tests {
         assert!(Srgb::from_str("hsl(360 100%)").is_err());
         assert!(Srgb::from_str(" ").is_err());
     }
+
+    #[test]
+    pub fn test_auth_request_prompt() {
+        let req = AuthRequest {
+            client_id: "test".to_string(),
+            redirect_uri: "http://localhost:3000/callback?tenant=1".to_string(),
+            response_type: "code".to_string(),
+            scope: "openid".to_string(),
+            state: Some("a b&c=d".to_string()),
+            nonce: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            claims: None,
+            acr_values: None,
+            prompt: Some("login  consent".to_string()),
+            max_age: None,
+            login_hint: None,
+        };
+        assert!(req.has_prompt("login"));
+        assert!(req.has_prompt("consent"));
+        assert!(!req.has_prompt("none"));
+        // only whole values must match
+        assert!(!req.has_prompt("log"));
+        assert!(!req.has_prompt(""));
+
+        assert_eq!(
+            req.error_redirect_uri("login_required", "No valid session"),
+            "http://localhost:3000/callback?tenant=1&error=login_required&error_description=No+valid+session&state=a+b%26c%3Dd"
+        );
+
+        let req = AuthRequest {
+            state: None,
+            prompt: None,
+            ..req
+        };
+        assert!(!req.has_prompt("login"));
+        assert_eq!(
+            req.error_redirect_uri("interaction_required", "Consent & more"),
+            "http://localhost:3000/callback?tenant=1&error=interaction_required&error_description=Consent+%26+more"
+        );
+    }
+
+    #[test]
+    pub fn test_claims_request() {
+        let claims = ClaimsRequest::parse(
+            r#"{
+                "id_token": {
+                    "email": null,
+                    "acr": {"essential": true, "values": ["mfa", "pwd"]},
+                    "department": {"essential": true}
+                },
+                "userinfo": {"given_name": null, "birthdate_custom": null}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(claims.acr_values(), vec!["mfa", "pwd"]);
+        assert!(claims.wants_id_token("email"));
+        assert!(!claims.wants_id_token("given_name"));
+        assert_eq!(claims.custom_id_token(), vec!["department"]);
+        assert_eq!(claims.custom_userinfo(), vec!["birthdate_custom"]);
+
+        let mut granted_claims = claims.clone();
+        granted_claims.retain_granted(&HashSet::from(["birthdate_custom"]));
+        assert!(granted_claims.custom_id_token().is_empty());
+        assert_eq!(granted_claims.custom_userinfo(), vec!["birthdate_custom"]);
+        // standard claims are never filtered
+        assert!(granted_claims.wants_id_token("email"));
+        assert_eq!(granted_claims.acr_values(), vec!["mfa", "pwd"]);
+
+        let mut granted_claims = claims.clone();
+        granted_claims.retain_granted(&HashSet::default());
+        assert!(granted_claims.custom_id_token().is_empty());
+        assert!(granted_claims.custom_userinfo().is_empty());
+
+        assert!(ClaimsRequest::parse("{}").unwrap().acr_values().is_empty());
+        assert!(ClaimsRequest::parse("[]").is_err());
+        assert!(ClaimsRequest::parse(r#"{"id_token": "email"}"#).is_err());
+    }
+
+    #[test]
+    fn test_post_logout_location() {
+        let mut req = LogoutRequest {
+            id_token_hint: None,
+            post_logout_redirect_uri: None,
+            state: None,
+            client_id: None,
+        };
+        assert_eq!(
+            req.post_logout_location("https://app.example.com/logout"),
+            "https://app.example.com/logout"
+        );
+
+        req.state = Some("a b&c=d".to_string());
+        assert_eq!(
+            req.post_logout_location("https://app.example.com/logout"),
+            "https://app.example.com/logout?state=a+b%26c%3Dd"
+        );
+        assert_eq!(
+            req.post_logout_location("https://app.example.com/logout?tenant=1"),
+            "https://app.example.com/logout?tenant=1&state=a+b%26c%3Dd"
+        );
+    }
 }
@@ -1,9 +1,15 @@
 use crate::entity::api_keys::{ApiKey, ApiKeyAccess};
 use crate::entity::clients::Client;
+use crate::entity::clients_dyn::ClientDyn;
 use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg, JwkKeyPairType, JWKS};
 use crate::entity::password::PasswordPolicy;
+use crate::entity::provisioning::{
+    ProvisioningAction, ProvisioningConnector, ProvisioningConnectorType, ProvisioningDelivery,
+    ProvisioningResource, ProvisioningStatus,
+};
 use crate::entity::scopes::Scope;
 use crate::entity::sessions::SessionState;
+use crate::entity::upstream_providers::UpstreamProvider;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
 use crate::entity::users::{AccountType, User};
 use crate::entity::webauthn::PasskeyEntity;
@@ -15,6 +21,7 @@ use rio_api::formatter::TriplesFormatter;
 use rio_api::model::{Literal, NamedNode, Subject, Term, Triple};
 use rio_turtle::TurtleFormatter;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use time::OffsetDateTime;
 use tracing::debug;
 use utoipa::ToSchema;
@@ -86,6 +93,40 @@ pub struct ClientResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub challenges: Option<Vec<String>>,
     pub force_mfa: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_exchange_audiences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backchannel_logout_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontchannel_logout_uri: Option<String>,
+    pub require_par: bool,
+    pub require_consent: bool,
+    pub refresh_token_rotation: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_uri: Option<String>,
+    pub token_endpoint_auth_method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_client_auth_subject_dn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_client_auth_thumbprints: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token_encrypted_response_alg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token_encrypted_response_enc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_encrypted_response_alg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_encrypted_response_enc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_signed_response_alg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sector_identifier_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_uris: Option<Vec<String>>,
 }
 
 impl From<Client> for ClientResponse {
@@ -97,6 +138,9 @@ impl From<Client> for ClientResponse {
         let scopes = client.get_scopes();
         let default_scopes = client.get_default_scopes();
         let challenges = client.get_challenges();
+        let token_exchange_audiences = client.get_token_exchange_audiences();
+        let tls_client_auth_thumbprints = client.get_tls_client_auth_thumbprints();
+        let request_uris = client.get_request_uris();
 
         Self {
             id: client.id,
@@ -116,6 +160,25 @@ impl From<Client> for ClientResponse {
             default_scopes,
             challenges,
             force_mfa: client.force_mfa,
+            token_exchange_audiences,
+            backchannel_logout_uri: client.backchannel_logout_uri,
+            frontchannel_logout_uri: client.frontchannel_logout_uri,
+            require_par: client.require_par,
+            require_consent: client.require_consent,
+            refresh_token_rotation: client.refresh_token_rotation,
+            jwks: client.jwks,
+            jwks_uri: client.jwks_uri,
+            token_endpoint_auth_method: client.token_endpoint_auth_method,
+            tls_client_auth_subject_dn: client.tls_client_auth_subject_dn,
+            tls_client_auth_thumbprints,
+            id_token_encrypted_response_alg: client.id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc: client.id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg: client.userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc: client.userinfo_encrypted_response_enc,
+            userinfo_signed_response_alg: client.userinfo_signed_response_alg,
+            subject_type: client.subject_type,
+            sector_identifier_uri: client.sector_identifier_uri,
+            request_uris,
         }
     }
 }
@@ -127,6 +190,131 @@ pub struct ClientSecretResponse {
     pub secret: Option<String>,
 }
 
+/// The details of a pending consent, which will be shown on the consent page
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConsentInfoResponse {
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+/// Device Authorization Response as defined in RFC 8628
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    pub interval: u32,
+}
+
+/// Client Information Response as defined in RFC 7591 / RFC 7592
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DynClientResponse {
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub client_id_issued_at: i64,
+    // 0 means the secret does not expire - only set for confidential clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret_expires_at: Option<i64>,
+    // will only be returned once after the registration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration_access_token: Option<String>,
+    pub registration_client_uri: String,
+    pub redirect_uris: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_logout_redirect_uris: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backchannel_logout_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontchannel_logout_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_uri: Option<String>,
+    pub grant_types: Vec<String>,
+    pub response_types: Vec<String>,
+    pub token_endpoint_auth_method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_client_auth_subject_dn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token_encrypted_response_alg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token_encrypted_response_enc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_encrypted_response_alg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_encrypted_response_enc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_signed_response_alg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sector_identifier_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_uris: Option<Vec<String>>,
+    pub scope: String,
+    pub default_max_age: i32,
+    pub access_token_signed_response_alg: String,
+    pub id_token_signed_response_alg: String,
+}
+
+impl DynClientResponse {
+    pub fn build(
+        issuer: &str,
+        client: Client,
+        client_dyn: &ClientDyn,
+        client_secret: Option<String>,
+        registration_access_token: Option<String>,
+    ) -> Self {
+        let redirect_uris = client.get_redirect_uris();
+        let post_logout_redirect_uris = client.get_post_logout_uris();
+        let grant_types = client.get_flows();
+        let scope = client.get_scopes().join(" ");
+        let request_uris = client.get_request_uris();
+
+        Self {
+            client_secret_expires_at: client_secret.as_ref().map(|_| 0),
+            registration_client_uri: format!("{}/oidc/register/{}", issuer, client.id),
+            client_id: client.id,
+            client_name: client.name,
+            client_secret,
+            client_id_issued_at: client_dyn.created,
+            registration_access_token,
+            redirect_uris,
+            post_logout_redirect_uris,
+            backchannel_logout_uri: client.backchannel_logout_uri,
+            frontchannel_logout_uri: client.frontchannel_logout_uri,
+            jwks: client
+                .jwks
+                .as_deref()
+                .and_then(|jwks| serde_json::from_str(jwks).ok()),
+            jwks_uri: client.jwks_uri,
+            grant_types,
+            response_types: vec!["code".to_string()],
+            token_endpoint_auth_method: client.token_endpoint_auth_method,
+            tls_client_auth_subject_dn: client.tls_client_auth_subject_dn,
+            id_token_encrypted_response_alg: client.id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc: client.id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg: client.userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc: client.userinfo_encrypted_response_enc,
+            userinfo_signed_response_alg: client.userinfo_signed_response_alg,
+            subject_type: client.subject_type,
+            sector_identifier_uri: client.sector_identifier_uri,
+            request_uris,
+            scope,
+            default_max_age: client.access_token_lifetime,
+            access_token_signed_response_alg: client.access_token_alg,
+            id_token_signed_response_alg: client.id_token_alg,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct EncKeysResponse<'a> {
     pub active: &'a str,
@@ -146,6 +334,55 @@ pub struct HealthResponse {
     pub cache_connected_hosts: Option<usize>,
 }
 
+/// Token introspection response as defined in RFC 7662
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<JktClaim>,
+}
+
+/// The claims of a signed introspection response (RFC 9701)
+#[derive(Debug, Serialize)]
+pub struct IntrospectionJwtClaims<'a> {
+    pub iss: &'a str,
+    pub aud: &'a str,
+    pub iat: i64,
+    pub token_introspection: &'a IntrospectionResponse,
+}
+
+/// The claims of a signed userinfo response
+#[derive(Debug, Serialize)]
+pub struct UserinfoJwtClaims<'a> {
+    pub iss: &'a str,
+    pub aud: &'a str,
+    pub iat: i64,
+    #[serde(flatten)]
+    pub userinfo: &'a Userinfo,
+}
+
 #[derive(Debug, Default, Serialize, ToSchema)]
 pub struct JWKSCerts {
     pub keys: Vec<JWKSPublicKeyCerts>,
@@ -198,6 +435,36 @@ pub struct LoginTimeResponse {
     pub num_cpus: usize,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LogoutResponse {
+    /// Front-Channel Logout URIs of all clients the session has been logged into, which must be
+    /// loaded inside (hidden) iframes to finish the logout
+    pub frontchannel_logout_uris: Vec<String>,
+    /// The validated `post_logout_redirect_uri` with the `state` appended, if any was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_logout_redirect_uri: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OfflineTokenResponse {
+    /// The id of the token family, which stays the same across refreshes
+    pub id: String,
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    pub created_at: i64,
+    pub last_used: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+}
+
+/// Pushed Authorization Response as defined in RFC 9126
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ParResponse {
+    pub request_uri: String,
+    pub expires_in: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PasskeyResponse {
     pub name: String,
@@ -252,6 +519,74 @@ impl From<PasswordPolicy> for PasswordPolicyResponse {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProvisioningConnectorResponse {
+    pub id: String,
+    pub client_id: String,
+    pub typ: ProvisioningConnectorType,
+    pub endpoint: String,
+    /// `true` if a secret is configured. The secret itself will never be returned.
+    pub has_secret: bool,
+    pub enabled: bool,
+    pub created_at: i64,
+}
+
+impl From<ProvisioningConnector> for ProvisioningConnectorResponse {
+    fn from(c: ProvisioningConnector) -> Self {
+        Self {
+            id: c.id,
+            client_id: c.client_id,
+            typ: c.typ,
+            endpoint: c.endpoint,
+            has_secret: c.secret.is_some(),
+            enabled: c.enabled,
+            created_at: c.created_at,
+        }
+    }
+}
+
+/// The delivery status of a single queued change. All timestamps are unix millis.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProvisioningDeliveryResponse {
+    pub id: String,
+    pub connector_id: String,
+    pub resource_typ: ProvisioningResource,
+    pub resource_id: String,
+    pub action: ProvisioningAction,
+    pub status: ProvisioningStatus,
+    pub attempts: i64,
+    pub next_attempt: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<i64>,
+}
+
+impl From<ProvisioningDelivery> for ProvisioningDeliveryResponse {
+    fn from(d: ProvisioningDelivery) -> Self {
+        Self {
+            id: d.id,
+            connector_id: d.connector_id,
+            resource_typ: d.resource_typ,
+            resource_id: d.resource_id,
+            action: d.action,
+            status: d.status,
+            attempts: d.attempts,
+            next_attempt: d.next_attempt,
+            last_error: d.last_error,
+            created_at: d.created_at,
+            delivered_at: d.delivered_at,
+        }
+    }
+}
+
+/// The plain recovery codes, which will only be shown to the user once after their creation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    pub codes: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ScopeResponse {
     pub id: String,
@@ -329,6 +664,82 @@ pub struct TokenInfo {
     pub cnf: Option<JktClaim>,
 }
 
+/// Returned instead of a passkey request, when the user needs to provide a TOTP during the login
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpLoginResponse {
+    pub code: String,
+    pub user_id: String,
+    pub exp: u64,
+    /// Always `true` to tell this response apart from a [WebauthnLoginResponse]
+    pub totp: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpRegStartResponse {
+    /// The base32 encoded secret for a manual setup of the authenticator app
+    pub secret: String,
+    /// The `otpauth://` URI, which should be shown as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpstreamProviderResponse {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub client_id: String,
+    /// `true` if a client secret is configured. The secret itself will never be returned.
+    pub confidential: bool,
+    pub scope: String,
+    pub use_pkce: bool,
+    pub claim_sub: String,
+    pub claim_email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_email_verified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_family_name: Option<String>,
+    pub auto_link: bool,
+    pub auto_provision: bool,
+}
+
+impl From<UpstreamProvider> for UpstreamProviderResponse {
+    fn from(p: UpstreamProvider) -> Self {
+        Self {
+            id: p.id,
+            name: p.name,
+            enabled: p.enabled,
+            issuer: p.issuer,
+            authorization_endpoint: p.authorization_endpoint,
+            token_endpoint: p.token_endpoint,
+            userinfo_endpoint: p.userinfo_endpoint,
+            client_id: p.client_id,
+            confidential: p.secret.is_some(),
+            scope: p.scope,
+            use_pkce: p.use_pkce,
+            claim_sub: p.claim_sub,
+            claim_email: p.claim_email,
+            claim_email_verified: p.claim_email_verified,
+            claim_given_name: p.claim_given_name,
+            claim_family_name: p.claim_family_name,
+            auto_link: p.auto_link,
+            auto_provision: p.auto_provision,
+        }
+    }
+}
+
+/// Minimal information about an upstream provider, which is publicly available for the login page
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpstreamProviderLoginResponse {
+    pub id: String,
+    pub name: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserAttrConfigResponse {
     pub values: Vec<UserAttrConfigEntity>,
@@ -357,6 +768,16 @@ pub struct UserAttrValuesResponse {
     pub values: Vec<UserAttrValueResponse>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserConsentResponse {
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+    pub last_update: i64,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Userinfo {
     pub id: String,
@@ -369,6 +790,9 @@ pub struct Userinfo {
     pub preferred_username: String,
     pub given_name: String,
     pub family_name: String,
+    /// Custom user attributes, which have been requested via the `claims` param
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom: Option<HashMap<String, serde_json::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -419,6 +843,7 @@ pub struct UserResponse {
     pub user_expires: Option<i64>,
     pub account_type: UserAccountTypeResponse,
     pub webauthn_user_id: Option<String>,
+    pub totp_enabled: bool,
 }
 
 impl From<User> for UserResponse {
@@ -449,6 +874,7 @@ impl From<User> for UserResponse {
             user_expires: u.user_expires,
             account_type,
             webauthn_user_id: u.webauthn_user_id,
+            totp_enabled: u.totp_enabled,
         }
     }
 }
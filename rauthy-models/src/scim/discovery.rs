@@ -0,0 +1,183 @@
+use crate::entity::user_attr::UserAttrConfigEntity;
+use crate::scim::{
+    SCIM_MAX_RESULTS, SCIM_SCHEMA_GROUP, SCIM_SCHEMA_RAUTHY_USER, SCIM_SCHEMA_RESOURCE_TYPE,
+    SCIM_SCHEMA_SCHEMA, SCIM_SCHEMA_SP_CONFIG, SCIM_SCHEMA_USER,
+};
+use rauthy_common::constants::SCIM_ALLOW_PASSWORD;
+use serde_json::{json, Value};
+
+/// The service provider configuration (RFC 7643 Section 5).
+pub fn service_provider_config(issuer: &str) -> Value {
+    json!({
+        "schemas": [SCIM_SCHEMA_SP_CONFIG],
+        "patch": { "supported": true },
+        "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+        "filter": { "supported": true, "maxResults": SCIM_MAX_RESULTS },
+        "changePassword": { "supported": *SCIM_ALLOW_PASSWORD },
+        "sort": { "supported": false },
+        "etag": { "supported": false },
+        "authenticationSchemes": [{
+            "type": "oauthbearertoken",
+            "name": "API Key",
+            "description": "An API Key with access to the 'Scim' group, given as \
+                `Authorization: Bearer <name>$<secret>`",
+            "primary": true
+        }],
+        "meta": {
+            "resourceType": "ServiceProviderConfig",
+            "location": format!("{}/scim/v2/ServiceProviderConfig", issuer)
+        }
+    })
+}
+
+/// The supported resource types (RFC 7643 Section 6).
+pub fn resource_types(issuer: &str) -> Vec<Value> {
+    vec![
+        json!({
+            "schemas": [SCIM_SCHEMA_RESOURCE_TYPE],
+            "id": "User",
+            "name": "User",
+            "endpoint": "/Users",
+            "description": "User Account",
+            "schema": SCIM_SCHEMA_USER,
+            "schemaExtensions": [{ "schema": SCIM_SCHEMA_RAUTHY_USER, "required": false }],
+            "meta": {
+                "resourceType": "ResourceType",
+                "location": format!("{}/scim/v2/ResourceTypes/User", issuer)
+            }
+        }),
+        json!({
+            "schemas": [SCIM_SCHEMA_RESOURCE_TYPE],
+            "id": "Group",
+            "name": "Group",
+            "endpoint": "/Groups",
+            "description": "Group",
+            "schema": SCIM_SCHEMA_GROUP,
+            "meta": {
+                "resourceType": "ResourceType",
+                "location": format!("{}/scim/v2/ResourceTypes/Group", issuer)
+            }
+        }),
+    ]
+}
+
+/// The schemas of all resources (RFC 7643 Section 7). The extension for the custom user
+/// attributes is built from the currently configured `UserAttrConfigEntity`s.
+pub fn schemas(issuer: &str, custom_attrs: Vec<UserAttrConfigEntity>) -> Vec<Value> {
+    let multi_value = |name: &str, mutability: &str| {
+        complex(
+            name,
+            true,
+            mutability,
+            vec![
+                attr("value", "string", false, true, mutability),
+                attr("display", "string", false, false, "readOnly"),
+                attr("type", "string", false, false, mutability),
+                attr("primary", "boolean", false, false, mutability),
+                attr("$ref", "reference", false, false, "readOnly"),
+            ],
+        )
+    };
+
+    let mut user_name = attr("userName", "string", false, true, "readWrite");
+    user_name["uniqueness"] = json!("server");
+    // passwords can only be provisioned, if this has been explicitly allowed
+    let password_mutability = if *SCIM_ALLOW_PASSWORD {
+        "writeOnly"
+    } else {
+        "readOnly"
+    };
+    let mut password = attr("password", "string", false, false, password_mutability);
+    password["returned"] = json!("never");
+
+    let user = schema(
+        issuer,
+        SCIM_SCHEMA_USER,
+        "User",
+        "User Account",
+        vec![
+            user_name,
+            complex(
+                "name",
+                false,
+                "readWrite",
+                vec![
+                    attr("formatted", "string", false, false, "readOnly"),
+                    attr("familyName", "string", false, true, "readWrite"),
+                    attr("givenName", "string", false, true, "readWrite"),
+                ],
+            ),
+            attr("displayName", "string", false, false, "readOnly"),
+            attr("preferredLanguage", "string", false, false, "readWrite"),
+            attr("active", "boolean", false, false, "readWrite"),
+            password,
+            multi_value("emails", "readOnly"),
+            multi_value("groups", "readOnly"),
+            multi_value("roles", "readWrite"),
+        ],
+    );
+
+    let group = schema(
+        issuer,
+        SCIM_SCHEMA_GROUP,
+        "Group",
+        "Group",
+        vec![
+            attr("displayName", "string", false, true, "readWrite"),
+            multi_value("members", "readWrite"),
+        ],
+    );
+
+    let custom = custom_attrs
+        .into_iter()
+        .map(|a| {
+            let mut value = attr(&a.name, "string", false, false, "readWrite");
+            if let Some(desc) = a.desc {
+                value["description"] = json!(desc);
+            }
+            value
+        })
+        .collect();
+    let extension = schema(
+        issuer,
+        SCIM_SCHEMA_RAUTHY_USER,
+        "RauthyUser",
+        "Custom user attributes",
+        custom,
+    );
+
+    vec![user, group, extension]
+}
+
+fn schema(issuer: &str, id: &str, name: &str, description: &str, attrs: Vec<Value>) -> Value {
+    json!({
+        "schemas": [SCIM_SCHEMA_SCHEMA],
+        "id": id,
+        "name": name,
+        "description": description,
+        "attributes": attrs,
+        "meta": {
+            "resourceType": "Schema",
+            "location": format!("{}/scim/v2/Schemas/{}", issuer, id)
+        }
+    })
+}
+
+fn attr(name: &str, typ: &str, multi_valued: bool, required: bool, mutability: &str) -> Value {
+    json!({
+        "name": name,
+        "type": typ,
+        "multiValued": multi_valued,
+        "required": required,
+        "caseExact": false,
+        "mutability": mutability,
+        "returned": "default",
+        "uniqueness": "none"
+    })
+}
+
+fn complex(name: &str, multi_valued: bool, mutability: &str, sub_attrs: Vec<Value>) -> Value {
+    let mut value = attr(name, "complex", multi_valued, false, mutability);
+    value["subAttributes"] = json!(sub_attrs);
+    value
+}
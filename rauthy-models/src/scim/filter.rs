@@ -0,0 +1,787 @@
+use crate::scim::{ScimError, ScimErrorType};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+// a filter is always a single query param and a sane one will never come close to this
+const FILTER_MAX_LEN: usize = 1024;
+// the maximum nesting of parentheses, `not` and value filters
+const FILTER_MAX_DEPTH: usize = 8;
+
+/// An attribute like `userName`, `name.givenName` or a fully qualified one like
+/// `urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:department`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrPath {
+    pub urn: Option<String>,
+    pub attr: String,
+    pub sub_attr: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Co,
+    Sw,
+    Ew,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// A parsed filter expression (RFC 7644 Section 3.4.2.2), which is evaluated against the JSON
+/// representation of a resource. All string comparisons are case-insensitive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScimFilter {
+    And(Box<ScimFilter>, Box<ScimFilter>),
+    Or(Box<ScimFilter>, Box<ScimFilter>),
+    Not(Box<ScimFilter>),
+    Present(AttrPath),
+    Compare(AttrPath, CompareOp, Value),
+    // `emails[type eq "work"]` -> matches, if any single value matches the inner filter
+    ValuePath(AttrPath, Box<ScimFilter>),
+}
+
+/// A condition for the `users` table, which has been translated from a [ScimFilter].
+/// The `params` must be bound in order to the `$1`, `$2`, ... placeholders in the `clause`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlFilter {
+    pub clause: String,
+    pub params: Vec<String>,
+}
+
+/// The target of a PATCH operation (RFC 7644 Section 3.5.2) like `name.givenName`,
+/// `members[value eq "abc"]` or `emails[type eq "work"].value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchPath {
+    pub attr: AttrPath,
+    pub filter: Option<ScimFilter>,
+    // the sub-attribute after a value filter
+    pub sub_attr: Option<String>,
+}
+
+impl AttrPath {
+    pub fn parse(value: &str) -> Result<Self, ScimError> {
+        let (urn, rest) = match value.rfind(':') {
+            Some(i) => (Some(value[..i].to_string()), &value[i + 1..]),
+            None => (None, value),
+        };
+        let (attr, sub_attr) = match rest.split_once('.') {
+            Some((attr, sub)) => (attr, Some(sub.to_string())),
+            None => (rest, None),
+        };
+
+        let is_valid = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/' | '$'))
+        };
+        if !is_valid(attr) || !sub_attr.as_deref().map(is_valid).unwrap_or(true) {
+            return Err(ScimError::bad_request(
+                ScimErrorType::InvalidPath,
+                format!("Invalid attribute path '{}'", value),
+            ));
+        }
+
+        Ok(Self {
+            urn,
+            attr: attr.to_string(),
+            sub_attr,
+        })
+    }
+
+    /// Returns `true` if the attribute belongs to one of the core schemas, which means it lives
+    /// on the top level of a resource.
+    pub fn is_core(&self) -> bool {
+        self.urn
+            .as_deref()
+            .map(|urn| {
+                urn.to_ascii_lowercase()
+                    .starts_with("urn:ietf:params:scim:schemas:core:")
+            })
+            .unwrap_or(true)
+    }
+
+    /// Resolves all values of this attribute. Multi-valued attributes will be flattened.
+    fn resolve<'a>(&self, resource: &'a Value) -> Vec<&'a Value> {
+        let mut current = resource;
+        if !self.is_core() {
+            match get_ci(current, self.urn.as_deref().unwrap_or_default()) {
+                Some(ext) => current = ext,
+                None => return Vec::default(),
+            }
+        }
+
+        let values = match get_ci(current, &self.attr) {
+            Some(Value::Array(values)) => values.iter().collect::<Vec<&Value>>(),
+            Some(value) => vec![value],
+            None => return Vec::default(),
+        };
+        match &self.sub_attr {
+            None => values,
+            Some(sub) => values.into_iter().filter_map(|v| get_ci(v, sub)).collect(),
+        }
+    }
+}
+
+impl CompareOp {
+    fn parse(value: &str) -> Result<Self, ScimError> {
+        let op = match value.to_ascii_lowercase().as_str() {
+            "eq" => Self::Eq,
+            "ne" => Self::Ne,
+            "co" => Self::Co,
+            "sw" => Self::Sw,
+            "ew" => Self::Ew,
+            "gt" => Self::Gt,
+            "ge" => Self::Ge,
+            "lt" => Self::Lt,
+            "le" => Self::Le,
+            _ => {
+                return Err(ScimError::bad_request(
+                    ScimErrorType::InvalidFilter,
+                    format!("Unknown operator '{}'", value),
+                ))
+            }
+        };
+        Ok(op)
+    }
+
+    fn compare(&self, actual: &Value, expected: &Value) -> bool {
+        match (actual, expected) {
+            (Value::String(a), Value::String(b)) => {
+                let a = a.to_lowercase();
+                let b = b.to_lowercase();
+                match self {
+                    Self::Co => a.contains(&b),
+                    Self::Sw => a.starts_with(&b),
+                    Self::Ew => a.ends_with(&b),
+                    _ => self.is_ordering(a.cmp(&b)),
+                }
+            }
+            (Value::Number(a), Value::Number(b)) => match (a.as_f64(), b.as_f64()) {
+                (Some(a), Some(b)) => a
+                    .partial_cmp(&b)
+                    .map(|ord| self.is_ordering(ord))
+                    .unwrap_or(false),
+                _ => false,
+            },
+            (Value::Bool(a), Value::Bool(b)) => match self {
+                Self::Eq => a == b,
+                Self::Ne => a != b,
+                _ => false,
+            },
+            (a, Value::Null) => match self {
+                Self::Eq => a.is_null(),
+                Self::Ne => !a.is_null(),
+                _ => false,
+            },
+            _ => *self == Self::Ne,
+        }
+    }
+
+    fn is_ordering(&self, ord: Ordering) -> bool {
+        match self {
+            Self::Eq => ord == Ordering::Equal,
+            Self::Ne => ord != Ordering::Equal,
+            Self::Gt => ord == Ordering::Greater,
+            Self::Ge => ord != Ordering::Less,
+            Self::Lt => ord == Ordering::Less,
+            Self::Le => ord != Ordering::Greater,
+            Self::Co | Self::Sw | Self::Ew => false,
+        }
+    }
+}
+
+impl ScimFilter {
+    pub fn parse(value: &str) -> Result<Self, ScimError> {
+        let mut parser = Parser::new(value)?;
+        let filter = parser.parse_or()?;
+        parser.expect_end()?;
+        Ok(filter)
+    }
+
+    /// Translates the filter into a condition for the `users` table, which selects at least all
+    /// matching users. Only the parts of an `and` that map to a column might be translated, so
+    /// the results must still be checked with [matches](Self::matches).
+    pub fn users_sql(&self) -> Option<SqlFilter> {
+        let mut params = Vec::new();
+        self.to_sql(false, &mut params)
+            .map(|clause| SqlFilter { clause, params })
+    }
+
+    /// With `exact` set, the condition must select exactly the matching users, which is needed
+    /// for anything below a `not`.
+    fn to_sql(&self, exact: bool, params: &mut Vec<String>) -> Option<String> {
+        match self {
+            Self::And(a, b) => {
+                let a = sql_or_rollback(a, exact, params);
+                let b = sql_or_rollback(b, exact, params);
+                match (a, b) {
+                    (Some(a), Some(b)) => Some(format!("({} AND {})", a, b)),
+                    (Some(c), None) | (None, Some(c)) if !exact => Some(c),
+                    _ => None,
+                }
+            }
+            Self::Or(a, b) => {
+                let a = a.to_sql(exact, params)?;
+                let b = b.to_sql(exact, params)?;
+                Some(format!("({} OR {})", a, b))
+            }
+            Self::Not(f) => f.to_sql(true, params).map(|c| format!("NOT {}", c)),
+            Self::Present(path) => match user_column(path)? {
+                "enabled" => None,
+                col => Some(format!("({} <> '')", col)),
+            },
+            Self::Compare(path, op, value) => {
+                let col = user_column(path)?;
+                if col == "enabled" {
+                    return match (op, value) {
+                        (CompareOp::Eq, Value::Bool(b)) => Some(format!("(enabled = {})", b)),
+                        (CompareOp::Ne, Value::Bool(b)) => Some(format!("(enabled <> {})", b)),
+                        _ => None,
+                    };
+                }
+
+                // Only ASCII values are translated, because SQLite's `LOWER` does not know
+                // about any other characters, while the filter compares case-insensitive.
+                let value = match value {
+                    Value::String(s) if s.is_ascii() => s.to_ascii_lowercase(),
+                    _ => return None,
+                };
+                let escaped = || {
+                    value
+                        .replace('\\', "\\\\")
+                        .replace('%', "\\%")
+                        .replace('_', "\\_")
+                };
+                let (cmp, param) = match op {
+                    CompareOp::Eq => ("=", value.clone()),
+                    CompareOp::Ne => ("<>", value.clone()),
+                    CompareOp::Co => ("LIKE", format!("%{}%", escaped())),
+                    CompareOp::Sw => ("LIKE", format!("{}%", escaped())),
+                    CompareOp::Ew => ("LIKE", format!("%{}", escaped())),
+                    _ => return None,
+                };
+                params.push(param);
+                let escape = if cmp == "LIKE" { " ESCAPE '\\'" } else { "" };
+                Some(format!(
+                    "(LOWER({}) {} ${}{})",
+                    col,
+                    cmp,
+                    params.len(),
+                    escape
+                ))
+            }
+            Self::ValuePath(_, _) => None,
+        }
+    }
+
+    pub fn matches(&self, resource: &Value) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(resource) && b.matches(resource),
+            Self::Or(a, b) => a.matches(resource) || b.matches(resource),
+            Self::Not(f) => !f.matches(resource),
+            Self::Present(path) => path.resolve(resource).into_iter().any(is_present),
+            Self::Compare(path, op, expected) => path.resolve(resource).into_iter().any(|v| {
+                // complex multi-valued attributes without a sub-attribute compare their `value`
+                let v = if v.is_object() {
+                    get_ci(v, "value")
+                } else {
+                    Some(v)
+                };
+                v.map(|v| op.compare(v, expected)).unwrap_or(false)
+            }),
+            Self::ValuePath(path, f) => path.resolve(resource).into_iter().any(|v| f.matches(v)),
+        }
+    }
+}
+
+impl PatchPath {
+    pub fn parse(value: &str) -> Result<Self, ScimError> {
+        let mut parser = Parser::new(value)?;
+        let attr = match parser.next() {
+            Some(Token::Word(w)) => AttrPath::parse(&w)?,
+            _ => return Err(invalid_path(value)),
+        };
+
+        let mut filter = None;
+        let mut sub_attr = None;
+        if parser.peek() == Some(&Token::LBracket) {
+            parser.pos += 1;
+            filter = Some(parser.parse_nested()?);
+            parser.expect(Token::RBracket)?;
+
+            if let Some(Token::Word(w)) = parser.peek() {
+                match w.strip_prefix('.') {
+                    Some(sub) if !sub.is_empty() => sub_attr = Some(sub.to_string()),
+                    _ => return Err(invalid_path(value)),
+                }
+                parser.pos += 1;
+            }
+        }
+        if parser.peek().is_some() {
+            return Err(invalid_path(value));
+        }
+
+        Ok(Self {
+            attr,
+            filter,
+            sub_attr,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, ScimError> {
+        if input.len() > FILTER_MAX_LEN {
+            return Err(invalid_filter(format!(
+                "Filter exceeds the maximum length of {} characters",
+                FILTER_MAX_LEN
+            )));
+        }
+
+        Ok(Self {
+            tokens: tokenize(input)?,
+            pos: 0,
+            depth: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), ScimError> {
+        if self.next() == Some(token.clone()) {
+            Ok(())
+        } else {
+            Err(invalid_filter(format!("Expected {:?}", token)))
+        }
+    }
+
+    fn expect_end(&self) -> Result<(), ScimError> {
+        if self.pos < self.tokens.len() {
+            Err(invalid_filter(format!(
+                "Unexpected {:?}",
+                self.tokens[self.pos]
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<ScimFilter, ScimError> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = ScimFilter::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<ScimFilter, ScimError> {
+        let mut left = self.parse_factor()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            let right = self.parse_factor()?;
+            left = ScimFilter::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// Parses the expression inside a group with a limited nesting depth, so a crafted filter
+    /// can never overflow the stack.
+    fn parse_nested(&mut self) -> Result<ScimFilter, ScimError> {
+        if self.depth >= FILTER_MAX_DEPTH {
+            return Err(invalid_filter(format!(
+                "Filter exceeds the maximum nesting depth of {}",
+                FILTER_MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        let filter = self.parse_or();
+        self.depth -= 1;
+        filter
+    }
+
+    fn parse_factor(&mut self) -> Result<ScimFilter, ScimError> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            self.expect(Token::LParen)?;
+            let filter = self.parse_nested()?;
+            self.expect(Token::RParen)?;
+            return Ok(ScimFilter::Not(Box::new(filter)));
+        }
+
+        match self.next() {
+            Some(Token::LParen) => {
+                let filter = self.parse_nested()?;
+                self.expect(Token::RParen)?;
+                Ok(filter)
+            }
+
+            Some(Token::Word(w)) => {
+                let path = AttrPath::parse(&w)
+                    .map_err(|_| invalid_filter(format!("Invalid attribute '{}'", w)))?;
+
+                match self.next() {
+                    Some(Token::LBracket) => {
+                        let filter = self.parse_nested()?;
+                        self.expect(Token::RBracket)?;
+                        Ok(ScimFilter::ValuePath(path, Box::new(filter)))
+                    }
+                    Some(Token::Word(op)) if op.eq_ignore_ascii_case("pr") => {
+                        Ok(ScimFilter::Present(path))
+                    }
+                    Some(Token::Word(op)) => {
+                        let op = CompareOp::parse(&op)?;
+                        let value = match self.next() {
+                            Some(Token::Str(s)) => Value::String(s),
+                            Some(Token::Word(w)) => parse_literal(&w)?,
+                            _ => return Err(invalid_filter(format!("Missing value for '{}'", w))),
+                        };
+                        Ok(ScimFilter::Compare(path, op, value))
+                    }
+                    _ => Err(invalid_filter(format!("Missing operator for '{}'", w))),
+                }
+            }
+
+            Some(token) => Err(invalid_filter(format!("Unexpected {:?}", token))),
+            None => Err(invalid_filter("Unexpected end of filter".to_string())),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ScimError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '[' => tokens.push(Token::LBracket),
+            ']' => tokens.push(Token::RBracket),
+
+            '"' => {
+                let mut end = None;
+                let mut escaped = false;
+                for (i, c) in chars.by_ref() {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        end = Some(i);
+                        break;
+                    }
+                }
+                let end = end.ok_or_else(|| invalid_filter("Unterminated string".to_string()))?;
+                // string values are JSON strings, including the escaping
+                let s = serde_json::from_str::<String>(&input[start..=end])
+                    .map_err(|_| invalid_filter("Invalid string value".to_string()))?;
+                tokens.push(Token::Str(s));
+            }
+
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '"') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(input[start..end].to_string()));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_literal(value: &str) -> Result<Value, ScimError> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        "null" => Ok(Value::Null),
+        _ => serde_json::from_str::<serde_json::Number>(value)
+            .map(Value::Number)
+            .map_err(|_| invalid_filter(format!("Invalid value '{}'", value))),
+    }
+}
+
+/// Translates the filter and removes all params it might have added, if this was not possible.
+fn sql_or_rollback(filter: &ScimFilter, exact: bool, params: &mut Vec<String>) -> Option<String> {
+    let len = params.len();
+    let res = filter.to_sql(exact, params);
+    if res.is_none() {
+        params.truncate(len);
+    }
+    res
+}
+
+/// Returns the `users` column for a `ScimUser` attribute, if there is a direct mapping.
+fn user_column(path: &AttrPath) -> Option<&'static str> {
+    if !path.is_core() {
+        return None;
+    }
+    let attr = path.attr.to_ascii_lowercase();
+    let sub_attr = path.sub_attr.as_deref().map(|s| s.to_ascii_lowercase());
+    match (attr.as_str(), sub_attr.as_deref()) {
+        ("id", None) => Some("id"),
+        ("username", None) | ("emails", None) | ("emails", Some("value")) => Some("email"),
+        ("name", Some("givenname")) => Some("given_name"),
+        ("name", Some("familyname")) => Some("family_name"),
+        ("active", None) => Some("enabled"),
+        _ => None,
+    }
+}
+
+/// Returns the value for the given key of an object, while the key is matched case-insensitive.
+pub(crate) fn get_ci<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    value
+        .as_object()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v)
+}
+
+fn is_present(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+        _ => true,
+    }
+}
+
+fn invalid_filter(detail: String) -> ScimError {
+    ScimError::bad_request(ScimErrorType::InvalidFilter, detail)
+}
+
+fn invalid_path(path: &str) -> ScimError {
+    ScimError::bad_request(
+        ScimErrorType::InvalidPath,
+        format!("Invalid path '{}'", path),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user() -> Value {
+        json!({
+            "schemas": [
+                "urn:ietf:params:scim:schemas:core:2.0:User",
+                "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User"
+            ],
+            "id": "2819c223",
+            "userName": "Bjensen@example.com",
+            "name": { "givenName": "Barbara", "familyName": "Jensen" },
+            "active": true,
+            "emails": [
+                { "value": "bjensen@example.com", "type": "work", "primary": true },
+                { "value": "babs@jensen.org", "type": "home" }
+            ],
+            "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User": {
+                "department": "IT",
+                "level": 3
+            },
+            "meta": { "created": "2024-01-23T04:56:22Z" }
+        })
+    }
+
+    fn matches(filter: &str) -> bool {
+        ScimFilter::parse(filter).unwrap().matches(&user())
+    }
+
+    #[test]
+    fn test_filter_compare() {
+        assert!(matches(r#"userName eq "bjensen@example.com""#));
+        assert!(matches(r#"USERNAME Eq "BJENSEN@EXAMPLE.COM""#));
+        assert!(!matches(r#"userName ne "bjensen@example.com""#));
+        assert!(matches(r#"name.familyName co "ens""#));
+        assert!(matches(r#"userName sw "bj""#));
+        assert!(matches(r#"userName ew "example.com""#));
+        assert!(matches(r#"meta.created gt "2024-01-01T00:00:00Z""#));
+        assert!(!matches(r#"meta.created lt "2024-01-01T00:00:00Z""#));
+        assert!(matches("active eq true"));
+        assert!(!matches("active eq false"));
+        assert!(!matches(r#"title eq "dr""#));
+        assert!(!matches("title pr"));
+        assert!(matches("name.givenName pr"));
+    }
+
+    #[test]
+    fn test_filter_multi_valued() {
+        assert!(matches(r#"emails co "jensen.org""#));
+        assert!(matches(r#"emails.value eq "babs@jensen.org""#));
+        assert!(matches(
+            r#"emails[type eq "work" and value co "@example.com"]"#
+        ));
+        assert!(!matches(r#"emails[type eq "home" and primary eq true]"#));
+    }
+
+    #[test]
+    fn test_filter_logical() {
+        assert!(matches(
+            r#"userName eq "nope" or (name.givenName eq "barbara" and active eq true)"#
+        ));
+        assert!(!matches(
+            r#"userName eq "nope" or name.givenName eq "nope""#
+        ));
+        assert!(matches(r#"not (userName eq "nope")"#));
+        assert!(!matches(r#"not(userName pr)"#));
+    }
+
+    #[test]
+    fn test_filter_urn() {
+        assert!(matches(
+            r#"urn:ietf:params:scim:schemas:core:2.0:User:userName eq "bjensen@example.com""#
+        ));
+        assert!(matches(
+            r#"urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:department eq "it""#
+        ));
+        assert!(matches(
+            "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:level ge 3"
+        ));
+        assert!(!matches(
+            "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:level lt 3"
+        ));
+    }
+
+    #[test]
+    fn test_filter_escaped_string() {
+        let filter = ScimFilter::parse(r#"displayName eq "a \"quoted\" name""#).unwrap();
+        assert!(filter.matches(&json!({ "displayName": "A \"Quoted\" Name" })));
+    }
+
+    #[test]
+    fn test_filter_invalid() {
+        assert!(ScimFilter::parse("").is_err());
+        assert!(ScimFilter::parse("userName").is_err());
+        assert!(ScimFilter::parse(r#"userName xx "a""#).is_err());
+        assert!(ScimFilter::parse(r#"userName eq "a"#).is_err());
+        assert!(ScimFilter::parse(r#"(userName eq "a""#).is_err());
+        assert!(ScimFilter::parse(r#"userName eq "a" and"#).is_err());
+        assert!(ScimFilter::parse(r#"userName eq "a" userName"#).is_err());
+        assert!(ScimFilter::parse("level gt abc").is_err());
+    }
+
+    #[test]
+    fn test_filter_limits() {
+        let nested =
+            |depth: usize| format!("{}userName pr{}", "(".repeat(depth), ")".repeat(depth));
+        assert!(ScimFilter::parse(&nested(FILTER_MAX_DEPTH)).is_ok());
+        assert!(ScimFilter::parse(&nested(FILTER_MAX_DEPTH + 1)).is_err());
+        // must be rejected without any recursion at all
+        assert!(ScimFilter::parse(&nested(100_000)).is_err());
+
+        let not = format!(
+            "{}userName pr{}",
+            "not (".repeat(FILTER_MAX_DEPTH + 1),
+            ")".repeat(FILTER_MAX_DEPTH + 1)
+        );
+        assert!(ScimFilter::parse(&not).is_err());
+        let value_path = format!(
+            "{}value pr{}",
+            "emails[".repeat(FILTER_MAX_DEPTH + 1),
+            "]".repeat(FILTER_MAX_DEPTH + 1)
+        );
+        assert!(ScimFilter::parse(&value_path).is_err());
+
+        let long = vec![r#"userName eq "a""#; 100].join(" or ");
+        assert!(long.len() > FILTER_MAX_LEN);
+        assert!(ScimFilter::parse(&long).is_err());
+        let long = vec![r#"userName eq "a""#; 10].join(" or ");
+        assert!(ScimFilter::parse(&long).is_ok());
+    }
+
+    #[test]
+    fn test_filter_users_sql() {
+        let sql = |filter: &str| ScimFilter::parse(filter).unwrap().users_sql();
+
+        let res = sql(r#"userName eq "Bjensen@example.com""#).unwrap();
+        assert_eq!(res.clause, "(LOWER(email) = $1)");
+        assert_eq!(res.params, vec!["bjensen@example.com"]);
+
+        let res = sql(r#"name.familyName sw "jen_" and active eq true"#).unwrap();
+        assert_eq!(
+            res.clause,
+            r"((LOWER(family_name) LIKE $1 ESCAPE '\') AND (enabled = true))"
+        );
+        assert_eq!(res.params, vec![r"jen\_%"]);
+
+        // only the translatable part of an `and` is used
+        let res = sql(r#"title eq "dr" and emails.value co "@example.com""#).unwrap();
+        assert_eq!(res.clause, r"(LOWER(email) LIKE $1 ESCAPE '\')");
+        assert_eq!(res.params, vec!["%@example.com%"]);
+
+        let res = sql(r#"not (id eq "a" or userName pr)"#).unwrap();
+        assert_eq!(res.clause, "NOT ((LOWER(id) = $1) OR (email <> ''))");
+
+        assert!(sql(r#"title eq "dr" or userName eq "a""#).is_none());
+        assert!(sql(r#"not (title eq "dr" and userName eq "a")"#).is_none());
+        assert!(sql(r#"emails[type eq "work"]"#).is_none());
+        assert!(sql(r#"meta.created gt "2024-01-01T00:00:00Z""#).is_none());
+        assert!(sql(r#"userName eq "jürgen@example.com""#).is_none());
+        assert!(sql("urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:level ge 3").is_none());
+    }
+
+    #[test]
+    fn test_patch_path() {
+        let path = PatchPath::parse("name.givenName").unwrap();
+        assert_eq!(path.attr.attr, "name");
+        assert_eq!(path.attr.sub_attr.as_deref(), Some("givenName"));
+        assert!(path.filter.is_none());
+
+        let path = PatchPath::parse(r#"emails[type eq "work"].value"#).unwrap();
+        assert_eq!(path.attr.attr, "emails");
+        assert!(path.filter.is_some());
+        assert_eq!(path.sub_attr.as_deref(), Some("value"));
+
+        let path =
+            PatchPath::parse("urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:department")
+                .unwrap();
+        assert!(!path.attr.is_core());
+        assert_eq!(path.attr.attr, "department");
+
+        assert!(PatchPath::parse("").is_err());
+        assert!(PatchPath::parse("members[").is_err());
+        assert!(PatchPath::parse(r#"members[value eq "a"]value"#).is_err());
+        assert!(PatchPath::parse("name.").is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use crate::entity::groups::Group;
+use crate::entity::user_attr::UserAttrValueEntity;
+use crate::entity::users::User;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, HttpResponseBuilder, ResponseError};
+use chrono::{DateTime, SecondsFormat};
+use rauthy_common::constants::APPLICATION_SCIM_JSON;
+use rauthy_common::error_response::ErrorResponse;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+use validator::Validate;
+
+pub mod discovery;
+pub mod filter;
+pub mod patch;
+
+pub const SCIM_SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const SCIM_SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+// the extension for the custom user attributes, which can be configured in Rauthy
+pub const SCIM_SCHEMA_RAUTHY_USER: &str = "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User";
+pub const SCIM_SCHEMA_LIST_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const SCIM_SCHEMA_PATCH_OP: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const SCIM_SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+pub const SCIM_SCHEMA_SP_CONFIG: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+pub const SCIM_SCHEMA_RESOURCE_TYPE: &str = "urn:ietf:params:scim:schemas:core:2.0:ResourceType";
+pub const SCIM_SCHEMA_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Schema";
+
+/// The maximum page size for all list responses, no matter which `count` has been requested.
+pub const SCIM_MAX_RESULTS: usize = 1000;
+
+/// The error types from RFC 7644 Section 3.12, which can actually be returned by Rauthy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScimErrorType {
+    InvalidFilter,
+    Uniqueness,
+    InvalidSyntax,
+    InvalidPath,
+    NoTarget,
+    InvalidValue,
+    Mutability,
+}
+
+/// The error format for all SCIM endpoints (RFC 7644 Section 3.12). Every `ErrorResponse` from
+/// the rest of the backend will be converted into this.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimError {
+    pub schemas: Vec<String>,
+    // the HTTP status code as a string, as the RFC demands
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scim_type: Option<ScimErrorType>,
+    pub detail: String,
+}
+
+impl ScimError {
+    pub fn new(status: StatusCode, scim_type: Option<ScimErrorType>, detail: String) -> Self {
+        Self {
+            schemas: vec![SCIM_SCHEMA_ERROR.to_string()],
+            status: status.as_u16().to_string(),
+            scim_type,
+            detail,
+        }
+    }
+
+    pub fn bad_request(scim_type: ScimErrorType, detail: String) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, Some(scim_type), detail)
+    }
+
+    pub fn uniqueness(detail: String) -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            Some(ScimErrorType::Uniqueness),
+            detail,
+        )
+    }
+}
+
+impl Display for ScimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "status: {} detail: {}", self.status, self.detail)
+    }
+}
+
+impl ResponseError for ScimError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+            .parse::<u16>()
+            .ok()
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponseBuilder::new(self.status_code())
+            .content_type(APPLICATION_SCIM_JSON)
+            .body(serde_json::to_string(&self).unwrap())
+    }
+}
+
+impl From<ErrorResponse> for ScimError {
+    fn from(value: ErrorResponse) -> Self {
+        Self::new(value.status_code(), None, value.message)
+    }
+}
+
+impl From<validator::ValidationErrors> for ScimError {
+    fn from(value: validator::ValidationErrors) -> Self {
+        Self::bad_request(ScimErrorType::InvalidValue, value.to_string())
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListParams {
+    /// Validation: max 1024 characters
+    #[validate(length(max = 1024))]
+    pub filter: Option<String>,
+    // 1-based index of the first result
+    pub start_index: Option<i64>,
+    pub count: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimListResponse<T> {
+    pub schemas: Vec<String>,
+    pub total_results: usize,
+    pub start_index: usize,
+    pub items_per_page: usize,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<T>,
+}
+
+impl<T> ScimListResponse<T> {
+    /// Returns the requested page of the already filtered resources. A `startIndex` < 1 is
+    /// interpreted as 1 and a negative `count` as 0 (RFC 7644 Section 3.4.2.4).
+    pub fn paginate(resources: Vec<T>, start_index: Option<i64>, count: Option<i64>) -> Self {
+        let total_results = resources.len();
+        let start_index = start_index.unwrap_or(1).max(1) as usize;
+        let count = count
+            .map(|c| c.max(0) as usize)
+            .unwrap_or(SCIM_MAX_RESULTS)
+            .min(SCIM_MAX_RESULTS);
+
+        let resources = resources
+            .into_iter()
+            .skip(start_index - 1)
+            .take(count)
+            .collect::<Vec<T>>();
+
+        Self {
+            schemas: vec![SCIM_SCHEMA_LIST_RESPONSE.to_string()],
+            total_results,
+            start_index,
+            items_per_page: resources.len(),
+            resources,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimMeta {
+    pub resource_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    pub location: String,
+}
+
+/// A single value of a multi-valued attribute like `emails`, `roles` or `members`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScimMultiValue {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+    #[serde(default, rename = "$ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimName {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub given_name: Option<String>,
+}
+
+/// The SCIM representation of a `User`. The `userName` is always the E-Mail address.
+///
+/// All `Option`s, which are not given with a PUT, will keep their current value.
+/// `groups` are read-only and can only be managed via the `/Groups` endpoints.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub user_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<ScimName>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub emails: Vec<ScimMultiValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_language: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_bool_lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub active: Option<bool>,
+    // write-only
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<ScimMultiValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<ScimMultiValue>>,
+    #[serde(
+        default,
+        rename = "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub attributes: Option<Map<String, Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+impl ScimUser {
+    pub fn from_user(
+        user: User,
+        groups: &[Group],
+        attrs: Vec<UserAttrValueEntity>,
+        issuer: &str,
+    ) -> Self {
+        let location = format!("{}/scim/v2/Users/{}", issuer, user.id);
+
+        let user_groups = user
+            .get_groups()
+            .into_iter()
+            .filter_map(|name| {
+                groups
+                    .iter()
+                    .find(|g| g.name == name)
+                    .map(|g| ScimMultiValue {
+                        value: g.id.clone(),
+                        display: Some(g.name.clone()),
+                        reference: Some(format!("{}/scim/v2/Groups/{}", issuer, g.id)),
+                        ..Default::default()
+                    })
+            })
+            .collect();
+        let roles = user
+            .get_roles()
+            .into_iter()
+            .map(|value| ScimMultiValue {
+                value,
+                ..Default::default()
+            })
+            .collect();
+        let attributes = attrs
+            .into_iter()
+            .filter_map(|attr| {
+                serde_json::from_slice::<Value>(&attr.value)
+                    .ok()
+                    .map(|value| (attr.key, value))
+            })
+            .collect();
+
+        Self {
+            schemas: vec![
+                SCIM_SCHEMA_USER.to_string(),
+                SCIM_SCHEMA_RAUTHY_USER.to_string(),
+            ],
+            display_name: Some(format!("{} {}", user.given_name, user.family_name)),
+            name: Some(ScimName {
+                formatted: Some(format!("{} {}", user.given_name, user.family_name)),
+                family_name: Some(user.family_name),
+                given_name: Some(user.given_name),
+            }),
+            emails: vec![ScimMultiValue {
+                value: user.email.clone(),
+                typ: Some("work".to_string()),
+                primary: Some(true),
+                ..Default::default()
+            }],
+            user_name: user.email,
+            preferred_language: Some(user.language.as_str().to_string()),
+            active: Some(user.enabled),
+            password: None,
+            groups: user_groups,
+            roles: Some(roles),
+            attributes: Some(attributes),
+            meta: Some(ScimMeta {
+                resource_type: "User".to_string(),
+                created: Some(format_timestamp(user.created_at)),
+                location,
+            }),
+            id: Some(user.id),
+        }
+    }
+}
+
+/// The SCIM representation of a `Group`. The `displayName` is the name of the group.
+///
+/// If `members` is not given with a PUT, the current members will be kept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub members: Option<Vec<ScimMultiValue>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ScimMeta>,
+}
+
+impl ScimGroup {
+    pub fn from_group(group: Group, users: &[User], issuer: &str) -> Self {
+        let members = users
+            .iter()
+            .filter(|u| u.get_groups().contains(&group.name))
+            .map(|u| ScimMultiValue {
+                value: u.id.clone(),
+                display: Some(u.email.clone()),
+                reference: Some(format!("{}/scim/v2/Users/{}", issuer, u.id)),
+                ..Default::default()
+            })
+            .collect();
+
+        Self {
+            schemas: vec![SCIM_SCHEMA_GROUP.to_string()],
+            meta: Some(ScimMeta {
+                resource_type: "Group".to_string(),
+                created: None,
+                location: format!("{}/scim/v2/Groups/{}", issuer, group.id),
+            }),
+            id: Some(group.id),
+            display_name: group.name,
+            members: Some(members),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScimPatchOperation {
+    // case-insensitive, because some clients send `Add`, `Replace` and `Remove`
+    pub op: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScimPatchOp {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+fn format_timestamp(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Some clients, like Azure AD, send booleans as strings like `"False"` inside PATCH values.
+fn deserialize_bool_lenient<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Bool(b)) => Ok(Some(b)),
+        Some(Value::String(s)) if s.eq_ignore_ascii_case("true") => Ok(Some(true)),
+        Some(Value::String(s)) if s.eq_ignore_ascii_case("false") => Ok(Some(false)),
+        Some(v) => Err(D::Error::custom(format!("invalid boolean value: {}", v))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_paginate() {
+        let res = ScimListResponse::paginate((1..=10).collect(), None, None);
+        assert_eq!(res.total_results, 10);
+        assert_eq!(res.start_index, 1);
+        assert_eq!(res.items_per_page, 10);
+
+        let res = ScimListResponse::paginate((1..=10).collect(), Some(4), Some(3));
+        assert_eq!(res.total_results, 10);
+        assert_eq!(res.resources, vec![4, 5, 6]);
+
+        let res = ScimListResponse::paginate((1..=10).collect(), Some(-1), Some(-5));
+        assert_eq!(res.start_index, 1);
+        assert!(res.resources.is_empty());
+
+        let res = ScimListResponse::paginate((1..=10).collect(), Some(20), None);
+        assert_eq!(res.total_results, 10);
+        assert_eq!(res.items_per_page, 0);
+    }
+
+    #[test]
+    fn test_scim_user_deserialize() {
+        let user = serde_json::from_value::<ScimUser>(json!({
+            "schemas": [SCIM_SCHEMA_USER],
+            "userName": "scim@localhost.de",
+            "name": { "givenName": "Scim", "familyName": "User" },
+            "active": "False",
+            "password": "SuperSecure123$",
+            SCIM_SCHEMA_RAUTHY_USER: { "department": "IT" }
+        }))
+        .unwrap();
+
+        assert_eq!(user.user_name, "scim@localhost.de");
+        assert_eq!(user.active, Some(false));
+        assert_eq!(user.password.as_deref(), Some("SuperSecure123$"));
+        assert_eq!(user.roles, None);
+        assert_eq!(
+            user.attributes.unwrap().get("department"),
+            Some(&json!("IT"))
+        );
+
+        // the password is write-only
+        let user = ScimUser {
+            password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert!(serde_json::to_value(user)
+            .unwrap()
+            .get("password")
+            .is_none());
+    }
+}
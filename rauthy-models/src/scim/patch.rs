@@ -0,0 +1,440 @@
+use crate::scim::filter::{AttrPath, PatchPath, ScimFilter};
+use crate::scim::{ScimError, ScimErrorType, ScimPatchOperation};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PatchOpKind {
+    Add,
+    Remove,
+    Replace,
+}
+
+impl PatchOpKind {
+    fn parse(value: &str) -> Result<Self, ScimError> {
+        match value.to_ascii_lowercase().as_str() {
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            "replace" => Ok(Self::Replace),
+            _ => Err(ScimError::bad_request(
+                ScimErrorType::InvalidSyntax,
+                format!("Unknown PATCH operation '{}'", value),
+            )),
+        }
+    }
+}
+
+/// Applies all PATCH operations (RFC 7644 Section 3.5.2) to the JSON representation of a
+/// resource. The result must be validated afterward just like a full replace.
+///
+/// Removing a multi-valued attribute leaves an empty array behind, so it is not mistaken for
+/// an attribute, which should keep its current value.
+pub fn apply_patch(resource: &mut Value, ops: &[ScimPatchOperation]) -> Result<(), ScimError> {
+    for op in ops {
+        let kind = PatchOpKind::parse(&op.op)?;
+
+        match &op.path {
+            Some(path) => {
+                let path = PatchPath::parse(path)?;
+                let (obj, key) = target_mut(resource, &path.attr)?;
+                match &path.filter {
+                    None => match kind {
+                        PatchOpKind::Remove => remove_value(obj, &key, op.value.as_ref()),
+                        _ => set_value(obj, &key, required_value(op)?.clone(), kind),
+                    },
+                    Some(filter) => apply_filtered(
+                        obj,
+                        &key,
+                        filter,
+                        path.sub_attr.as_deref(),
+                        kind,
+                        op.value.as_ref(),
+                    )?,
+                }
+            }
+
+            None => {
+                if kind == PatchOpKind::Remove {
+                    return Err(ScimError::bad_request(
+                        ScimErrorType::NoTarget,
+                        "A 'remove' operation needs a 'path'".to_string(),
+                    ));
+                }
+                let values = match required_value(op)? {
+                    Value::Object(values) => values,
+                    _ => {
+                        return Err(ScimError::bad_request(
+                            ScimErrorType::InvalidValue,
+                            "Operations without a 'path' need an object as 'value'".to_string(),
+                        ))
+                    }
+                };
+
+                for (key, value) in values {
+                    // a whole extension schema, which contains its attributes as an object
+                    if key.to_ascii_lowercase().starts_with("urn:") && value.is_object() {
+                        let ext = child_object(obj_mut(resource)?, key)?;
+                        for (k, v) in value.as_object().unwrap() {
+                            set_value(ext, k, v.clone(), kind);
+                        }
+                    } else {
+                        let attr = AttrPath::parse(key)?;
+                        let (obj, key) = target_mut(resource, &attr)?;
+                        set_value(obj, &key, value.clone(), kind);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn required_value(op: &ScimPatchOperation) -> Result<&Value, ScimError> {
+    op.value.as_ref().ok_or_else(|| {
+        ScimError::bad_request(
+            ScimErrorType::InvalidValue,
+            format!("The '{}' operation needs a 'value'", op.op),
+        )
+    })
+}
+
+/// Returns the object containing the target attribute and the key of the attribute inside it.
+/// Missing parent objects will be created.
+fn target_mut<'a>(
+    resource: &'a mut Value,
+    attr: &AttrPath,
+) -> Result<(&'a mut Map<String, Value>, String), ScimError> {
+    let mut obj = obj_mut(resource)?;
+    if !attr.is_core() {
+        obj = child_object(obj, attr.urn.as_deref().unwrap_or_default())?;
+    }
+    match &attr.sub_attr {
+        None => Ok((obj, attr.attr.clone())),
+        Some(sub) => Ok((child_object(obj, &attr.attr)?, sub.clone())),
+    }
+}
+
+fn obj_mut(value: &mut Value) -> Result<&mut Map<String, Value>, ScimError> {
+    value.as_object_mut().ok_or_else(|| {
+        ScimError::bad_request(
+            ScimErrorType::InvalidPath,
+            "The target is not a complex attribute".to_string(),
+        )
+    })
+}
+
+fn child_object<'a>(
+    obj: &'a mut Map<String, Value>,
+    key: &str,
+) -> Result<&'a mut Map<String, Value>, ScimError> {
+    let key = find_key(obj, key).unwrap_or_else(|| key.to_string());
+    let child = obj.entry(key).or_insert(Value::Null);
+    if child.is_null() {
+        *child = Value::Object(Map::new());
+    }
+    obj_mut(child)
+}
+
+/// Returns the existing key inside the object, which matches case-insensitive.
+fn find_key(obj: &Map<String, Value>, key: &str) -> Option<String> {
+    obj.keys().find(|k| k.eq_ignore_ascii_case(key)).cloned()
+}
+
+fn set_value(obj: &mut Map<String, Value>, key: &str, value: Value, kind: PatchOpKind) {
+    let key = find_key(obj, key).unwrap_or_else(|| key.to_string());
+    match (kind, obj.get_mut(&key)) {
+        (PatchOpKind::Add, Some(Value::Array(values))) => {
+            let new_values = match value {
+                Value::Array(new_values) => new_values,
+                value => vec![value],
+            };
+            for value in new_values {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+        }
+
+        // complex attributes are merged on the level of their sub-attributes
+        (_, Some(Value::Object(existing))) if value.is_object() => {
+            if let Value::Object(new_values) = value {
+                for (k, v) in new_values {
+                    let k = find_key(existing, &k).unwrap_or(k);
+                    existing.insert(k, v);
+                }
+            }
+        }
+
+        _ => {
+            obj.insert(key, value);
+        }
+    }
+}
+
+fn remove_value(obj: &mut Map<String, Value>, key: &str, value: Option<&Value>) {
+    let Some(key) = find_key(obj, key) else {
+        return;
+    };
+
+    match obj.get_mut(&key) {
+        Some(Value::Array(values)) => match value {
+            // only the given values, like `"value": [{"value": "2819c223"}]` for members
+            Some(value) => {
+                let to_remove = match value {
+                    Value::Array(to_remove) => to_remove.iter().collect::<Vec<&Value>>(),
+                    value => vec![value],
+                };
+                values.retain(|v| !to_remove.iter().any(|r| is_same_value(v, r)));
+            }
+            None => values.clear(),
+        },
+        _ => {
+            obj.remove(&key);
+        }
+    }
+}
+
+fn apply_filtered(
+    obj: &mut Map<String, Value>,
+    key: &str,
+    filter: &ScimFilter,
+    sub_attr: Option<&str>,
+    kind: PatchOpKind,
+    value: Option<&Value>,
+) -> Result<(), ScimError> {
+    let values = match find_key(obj, key) {
+        Some(key) => obj.get_mut(&key).and_then(Value::as_array_mut),
+        None => None,
+    };
+
+    if kind == PatchOpKind::Remove {
+        if let Some(values) = values {
+            match sub_attr {
+                Some(sub) => {
+                    for v in values.iter_mut() {
+                        if filter.matches(v) {
+                            if let Some(o) = v.as_object_mut() {
+                                if let Some(k) = find_key(o, sub) {
+                                    o.remove(&k);
+                                }
+                            }
+                        }
+                    }
+                }
+                None => values.retain(|v| !filter.matches(v)),
+            }
+        }
+        return Ok(());
+    }
+
+    let value = value.ok_or_else(|| {
+        ScimError::bad_request(
+            ScimErrorType::InvalidValue,
+            "The operation needs a 'value'".to_string(),
+        )
+    })?;
+    let mut matched = false;
+    for v in values.into_iter().flatten() {
+        if !filter.matches(v) {
+            continue;
+        }
+        matched = true;
+
+        let Some(o) = v.as_object_mut() else {
+            continue;
+        };
+        match sub_attr {
+            Some(sub) => set_value(o, sub, value.clone(), PatchOpKind::Replace),
+            None => set_value_obj(o, value)?,
+        }
+    }
+
+    if !matched {
+        return Err(ScimError::bad_request(
+            ScimErrorType::NoTarget,
+            "No value matches the filter of the 'path'".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn set_value_obj(obj: &mut Map<String, Value>, value: &Value) -> Result<(), ScimError> {
+    let Value::Object(new_values) = value else {
+        return Err(ScimError::bad_request(
+            ScimErrorType::InvalidValue,
+            "The 'value' must be an object".to_string(),
+        ));
+    };
+    for (k, v) in new_values {
+        set_value(obj, k, v.clone(), PatchOpKind::Replace);
+    }
+    Ok(())
+}
+
+// Values of multi-valued complex attributes are identified by their `value` sub-attribute.
+fn is_same_value(a: &Value, b: &Value) -> bool {
+    match (a.get("value"), b.get("value")) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn op(op: &str, path: Option<&str>, value: Option<Value>) -> ScimPatchOperation {
+        ScimPatchOperation {
+            op: op.to_string(),
+            path: path.map(String::from),
+            value,
+        }
+    }
+
+    fn user() -> Value {
+        json!({
+            "userName": "bjensen@example.com",
+            "name": { "givenName": "Barbara", "familyName": "Jensen" },
+            "active": true,
+            "emails": [{ "value": "bjensen@example.com", "type": "work", "primary": true }],
+            "roles": [{ "value": "user" }, { "value": "admin" }],
+            "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User": { "department": "IT" }
+        })
+    }
+
+    #[test]
+    fn test_patch_simple_path() {
+        let mut res = user();
+        apply_patch(
+            &mut res,
+            &[
+                op("Replace", Some("name.givenName"), Some(json!("Babs"))),
+                op("replace", Some("ACTIVE"), Some(json!(false))),
+                op("add", Some("displayName"), Some(json!("Babs Jensen"))),
+                op("add", Some("roles"), Some(json!([{ "value": "dev" }]))),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(res["name"]["givenName"], json!("Babs"));
+        assert_eq!(res["name"]["familyName"], json!("Jensen"));
+        assert_eq!(res["active"], json!(false));
+        assert_eq!(res["displayName"], json!("Babs Jensen"));
+        assert_eq!(res["roles"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_patch_remove() {
+        let mut res = user();
+        apply_patch(
+            &mut res,
+            &[
+                op("remove", Some("roles"), Some(json!([{ "value": "admin" }]))),
+                op("remove", Some("name.familyName"), None),
+            ],
+        )
+        .unwrap();
+        assert_eq!(res["roles"], json!([{ "value": "user" }]));
+        assert!(res["name"].get("familyName").is_none());
+
+        apply_patch(&mut res, &[op("remove", Some("roles"), None)]).unwrap();
+        assert_eq!(res["roles"], json!([]));
+
+        apply_patch(
+            &mut res,
+            &[op("remove", Some(r#"emails[type eq "work"]"#), None)],
+        )
+        .unwrap();
+        assert_eq!(res["emails"], json!([]));
+
+        assert!(apply_patch(&mut res, &[op("remove", None, None)]).is_err());
+    }
+
+    #[test]
+    fn test_patch_value_filter() {
+        let mut res = user();
+        apply_patch(
+            &mut res,
+            &[op(
+                "replace",
+                Some(r#"emails[type eq "work"].value"#),
+                Some(json!("babs@example.com")),
+            )],
+        )
+        .unwrap();
+        assert_eq!(res["emails"][0]["value"], json!("babs@example.com"));
+        assert_eq!(res["emails"][0]["primary"], json!(true));
+
+        let err = apply_patch(
+            &mut res,
+            &[op(
+                "replace",
+                Some(r#"emails[type eq "home"].value"#),
+                Some(json!("babs@jensen.org")),
+            )],
+        )
+        .unwrap_err();
+        assert_eq!(err.scim_type, Some(ScimErrorType::NoTarget));
+    }
+
+    #[test]
+    fn test_patch_without_path() {
+        let mut res = user();
+        apply_patch(
+            &mut res,
+            &[op(
+                "replace",
+                None,
+                Some(json!({
+                    "name": { "givenName": "Babs" },
+                    "urn:ietf:params:scim:schemas:core:2.0:User:active": false,
+                    "urn:ietf:params:scim:schemas:extension:rauthy:2.0:User": { "level": 3 }
+                })),
+            )],
+        )
+        .unwrap();
+
+        assert_eq!(res["name"]["givenName"], json!("Babs"));
+        assert_eq!(res["name"]["familyName"], json!("Jensen"));
+        assert_eq!(res["active"], json!(false));
+        assert_eq!(
+            res["urn:ietf:params:scim:schemas:extension:rauthy:2.0:User"],
+            json!({ "department": "IT", "level": 3 })
+        );
+    }
+
+    #[test]
+    fn test_patch_extension_path() {
+        let mut res = user();
+        apply_patch(
+            &mut res,
+            &[
+                op(
+                    "add",
+                    Some("urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:level"),
+                    Some(json!(3)),
+                ),
+                op(
+                    "remove",
+                    Some("urn:ietf:params:scim:schemas:extension:rauthy:2.0:User:department"),
+                    None,
+                ),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            res["urn:ietf:params:scim:schemas:extension:rauthy:2.0:User"],
+            json!({ "level": 3 })
+        );
+    }
+
+    #[test]
+    fn test_patch_invalid() {
+        let mut res = user();
+        assert!(apply_patch(&mut res, &[op("move", Some("active"), None)]).is_err());
+        assert!(apply_patch(&mut res, &[op("add", Some("active"), None)]).is_err());
+        assert!(apply_patch(&mut res, &[op("add", None, Some(json!("a")))]).is_err());
+        assert!(apply_patch(&mut res, &[op("add", Some("a b"), Some(json!("a")))]).is_err());
+    }
+}
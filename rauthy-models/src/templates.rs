@@ -2,6 +2,8 @@ use crate::entity::colors::Colors;
 use crate::entity::password::PasswordPolicy;
 use crate::i18n::account::I18nAccount;
 use crate::i18n::authorize::I18nAuthorize;
+use crate::i18n::consent::I18nConsent;
+use crate::i18n::device::I18nDevice;
 use crate::i18n::email_confirm_change_html::I18nEmailConfirmChangeHtml;
 use crate::i18n::error::I18nError;
 use crate::i18n::index::I18nIndex;
@@ -21,6 +23,7 @@ use std::fmt::Debug;
 pub enum FrontendAction {
     Refresh,
     MfaLogin(String),
+    LoginHint(String),
     None,
 }
 
@@ -29,6 +32,7 @@ impl ToString for FrontendAction {
         match self {
             FrontendAction::Refresh => "Refresh".to_string(),
             FrontendAction::MfaLogin(s) => format!("MfaLogin {}", s),
+            FrontendAction::LoginHint(s) => format!("LoginHint {}", s),
             FrontendAction::None => "None".to_string(),
         }
     }
@@ -1129,6 +1133,110 @@ impl CallbackHtml<'_> {
     }
 }
 
+#[derive(Default, Template)]
+#[template(path = "html/oidc/consent.html")]
+pub struct ConsentHtml<'a> {
+    pub lang: &'a str,
+    pub csrf_token: &'a str,
+    pub data: &'a str,
+    pub action: &'a str,
+    pub col_act1: &'a str,
+    pub col_act1a: &'a str,
+    pub col_act2: &'a str,
+    pub col_act2a: &'a str,
+    pub col_acnt: &'a str,
+    pub col_acnta: &'a str,
+    pub col_ok: &'a str,
+    pub col_err: &'a str,
+    pub col_glow: &'a str,
+    pub col_gmid: &'a str,
+    pub col_ghigh: &'a str,
+    pub col_text: &'a str,
+    pub col_bg: &'a str,
+    pub nonce: &'a str,
+    pub i18n: String,
+}
+
+impl ConsentHtml<'_> {
+    pub fn build(colors: &Colors, lang: &Language) -> (String, String) {
+        let nonce = nonce();
+
+        let res = ConsentHtml {
+            lang: lang.as_str(),
+            col_act1: &colors.act1,
+            col_act1a: &colors.act1a,
+            col_act2: &colors.act2,
+            col_act2a: &colors.act2a,
+            col_acnt: &colors.acnt,
+            col_acnta: &colors.acnta,
+            col_ok: &colors.ok,
+            col_err: &colors.err,
+            col_glow: &colors.glow,
+            col_gmid: &colors.gmid,
+            col_ghigh: &colors.ghigh,
+            col_text: &colors.text,
+            col_bg: &colors.bg,
+            nonce: &nonce,
+            i18n: I18nConsent::build(lang).as_json(),
+            ..Default::default()
+        };
+
+        (res.render().unwrap(), nonce)
+    }
+}
+
+#[derive(Default, Template)]
+#[template(path = "html/oidc/device.html")]
+pub struct DeviceHtml<'a> {
+    pub lang: &'a str,
+    pub csrf_token: &'a str,
+    pub data: &'a str,
+    pub action: &'a str,
+    pub col_act1: &'a str,
+    pub col_act1a: &'a str,
+    pub col_act2: &'a str,
+    pub col_act2a: &'a str,
+    pub col_acnt: &'a str,
+    pub col_acnta: &'a str,
+    pub col_ok: &'a str,
+    pub col_err: &'a str,
+    pub col_glow: &'a str,
+    pub col_gmid: &'a str,
+    pub col_ghigh: &'a str,
+    pub col_text: &'a str,
+    pub col_bg: &'a str,
+    pub nonce: &'a str,
+    pub i18n: String,
+}
+
+impl DeviceHtml<'_> {
+    pub fn build(colors: &Colors, lang: &Language) -> (String, String) {
+        let nonce = nonce();
+
+        let res = DeviceHtml {
+            lang: lang.as_str(),
+            col_act1: &colors.act1,
+            col_act1a: &colors.act1a,
+            col_act2: &colors.act2,
+            col_act2a: &colors.act2a,
+            col_acnt: &colors.acnt,
+            col_acnta: &colors.acnta,
+            col_ok: &colors.ok,
+            col_err: &colors.err,
+            col_glow: &colors.glow,
+            col_gmid: &colors.gmid,
+            col_ghigh: &colors.ghigh,
+            col_text: &colors.text,
+            col_bg: &colors.bg,
+            nonce: &nonce,
+            i18n: I18nDevice::build(lang).as_json(),
+            ..Default::default()
+        };
+
+        (res.render().unwrap(), nonce)
+    }
+}
+
 #[derive(Default, Template)]
 #[template(path = "html/oidc/logout.html")]
 pub struct LogoutHtml<'a> {
@@ -1,42 +1,58 @@
+use crate::consent;
 use crate::token_set::TokenSet;
 use actix_web::http::header;
 use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::Utc;
 use jwt_simple::algorithms::{
+    ECDSAP256KeyPairLike, ECDSAP256PublicKeyLike, ECDSAP384KeyPairLike, ECDSAP384PublicKeyLike,
     EdDSAKeyPairLike, EdDSAPublicKeyLike, RSAKeyPairLike, RSAPublicKeyLike,
 };
 use jwt_simple::claims;
 use jwt_simple::prelude::*;
 use rauthy_common::constants::{
     CACHE_NAME_12HR, CACHE_NAME_LOGIN_DELAY, COOKIE_MFA, ENABLE_SOLID_AUD, ENABLE_WEB_ID,
-    HEADER_DPOP_NONCE, IDX_JWKS, IDX_JWK_LATEST, IDX_LOGIN_TIME, SESSION_RENEW_MFA, TOKEN_BEARER,
-    WEBAUTHN_REQ_EXP,
+    GRANT_TYPE_DEVICE_CODE, GRANT_TYPE_TOKEN_EXCHANGE, HEADER_DPOP_NONCE, IDX_JWKS, IDX_JWK_LATEST,
+    IDX_LOGIN_TIME, OFFLINE_TOKEN_LT, SESSION_RENEW_MFA, TOKEN_BEARER, TOKEN_INTROSPECTION_JWT_TYP,
+    TOKEN_TYPE_ACCESS_TOKEN, WEBAUTHN_REQ_EXP,
 };
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_common::password_hasher::HashPassword;
-use rauthy_common::utils::{base64_url_encode, encrypt, get_client_ip, get_rand};
+use rauthy_common::utils::{base64_url_encode, get_client_ip, get_rand};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::client_certificates::ClientCertificate;
 use rauthy_models::entity::clients::Client;
 use rauthy_models::entity::colors::ColorEntity;
+use rauthy_models::entity::device_codes::DeviceCode;
 use rauthy_models::entity::dpop_proof::DPoPProof;
 use rauthy_models::entity::jwk::{Jwk, JwkKeyPair, JwkKeyPairAlg};
+use rauthy_models::entity::pairwise_subjects::PairwiseSubject;
+use rauthy_models::entity::pushed_auth_requests::PushedAuthRequest;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::entity::revoked_tokens::RevokedToken;
 use rauthy_models::entity::scopes::Scope;
+use rauthy_models::entity::session_clients::SessionClient;
 use rauthy_models::entity::sessions::{Session, SessionState};
+use rauthy_models::entity::user_attr::UserAttrValueEntity;
 use rauthy_models::entity::users::{AccountType, User};
 use rauthy_models::entity::webauthn::{WebauthnCookie, WebauthnLoginReq};
 use rauthy_models::entity::webids::WebId;
 use rauthy_models::events::event::Event;
 use rauthy_models::events::ip_blacklist_handler::{IpBlacklistReq, IpFailedLoginCheck};
 use rauthy_models::language::Language;
-use rauthy_models::request::{LoginRefreshRequest, LoginRequest, LogoutRequest, TokenRequest};
-use rauthy_models::response::{TokenInfo, Userinfo};
+use rauthy_models::request::{
+    ClaimsRequest, IntrospectionRequest, LoginRefreshRequest, LoginRequest, LogoutRequest,
+    RevokeRequest, TokenRequest,
+};
+use rauthy_models::response::{
+    IntrospectionJwtClaims, IntrospectionResponse, TokenInfo, Userinfo, UserinfoJwtClaims,
+};
 use rauthy_models::templates::{LogoutHtml, TooManyRequestsHtml};
 use rauthy_models::{
-    sign_jwt, validate_jwt, AuthStep, AuthStepAwaitWebauthn, AuthStepLoggedIn, JktClaim,
-    JwtAccessClaims, JwtAmrValue, JwtCommonClaims, JwtIdClaims, JwtRefreshClaims, JwtTokenType,
+    real_ip_from_req, sign_jwt, validate_jwt, AuthStep, AuthStepAwaitTotp, AuthStepAwaitWebauthn,
+    AuthStepLoggedIn, JktClaim, JwtAccessClaims, JwtActClaim, JwtAmrValue, JwtCommonClaims,
+    JwtIdClaims, JwtRefreshClaims, JwtTokenType,
 };
 use redhac::cache_del;
 use redhac::{cache_get, cache_get_from, cache_get_value, cache_put};
@@ -54,10 +70,23 @@ use tracing::{debug, error, info, warn};
 pub async fn authorize(
     data: &web::Data<AppState>,
     req: &HttpRequest,
-    req_data: LoginRequest,
+    mut req_data: LoginRequest,
     mut session: Session,
     // the second argument with the error will be 'true' if a login delay should be added
 ) -> Result<AuthStep, (ErrorResponse, bool)> {
+    // with a pushed authorization request, only the already validated params will be used
+    let par = match req_data.request_uri.take() {
+        Some(request_uri) => {
+            let par =
+                PushedAuthRequest::find_by_request_uri(data, &req_data.client_id, &request_uri)
+                    .await
+                    .map_err(|err| (err, false))?;
+            par.apply_to_login(&mut req_data);
+            Some(par)
+        }
+        None => None,
+    };
+
     // This Error must be the same if user does not exist AND passwords do not match to prevent
     // username enumeration
     let mut user = User::find_by_email(data, req_data.email)
@@ -150,6 +179,20 @@ pub async fn authorize(
         .validate_mfa(&user)
         .map_err(|err| (err, has_password_been_hashed))?;
 
+    // a requested authentication context may require MFA, even if the client does not force it
+    let claims = req_data
+        .claims
+        .as_deref()
+        .map(ClaimsRequest::parse)
+        .transpose()
+        .map_err(|err| (err, has_password_been_hashed))?;
+    let acr_values = requested_acr(req_data.acr_values.as_deref(), claims.as_ref());
+    let acr = validate_acr(&acr_values, &user).map_err(|err| (err, has_password_been_hashed))?;
+
+    client
+        .validate_par(par.is_some())
+        .map_err(|err| (err, !user_must_provide_password))?;
+
     // check allowed origin
     let header_origin = client
         .validate_origin(req, &data.listen_scheme, &data.public_url)
@@ -206,7 +249,7 @@ pub async fn authorize(
     }
 
     // add the timeout for mfa verification to the auth code lifetime
-    let code_lifetime = if user.has_webauthn_enabled() {
+    let code_lifetime = if user.has_mfa_enabled() {
         client.auth_code_lifetime + *WEBAUTHN_REQ_EXP as i32
     } else {
         client.auth_code_lifetime
@@ -216,25 +259,46 @@ pub async fn authorize(
     let scopes = client
         .sanitize_login_scopes(&req_data.scopes)
         .map_err(|err| (err, !user_must_provide_password))?;
-    let code = AuthCode::new(
+    let mut code = AuthCode::new(
         user.id.clone(),
-        client.id,
+        client.id.clone(),
         Some(session.id.clone()),
         challenge,
         challenge_method,
         req_data.nonce,
         scopes,
+        claims,
+        acr,
+        Some(OffsetDateTime::now_utc().unix_timestamp()),
         code_lifetime,
     );
     code.save(data)
         .await
         .map_err(|err| (err, !user_must_provide_password))?;
+    // a pushed request can only be used once to issue a code
+    if let Some(par) = par {
+        par.delete(data)
+            .await
+            .map_err(|err| (err, !user_must_provide_password))?;
+    }
 
     // build location header
     let mut loc = format!("{}?code={}", req_data.redirect_uri, code.id);
-    if let Some(state) = req_data.state {
+    if let Some(state) = &req_data.state {
         loc = format!("{}&state={}", loc, state);
     };
+    // the code will only be handed out after a possibly needed consent
+    let loc = consent::consent_redirect(
+        data,
+        &client,
+        &mut code,
+        &req_data.redirect_uri,
+        req_data.state.as_deref(),
+        req_data.prompt.as_deref(),
+        loc,
+    )
+    .await
+    .map_err(|err| (err, !user_must_provide_password))?;
 
     // TODO double check that we do not have any problems with the direct webauthn login here
     // TODO should we allow to skip this step if set so in the config?
@@ -259,6 +323,7 @@ pub async fn authorize(
         let login_req = WebauthnLoginReq {
             code: step.code.clone(),
             user_id: user.id,
+            auth_code_id: code.id.clone(),
             header_loc: loc,
             header_origin: step
                 .header_origin
@@ -271,6 +336,23 @@ pub async fn authorize(
             .map_err(|err| (err, !user_must_provide_password))?;
 
         Ok(AuthStep::AwaitWebauthn(step))
+    } else if user.totp_enabled {
+        session
+            .set_mfa(data, true)
+            .await
+            .map_err(|err| (err, !user_must_provide_password))?;
+
+        await_totp(
+            data,
+            &session,
+            &user,
+            code.id.clone(),
+            loc,
+            header_origin,
+            has_password_been_hashed,
+        )
+        .await
+        .map_err(|err| (err, !user_must_provide_password))
     } else {
         Ok(AuthStep::LoggedIn(AuthStepLoggedIn {
             has_password_been_hashed,
@@ -283,12 +365,17 @@ pub async fn authorize(
 }
 
 /// # Business logic for [POST /oidc/authorize/refresh](crate::handlers::post_authorize_refresh)
+///
+/// With `silent` set, which is used for `prompt=none`, this will fail with `interaction_required`
+/// instead of asking for a passkey verification, or with `consent_required` instead of showing
+/// the consent page.
 pub async fn authorize_refresh(
     data: &web::Data<AppState>,
     session: &Session,
     client: Client,
     header_origin: Option<(HeaderName, HeaderValue)>,
     req_data: LoginRefreshRequest,
+    silent: bool,
 ) -> Result<AuthStep, ErrorResponse> {
     let user_id = session.user_id.as_ref().ok_or_else(|| {
         ErrorResponse::new(
@@ -300,36 +387,100 @@ pub async fn authorize_refresh(
     user.check_enabled()?;
     user.check_expired()?;
 
+    // the session may have been valid when the login page was rendered, but not anymore
+    if session.is_auth_expired(req_data.max_age) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("login_required".to_string()),
+            "The last authentication is older than 'max_age'".to_string(),
+        ));
+    }
+
     client.validate_mfa(&user)?;
 
+    let claims = req_data
+        .claims
+        .as_deref()
+        .map(ClaimsRequest::parse)
+        .transpose()?;
+    let acr_values = requested_acr(req_data.acr_values.as_deref(), claims.as_ref());
+    let acr = validate_acr(&acr_values, &user)?;
+    // an explicitly requested 'mfa' context always needs a fresh verification of the 2nd factor
+    let force_mfa = acr_values
+        .iter()
+        .any(|acr| matches!(JwtAmrValue::from_str(acr), Ok(JwtAmrValue::Mfa)));
+    let needs_mfa_step = user.has_mfa_enabled() && (*SESSION_RENEW_MFA || force_mfa);
+    if silent && needs_mfa_step {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("interaction_required".to_string()),
+            "The user must verify the passkey".to_string(),
+        ));
+    }
+    // a fresh verification of the 2nd factor counts as a new active authentication
+    let auth_time = if needs_mfa_step {
+        Some(OffsetDateTime::now_utc().unix_timestamp())
+    } else {
+        session.auth_time
+    };
+
     let scopes = client.sanitize_login_scopes(&req_data.scopes)?;
-    let code_lifetime = if user.has_webauthn_enabled() {
+    let code_lifetime = if user.has_mfa_enabled() {
         client.auth_code_lifetime + *WEBAUTHN_REQ_EXP as i32
     } else {
         client.auth_code_lifetime
     };
 
-    let code = AuthCode::new(
+    let mut code = AuthCode::new(
         user.id.clone(),
-        client.id,
+        client.id.clone(),
         Some(session.id.clone()),
         req_data.code_challenge,
         req_data.code_challenge_method,
         req_data.nonce,
         scopes,
+        claims,
+        acr,
+        auth_time,
         code_lifetime,
     );
     code.save(data).await?;
 
     // build location header
-    let header_loc = if let Some(s) = req_data.state {
+    let header_loc = if let Some(s) = &req_data.state {
         format!("{}?code={}&state={}", req_data.redirect_uri, code.id, s)
     } else {
         format!("{}?code={}", req_data.redirect_uri, code.id)
     };
+    if silent && !consent::is_granted(data, &client, &code.user_id, &code.scopes).await? {
+        code.delete(data).await?;
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("consent_required".to_string()),
+            "The user must grant the requested scopes".to_string(),
+        ));
+    }
+    let header_loc = consent::consent_redirect(
+        data,
+        &client,
+        &mut code,
+        &req_data.redirect_uri,
+        req_data.state.as_deref(),
+        req_data.prompt.as_deref(),
+        header_loc,
+    )
+    .await?;
 
     // check if we need to validate the 2nd factor
-    if user.has_webauthn_enabled() && *SESSION_RENEW_MFA {
+    if needs_mfa_step && !user.has_webauthn_enabled() {
+        await_totp(
+            data,
+            session,
+            &user,
+            code.id.clone(),
+            header_loc,
+            header_origin,
+            false,
+        )
+        .await
+    } else if needs_mfa_step {
         let step = AuthStepAwaitWebauthn {
             has_password_been_hashed: false,
             code: get_rand(48),
@@ -344,6 +495,7 @@ pub async fn authorize_refresh(
         let login_req = WebauthnLoginReq {
             code: step.code.clone(),
             user_id: user.id,
+            auth_code_id: code.id.clone(),
             header_loc,
             header_origin: step
                 .header_origin
@@ -367,16 +519,97 @@ pub async fn authorize_refresh(
     }
 }
 
+/// Saves the pending login and asks the user for a TOTP, which will be verified with
+/// [POST /users/{id}/totp/auth](crate::handlers::post_totp_auth).
+async fn await_totp(
+    data: &web::Data<AppState>,
+    session: &Session,
+    user: &User,
+    auth_code_id: String,
+    header_loc: String,
+    header_origin: Option<(HeaderName, HeaderValue)>,
+    has_password_been_hashed: bool,
+) -> Result<AuthStep, ErrorResponse> {
+    // the pending login is the same as for a passkey and only differs in how it will be finished
+    let login_req = WebauthnLoginReq {
+        code: get_rand(48),
+        user_id: user.id.clone(),
+        auth_code_id,
+        header_loc,
+        header_origin: header_origin
+            .as_ref()
+            .map(|h| h.1.to_str().unwrap().to_string()),
+    };
+    login_req.save(data).await?;
+
+    Ok(AuthStep::AwaitTotp(AuthStepAwaitTotp {
+        has_password_been_hashed,
+        code: login_req.code,
+        header_csrf: Session::get_csrf_header(&session.csrf_token),
+        header_origin,
+        user_id: user.id.clone(),
+        exp: *WEBAUTHN_REQ_EXP,
+    }))
+}
+
+/// Collects all authentication context class references, which have been requested either via
+/// `acr_values` or via the `acr` member of the `claims` param.
+pub(crate) fn requested_acr(
+    acr_values: Option<&str>,
+    claims: Option<&ClaimsRequest>,
+) -> Vec<String> {
+    let mut values = acr_values
+        .map(|v| {
+            v.split_whitespace()
+                .map(String::from)
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    if let Some(claims) = claims {
+        values.extend(claims.acr_values());
+    }
+    values
+}
+
+/// Returns the value for the `acr` claim, if any authentication context has been requested.
+/// This is always the strongest one the user satisfies. The `mfa` context can only be satisfied
+/// with a passkey or TOTP, no matter if the client forces MFA or not.
+pub(crate) fn validate_acr(
+    requested: &[String],
+    user: &User,
+) -> Result<Option<String>, ErrorResponse> {
+    if requested.is_empty() {
+        Ok(None)
+    } else if user.has_mfa_enabled() {
+        Ok(Some(JwtAmrValue::Mfa.to_string()))
+    } else if requested
+        .iter()
+        .any(|acr| matches!(JwtAmrValue::from_str(acr), Ok(JwtAmrValue::Mfa)))
+    {
+        Err(ErrorResponse::new(
+            ErrorResponseType::MfaRequired,
+            "MFA is required for the requested 'acr_values'".to_string(),
+        ))
+    } else {
+        Ok(Some(JwtAmrValue::Pwd.to_string()))
+    }
+}
+
 /// Builds the access token for a user after all validation has been successful
-#[allow(clippy::type_complexity)]
+///
+/// The `audience` defaults to the client itself and will only differ for the token exchange grant.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub async fn build_access_token(
     user: Option<&User>,
     data: &web::Data<AppState>,
     client: &Client,
-    dpop_fingerprint: Option<String>,
+    cnf: Option<JktClaim>,
     lifetime: i64,
     scope: Option<String>,
     scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
+    userinfo_claims: Option<Vec<String>>,
+    audience: Option<String>,
+    act: Option<JwtActClaim>,
 ) -> Result<String, ErrorResponse> {
     let scope = if let Some(s) = scope {
         s
@@ -393,16 +626,24 @@ pub async fn build_access_token(
         preferred_username: None,
         roles: None,
         groups: None,
-        cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt }),
+        cnf,
+        act,
         custom: None,
+        userinfo_claims,
     };
 
     // add user specific claims if available
     let mut sub = None;
     if let Some(user) = user {
-        sub = Some(user.email.clone());
-        custom_claims.preferred_username = Some(user.email.clone());
-        custom_claims.uid = Some(user.id.clone());
+        // pairwise clients must not see any global identifier of the user
+        if let Some(pairwise_sub) = client.get_pairwise_sub(&user.id)? {
+            PairwiseSubject::create(data, &pairwise_sub, &user.id).await?;
+            sub = Some(pairwise_sub);
+        } else {
+            sub = Some(user.email.clone());
+            custom_claims.preferred_username = Some(user.email.clone());
+            custom_claims.uid = Some(user.id.clone());
+        }
         custom_claims.roles = Some(user.get_roles());
 
         if custom_claims.scope.contains("groups") {
@@ -435,7 +676,9 @@ pub async fn build_access_token(
         coarsetime::Duration::from_secs(lifetime as u64),
     )
     .with_issuer(data.issuer.clone())
-    .with_audience(client.id.to_string());
+    .with_audience(audience.unwrap_or_else(|| client.id.to_string()))
+    // the 'jti' makes it possible to revoke single access tokens
+    .with_jwt_id(get_rand(24));
 
     if let Some(sub) = sub {
         claims = claims.with_subject(sub);
@@ -455,58 +698,143 @@ pub async fn build_id_token(
     nonce: Option<String>,
     scope: &str,
     scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
+    claims: Option<(&ClaimsRequest, &Option<HashMap<String, Vec<u8>>>)>,
+    acr: Option<String>,
+    amr: Option<Vec<String>>,
+    auth_time: Option<i64>,
     is_auth_code_flow: bool,
+    sid: Option<String>,
 ) -> Result<String, ErrorResponse> {
-    let amr = match user.has_webauthn_enabled() {
-        true => {
-            if is_auth_code_flow {
-                JwtAmrValue::Mfa.to_string()
-            } else {
-                JwtAmrValue::Pwd.to_string()
-            }
-        }
-        false => JwtAmrValue::Pwd.to_string(),
+    let pairwise_sub = client.get_pairwise_sub(&user.id)?;
+    let custom_claims = build_id_claims(
+        user,
+        client,
+        pairwise_sub.is_some(),
+        dpop_fingerprint,
+        scope,
+        scope_customs,
+        claims,
+        acr,
+        amr,
+        auth_time,
+        is_auth_code_flow,
+        sid,
+    );
+
+    let mut claims = Claims::with_custom_claims(
+        custom_claims,
+        coarsetime::Duration::from_secs(lifetime as u64),
+    )
+    .with_subject(pairwise_sub.unwrap_or_else(|| user.id.clone()))
+    .with_issuer(data.issuer.clone());
+
+    // TODO should we maybe always include the "solid" claim here depending on if a webid exists?
+    // like it is now, static clients would never include this claim, even though they might need it
+    if client.is_ephemeral() && *ENABLE_SOLID_AUD {
+        let mut aud = HashSet::with_capacity(2);
+        aud.insert("solid".to_string());
+        aud.insert(client.id.to_string());
+        claims = claims.with_audiences(aud);
+    } else {
+        claims = claims.with_audience(client.id.to_string());
+    }
+
+    if let Some(nonce) = nonce {
+        claims = claims.with_nonce(nonce);
+    }
+
+    let id_token = sign_id_token(data, claims, client).await?;
+    // encrypted ID tokens are nested JWTs: signed first and encrypted afterward
+    client.encrypt_id_token(data, id_token).await
+}
+
+/// Builds the custom claims of the id token. Pairwise clients must not see any global
+/// identifier of the user.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn build_id_claims(
+    user: &User,
+    client: &Client,
+    is_pairwise: bool,
+    dpop_fingerprint: Option<String>,
+    scope: &str,
+    scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
+    claims: Option<(&ClaimsRequest, &Option<HashMap<String, Vec<u8>>>)>,
+    acr: Option<String>,
+    amr: Option<Vec<String>>,
+    auth_time: Option<i64>,
+    is_auth_code_flow: bool,
+    sid: Option<String>,
+) -> JwtIdClaims {
+    let amr = if let Some(amr) = amr {
+        amr
+    } else if is_auth_code_flow && user.has_webauthn_enabled() {
+        vec![JwtAmrValue::Mfa.to_string()]
+    } else if is_auth_code_flow && user.totp_enabled {
+        vec![
+            JwtAmrValue::Pwd.to_string(),
+            JwtAmrValue::Otp.to_string(),
+            JwtAmrValue::Mfa.to_string(),
+        ]
+    } else {
+        vec![JwtAmrValue::Pwd.to_string()]
     };
 
-    let webid = if *ENABLE_WEB_ID && scope.contains("webid") {
+    // the webid contains the global user id
+    let webid = if *ENABLE_WEB_ID && scope.contains("webid") && !is_pairwise {
         Some(WebId::resolve_webid_uri(&user.id))
     } else {
         None
     };
+    // for pairwise clients, the email is only exposed with the explicitly granted scope
+    let preferred_username = if !is_pairwise || scope.contains("email") {
+        Some(user.email.clone())
+    } else {
+        None
+    };
 
     let mut custom_claims = JwtIdClaims {
         azp: client.id.clone(),
         typ: JwtTokenType::Id,
-        amr: vec![amr],
-        preferred_username: user.email.clone(),
+        amr,
+        acr,
+        auth_time,
+        preferred_username,
         email: None,
         email_verified: None,
         given_name: None,
         family_name: None,
         roles: user.get_roles(),
         groups: None,
-        cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt }),
+        cnf: JktClaim::new(dpop_fingerprint, None),
         custom: None,
         webid,
+        sid,
     };
 
-    if scope.contains("email") {
+    // claims requested via the `claims` param are added on top of the ones from the scopes
+    let wants = |name: &str| claims.map(|(c, _)| c.wants_id_token(name)).unwrap_or(false);
+
+    if scope.contains("email") || wants("email") {
         custom_claims.email = Some(user.email.clone());
+    }
+    if scope.contains("email") || wants("email_verified") {
         custom_claims.email_verified = Some(user.email_verified);
     }
 
-    if scope.contains("profile") {
+    if scope.contains("profile") || wants("given_name") {
         custom_claims.given_name = Some(user.given_name.clone());
+    }
+    if scope.contains("profile") || wants("family_name") {
         custom_claims.family_name = Some(user.family_name.clone());
     }
 
-    if scope.contains("groups") {
+    if scope.contains("groups") || wants("groups") {
         custom_claims.groups = Some(user.get_groups());
     }
 
+    let mut attr = HashMap::new();
     if let Some((cust, user_attrs)) = scope_customs {
         let user_attrs = user_attrs.as_ref().unwrap();
-        let mut attr = HashMap::with_capacity(cust.len());
         for c in cust {
             if let Some(csv) = &c.attr_include_id {
                 let scopes = csv.split(',');
@@ -519,37 +847,26 @@ pub async fn build_id_token(
                 }
             }
         }
-        if !attr.is_empty() {
-            custom_claims.custom = Some(attr);
-        }
     }
-
-    let mut claims = Claims::with_custom_claims(
-        custom_claims,
-        coarsetime::Duration::from_secs(lifetime as u64),
-    )
-    .with_subject(user.id.clone())
-    .with_issuer(data.issuer.clone());
-
-    // TODO should we maybe always include the "solid" claim here depending on if a webid exists?
-    // like it is now, static clients would never include this claim, even though they might need it
-    if client.is_ephemeral() && *ENABLE_SOLID_AUD {
-        let mut aud = HashSet::with_capacity(2);
-        aud.insert("solid".to_string());
-        aud.insert(client.id.to_string());
-        claims = claims.with_audiences(aud);
-    } else {
-        claims = claims.with_audience(client.id.to_string());
+    if let Some((claims, Some(user_attrs))) = claims {
+        for cust_name in claims.custom_id_token() {
+            if let Some(value) = user_attrs.get(cust_name) {
+                let json = serde_json::from_slice(value.as_slice())
+                    .expect("Converting cust user id attr to json");
+                attr.insert(cust_name.to_string(), json);
+            }
+        }
     }
-
-    if let Some(nonce) = nonce {
-        claims = claims.with_nonce(nonce);
+    if !attr.is_empty() {
+        custom_claims.custom = Some(attr);
     }
 
-    sign_id_token(data, claims, client).await
+    custom_claims
 }
 
-/// Builds the refresh token for a user after all validation has been successful
+/// Builds the refresh token for a user after all validation has been successful.
+/// A new token family will be started, if no `origin` token from a refresh is given.
+#[allow(clippy::too_many_arguments)]
 pub async fn build_refresh_token(
     user: &User,
     data: &web::Data<AppState>,
@@ -558,17 +875,39 @@ pub async fn build_refresh_token(
     access_token_lifetime: i64,
     scope: Option<String>,
     is_mfa: bool,
+    origin: Option<&RefreshToken>,
+    session_id: Option<String>,
+    ip: Option<String>,
 ) -> Result<String, ErrorResponse> {
+    // Offline tokens are only issued, if the client is allowed and `offline_access` has been
+    // requested explicitly. The `default_scopes` alone must never lead to an offline token.
+    let offline = client.allows_offline_access()
+        && scope
+            .as_deref()
+            .map(|scope| scope.split([' ', ',']).any(|s| s == "offline_access"))
+            .unwrap_or(false);
+    let lifetime = if offline {
+        *OFFLINE_TOKEN_LT
+    } else {
+        48 * 3600
+    };
+
     let custom_claims = JwtRefreshClaims {
         azp: client.id.clone(),
         typ: JwtTokenType::Refresh,
-        uid: user.id.clone(),
-        cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt }),
+        // the user is looked up via the database entry and never via the `uid`
+        uid: client
+            .get_pairwise_sub(&user.id)?
+            .unwrap_or_else(|| user.id.clone()),
+        cnf: JktClaim::new(dpop_fingerprint, None),
     };
 
-    let claims = Claims::with_custom_claims(custom_claims, coarsetime::Duration::from_hours(48))
-        .with_issuer(data.issuer.clone())
-        .with_audience(client.id.to_string());
+    let claims = Claims::with_custom_claims(
+        custom_claims,
+        coarsetime::Duration::from_secs(lifetime as u64),
+    )
+    .with_issuer(data.issuer.clone())
+    .with_audience(client.id.to_string());
 
     let token = sign_refresh_token(data, claims).await?;
 
@@ -576,24 +915,41 @@ pub async fn build_refresh_token(
     let validation_string = String::from(&token).split_off(token.len() - 49);
 
     // TODO extract the nbf and exp from the claims -> adjust entity
-    let nbf = OffsetDateTime::now_utc().add(::time::Duration::seconds(access_token_lifetime - 60));
-    let exp = &nbf.add(::time::Duration::seconds(48 * 3600));
-    RefreshToken::create(
-        data,
-        validation_string,
-        user.id.clone(),
-        nbf,
-        *exp,
+    let now = OffsetDateTime::now_utc();
+    let nbf = now.add(::time::Duration::seconds(access_token_lifetime - 60));
+    let exp = nbf.add(::time::Duration::seconds(lifetime));
+    let rt = RefreshToken {
+        id: validation_string,
+        user_id: user.id.clone(),
+        nbf: nbf.unix_timestamp(),
+        exp: exp.unix_timestamp(),
         scope,
         is_mfa,
-    )
-    .await?;
+        client_id: Some(client.id.clone()),
+        family_id: Some(
+            origin
+                .and_then(|rt| rt.family_id.clone())
+                .unwrap_or_else(|| get_rand(24)),
+        ),
+        // offline tokens must survive the end of the session they have been issued in
+        session_id: if offline { None } else { session_id },
+        used: false,
+        offline,
+        created_at: Some(
+            origin
+                .and_then(|rt| rt.created_at)
+                .unwrap_or_else(|| now.unix_timestamp()),
+        ),
+        last_used: Some(now.unix_timestamp()),
+        ip,
+    };
+    rt.save(data).await?;
 
     Ok(token)
 }
 
 #[inline(always)]
-fn get_bearer_token_from_header(headers: &HeaderMap) -> Result<String, ErrorResponse> {
+pub fn get_bearer_token_from_header(headers: &HeaderMap) -> Result<String, ErrorResponse> {
     let bearer = headers.get("Authorization").ok_or_else(|| {
         ErrorResponse::new(
             ErrorResponseType::Unauthorized,
@@ -632,31 +988,57 @@ fn get_bearer_token_from_header(headers: &HeaderMap) -> Result<String, ErrorResp
     Ok(bearer.to_string())
 }
 
-/// Returns the 'userInfo' for the [/oidc/userinfo endpoint](crate::handlers::get_userinfo)<br>
+/// Returns the 'userInfo' for the [/oidc/userinfo endpoint](crate::handlers::get_userinfo)
+/// together with the client the token has been issued to, which may want it encrypted.<br>
 /// **Important: This function does NOT validate the token again!**
 pub async fn get_userinfo(
     data: &web::Data<AppState>,
     req: HttpRequest,
-) -> Result<Userinfo, ErrorResponse> {
+) -> Result<(Userinfo, Client), ErrorResponse> {
     // get bearer token
     let bearer = get_bearer_token_from_header(req.headers())?;
 
     // token should already be validated in the permission extractor
-    let claims = validate_token::<JwtCommonClaims>(data, &bearer).await?;
+    let claims = validate_token::<JwtAccessClaims>(data, &bearer).await?;
+    let thumbprint = ClientCertificate::from_req(&req).map(|cert| cert.thumbprint);
+    validate_cert_binding(&claims.custom.cnf, thumbprint.as_deref())?;
 
-    let email = claims.subject.ok_or_else(|| {
+    let uid = access_token_user_id(data, &claims).await?.ok_or_else(|| {
         ErrorResponse::new(
             ErrorResponseType::Internal,
-            String::from("Token without 'sub' - could not extract the Principal"),
+            String::from("Token without 'uid' - could not extract the Principal"),
         )
     })?;
-    let user = User::find_by_email(data, email).await?;
+    let user = User::find(data, uid).await?;
+    let client = Client::find_maybe_ephemeral(data, claims.custom.azp).await?;
+
+    // pairwise clients must not see the global user id in any field
+    let (id, sub) = match client.get_pairwise_sub(&user.id)? {
+        Some(sub) => (sub.clone(), sub),
+        None => (user.id.clone(), user.email.clone()),
+    };
+    // custom user attributes will only be added, if they have been requested via `claims`
+    let custom = match claims.custom.userinfo_claims {
+        Some(names) => {
+            let values = UserAttrValueEntity::find_for_user(data, &user.id).await?;
+            let mut attrs = HashMap::with_capacity(names.len());
+            for value in values {
+                if names.contains(&value.key) {
+                    let json = serde_json::from_slice(value.value.as_slice())
+                        .expect("Converting cust user attr to json");
+                    attrs.insert(value.key, json);
+                }
+            }
+            Some(attrs).filter(|attrs| !attrs.is_empty())
+        }
+        None => None,
+    };
 
     let roles = user.get_roles();
     let groups = user.get_groups();
     let userinfo = Userinfo {
-        id: user.id,
-        sub: user.email.clone(),
+        id,
+        sub,
         email: user.email.clone(),
         email_verified: user.email_verified,
         name: format!("{} {}", &user.given_name, &user.family_name),
@@ -665,9 +1047,23 @@ pub async fn get_userinfo(
         preferred_username: user.email,
         given_name: user.given_name,
         family_name: user.family_name,
+        custom,
     };
 
-    Ok(userinfo)
+    Ok((userinfo, client))
+}
+
+/// Returns the id of the user an access token has been issued for. Tokens for pairwise clients
+/// do not contain the `uid`, which is why their `sub` must be mapped back to the user.
+async fn access_token_user_id(
+    data: &web::Data<AppState>,
+    claims: &claims::JWTClaims<JwtAccessClaims>,
+) -> Result<Option<String>, ErrorResponse> {
+    match (&claims.custom.uid, &claims.subject) {
+        (Some(uid), _) => Ok(Some(uid.clone())),
+        (None, Some(sub)) => PairwiseSubject::find_user_id(data, sub).await.map(Some),
+        (None, None) => Ok(None),
+    }
 }
 
 /// Returns [TokenInfo](crate::models::response::TokenInfo) for the
@@ -675,8 +1071,11 @@ pub async fn get_userinfo(
 pub async fn get_token_info(
     data: &web::Data<AppState>,
     token: &str,
+    x5t_s256: Option<&str>,
 ) -> Result<TokenInfo, ErrorResponse> {
-    let claims_res = validate_token::<JwtCommonClaims>(data, token).await;
+    let claims_res = validate_token::<JwtCommonClaims>(data, token)
+        .await
+        .and_then(|claims| validate_cert_binding(&claims.custom.cnf, x5t_s256).map(|_| claims));
     if claims_res.is_err() {
         return Ok(TokenInfo {
             active: false,
@@ -706,6 +1105,195 @@ pub async fn get_token_info(
     })
 }
 
+/// Revokes a refresh or access token for the [/oidc/revoke endpoint](crate::handlers::post_revoke)
+/// like defined in RFC 7009.
+///
+/// The `token_type_hint` is ignored, since the type can be extracted from the token itself.
+/// Refresh tokens will be deleted, while the `jti` of access tokens will be added to the
+/// revocation list.
+pub async fn revoke_token(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    req_data: RevokeRequest,
+) -> Result<(), ErrorResponse> {
+    let (client_id, credentials) = req_data.try_get_client_credentials(req)?;
+    let client = Client::find(data, client_id).await.map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("Invalid client"),
+        )
+    })?;
+    client
+        .validate_credentials(data, credentials, req)
+        .await
+        .map_err(|err| ErrorResponse::new(ErrorResponseType::Unauthorized, err.message))?;
+
+    // Invalid, expired or already revoked tokens do not lead to an error, since the client
+    // cannot handle it anyway and the purpose of the request has been achieved.
+    let claims = match validate_token::<JwtCommonClaims>(data, &req_data.token).await {
+        Ok(claims) => claims,
+        Err(_) => return Ok(()),
+    };
+    if claims.custom.azp != client.id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("unauthorized_client".to_string()),
+            String::from("The token has not been issued to this client"),
+        ));
+    }
+
+    match claims.custom.typ {
+        JwtTokenType::Refresh => {
+            let token = &req_data.token;
+            let (_, validation_str) = token.split_at(token.len() - 49);
+            if let Ok(rt) = RefreshToken::find(data, validation_str).await {
+                rt.delete(data).await?;
+                info!("Refresh token revoked for client '{}'", client.id);
+            }
+        }
+        JwtTokenType::Bearer | JwtTokenType::DPoP => {
+            // access tokens issued before the 'jti' was added cannot be revoked and simply expire
+            if let (Some(jti), Some(exp)) = (claims.jwt_id, claims.expires_at) {
+                RevokedToken::create(data, jti, exp.as_secs() as i64).await?;
+                info!("Access token revoked for client '{}'", client.id);
+            }
+        }
+        JwtTokenType::Id => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("unsupported_token_type".to_string()),
+                String::from("ID tokens cannot be revoked"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Token introspection for the [/oidc/introspect endpoint](crate::handlers::post_introspect)
+/// like defined in RFC 7662.
+///
+/// The requester must authenticate as a client and only tokens issued to this client are
+/// reported as active. The requesting client is returned as well, because it is needed for a
+/// possibly requested signed JWT response (RFC 9701).
+pub async fn introspect_token(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    req_data: IntrospectionRequest,
+) -> Result<(IntrospectionResponse, Client), ErrorResponse> {
+    let (client_id, credentials) =
+        req_data.try_get_client_credentials(req).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                String::from("Client authentication is required"),
+            )
+        })??;
+    let client = Client::find(data, client_id).await.map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("Invalid client"),
+        )
+    })?;
+    client
+        .validate_credentials(data, credentials, req)
+        .await
+        .map_err(|err| ErrorResponse::new(ErrorResponseType::Unauthorized, err.message))?;
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("client is disabled"),
+        ));
+    }
+
+    let claims = match validate_token::<JwtCommonClaims>(data, &req_data.token).await {
+        Ok(claims) => claims,
+        Err(_) => return Ok((IntrospectionResponse::default(), client)),
+    };
+    // a client must not learn anything about tokens, which have not been issued to it
+    if !is_issued_to(&client.id, &claims.custom.azp, claims.audiences.as_ref()) {
+        return Ok((IntrospectionResponse::default(), client));
+    }
+
+    // refresh tokens are only active as long as they exist in the database
+    if claims.custom.typ == JwtTokenType::Refresh {
+        let token = &req_data.token;
+        let (_, validation_str) = token.split_at(token.len() - 49);
+        if RefreshToken::find(data, validation_str).await.is_err() {
+            return Ok((IntrospectionResponse::default(), client));
+        }
+    }
+
+    let token_type = match claims.custom.typ {
+        JwtTokenType::Bearer => "Bearer",
+        JwtTokenType::DPoP => "DPoP",
+        JwtTokenType::Refresh => "refresh_token",
+        JwtTokenType::Id => "id_token",
+    };
+
+    let resp = IntrospectionResponse {
+        active: true,
+        // scope does not exist for ID tokens
+        scope: claims.custom.scope,
+        client_id: Some(claims.custom.azp),
+        // the `sub` is not necessarily human-readable, e.g. for pairwise subjects
+        username: claims.custom.preferred_username,
+        token_type: Some(token_type.to_string()),
+        exp: claims.expires_at.map(|exp| exp.as_secs()),
+        iat: claims.issued_at.map(|iat| iat.as_secs()),
+        nbf: claims.invalid_before.map(|nbf| nbf.as_secs()),
+        sub: claims.subject,
+        aud: claims
+            .audiences
+            .map(|aud| aud.into_set().into_iter().collect::<Vec<String>>()),
+        iss: claims.issuer,
+        jti: claims.jwt_id,
+        cnf: claims.custom.cnf,
+    };
+
+    Ok((resp, client))
+}
+
+/// Signs an introspection response for the given client like defined in RFC 9701.
+/// The key of the clients `access_token_alg` is used.
+pub async fn sign_introspection_response(
+    data: &web::Data<AppState>,
+    client: &Client,
+    resp: &IntrospectionResponse,
+) -> Result<String, ErrorResponse> {
+    let claims = IntrospectionJwtClaims {
+        iss: &data.issuer,
+        aud: &client.id,
+        iat: OffsetDateTime::now_utc().unix_timestamp(),
+        token_introspection: resp,
+    };
+
+    let key_pair_type = JwkKeyPairAlg::from_str(&client.access_token_alg)?;
+    let kp = JwkKeyPair::find_latest(data, &client.access_token_alg, key_pair_type).await?;
+    kp.sign_with_typ(&claims, TOKEN_INTROSPECTION_JWT_TYP)
+}
+
+/// Signs the userinfo for the given client. The key of the clients
+/// `userinfo_signed_response_alg` is used, or the one of its `id_token_alg` as a fallback, if
+/// the JWT has only been requested via the `Accept` header.
+pub async fn sign_userinfo(
+    data: &web::Data<AppState>,
+    client: &Client,
+    userinfo: &Userinfo,
+) -> Result<String, ErrorResponse> {
+    let claims = UserinfoJwtClaims {
+        iss: &data.issuer,
+        aud: &client.id,
+        iat: OffsetDateTime::now_utc().unix_timestamp(),
+        userinfo,
+    };
+
+    let alg = client
+        .userinfo_signed_response_alg
+        .as_ref()
+        .unwrap_or(&client.id_token_alg);
+    let key_pair_type = JwkKeyPairAlg::from_str(alg)?;
+    let kp = JwkKeyPair::find_latest(data, alg, key_pair_type).await?;
+    kp.sign_with_typ(&claims, "JWT")
+}
+
 /// Main entrance function for returning a whole new [TokenSet](crate::models::response::TokenSet)
 pub async fn get_token_set(
     req_data: TokenRequest,
@@ -717,6 +1305,8 @@ pub async fn get_token_set(
         "client_credentials" => grant_type_credentials(data, req, req_data).await,
         "password" => grant_type_password(data, req, req_data).await,
         "refresh_token" => grant_type_refresh(data, req, req_data).await,
+        GRANT_TYPE_DEVICE_CODE => grant_type_device_code(data, req, req_data).await,
+        GRANT_TYPE_TOKEN_EXCHANGE => grant_type_token_exchange(data, req, req_data).await,
         _ => Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
             String::from("Invalid 'grant_type'"),
@@ -745,7 +1335,7 @@ async fn grant_type_code(
     // }
 
     // check the client for external origin and auth flow
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let (client_id, credentials) = req_data.try_get_client_credentials(&req)?;
     let client = Client::find_maybe_ephemeral(data, client_id.clone())
         .await
         .map_err(|_| {
@@ -755,16 +1345,7 @@ async fn grant_type_code(
             )
         })?;
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
-    if client.confidential {
-        let secret = client_secret.ok_or_else(|| {
-            warn!("'client_secret' is missing");
-            ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                String::from("'client_secret' is missing"),
-            )
-        })?;
-        client.validate_secret(data, &secret, &req)?;
-    }
+    client.validate_credentials(data, credentials, &req).await?;
     client.validate_flow("authorization_code")?;
 
     // check for DPoP header
@@ -803,6 +1384,13 @@ async fn grant_type_code(
         warn!(err);
         return Err(ErrorResponse::new(ErrorResponseType::Unauthorized, err));
     }
+    if code.consent_pending {
+        warn!("The consent for the Authorization Code is still pending");
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("The consent for the Authorization Code is still pending"),
+        ));
+    }
     if code.exp < OffsetDateTime::now_utc().unix_timestamp() {
         warn!("The Authorization Code has expired");
         return Err(ErrorResponse::new(
@@ -847,10 +1435,17 @@ async fn grant_type_code(
         &user,
         data,
         &client,
-        dpop_fingerprint,
+        JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(&req)),
         code.nonce.clone(),
         Some(code.scopes.join(" ")),
+        code.claims.as_ref(),
+        code.acr.clone(),
+        code.amr.clone(),
+        code.auth_time,
         true,
+        code.session_id.as_deref(),
+        None,
+        real_ip_from_req(&req),
     )
     .await?;
 
@@ -866,9 +1461,13 @@ async fn grant_type_code(
         }
         session.validate_user_expiry(&user)?;
         session.user_id = Some(user.id);
+        session.auth_time = code.auth_time;
         session.roles = Some(user.roles);
         session.groups = user.groups;
         session.save(data).await?;
+
+        // needed for the back-channel logout
+        SessionClient::create(data, &session.id, &client.id).await?;
     }
     code.delete(data).await?;
 
@@ -882,14 +1481,7 @@ async fn grant_type_credentials(
     req: HttpRequest,
     req_data: TokenRequest,
 ) -> Result<(TokenSet, Vec<(HeaderName, HeaderValue)>), ErrorResponse> {
-    if req_data.client_secret.is_none() {
-        return Err(ErrorResponse::new(
-            ErrorResponseType::BadRequest,
-            String::from("'client_secret' is missing"),
-        ));
-    }
-
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let (client_id, credentials) = req_data.try_get_client_credentials(&req)?;
     let client = Client::find(data, client_id).await?;
     if !client.confidential {
         return Err(ErrorResponse::new(
@@ -903,15 +1495,56 @@ async fn grant_type_credentials(
             String::from("client is disabled"),
         ));
     }
-    let secret = client_secret.ok_or_else(|| {
+    client.validate_credentials(data, credentials, &req).await?;
+    client.validate_flow("client_credentials")?;
+    let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
+
+    let mut headers = Vec::new();
+    let dpop_fingerprint =
+        if let Some(proof) = DPoPProof::opt_validated_from(data, &req, &header_origin).await? {
+            if let Some(nonce) = &proof.claims.nonce {
+                headers.push((
+                    HeaderName::from_str(HEADER_DPOP_NONCE).unwrap(),
+                    HeaderValue::from_str(nonce).unwrap(),
+                ));
+            }
+            Some(proof.jwk_fingerprint()?)
+        } else {
+            None
+        };
+    // We do not push the origin header, because client credentials should never used from
+    // any browser at all
+
+    let cnf = JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(&req));
+    let ts = TokenSet::for_client_credentials(data, &client, cnf).await?;
+    Ok((ts, headers))
+}
+
+/// Return a [TokenSet](crate::models::response::TokenSet) for the `device_code` flow (RFC 8628)
+#[tracing::instrument(skip_all, fields(client_id = req_data.client_id))]
+async fn grant_type_device_code(
+    data: &web::Data<AppState>,
+    req: HttpRequest,
+    req_data: TokenRequest,
+) -> Result<(TokenSet, Vec<(HeaderName, HeaderValue)>), ErrorResponse> {
+    let device_code = req_data.device_code.as_deref().ok_or_else(|| {
         ErrorResponse::new(
             ErrorResponseType::BadRequest,
-            String::from("'client_secret' is missing"),
+            String::from("'device_code' is missing"),
         )
     })?;
-    client.validate_secret(data, &secret, &req)?;
-    client.validate_flow("client_credentials")?;
+
+    let (client_id, credentials) = req_data.try_get_client_credentials(&req)?;
+    let client = Client::find(data, client_id).await?;
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("client is disabled"),
+        ));
+    }
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
+    client.validate_credentials(data, credentials, &req).await?;
+    client.validate_flow(GRANT_TYPE_DEVICE_CODE)?;
 
     let mut headers = Vec::new();
     let dpop_fingerprint =
@@ -926,10 +1559,93 @@ async fn grant_type_credentials(
         } else {
             None
         };
-    // We do not push the origin header, because client credentials should never used from
-    // any browser at all
+    if let Some(h) = header_origin {
+        headers.push(h);
+    }
+
+    // the errors from here on must be the exact ones from the RFC, since devices rely on them
+    let mut code = DeviceCode::find(data, device_code).await?.ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::OAuth("expired_token".to_string()),
+            String::from("The 'device_code' is invalid or has expired"),
+        )
+    })?;
+    if code.client_id != client.id {
+        warn!("Wrong 'device_code' for client_id '{}'", client.id);
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("invalid_grant".to_string()),
+            format!("Wrong 'device_code' for client_id '{}'", client.id),
+        ));
+    }
+
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+    if code.exp < now {
+        code.delete(data).await?;
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("expired_token".to_string()),
+            String::from("The 'device_code' has expired"),
+        ));
+    }
+
+    if let Some(last_poll) = code.last_poll {
+        if now - last_poll < code.interval as i64 {
+            // by RFC, the interval must be increased by 5 seconds with each `slow_down`
+            code.interval += 5;
+            code.last_poll = Some(now);
+            code.save(data).await?;
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("slow_down".to_string()),
+                format!(
+                    "Polling too fast, the interval is now {} seconds",
+                    code.interval
+                ),
+            ));
+        }
+    }
+
+    if code.denied {
+        code.delete(data).await?;
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("access_denied".to_string()),
+            String::from("The authorization request has been denied"),
+        ));
+    }
+
+    let user_id = match code.verified_by.clone() {
+        None => {
+            code.last_poll = Some(now);
+            code.save(data).await?;
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("authorization_pending".to_string()),
+                String::from("The authorization request is still pending"),
+            ));
+        }
+        Some(id) => id,
+    };
+    // the device code is single use only
+    code.delete(data).await?;
+
+    let user = User::find(data, user_id).await?;
+    user.check_enabled()?;
+    user.check_expired()?;
 
-    let ts = TokenSet::for_client_credentials(data, &client, dpop_fingerprint).await?;
+    let ts = TokenSet::from_user(
+        &user,
+        data,
+        &client,
+        JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(&req)),
+        None,
+        Some(code.scopes.join(" ")),
+        None,
+        None,
+        None,
+        None,
+        code.is_mfa,
+        None,
+        None,
+        real_ip_from_req(&req),
+    )
+    .await?;
     Ok((ts, headers))
 }
 
@@ -953,21 +1669,13 @@ async fn grant_type_password(
         ));
     }
 
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let (client_id, credentials) = req_data.try_get_client_credentials(&req)?;
     let email = req_data.username.as_ref().unwrap();
     let password = req_data.password.unwrap();
 
     let client = Client::find(data, client_id).await?;
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
-    if client.confidential {
-        let secret = client_secret.ok_or_else(|| {
-            ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                String::from("Missing 'client_secret'"),
-            )
-        })?;
-        client.validate_secret(data, &secret, &req)?;
-    }
+    client.validate_credentials(data, credentials, &req).await?;
     client.validate_flow("password")?;
 
     let mut headers = Vec::new();
@@ -1022,8 +1730,23 @@ async fn grant_type_password(
 
             user.save(data, None, None).await?;
 
-            let ts = TokenSet::from_user(&user, data, &client, dpop_fingerprint, None, None, false)
-                .await?;
+            let ts = TokenSet::from_user(
+                &user,
+                data,
+                &client,
+                JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(&req)),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                None,
+                real_ip_from_req(&req),
+            )
+            .await?;
             Ok((ts, headers))
         }
         Err(err) => {
@@ -1057,20 +1780,12 @@ async fn grant_type_refresh(
             String::from("'refresh_token' is missing"),
         ));
     }
-    let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let (client_id, credentials) = req_data.try_get_client_credentials(&req)?;
     let client = Client::find_maybe_ephemeral(data, client_id).await?;
 
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
 
-    if client.confidential {
-        let secret = client_secret.ok_or_else(|| {
-            ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                String::from("'client_secret' is missing"),
-            )
-        })?;
-        client.validate_secret(data, &secret, &req)?;
-    }
+    client.validate_credentials(data, credentials, &req).await?;
 
     client.validate_flow("refresh_token")?;
 
@@ -1093,6 +1808,188 @@ async fn grant_type_refresh(
     Ok((ts, headers))
 }
 
+/// Return a [TokenSet](crate::models::response::TokenSet) for the `token-exchange` flow (RFC 8693)
+#[tracing::instrument(skip_all, fields(client_id = req_data.client_id, audience = req_data.audience))]
+async fn grant_type_token_exchange(
+    data: &web::Data<AppState>,
+    req: HttpRequest,
+    req_data: TokenRequest,
+) -> Result<(TokenSet, Vec<(HeaderName, HeaderValue)>), ErrorResponse> {
+    let subject_token = req_data.subject_token.as_ref().ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("'subject_token' is missing"),
+        )
+    })?;
+    if req_data.subject_token_type.as_deref() != Some(TOKEN_TYPE_ACCESS_TOKEN) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("'subject_token_type' must be '{}'", TOKEN_TYPE_ACCESS_TOKEN),
+        ));
+    }
+    if let Some(typ) = &req_data.requested_token_type {
+        if typ != TOKEN_TYPE_ACCESS_TOKEN {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported 'requested_token_type': {}", typ),
+            ));
+        }
+    }
+
+    // only confidential clients are allowed to exchange tokens
+    let (client_id, credentials) = req_data.try_get_client_credentials(&req)?;
+    let client = Client::find(data, client_id).await?;
+    if !client.confidential {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("'token-exchange' flow is allowed for confidential clients only"),
+        ));
+    }
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("client is disabled"),
+        ));
+    }
+    client.validate_credentials(data, credentials, &req).await?;
+    client.validate_flow(GRANT_TYPE_TOKEN_EXCHANGE)?;
+    let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
+
+    let audience = req_data.audience.unwrap_or_else(|| client.id.clone());
+    client.validate_token_exchange_audience(&audience)?;
+
+    let subject = validate_token::<JwtAccessClaims>(data, subject_token)
+        .await
+        .map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                String::from("Invalid 'subject_token'"),
+            )
+        })?;
+    if subject.custom.typ != JwtTokenType::Bearer && subject.custom.typ != JwtTokenType::DPoP {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("The 'subject_token' is not an access token"),
+        ));
+    }
+    // Only the party the token has been issued to may exchange it. Otherwise, any client could
+    // exchange each access token it gets hold of.
+    if !is_issued_to(&client.id, &subject.custom.azp, subject.audiences.as_ref()) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            String::from("The 'subject_token' was not issued to the requesting client"),
+        ));
+    }
+    // A DPoP or certificate bound token would lose its binding with the exchange.
+    if subject.custom.cnf.is_some() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("DPoP or certificate bound tokens cannot be exchanged"),
+        ));
+    }
+
+    // the exchanged token can only ever be downscoped
+    let subject_scopes = subject.custom.scope.split(' ').collect::<Vec<&str>>();
+    let scope = if let Some(scope) = req_data.scope {
+        if let Some(s) = scope.split(' ').find(|s| !subject_scopes.contains(s)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                format!("Scope '{}' is not included in the 'subject_token'", s),
+            ));
+        }
+        scope
+    } else {
+        subject.custom.scope.clone()
+    };
+
+    let act = if let Some(actor_token) = &req_data.actor_token {
+        if req_data.actor_token_type.as_deref() != Some(TOKEN_TYPE_ACCESS_TOKEN) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("'actor_token_type' must be '{}'", TOKEN_TYPE_ACCESS_TOKEN),
+            ));
+        }
+        let actor = validate_token::<JwtAccessClaims>(data, actor_token)
+            .await
+            .map_err(|_| {
+                ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    String::from("Invalid 'actor_token'"),
+                )
+            })?;
+        // Only the client itself can be the actor. Otherwise, any client could claim to act on
+        // behalf of any other party, from which it got a token.
+        if actor.custom.azp != client.id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                String::from("The 'actor_token' was not issued to the requesting client"),
+            ));
+        }
+        Some(JwtActClaim {
+            sub: actor.subject.unwrap_or(actor.custom.azp),
+            act: subject.custom.act.map(Box::new),
+        })
+    } else {
+        subject.custom.act
+    };
+
+    let user = if let Some(uid) = access_token_user_id(data, &subject).await? {
+        let user = User::find(data, uid).await?;
+        user.check_enabled()?;
+        user.check_expired()?;
+        Some(user)
+    } else {
+        None
+    };
+
+    // the exchanged token must never outlive the subject token
+    let subject_exp = subject
+        .expires_at
+        .map(|exp| exp.as_secs() as i64)
+        .unwrap_or_default();
+    let subject_lifetime = subject_exp - OffsetDateTime::now_utc().unix_timestamp();
+    let lifetime = (client.access_token_lifetime as i64).min(subject_lifetime);
+    if lifetime < 1 {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("The 'subject_token' has expired"),
+        ));
+    }
+
+    let mut headers = Vec::new();
+    let dpop_fingerprint =
+        if let Some(proof) = DPoPProof::opt_validated_from(data, &req, &header_origin).await? {
+            if let Some(nonce) = &proof.claims.nonce {
+                headers.push((
+                    HeaderName::from_str(HEADER_DPOP_NONCE).unwrap(),
+                    HeaderValue::from_str(nonce).unwrap(),
+                ));
+            }
+            Some(proof.jwk_fingerprint()?)
+        } else {
+            None
+        };
+    // Just like for client credentials, we do not push the origin header. Token exchange is
+    // meant for backend services only.
+
+    info!(
+        "Token exchange for audience '{}' by client '{}'",
+        audience, client.id
+    );
+    let ts = TokenSet::for_token_exchange(
+        user.as_ref(),
+        data,
+        &client,
+        JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(&req)),
+        lifetime,
+        scope,
+        audience,
+        act,
+    )
+    .await?;
+    Ok((ts, headers))
+}
+
 /**
 Handles the login delay.
 
@@ -1315,22 +2212,44 @@ pub async fn handle_login_delay(
     }
 }
 
-/// Returns the Logout HTML Page for [GET /oidc/logout](crate::handlers::get_logout)
+/// Returns the Logout HTML Page for [GET /oidc/logout](crate::handlers::get_logout) together with
+/// the nonce and the origins of all Front-Channel Logout URIs, which are needed for the CSP.
 pub async fn logout(
     logout_request: LogoutRequest,
     session: &Session,
     data: &web::Data<AppState>,
     lang: &Language,
-) -> Result<(String, String), ErrorResponse> {
+) -> Result<(String, String, Vec<String>), ErrorResponse> {
     let colors = ColorEntity::find_rauthy(data).await?;
+    let clients = SessionClient::find_clients(data, &session.id).await?;
+    let frame_origins = SessionClient::frontchannel_logout_origins(&clients);
 
-    if logout_request.id_token_hint.is_none() {
-        return Ok(LogoutHtml::build(&session.csrf_token, false, &colors, lang));
-    }
+    let id_claims = validate_id_token_hint(data, logout_request.id_token_hint.as_deref()).await?;
+    post_logout_redirect_uri(data, &logout_request, id_claims.as_ref()).await?;
+
+    // with a valid ID token as hint, the logout confirmation can be skipped
+    let (body, nonce) = LogoutHtml::build(&session.csrf_token, id_claims.is_some(), &colors, lang);
+    Ok((body, nonce, frame_origins))
+}
 
-    // check if the provided token hint is a valid
-    let token_raw = logout_request.id_token_hint.unwrap();
-    let claims = validate_token::<JwtIdClaims>(data, &token_raw).await?;
+/// Validates the optional `id_token_hint` from a logout request and returns its claims.
+/// The hint is accepted even if it has expired already, since ID tokens are usually short-lived
+/// while the logout may happen a lot later.
+pub async fn validate_id_token_hint(
+    data: &web::Data<AppState>,
+    id_token_hint: Option<&str>,
+) -> Result<Option<JwtIdClaims>, ErrorResponse> {
+    let token_raw = match id_token_hint {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    // jwt_simple cannot skip the `exp` check - a tolerance covering the whole epoch disables it
+    let options = jwt_simple::prelude::VerificationOptions {
+        allowed_issuers: Some(HashSet::from_strings(&[&data.issuer])),
+        time_tolerance: Some(coarsetime::Clock::now_since_epoch()),
+        ..Default::default()
+    };
+    let claims = validate_token_with::<JwtIdClaims>(data, token_raw, options).await?;
 
     // check if it is an ID token
     if JwtTokenType::Id != claims.custom.typ {
@@ -1340,39 +2259,57 @@ pub async fn logout(
         ));
     }
 
-    // from here on, the token_hint contains a valid ID token -> skip the logout confirmation
-    if logout_request.post_logout_redirect_uri.is_some() {
-        // unwrap is safe since the token is valid already
-        let client_id = claims.custom.azp;
-        let client = Client::find(data, client_id).await?;
-        if client.post_logout_redirect_uris.is_none() {
+    Ok(Some(claims.custom))
+}
+
+/// Validates the `post_logout_redirect_uri` against the client from either the `id_token_hint`
+/// or the `client_id` and returns the final redirect location with the `state` appended.
+pub async fn post_logout_redirect_uri(
+    data: &web::Data<AppState>,
+    logout_request: &LogoutRequest,
+    id_claims: Option<&JwtIdClaims>,
+) -> Result<Option<String>, ErrorResponse> {
+    let target = match &logout_request.post_logout_redirect_uri {
+        Some(uri) => uri,
+        None => return Ok(None),
+    };
+
+    let client_id = match (id_claims, &logout_request.client_id) {
+        (Some(claims), Some(client_id)) if &claims.azp != client_id => {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
-                String::from("Given 'post_logout_redirect_uri' is not allowed"),
+                String::from("'client_id' does not match the 'id_token_hint'"),
             ));
         }
-
-        let target = logout_request.post_logout_redirect_uri.unwrap();
-        let uri_vec = client.get_post_logout_uris();
-        let valid_redirect = uri_vec.as_ref().unwrap().iter().filter(|uri| {
-            if uri.ends_with('*') && target.starts_with(uri.split_once('*').unwrap().0) {
-                return true;
-            }
-            if target.eq(*uri) {
-                return true;
-            }
-            false
-        });
-        if valid_redirect.count() == 0 {
+        (Some(claims), _) => claims.azp.clone(),
+        (None, Some(client_id)) => client_id.clone(),
+        (None, None) => {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
-                String::from("Given 'post_logout_redirect_uri' is not allowed"),
+                String::from(
+                    "'post_logout_redirect_uri' needs either an 'id_token_hint' or a 'client_id'",
+                ),
             ));
         }
-        // redirect uri is valid at this point
+    };
+
+    let client = Client::find(data, client_id).await?;
+    let is_allowed = client
+        .get_post_logout_uris()
+        .unwrap_or_default()
+        .iter()
+        .any(|uri| match uri.split_once('*') {
+            Some((prefix, _)) if uri.ends_with('*') => target.starts_with(prefix),
+            _ => target == uri,
+        });
+    if !is_allowed {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("Given 'post_logout_redirect_uri' is not allowed"),
+        ));
     }
 
-    Ok(LogoutHtml::build(&session.csrf_token, true, &colors, lang))
+    Ok(Some(logout_request.post_logout_location(target)))
 }
 
 // /// The permission extractor for the `GrantsMiddleware`
@@ -1515,94 +2452,20 @@ pub async fn rotate_jwks(data: &web::Data<AppState>) -> Result<(), ErrorResponse
 
     let key = data.enc_keys.get(&data.enc_key_active).unwrap();
 
-    // RSA256
-    let jwk_plain = web::block(|| {
-        RS256KeyPair::generate(2048)
-            .unwrap()
-            .with_key_id(&get_rand(24))
-    })
-    .await?;
-    let jwk = encrypt(jwk_plain.to_der().unwrap().as_slice(), key)?;
-    let entity = Jwk {
-        kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-        created_at: OffsetDateTime::now_utc().unix_timestamp(),
-        signature: JwkKeyPairAlg::RS256,
-        enc_key_id: data.enc_key_active.to_string(),
-        jwk,
-    };
-    entity.save(&data.db).await?;
-
-    // RS384
-    let jwk_plain = web::block(|| {
-        RS384KeyPair::generate(3072)
-            .unwrap()
-            .with_key_id(&get_rand(24))
-    })
-    .await?;
-    let jwk = encrypt(jwk_plain.to_der().unwrap().as_slice(), key)?;
-    let entity = Jwk {
-        kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-        created_at: OffsetDateTime::now_utc().unix_timestamp(),
-        signature: JwkKeyPairAlg::RS384,
-        enc_key_id: data.enc_key_active.to_string(),
-        jwk,
-    };
-    entity.save(&data.db).await?;
-
-    // RSA512
-    let jwk_plain = web::block(|| {
-        RS512KeyPair::generate(4096)
-            .unwrap()
-            .with_key_id(&get_rand(24))
-    })
-    .await?;
-    let jwk = encrypt(jwk_plain.to_der().unwrap().as_slice(), key)?;
-    let entity = Jwk {
-        kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-        created_at: OffsetDateTime::now_utc().unix_timestamp(),
-        signature: JwkKeyPairAlg::RS512,
-        enc_key_id: data.enc_key_active.to_string(),
-        jwk,
-    };
-    entity.save(&data.db).await?;
-
-    // Ed25519
-    let jwk_plain = web::block(|| Ed25519KeyPair::generate().with_key_id(&get_rand(24))).await?;
-    let jwk = encrypt(jwk_plain.to_der().as_slice(), key)?;
-    let entity = Jwk {
-        kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-        created_at: OffsetDateTime::now_utc().unix_timestamp(),
-        signature: JwkKeyPairAlg::EdDSA,
-        enc_key_id: data.enc_key_active.to_string(),
-        jwk,
-    };
-    entity.save(&data.db).await?;
-
-    // clear all latest_jwk from cache
-    cache_del(
-        CACHE_NAME_12HR.to_string(),
-        format!("{}{}", IDX_JWK_LATEST, JwkKeyPairAlg::RS256.to_string()),
-        &data.caches.ha_cache_config,
-    )
-    .await?;
-    cache_del(
-        CACHE_NAME_12HR.to_string(),
-        format!("{}{}", IDX_JWK_LATEST, JwkKeyPairAlg::RS384.to_string()),
-        &data.caches.ha_cache_config,
-    )
-    .await?;
-    cache_del(
-        CACHE_NAME_12HR.to_string(),
-        format!("{}{}", IDX_JWK_LATEST, JwkKeyPairAlg::RS512.to_string()),
-        &data.caches.ha_cache_config,
-    )
-    .await?;
-    cache_del(
-        CACHE_NAME_12HR.to_string(),
-        format!("{}{}", IDX_JWK_LATEST, JwkKeyPairAlg::EdDSA.to_string()),
-        &data.caches.ha_cache_config,
-    )
-    .await?;
+    for alg in JwkKeyPairAlg::all() {
+        Jwk::generate(alg.clone(), &data.enc_key_active, key)
+            .await?
+            .save(&data.db)
+            .await?;
+
+        // clear the latest_jwk for this alg from cache
+        cache_del(
+            CACHE_NAME_12HR.to_string(),
+            format!("{}{}", IDX_JWK_LATEST, alg.as_str()),
+            &data.caches.ha_cache_config,
+        )
+        .await?;
+    }
 
     // clear the all_certs / JWKS cache
     cache_del(
@@ -1744,9 +2607,6 @@ pub async fn validate_refresh_token(
         ));
     }
 
-    // get uid
-    let uid = claims.custom.uid;
-
     // get azp / client
     let client = if let Some(c) = client_opt {
         c
@@ -1762,11 +2622,11 @@ pub async fn validate_refresh_token(
     let header_origin = client.validate_origin(req, &data.listen_scheme, &data.public_url)?;
 
     // validate DPoP proof
-    let (dpop_fingerprint, dpop_nonce) = if let Some(cnf) = claims.custom.cnf {
+    let (dpop_fingerprint, dpop_nonce) = if let Some(jkt) = claims.custom.cnf.and_then(|c| c.jkt) {
         // if the refresh token contains the 'cnf' header, we must validate the DPoP as well
         if let Some(proof) = DPoPProof::opt_validated_from(data, req, &header_origin).await? {
             let fingerprint = proof.jwk_fingerprint()?;
-            if fingerprint != cnf.jkt {
+            if fingerprint != jkt {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Forbidden,
                     "The refresh token is bound to a missing DPoP proof".to_string(),
@@ -1789,6 +2649,11 @@ pub async fn validate_refresh_token(
 
     let mut rt = RefreshToken::find(data, validation_str).await?;
 
+    // with rotation, an already used refresh token must never be presented again
+    if client.refresh_token_rotation && rt.used {
+        return Err(revoke_refresh_token_family(data, req, &rt).await);
+    }
+
     // check expires_at from the db entry
     if rt.exp < OffsetDateTime::now_utc().unix_timestamp() {
         // if an already used refresh token was provided again, invalidate all existing ones for the
@@ -1803,7 +2668,7 @@ pub async fn validate_refresh_token(
         ));
     }
 
-    let mut user = User::find(data, uid).await?;
+    let mut user = User::find(data, rt.user_id.clone()).await?;
     user.check_enabled()?;
     user.check_expired()?;
 
@@ -1815,24 +2680,38 @@ pub async fn validate_refresh_token(
     user.save(data, None, None).await?;
 
     // invalidate current refresh token
-    let now = OffsetDateTime::now_utc().unix_timestamp();
-    let exp_at_secs = now + data.refresh_grace_time as i64;
-    // do not set expires_at, if we are below our refresh token grace time anyway already
-    if rt.exp > exp_at_secs + 1 {
-        rt.exp = exp_at_secs;
-        rt.save(data).await?;
+    if client.refresh_token_rotation {
+        // the used token is kept until it expires to be able to detect a reuse later on
+        if !rt.mark_used(data).await? {
+            return Err(revoke_refresh_token_family(data, req, &rt).await);
+        }
+    } else {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let exp_at_secs = now + data.refresh_grace_time as i64;
+        // do not set expires_at, if we are below our refresh token grace time anyway already
+        if rt.exp > exp_at_secs + 1 {
+            rt.exp = exp_at_secs;
+            rt.save(data).await?;
+        }
     }
 
     // TODO do we somehow need to be able to set ID 'nonce' here too?
-    let ts = if let Some(s) = rt.scope {
+    let ts = if let Some(s) = rt.scope.clone() {
         TokenSet::from_user(
             &user,
             data,
             &client,
-            dpop_fingerprint,
+            JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(req)),
             None,
             Some(s),
+            None,
+            None,
+            None,
+            None,
             rt.is_mfa,
+            rt.session_id.as_deref(),
+            Some(&rt),
+            real_ip_from_req(req),
         )
         .await
     } else {
@@ -1840,16 +2719,99 @@ pub async fn validate_refresh_token(
             &user,
             data,
             &client,
-            dpop_fingerprint,
+            JktClaim::new(dpop_fingerprint, client.tls_cert_thumbprint(req)),
+            None,
+            None,
+            None,
+            None,
             None,
             None,
             rt.is_mfa,
+            rt.session_id.as_deref(),
+            Some(&rt),
+            real_ip_from_req(req),
         )
         .await
     }?;
     Ok((ts, dpop_nonce))
 }
 
+/// An already rotated refresh token has been presented again, which means that it has most
+/// probably been stolen. Since it is unknown if the attacker or the legitimate client has used
+/// it first, the whole token family and the session it originates from will be revoked.
+async fn revoke_refresh_token_family(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    rt: &RefreshToken,
+) -> ErrorResponse {
+    warn!(
+        "Refresh token reuse detected for user {} - revoking the token family",
+        rt.user_id
+    );
+
+    if let Err(err) = rt.delete_family(data).await {
+        return err;
+    }
+    if let Some(sid) = &rt.session_id {
+        // the session may have been logged out or expired already in the meantime
+        if let Ok(mut session) = Session::find(data, sid.clone()).await {
+            if let Err(err) = session.invalidate(data).await {
+                return err;
+            }
+        }
+    }
+
+    data.tx_events
+        .send_async(Event::refresh_token_reuse(
+            format!(
+                "User: {} / Client: {}",
+                rt.user_id,
+                rt.client_id.as_deref().unwrap_or_default()
+            ),
+            real_ip_from_req(req),
+        ))
+        .await
+        .unwrap();
+
+    ErrorResponse::new(
+        ErrorResponseType::BadRequest,
+        String::from(
+            "Refresh Token has been used already. The whole token family and the session \
+            have been revoked because of misuse.",
+        ),
+    )
+}
+
+/// Returns `true` if a token with the given `azp` and `aud` has been issued to the client.
+fn is_issued_to(client_id: &str, azp: &str, aud: Option<&Audiences>) -> bool {
+    if azp == client_id {
+        return true;
+    }
+    match aud {
+        Some(Audiences::AsSet(set)) => set.contains(client_id),
+        Some(Audiences::AsString(aud)) => aud == client_id,
+        None => false,
+    }
+}
+
+/// Validates the binding of a certificate bound token (RFC 8705) against the thumbprint of the
+/// TLS client certificate, which has been used to present the token. Tokens without a
+/// `cnf.x5t#S256` are always valid.
+pub fn validate_cert_binding(
+    cnf: &Option<JktClaim>,
+    thumbprint: Option<&str>,
+) -> Result<(), ErrorResponse> {
+    if let Some(x5t) = cnf.as_ref().and_then(|cnf| cnf.x5t_s256.as_deref()) {
+        if thumbprint != Some(x5t) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                String::from("The token is bound to a different TLS client certificate"),
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Validates a given JWT Access Token
 pub async fn validate_token<T: serde::Serialize + for<'de> ::serde::Deserialize<'de>>(
     data: &web::Data<AppState>,
@@ -1860,16 +2822,93 @@ pub async fn validate_token<T: serde::Serialize + for<'de> ::serde::Deserialize<
         allowed_issuers: Some(HashSet::from_strings(&[&data.issuer])),
         ..Default::default()
     };
+    validate_token_with(data, token, options).await
+}
 
+async fn validate_token_with<T: serde::Serialize + for<'de> ::serde::Deserialize<'de>>(
+    data: &web::Data<AppState>,
+    token: &str,
+    options: jwt_simple::prelude::VerificationOptions,
+) -> Result<claims::JWTClaims<T>, ErrorResponse> {
     // extract metadata
     let kid = JwkKeyPair::kid_from_token(token)?;
 
     // retrieve jwk for kid
     let kp = JwkKeyPair::find(data, kid).await?;
-    validate_jwt!(T, kp, token, options)
+    let claims = validate_jwt!(T, kp, token, options)?;
+
+    if let Some(jti) = &claims.jwt_id {
+        RevokedToken::check_not_revoked(data, jti).await?;
+    }
 
     // TODO check roles if we add more users / roles
+    Ok(claims)
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_id_claims_pairwise() {
+        let user = User {
+            email: "user@example.com".to_string(),
+            ..Default::default()
+        };
+        let client = Client {
+            id: "pairwise".to_string(),
+            subject_type: Some("pairwise".to_string()),
+            ..Default::default()
+        };
+        let claims = |is_pairwise: bool, scope: &str| {
+            let claims = build_id_claims(
+                &user,
+                &client,
+                is_pairwise,
+                None,
+                scope,
+                None,
+                None,
+                None,
+                None,
+                None,
+                true,
+                None,
+            );
+            serde_json::to_string(&claims).unwrap()
+        };
+
+        // no global identifier must leak to a pairwise client without the email scope
+        let json = claims(true, "openid profile webid");
+        assert!(!json.contains(&user.id));
+        assert!(!json.contains(&user.email));
+        assert!(!json.contains("webid"));
+        assert!(!json.contains("preferred_username"));
+
+        // with an explicitly granted email scope, the email is wanted by the client
+        let json = claims(true, "openid email");
+        assert!(!json.contains(&user.id));
+        assert!(json.contains("\"preferred_username\":\"user@example.com\""));
+
+        let json = claims(false, "openid");
+        assert!(json.contains("\"preferred_username\":\"user@example.com\""));
+    }
+
+    #[test]
+    fn test_is_issued_to() {
+        assert!(is_issued_to("client_a", "client_a", None));
+        assert!(!is_issued_to("client_b", "client_a", None));
+
+        let aud = Audiences::AsString("client_a".to_string());
+        assert!(!is_issued_to("client_b", "client_a", Some(&aud)));
+        let aud = Audiences::AsString("client_b".to_string());
+        assert!(is_issued_to("client_b", "client_a", Some(&aud)));
+
+        let aud = Audiences::AsSet(HashSet::from([
+            "client_a".to_string(),
+            "client_c".to_string(),
+        ]));
+        assert!(!is_issued_to("client_b", "client_a", Some(&aud)));
+        assert!(is_issued_to("client_c", "client_a", Some(&aud)));
+    }
+}
@@ -1,9 +1,17 @@
-use actix_web::web;
+use crate::auth::get_bearer_token_from_header;
+use actix_web::{web, HttpRequest};
+use rauthy_common::constants::{
+    DYN_CLIENT_REG_ALLOWED_FLOWS, DYN_CLIENT_REG_ALLOWED_SCOPES, DYN_CLIENT_REG_REDIRECT_URIS,
+    DYN_CLIENT_REG_TOKEN,
+};
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::get_rand;
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
-use rauthy_models::request::UpdateClientRequest;
-use rauthy_models::response::ClientSecretResponse;
+use rauthy_models::entity::clients_dyn::ClientDyn;
+use rauthy_models::request::{DynClientRequest, UpdateClientRequest};
+use rauthy_models::response::{ClientSecretResponse, DynClientResponse};
+use tracing::info;
 
 // Updates a client.<br>
 // A client secret will be automatically generated if the
@@ -56,6 +64,67 @@ pub async fn update_client(
 
     client.challenge = client_req.challenges.map(|c| c.join(","));
     client.force_mfa = client_req.force_mfa;
+    client.token_exchange_audiences = client_req
+        .token_exchange_audiences
+        .filter(|auds| !auds.is_empty())
+        .map(|auds| auds.join(","));
+    client.backchannel_logout_uri = client_req
+        .backchannel_logout_uri
+        .filter(|uri| !uri.is_empty());
+    client.frontchannel_logout_uri = client_req
+        .frontchannel_logout_uri
+        .filter(|uri| !uri.is_empty());
+    client.require_par = client_req.require_par;
+    client.require_consent = client_req.require_consent;
+    client.refresh_token_rotation = client_req.refresh_token_rotation;
+    client.jwks = client_req.jwks.filter(|jwks| !jwks.is_empty());
+    client.jwks_uri = client_req.jwks_uri.filter(|uri| !uri.is_empty());
+    client.validate_jwks()?;
+    client.token_endpoint_auth_method = if client.confidential {
+        match client_req.token_endpoint_auth_method {
+            Some(method) => method,
+            // the client may just have been switched to confidential
+            None if client.token_endpoint_auth_method == "none" => {
+                "client_secret_basic".to_string()
+            }
+            None => client.token_endpoint_auth_method,
+        }
+    } else {
+        "none".to_string()
+    };
+    client.tls_client_auth_subject_dn = client_req
+        .tls_client_auth_subject_dn
+        .filter(|dn| !dn.is_empty());
+    client.tls_client_auth_thumbprints = client_req
+        .tls_client_auth_thumbprints
+        .filter(|thumbprints| !thumbprints.is_empty())
+        .map(|thumbprints| thumbprints.join(","));
+    client.validate_token_endpoint_auth_method()?;
+    client.id_token_encrypted_response_alg = client_req
+        .id_token_encrypted_response_alg
+        .filter(|alg| !alg.is_empty());
+    client.id_token_encrypted_response_enc = client_req
+        .id_token_encrypted_response_enc
+        .filter(|enc| !enc.is_empty());
+    client.userinfo_encrypted_response_alg = client_req
+        .userinfo_encrypted_response_alg
+        .filter(|alg| !alg.is_empty());
+    client.userinfo_encrypted_response_enc = client_req
+        .userinfo_encrypted_response_enc
+        .filter(|enc| !enc.is_empty());
+    client.validate_jwe()?;
+    client.userinfo_signed_response_alg = client_req
+        .userinfo_signed_response_alg
+        .map(|alg| alg.to_string());
+    client.subject_type = client_req.subject_type.filter(|typ| typ == "pairwise");
+    client.sector_identifier_uri = client_req
+        .sector_identifier_uri
+        .filter(|uri| !uri.is_empty());
+    client.validate_subject_type().await?;
+    client.request_uris = client_req
+        .request_uris
+        .filter(|uris| !uris.is_empty())
+        .map(|uris| uris.join(","));
 
     client.save(data, None).await?;
     Ok(client)
@@ -102,3 +171,257 @@ pub async fn generate_new_secret(
         secret: Some(clear),
     })
 }
+
+/// Registers a new client via the dynamic client registration (RFC 7591).<br>
+/// If `DYN_CLIENT_REG_TOKEN` is set, the request must contain it as a Bearer token.
+pub async fn register_dyn_client(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    payload: DynClientRequest,
+) -> Result<DynClientResponse, ErrorResponse> {
+    if let Some(token) = &*DYN_CLIENT_REG_TOKEN {
+        let bearer = get_bearer_token_from_header(req.headers())?;
+        if ClientDyn::hash_token(&bearer) != ClientDyn::hash_token(token) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                String::from("Invalid initial access token"),
+            ));
+        }
+    }
+
+    // dynamically registered clients are third party applications by nature
+    let mut client = Client {
+        id: get_rand(24).to_lowercase(),
+        require_consent: true,
+        ..Default::default()
+    };
+    let auth_method = apply_dyn_client_req(data, &mut client, payload).await?;
+    let secret = client.get_secret_cleartext(data)?;
+
+    let (client_dyn, token) = ClientDyn::create(data, &client, auth_method).await?;
+    info!("New client '{}' registered dynamically", client.id);
+
+    Ok(DynClientResponse::build(
+        &data.issuer,
+        client,
+        &client_dyn,
+        secret,
+        Some(token),
+    ))
+}
+
+/// Returns the configuration of a dynamically registered client (RFC 7592).
+pub async fn get_dyn_client(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    id: String,
+) -> Result<DynClientResponse, ErrorResponse> {
+    let client_dyn = find_dyn_client_validated(data, req, &id).await?;
+    let client = Client::find(data, id).await?;
+    let secret = client.get_secret_cleartext(data)?;
+
+    Ok(DynClientResponse::build(
+        &data.issuer,
+        client,
+        &client_dyn,
+        secret,
+        None,
+    ))
+}
+
+/// Replaces the configuration of a dynamically registered client (RFC 7592).<br>
+/// The same policy restrictions as for the registration apply.
+pub async fn update_dyn_client(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    id: String,
+    payload: DynClientRequest,
+) -> Result<DynClientResponse, ErrorResponse> {
+    let mut client_dyn = find_dyn_client_validated(data, req, &id).await?;
+    let mut client = Client::find(data, id).await?;
+    client_dyn.token_endpoint_auth_method =
+        apply_dyn_client_req(data, &mut client, payload).await?;
+    let secret = client.get_secret_cleartext(data)?;
+
+    let mut txn = data.db.begin().await?;
+    client.save(data, Some(&mut txn)).await?;
+    client_dyn.save(data, Some(&mut txn)).await?;
+    txn.commit().await?;
+
+    Ok(DynClientResponse::build(
+        &data.issuer,
+        client,
+        &client_dyn,
+        secret,
+        None,
+    ))
+}
+
+/// Deletes a dynamically registered client (RFC 7592).
+pub async fn delete_dyn_client(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    id: String,
+) -> Result<(), ErrorResponse> {
+    find_dyn_client_validated(data, req, &id).await?;
+    let client = Client::find(data, id).await?;
+    client.delete(data).await?;
+    info!("Dynamically registered client '{}' deleted", client.id);
+    Ok(())
+}
+
+/// Returns the [ClientDyn] after a successful validation of the `registration_access_token`.
+/// An unknown client will lead to the same error as an invalid token, like required by RFC 7592.
+async fn find_dyn_client_validated(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    id: &str,
+) -> Result<ClientDyn, ErrorResponse> {
+    let bearer = get_bearer_token_from_header(req.headers())?;
+    let client_dyn = ClientDyn::find(data, id).await.map_err(|_| {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("Invalid registration access token"),
+        )
+    })?;
+    client_dyn.validate_token(&bearer)?;
+    Ok(client_dyn)
+}
+
+/// Applies the client metadata to the given client while checking it against the configured
+/// policy for dynamically registered clients. Returns the `token_endpoint_auth_method`.
+async fn apply_dyn_client_req(
+    data: &web::Data<AppState>,
+    client: &mut Client,
+    payload: DynClientRequest,
+) -> Result<String, ErrorResponse> {
+    if let Some(response_types) = &payload.response_types {
+        if response_types.iter().any(|t| t != "code") {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("invalid_client_metadata".to_string()),
+                String::from("Only the 'code' response type is supported"),
+            ));
+        }
+    }
+
+    let grant_types = payload
+        .grant_types
+        .unwrap_or_else(|| vec!["authorization_code".to_string()]);
+    if let Some(grant_type) = grant_types
+        .iter()
+        .find(|g| !DYN_CLIENT_REG_ALLOWED_FLOWS.contains(g))
+    {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("invalid_client_metadata".to_string()),
+            format!("The grant type '{}' is not allowed", grant_type),
+        ));
+    }
+
+    if payload.redirect_uris.is_empty() && grant_types.iter().any(|g| g == "authorization_code") {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("invalid_redirect_uri".to_string()),
+            String::from("'redirect_uris' must not be empty"),
+        ));
+    }
+    let uris = payload
+        .redirect_uris
+        .iter()
+        .chain(payload.post_logout_redirect_uris.iter().flatten())
+        .chain(payload.backchannel_logout_uri.iter())
+        .chain(payload.frontchannel_logout_uri.iter());
+    for uri in uris {
+        let is_allowed =
+            DYN_CLIENT_REG_REDIRECT_URIS
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => uri.starts_with(prefix),
+                    None => uri == pattern,
+                });
+        if !is_allowed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("invalid_redirect_uri".to_string()),
+                format!("The redirect URI '{}' is not allowed", uri),
+            ));
+        }
+    }
+
+    let scopes = match &payload.scope {
+        None => DYN_CLIENT_REG_ALLOWED_SCOPES.clone(),
+        Some(scope) => scope
+            .split(' ')
+            .filter(|s| {
+                DYN_CLIENT_REG_ALLOWED_SCOPES
+                    .iter()
+                    .any(|allowed| allowed == s)
+            })
+            .map(String::from)
+            .collect(),
+    };
+
+    let auth_method = payload
+        .token_endpoint_auth_method
+        .unwrap_or_else(|| "client_secret_basic".to_string());
+    let confidential = auth_method != "none";
+    if confidential && !client.confidential {
+        let (_, enc) = Client::generate_new_secret(data)?;
+        client.secret = Some(enc);
+    } else if !confidential {
+        client.secret = None;
+    }
+    client.confidential = confidential;
+
+    client.name = payload.client_name;
+    client.redirect_uris = payload.redirect_uris.join(",");
+    client.post_logout_redirect_uris = payload.post_logout_redirect_uris.map(|u| u.join(","));
+    client.backchannel_logout_uri = payload.backchannel_logout_uri;
+    client.frontchannel_logout_uri = payload.frontchannel_logout_uri;
+    client.jwks = payload.jwks.map(|jwks| jwks.to_string());
+    client.jwks_uri = payload.jwks_uri;
+    client.token_endpoint_auth_method = auth_method.clone();
+    client.tls_client_auth_subject_dn = payload.tls_client_auth_subject_dn;
+    client.id_token_encrypted_response_alg = payload.id_token_encrypted_response_alg;
+    client.id_token_encrypted_response_enc = payload.id_token_encrypted_response_enc;
+    client.userinfo_encrypted_response_alg = payload.userinfo_encrypted_response_alg;
+    client.userinfo_encrypted_response_enc = payload.userinfo_encrypted_response_enc;
+    client.userinfo_signed_response_alg = payload
+        .userinfo_signed_response_alg
+        .map(|alg| alg.to_string());
+    client.subject_type = payload.subject_type.filter(|typ| typ == "pairwise");
+    client.sector_identifier_uri = payload.sector_identifier_uri;
+    client.request_uris = payload
+        .request_uris
+        .filter(|uris| !uris.is_empty())
+        .map(|uris| uris.join(","));
+    client
+        .validate_jwks()
+        .and_then(|_| client.validate_token_endpoint_auth_method())
+        .and_then(|_| client.validate_jwe())
+        .map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::OAuth("invalid_client_metadata".to_string()),
+                err.message,
+            )
+        })?;
+    client.validate_subject_type().await.map_err(|err| {
+        ErrorResponse::new(
+            ErrorResponseType::OAuth("invalid_client_metadata".to_string()),
+            err.message,
+        )
+    })?;
+    client.refresh_token = grant_types.iter().any(|g| g == "refresh_token");
+    client.flows_enabled = grant_types.join(",");
+    client.access_token_alg = payload
+        .access_token_signed_response_alg
+        .unwrap_or_default()
+        .to_string();
+    client.id_token_alg = payload
+        .id_token_signed_response_alg
+        .unwrap_or_default()
+        .to_string();
+    client.access_token_lifetime = payload.default_max_age.unwrap_or(1800);
+    client.scopes = Client::sanitize_scopes(data, scopes).await?;
+    client.default_scopes = "openid".to_string();
+    client.challenge = Some("S256".to_string());
+
+    Ok(auth_method)
+}
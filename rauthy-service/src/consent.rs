@@ -0,0 +1,175 @@
+use actix_web::web;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_consents::{ConsentReq, UserConsent};
+use rauthy_models::request::ConsentRequest;
+use rauthy_models::response::{ConsentInfoResponse, UserConsentResponse};
+use std::ops::Add;
+use time::OffsetDateTime;
+use tracing::info;
+
+/// Holds back the redirect with the already issued `code`, if the client requires a consent,
+/// which does not cover all requested scopes yet, or if `prompt=consent` has been requested.
+/// The returned location will point to the consent page in that case and the `code` cannot be
+/// exchanged until the consent has been granted.
+#[allow(clippy::too_many_arguments)]
+pub async fn consent_redirect(
+    data: &web::Data<AppState>,
+    client: &Client,
+    code: &mut AuthCode,
+    redirect_uri: &str,
+    state: Option<&str>,
+    prompt: Option<&str>,
+    header_loc: String,
+) -> Result<String, ErrorResponse> {
+    let force = prompt
+        .map(|p| p.split_whitespace().any(|p| p == "consent"))
+        .unwrap_or(false);
+    if !client.require_consent && !force {
+        return Ok(header_loc);
+    }
+    if !force && is_granted(data, client, &code.user_id, &code.scopes).await? {
+        return Ok(header_loc);
+    }
+    code.consent_pending = true;
+    code.save(data).await?;
+
+    let header_loc_denied = ConsentReq::denied_uri(redirect_uri, state);
+    let consent_req = ConsentReq::new(
+        code.user_id.clone(),
+        code.session_id.clone().unwrap_or_default(),
+        client.id.clone(),
+        code.id.clone(),
+        code.scopes.clone(),
+        header_loc,
+        header_loc_denied,
+    );
+    consent_req.save(data).await?;
+
+    Ok(consent_req.consent_uri())
+}
+
+/// Returns `true`, if the user has already granted all the given scopes to the client.
+pub async fn is_granted(
+    data: &web::Data<AppState>,
+    client: &Client,
+    user_id: &str,
+    scopes: &[String],
+) -> Result<bool, ErrorResponse> {
+    if !client.require_consent {
+        return Ok(true);
+    }
+    let granted = UserConsent::find(data, user_id, &client.id)
+        .await?
+        .map(|consent| consent.covers(scopes))
+        .unwrap_or(false);
+    Ok(granted)
+}
+
+/// # Business logic for [GET /oidc/consent/{id}](crate::handlers::get_consent_info)
+pub async fn get_consent_info(
+    data: &web::Data<AppState>,
+    session: &Session,
+    id: String,
+) -> Result<ConsentInfoResponse, ErrorResponse> {
+    let consent_req = find_consent_req(data, session, id).await?;
+    let client = Client::find(data, consent_req.client_id).await?;
+
+    Ok(ConsentInfoResponse {
+        client_id: client.id,
+        client_name: client.name,
+        scopes: consent_req.scopes,
+    })
+}
+
+/// # Business logic for [POST /oidc/consent](crate::handlers::post_consent)
+///
+/// Grants or denies the requested scopes and returns the location for the redirect back to
+/// the client.
+pub async fn post_consent(
+    data: &web::Data<AppState>,
+    session: &Session,
+    req_data: ConsentRequest,
+) -> Result<String, ErrorResponse> {
+    let consent_req = find_consent_req(data, session, req_data.id).await?;
+    consent_req.delete(data).await?;
+
+    let mut code = AuthCode::find(data, consent_req.code.clone())
+        .await?
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "The authorization request has expired".to_string(),
+            )
+        })?;
+
+    if req_data.deny == Some(true) {
+        code.delete(data).await?;
+        return Ok(consent_req.header_loc_denied);
+    }
+
+    UserConsent::grant(
+        data,
+        &consent_req.user_id,
+        &consent_req.client_id,
+        &consent_req.scopes,
+    )
+    .await?;
+    info!(
+        "Consent for client '{}' granted by user {}",
+        consent_req.client_id, consent_req.user_id
+    );
+
+    // the code lifetime starts after the consent to not depend on how long the user needed
+    let client = Client::find(data, consent_req.client_id).await?;
+    code.exp = OffsetDateTime::now_utc()
+        .add(time::Duration::seconds(client.auth_code_lifetime as i64))
+        .unix_timestamp();
+    code.consent_pending = false;
+    code.save(data).await?;
+
+    Ok(consent_req.header_loc)
+}
+
+/// Returns all consents of the given user together with the client names.
+pub async fn get_user_consents(
+    data: &web::Data<AppState>,
+    user_id: &str,
+) -> Result<Vec<UserConsentResponse>, ErrorResponse> {
+    let consents = UserConsent::find_for_user(data, user_id).await?;
+    let mut res = Vec::with_capacity(consents.len());
+    for consent in consents {
+        let client_name = Client::find(data, consent.client_id.clone())
+            .await
+            .ok()
+            .and_then(|c| c.name);
+        res.push(UserConsentResponse {
+            scopes: consent.get_scopes(),
+            client_id: consent.client_id,
+            client_name,
+            created_at: consent.created_at,
+            last_update: consent.last_update,
+        });
+    }
+    Ok(res)
+}
+
+/// A consent request can only be answered by the session it has been created for. The session
+/// will not be authenticated before the code has been exchanged after a fresh login.
+async fn find_consent_req(
+    data: &web::Data<AppState>,
+    session: &Session,
+    id: String,
+) -> Result<ConsentReq, ErrorResponse> {
+    let consent_req = ConsentReq::find(data, id).await?;
+    if consent_req.session_id != session.id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "The consent request belongs to another session".to_string(),
+        ));
+    }
+    Ok(consent_req)
+}
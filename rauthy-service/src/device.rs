@@ -0,0 +1,123 @@
+use crate::consent;
+use actix_web::{web, HttpRequest};
+use rauthy_common::constants::{DEVICE_GRANT_CODE_LIFETIME, GRANT_TYPE_DEVICE_CODE};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::device_codes::DeviceCode;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_consents::UserConsent;
+use rauthy_models::entity::users::User;
+use rauthy_models::request::{DeviceRequest, DeviceVerifyRequest};
+use rauthy_models::response::DeviceCodeResponse;
+use tracing::info;
+
+/// # Business logic for [POST /oidc/device](crate::handlers::post_device_auth)
+///
+/// Validates the client and issues a new `device_code` / `user_code` pair.
+pub async fn device_authorization(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    req_data: DeviceRequest,
+) -> Result<DeviceCodeResponse, ErrorResponse> {
+    let (client_id, credentials) = req_data.try_get_client_credentials(req)?;
+    let client = Client::find(data, client_id).await?;
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("client is disabled"),
+        ));
+    }
+    client.validate_credentials(data, credentials, req).await?;
+    client.validate_flow(GRANT_TYPE_DEVICE_CODE)?;
+
+    let scopes = client.sanitize_login_scopes(
+        &req_data
+            .scope
+            .map(|s| s.split(' ').map(|s| s.to_string()).collect()),
+    )?;
+    let code = DeviceCode::new(client.id, scopes);
+    code.save(data).await?;
+
+    let verification_uri = format!("{}/oidc/device", data.issuer);
+    let user_code = code.user_code_display();
+    Ok(DeviceCodeResponse {
+        verification_uri_complete: format!("{}?user_code={}", verification_uri, user_code),
+        device_code: code.device_code,
+        user_code,
+        verification_uri,
+        expires_in: *DEVICE_GRANT_CODE_LIFETIME,
+        interval: code.interval,
+    })
+}
+
+/// # Business logic for [POST /oidc/device/verify](crate::handlers::post_device_verify)
+///
+/// Authorizes or denies the device with the given `user_code` for the user of the current
+/// session. The device will receive its tokens with the next poll on the token endpoint.
+pub async fn verify_device(
+    data: &web::Data<AppState>,
+    session: &Session,
+    req_data: DeviceVerifyRequest,
+) -> Result<(), ErrorResponse> {
+    let mut code = DeviceCode::find_by_user_code(data, &req_data.user_code)
+        .await?
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                String::from("The user code is invalid or has expired"),
+            )
+        })?;
+    if code.verified_by.is_some() || code.denied {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("The user code has been used already"),
+        ));
+    }
+
+    if req_data.deny == Some(true) {
+        code.denied = true;
+        return code.save(data).await;
+    }
+
+    let user_id = session.user_id.clone().ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            String::from("No user linked to this session"),
+        )
+    })?;
+    let user = User::find(data, user_id).await?;
+    user.check_enabled()?;
+    user.check_expired()?;
+
+    let client = Client::find(data, code.client_id.clone()).await?;
+    client.validate_mfa(&user)?;
+    if client.force_mfa && !session.is_mfa {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::MfaRequired,
+            "MFA is required for this client, please log in again with your passkey".to_string(),
+        ));
+    }
+    // the device grant has no consent page, the user must agree on the verification page
+    if !consent::is_granted(data, &client, &user.id, &code.scopes).await? {
+        if req_data.consent != Some(true) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::OAuth("consent_required".to_string()),
+                "The user must grant the requested scopes".to_string(),
+            ));
+        }
+        UserConsent::grant(data, &user.id, &client.id, &code.scopes).await?;
+        info!(
+            "Consent for client '{}' granted by user {}",
+            client.id, user.id
+        );
+    }
+
+    info!(
+        "Device for client '{}' authorized by user {}",
+        client.id, user.email
+    );
+    code.verified_by = Some(user.id);
+    code.is_mfa = session.is_mfa;
+    code.save(data).await
+}
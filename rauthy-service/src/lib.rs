@@ -4,6 +4,14 @@
 
 pub mod auth;
 pub mod client;
+pub mod consent;
+pub mod device;
 pub mod encryption;
+pub mod offline_tokens;
+pub mod par;
 pub mod password_reset;
+pub mod recovery;
+pub mod scim;
 pub mod token_set;
+pub mod totp;
+pub mod upstream;
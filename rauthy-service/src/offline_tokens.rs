@@ -0,0 +1,48 @@
+use actix_web::web;
+use rauthy_common::error_response::ErrorResponse;
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::response::OfflineTokenResponse;
+use std::collections::HashMap;
+
+/// # Business logic for [GET /users/{id}/offline_tokens](crate::handlers::get_user_offline_tokens)
+///
+/// Returns one entry for each token family, which always shows the latest token.
+pub async fn get_for_user(
+    data: &web::Data<AppState>,
+    user_id: &str,
+) -> Result<Vec<OfflineTokenResponse>, ErrorResponse> {
+    let mut families: HashMap<String, RefreshToken> = HashMap::new();
+    for rt in RefreshToken::find_offline_for_user(data, user_id).await? {
+        let Some(family_id) = rt.family_id.clone() else {
+            continue;
+        };
+        match families.get(&family_id) {
+            Some(existing) if existing.last_used >= rt.last_used => {}
+            _ => {
+                families.insert(family_id, rt);
+            }
+        }
+    }
+
+    let mut res = Vec::with_capacity(families.len());
+    for (id, rt) in families {
+        let client_id = rt.client_id.unwrap_or_default();
+        let client_name = Client::find(data, client_id.clone())
+            .await
+            .ok()
+            .and_then(|c| c.name);
+        res.push(OfflineTokenResponse {
+            id,
+            client_id,
+            client_name,
+            created_at: rt.created_at.unwrap_or(rt.nbf),
+            last_used: rt.last_used.unwrap_or(rt.nbf),
+            ip: rt.ip,
+        });
+    }
+    res.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+
+    Ok(res)
+}
@@ -0,0 +1,112 @@
+use crate::auth::validate_auth_req_param;
+use actix_web::{web, HttpRequest};
+use rauthy_common::constants::PAR_REQUEST_URI_LIFETIME;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::pushed_auth_requests::PushedAuthRequest;
+use rauthy_models::entity::request_objects::RequestObject;
+use rauthy_models::request::{AuthRequestRef, ParRequest};
+use rauthy_models::response::ParResponse;
+use validator::Validate;
+
+/// # Business logic for [POST /oidc/par](crate::handlers::post_par)
+///
+/// Authenticates the client and validates the authorization request in the exact same way as
+/// on the authorization endpoint. The validated request will be saved for a short time and can
+/// be referenced afterward with the returned `request_uri`.
+pub async fn pushed_authorization_request(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    req_data: ParRequest,
+) -> Result<ParResponse, ErrorResponse> {
+    let (client_id, credentials) = req_data.try_get_client_credentials(req)?;
+    let client = Client::find_maybe_ephemeral(data, client_id).await?;
+    client
+        .validate_credentials(data, credentials, req)
+        .await
+        .map_err(|err| ErrorResponse::new(ErrorResponseType::Unauthorized, err.message))?;
+
+    let par = push(data, req, &client, req_data).await?;
+    Ok(ParResponse {
+        request_uri: par.request_uri(),
+        expires_in: *PAR_REQUEST_URI_LIFETIME,
+    })
+}
+
+/// # Business logic for signed request objects (RFC 9101) on [GET /oidc/authorize](crate::handlers::get_authorize)
+///
+/// Validates the request object, which is given either by value or by reference, and merges it
+/// with the additional query params. The result will be saved in the same way as a pushed
+/// authorization request, which means the login afterward only needs to know the `request_uri`
+/// without passing around the whole request object.
+pub async fn push_request_object(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    params: AuthRequestRef,
+) -> Result<PushedAuthRequest, ErrorResponse> {
+    let client = Client::find_maybe_ephemeral(data, params.client_id.clone()).await?;
+    client.validate_par(false)?;
+    // fail early, before a request object will be fetched from anywhere
+    if client.jwks.is_none() && client.jwks_uri.is_none() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("No JWKS registered for client '{}'", client.id),
+        ));
+    }
+
+    let token = match &params.request {
+        Some(request) => request.clone(),
+        None => {
+            // the schema validation makes sure, that one of both is given
+            let uri = params.request_uri.as_deref().unwrap_or_default();
+            RequestObject::fetch(&client, uri).await?
+        }
+    };
+    let req_data = RequestObject::validate(data, &client, &token)
+        .await?
+        .merge(params)?;
+    req_data.validate()?;
+
+    push(data, req, &client, req_data).await
+}
+
+async fn push(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    client: &Client,
+    req_data: ParRequest,
+) -> Result<PushedAuthRequest, ErrorResponse> {
+    let (client, _) = validate_auth_req_param(
+        data,
+        req,
+        &client.id,
+        &req_data.redirect_uri,
+        &req_data.code_challenge,
+        &req_data.code_challenge_method,
+    )
+    .await?;
+
+    if !client.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("client is disabled"),
+        ));
+    }
+    client.validate_flow("authorization_code")?;
+
+    if req_data.response_type != "code" {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("unsupported_response_type".to_string()),
+            String::from("Only the 'code' response_type is supported"),
+        ));
+    }
+
+    let scopes = client.sanitize_login_scopes(&Some(
+        req_data.scope.split(' ').map(|s| s.to_string()).collect(),
+    ))?;
+    let par = PushedAuthRequest::new(client.id, req_data, scopes);
+    par.save(data).await?;
+
+    Ok(par)
+}
@@ -1,3 +1,4 @@
+use crate::recovery;
 use actix_web::cookie::SameSite;
 use actix_web::{cookie, web, HttpRequest, HttpResponse};
 use rauthy_common::constants::{PWD_CSRF_HEADER, PWD_RESET_COOKIE};
@@ -148,7 +149,11 @@ pub async fn handle_put_user_passkey_finish<'a>(
         .max_age(cookie::time::Duration::ZERO)
         .path("/auth")
         .finish();
-    Ok(HttpResponse::Created().cookie(cookie).finish())
+    // a passkey only account has no other way back in if the passkey gets lost
+    match recovery::init(data, &user.id).await? {
+        Some(codes) => Ok(HttpResponse::Created().cookie(cookie).json(codes)),
+        None => Ok(HttpResponse::Created().cookie(cookie).finish()),
+    }
 }
 
 #[tracing::instrument(level = "debug", skip_all, fields(email = req_data.email))]
@@ -0,0 +1,106 @@
+use actix_web::{web, HttpRequest};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::recovery_codes::UserRecoveryCode;
+use rauthy_models::entity::users::{AccountType, User};
+use rauthy_models::entity::webauthn::WebauthnLoginReq;
+use rauthy_models::events::event::Event;
+use rauthy_models::real_ip_from_req;
+use rauthy_models::request::RecoveryCodeAuthRequest;
+use rauthy_models::response::RecoveryCodesResponse;
+use rauthy_models::JwtAmrValue;
+use tracing::{info, warn};
+
+/// Creates the initial recovery codes after an MFA enrollment. If the user already has unused
+/// codes, nothing will be created and `None` is returned.
+pub async fn init(
+    data: &web::Data<AppState>,
+    user_id: &str,
+) -> Result<Option<RecoveryCodesResponse>, ErrorResponse> {
+    if !UserRecoveryCode::find_for_user(data, user_id)
+        .await?
+        .is_empty()
+    {
+        return Ok(None);
+    }
+
+    let codes = UserRecoveryCode::generate(data, user_id).await?;
+    info!("Initial recovery codes created for user {}", user_id);
+    Ok(Some(RecoveryCodesResponse { codes }))
+}
+
+/// # Business logic for [POST /users/{id}/recovery_codes](crate::handlers::post_recovery_codes)
+///
+/// Invalidates all existing codes and creates a new set.
+pub async fn regenerate(
+    data: &web::Data<AppState>,
+    user_id: String,
+) -> Result<RecoveryCodesResponse, ErrorResponse> {
+    let user = User::find(data, user_id).await?;
+    if !user.has_mfa_enabled() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Recovery codes can only be created with MFA enabled".to_string(),
+        ));
+    }
+
+    let codes = UserRecoveryCode::generate(data, &user.id).await?;
+    info!("Recovery codes regenerated for user {}", user.id);
+    Ok(RecoveryCodesResponse { codes })
+}
+
+/// # Business logic for [POST /users/{id}/recovery_codes/auth](crate::handlers::post_recovery_code_auth)
+///
+/// Finishes a login, which is waiting for the MFA step, with a recovery code instead. Just like
+/// for a TOTP, the login request can only be used once.
+pub async fn auth(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    user_id: String,
+    req_data: RecoveryCodeAuthRequest,
+) -> Result<WebauthnLoginReq, ErrorResponse> {
+    let login_req = WebauthnLoginReq::find(data, req_data.code).await?;
+    if login_req.user_id != user_id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "The login request belongs to another user".to_string(),
+        ));
+    }
+    login_req.delete(data).await?;
+
+    let user = User::find(data, user_id).await?;
+    if let Err(err) = UserRecoveryCode::redeem(data, &user.id, &req_data.recovery_code).await {
+        warn!("Invalid recovery code during login for user {}", user.id);
+        return Err(err);
+    }
+    info!("Recovery code login successful for user {}", user.id);
+
+    // The recovery code replaces the 2nd factor. The tokens must report this instead of the
+    // MFA methods of the user, and an account without a password did not use MFA at all.
+    if let Some(mut code) = AuthCode::find(data, login_req.auth_code_id.clone()).await? {
+        if user.account_type() == AccountType::Password {
+            code.amr = Some(vec![
+                JwtAmrValue::Pwd.to_string(),
+                JwtAmrValue::Otp.to_string(),
+                JwtAmrValue::Mfa.to_string(),
+            ]);
+        } else {
+            code.amr = Some(vec![JwtAmrValue::Otp.to_string()]);
+            if code.acr.as_deref() == Some(JwtAmrValue::Mfa.to_string().as_str()) {
+                code.acr = None;
+            }
+        }
+        code.save(data).await?;
+    }
+
+    data.tx_events
+        .send_async(Event::user_recovery_code_used(
+            user.email,
+            real_ip_from_req(req),
+        ))
+        .await
+        .unwrap();
+
+    Ok(login_req)
+}
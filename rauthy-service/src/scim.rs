@@ -0,0 +1,499 @@
+use actix_web::{web, HttpRequest};
+use rauthy_common::constants::{RAUTHY_ADMIN_ROLE, SCIM_ALLOW_PASSWORD};
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use rauthy_models::entity::users::User;
+use rauthy_models::events::event::Event;
+use rauthy_models::language::Language;
+use rauthy_models::real_ip_from_req;
+use rauthy_models::request::{
+    NewGroupRequest, NewUserRequest, UpdateUserRequest, UserAttrValueRequest,
+    UserAttrValuesUpdateRequest,
+};
+use rauthy_models::scim::filter::ScimFilter;
+use rauthy_models::scim::patch::apply_patch;
+use rauthy_models::scim::{
+    ScimError, ScimErrorType, ScimGroup, ScimListParams, ScimListResponse, ScimMultiValue,
+    ScimPatchOp, ScimUser,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::info;
+use validator::Validate;
+
+/// # Business logic for [GET /scim/v2/Users](crate::handlers::get_scim_users)
+pub async fn list_users(
+    data: &web::Data<AppState>,
+    params: ScimListParams,
+) -> Result<ScimListResponse<ScimUser>, ScimError> {
+    let filter = params
+        .filter
+        .as_deref()
+        .map(ScimFilter::parse)
+        .transpose()?;
+    let groups = Group::find_all(data).await?;
+    let mut attrs = UserAttrValueEntity::find_all_by_user(data).await?;
+
+    // Narrow down the users in the database as far as possible. The filter will still be applied
+    // to each result, because only parts of it may be translated.
+    let mut users = match filter.as_ref().and_then(ScimFilter::users_sql) {
+        Some(sql) => User::find_by_scim_filter(data, &sql).await?,
+        None => User::find_all(data).await?,
+    };
+    // a stable order is needed for a working pagination
+    users.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+
+    let mut res = Vec::with_capacity(users.len());
+    for user in users {
+        let user_attrs = attrs.remove(&user.id).unwrap_or_default();
+        let user = ScimUser::from_user(user, &groups, user_attrs, &data.issuer);
+        if is_match(filter.as_ref(), &user)? {
+            res.push(user);
+        }
+    }
+
+    Ok(ScimListResponse::paginate(
+        res,
+        params.start_index,
+        params.count,
+    ))
+}
+
+/// # Business logic for [GET /scim/v2/Users/{id}](crate::handlers::get_scim_user)
+pub async fn get_user(data: &web::Data<AppState>, id: String) -> Result<ScimUser, ScimError> {
+    let user = User::find(data, id).await?;
+    let groups = Group::find_all(data).await?;
+    user_to_scim(data, user, &groups).await
+}
+
+/// # Business logic for [POST /scim/v2/Users](crate::handlers::post_scim_user)
+///
+/// The user will receive the usual E-Mail for setting the password, if none is given.
+/// A password can only be set with `SCIM_ALLOW_PASSWORD` enabled.
+pub async fn create_user(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    scim_user: ScimUser,
+) -> Result<ScimUser, ScimError> {
+    if User::find_by_email(data, scim_user.user_name.clone())
+        .await
+        .is_ok()
+    {
+        return Err(ScimError::uniqueness(format!(
+            "User '{}' already exists",
+            scim_user.user_name
+        )));
+    }
+
+    check_password(&scim_user)?;
+    let roles = role_names(scim_user.roles.clone().unwrap_or_default());
+    check_admin_role(&roles, None)?;
+
+    let (given_name, family_name) = names(&scim_user, None)?;
+    let new_user = NewUserRequest {
+        email: scim_user.user_name,
+        family_name,
+        given_name,
+        language: scim_user
+            .preferred_language
+            .as_deref()
+            .map(Language::from)
+            .unwrap_or_default(),
+        // groups are read-only on the user and can only be managed via the `/Groups`
+        groups: None,
+        roles,
+        user_expires: None,
+    };
+    new_user.validate()?;
+    let attrs = attr_values(data, scim_user.attributes, &[]).await?;
+
+    let mut user = User::create_from_new(data, new_user).await?;
+    let active = scim_user.active.unwrap_or(true);
+    if !active || scim_user.password.is_some() {
+        if let Some(password) = &scim_user.password {
+            user.apply_password_rules(data, password).await?;
+        }
+        user.enabled = active;
+        user.save(data, None, None).await?;
+    }
+    if !attrs.values.is_empty() {
+        UserAttrValueEntity::update_for_user(data, &user.id, attrs).await?;
+    }
+    info!("New user {} provisioned via SCIM", user.id);
+
+    data.tx_events
+        .send_async(Event::new_user(user.email.clone(), real_ip_from_req(req)))
+        .await
+        .unwrap();
+
+    get_user(data, user.id).await
+}
+
+/// # Business logic for [PUT /scim/v2/Users/{id}](crate::handlers::put_scim_user)
+pub async fn replace_user(
+    data: &web::Data<AppState>,
+    id: String,
+    scim_user: ScimUser,
+) -> Result<ScimUser, ScimError> {
+    let user = User::find(data, id).await?;
+    if user.email != scim_user.user_name
+        && User::find_by_email(data, scim_user.user_name.clone())
+            .await
+            .is_ok()
+    {
+        return Err(ScimError::uniqueness(format!(
+            "User '{}' already exists",
+            scim_user.user_name
+        )));
+    }
+
+    check_password(&scim_user)?;
+    let roles = match scim_user.roles {
+        Some(roles) => {
+            let roles = role_names(roles);
+            check_admin_role(&roles, Some(&user))?;
+            roles
+        }
+        None => user.get_roles(),
+    };
+
+    let (given_name, family_name) = names(&scim_user, Some(&user))?;
+    let upd_user = UpdateUserRequest {
+        email: scim_user.user_name,
+        given_name,
+        family_name,
+        language: scim_user.preferred_language.as_deref().map(Language::from),
+        password: scim_user.password,
+        roles,
+        groups: Some(user.get_groups()),
+        enabled: scim_user.active.unwrap_or(user.enabled),
+        email_verified: user.email_verified,
+        user_expires: user.user_expires,
+    };
+    upd_user.validate()?;
+
+    let existing_attrs = UserAttrValueEntity::find_for_user(data, &user.id)
+        .await?
+        .into_iter()
+        .map(|a| a.key)
+        .collect::<Vec<String>>();
+    let attrs = attr_values(data, scim_user.attributes, &existing_attrs).await?;
+
+    let (user, _) = User::update(data, user.id.clone(), upd_user, Some(user)).await?;
+    if !attrs.values.is_empty() {
+        UserAttrValueEntity::update_for_user(data, &user.id, attrs).await?;
+    }
+
+    get_user(data, user.id).await
+}
+
+/// # Business logic for [PATCH /scim/v2/Users/{id}](crate::handlers::patch_scim_user)
+///
+/// The operations are applied to the current state, which is then handled like a replace.
+pub async fn patch_user(
+    data: &web::Data<AppState>,
+    id: String,
+    patch: ScimPatchOp,
+) -> Result<ScimUser, ScimError> {
+    let current = get_user(data, id.clone()).await?;
+    let scim_user = patched(&current, &patch)?;
+    replace_user(data, id, scim_user).await
+}
+
+/// # Business logic for [DELETE /scim/v2/Users/{id}](crate::handlers::delete_scim_user)
+pub async fn delete_user(data: &web::Data<AppState>, id: String) -> Result<(), ScimError> {
+    let user = User::find(data, id).await?;
+    user.delete(data).await?;
+    info!("User {} deleted via SCIM", user.id);
+    Ok(())
+}
+
+/// # Business logic for [GET /scim/v2/Groups](crate::handlers::get_scim_groups)
+pub async fn list_groups(
+    data: &web::Data<AppState>,
+    params: ScimListParams,
+) -> Result<ScimListResponse<ScimGroup>, ScimError> {
+    let filter = params
+        .filter
+        .as_deref()
+        .map(ScimFilter::parse)
+        .transpose()?;
+    let users = User::find_all(data).await?;
+
+    let mut groups = Group::find_all(data).await?;
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut res = Vec::with_capacity(groups.len());
+    for group in groups {
+        let group = ScimGroup::from_group(group, &users, &data.issuer);
+        if is_match(filter.as_ref(), &group)? {
+            res.push(group);
+        }
+    }
+
+    Ok(ScimListResponse::paginate(
+        res,
+        params.start_index,
+        params.count,
+    ))
+}
+
+/// # Business logic for [GET /scim/v2/Groups/{id}](crate::handlers::get_scim_group)
+pub async fn get_group(data: &web::Data<AppState>, id: String) -> Result<ScimGroup, ScimError> {
+    let group = Group::find(data, id).await?;
+    let users = User::find_all(data).await?;
+    Ok(ScimGroup::from_group(group, &users, &data.issuer))
+}
+
+/// # Business logic for [POST /scim/v2/Groups](crate::handlers::post_scim_group)
+pub async fn create_group(
+    data: &web::Data<AppState>,
+    scim_group: ScimGroup,
+) -> Result<ScimGroup, ScimError> {
+    let group_req = NewGroupRequest {
+        group: scim_group.display_name,
+    };
+    group_req.validate()?;
+    check_group_name_free(data, &group_req.group).await?;
+    let member_ids = member_ids(scim_group.members);
+    if let Some(ids) = &member_ids {
+        check_members_exist(data, ids).await?;
+    }
+
+    let group = Group::create(data, group_req).await?;
+    if let Some(ids) = member_ids {
+        set_group_members(data, &group.name, &ids).await?;
+    }
+    info!("New group {} provisioned via SCIM", group.id);
+
+    get_group(data, group.id).await
+}
+
+/// # Business logic for [PUT /scim/v2/Groups/{id}](crate::handlers::put_scim_group)
+pub async fn replace_group(
+    data: &web::Data<AppState>,
+    id: String,
+    scim_group: ScimGroup,
+) -> Result<ScimGroup, ScimError> {
+    let mut group = Group::find(data, id).await?;
+    let member_ids = member_ids(scim_group.members);
+    if let Some(ids) = &member_ids {
+        check_members_exist(data, ids).await?;
+    }
+
+    if group.name != scim_group.display_name {
+        let group_req = NewGroupRequest {
+            group: scim_group.display_name,
+        };
+        group_req.validate()?;
+        check_group_name_free(data, &group_req.group).await?;
+        group = Group::update(data, group.id, group_req.group).await?;
+    }
+    if let Some(ids) = member_ids {
+        set_group_members(data, &group.name, &ids).await?;
+    }
+
+    get_group(data, group.id).await
+}
+
+/// # Business logic for [PATCH /scim/v2/Groups/{id}](crate::handlers::patch_scim_group)
+///
+/// The operations are applied to the current state, which is then handled like a replace.
+pub async fn patch_group(
+    data: &web::Data<AppState>,
+    id: String,
+    patch: ScimPatchOp,
+) -> Result<ScimGroup, ScimError> {
+    let current = get_group(data, id.clone()).await?;
+    let scim_group = patched(&current, &patch)?;
+    replace_group(data, id, scim_group).await
+}
+
+/// # Business logic for [DELETE /scim/v2/Groups/{id}](crate::handlers::delete_scim_group)
+pub async fn delete_group(data: &web::Data<AppState>, id: String) -> Result<(), ScimError> {
+    Group::delete(data, id).await?;
+    Ok(())
+}
+
+async fn user_to_scim(
+    data: &web::Data<AppState>,
+    user: User,
+    groups: &[Group],
+) -> Result<ScimUser, ErrorResponse> {
+    let attrs = UserAttrValueEntity::find_for_user(data, &user.id).await?;
+    Ok(ScimUser::from_user(user, groups, attrs, &data.issuer))
+}
+
+fn is_match<T: Serialize>(filter: Option<&ScimFilter>, resource: &T) -> Result<bool, ScimError> {
+    match filter {
+        None => Ok(true),
+        Some(filter) => {
+            Ok(filter.matches(&serde_json::to_value(resource).map_err(ErrorResponse::from)?))
+        }
+    }
+}
+
+fn patched<T: Serialize + DeserializeOwned>(
+    current: &T,
+    patch: &ScimPatchOp,
+) -> Result<T, ScimError> {
+    let mut value = serde_json::to_value(current).map_err(ErrorResponse::from)?;
+    apply_patch(&mut value, &patch.operations)?;
+    serde_json::from_value::<T>(value).map_err(|err| {
+        ScimError::bad_request(
+            ScimErrorType::InvalidValue,
+            format!("Invalid resource after PATCH: {}", err),
+        )
+    })
+}
+
+/// Returns `(given_name, family_name)`. Missing values fall back to the existing user, if any.
+fn names(scim_user: &ScimUser, user: Option<&User>) -> Result<(String, String), ScimError> {
+    let name = scim_user.name.clone().unwrap_or_default();
+    let given_name = name
+        .given_name
+        .or_else(|| user.map(|u| u.given_name.clone()));
+    let family_name = name
+        .family_name
+        .or_else(|| user.map(|u| u.family_name.clone()));
+
+    match (given_name, family_name) {
+        (Some(given_name), Some(family_name)) => Ok((given_name, family_name)),
+        _ => Err(ScimError::bad_request(
+            ScimErrorType::InvalidValue,
+            "'name.givenName' and 'name.familyName' are required".to_string(),
+        )),
+    }
+}
+
+/// Passwords are usually set by the users themselves and can only be provisioned via SCIM, if
+/// this has been explicitly allowed.
+fn check_password(scim_user: &ScimUser) -> Result<(), ScimError> {
+    if scim_user.password.is_some() && !*SCIM_ALLOW_PASSWORD {
+        return Err(ScimError::bad_request(
+            ScimErrorType::Mutability,
+            "Setting a password via SCIM is not allowed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The `rauthy_admin` role grants full access to Rauthy itself. It can only be managed locally
+/// and must never be added or removed via SCIM.
+fn check_admin_role(roles: &[String], user: Option<&User>) -> Result<(), ScimError> {
+    let is_admin = user.map(|u| u.is_admin()).unwrap_or(false);
+    if roles.contains(&RAUTHY_ADMIN_ROLE) != is_admin {
+        return Err(ScimError::bad_request(
+            ScimErrorType::Mutability,
+            format!(
+                "The '{}' role cannot be changed via SCIM",
+                *RAUTHY_ADMIN_ROLE
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn role_names(roles: Vec<ScimMultiValue>) -> Vec<String> {
+    roles.into_iter().map(|r| r.value).collect()
+}
+
+fn member_ids(members: Option<Vec<ScimMultiValue>>) -> Option<Vec<String>> {
+    members.map(|members| {
+        let mut ids = members
+            .into_iter()
+            .map(|m| m.value)
+            .collect::<Vec<String>>();
+        ids.sort();
+        ids.dedup();
+        ids
+    })
+}
+
+/// Builds the update for the custom attributes. `None` keeps all existing values, while all
+/// `existing` keys, which are missing in the given values, will be deleted.
+async fn attr_values(
+    data: &web::Data<AppState>,
+    values: Option<Map<String, Value>>,
+    existing: &[String],
+) -> Result<UserAttrValuesUpdateRequest, ScimError> {
+    let Some(values) = values else {
+        return Ok(UserAttrValuesUpdateRequest { values: Vec::new() });
+    };
+
+    let configured = UserAttrConfigEntity::find_all_as_set(data).await?;
+    let mut res = Vec::with_capacity(values.len() + existing.len());
+    for key in existing {
+        if !values.contains_key(key) {
+            res.push(UserAttrValueRequest {
+                key: key.clone(),
+                value: Value::Null,
+            });
+        }
+    }
+    for (key, value) in values {
+        if !configured.contains(&key) {
+            return Err(ScimError::bad_request(
+                ScimErrorType::InvalidValue,
+                format!("Custom attribute '{}' does not exist", key),
+            ));
+        }
+        res.push(UserAttrValueRequest { key, value });
+    }
+
+    Ok(UserAttrValuesUpdateRequest { values: res })
+}
+
+async fn check_group_name_free(data: &web::Data<AppState>, name: &str) -> Result<(), ScimError> {
+    if Group::find_all(data).await?.iter().any(|g| g.name == name) {
+        return Err(ScimError::uniqueness(format!(
+            "Group '{}' already exists",
+            name
+        )));
+    }
+    Ok(())
+}
+
+async fn check_members_exist(data: &web::Data<AppState>, ids: &[String]) -> Result<(), ScimError> {
+    let users = User::find_all(data).await?;
+    if let Some(id) = ids.iter().find(|id| !users.iter().any(|u| &u.id == *id)) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            format!("Member '{}' does not exist", id),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Assigns the group to exactly the given users and removes it from all others.
+async fn set_group_members(
+    data: &web::Data<AppState>,
+    group: &str,
+    member_ids: &[String],
+) -> Result<(), ErrorResponse> {
+    for mut user in User::find_all(data).await? {
+        let mut groups = user.get_groups();
+        let is_member = groups.iter().any(|g| g == group);
+        let should_be_member = member_ids.contains(&user.id);
+        if is_member == should_be_member {
+            continue;
+        }
+
+        if should_be_member {
+            groups.push(group.to_string());
+        } else {
+            groups.retain(|g| g != group);
+        }
+        user.groups = if groups.is_empty() {
+            None
+        } else {
+            Some(groups.join(","))
+        };
+        user.save(data, None, None).await?;
+    }
+    Ok(())
+}
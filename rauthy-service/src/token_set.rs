@@ -1,15 +1,19 @@
 use crate::auth;
 use actix_web::web;
-use rauthy_common::constants::OFFLINE_TOKEN_LT;
+use rauthy_common::constants::TOKEN_TYPE_ACCESS_TOKEN;
 use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::scopes::Scope;
+use rauthy_models::entity::sessions::Session;
 use rauthy_models::entity::user_attr::UserAttrValueEntity;
 use rauthy_models::entity::users::User;
-use rauthy_models::JwtTokenType;
+use rauthy_models::request::ClaimsRequest;
+use rauthy_models::{JktClaim, JwtActClaim, JwtAmrValue, JwtTokenType};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use time::OffsetDateTime;
 use utoipa::ToSchema;
 
@@ -22,27 +26,29 @@ pub struct TokenSet {
     pub expires_in: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refresh_token: Option<String>,
+    /// Only set for the token exchange grant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued_token_type: Option<String>,
 }
 
 impl TokenSet {
     pub async fn for_client_credentials(
         data: &web::Data<AppState>,
         client: &Client,
-        dpop_fingerprint: Option<String>,
+        cnf: Option<JktClaim>,
     ) -> Result<Self, ErrorResponse> {
-        let token_type = if dpop_fingerprint.is_some() {
-            JwtTokenType::DPoP
-        } else {
-            JwtTokenType::Bearer
-        };
+        let token_type = Self::token_type(&cnf);
         let access_token = auth::build_access_token(
             None,
             data,
             client,
-            dpop_fingerprint,
+            cnf,
             client.access_token_lifetime as i64,
             None,
             None,
+            None,
+            None,
+            None,
         )
         .await?;
 
@@ -52,17 +58,91 @@ impl TokenSet {
             id_token: None,
             expires_in: client.access_token_lifetime,
             refresh_token: None,
+            issued_token_type: None,
+        })
+    }
+
+    /// Builds the [TokenSet] for the token exchange grant, which only ever contains an access
+    /// token for the (already validated) `audience`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn for_token_exchange(
+        user: Option<&User>,
+        data: &web::Data<AppState>,
+        client: &Client,
+        cnf: Option<JktClaim>,
+        lifetime: i64,
+        scope: String,
+        audience: String,
+        act: Option<JwtActClaim>,
+    ) -> Result<Self, ErrorResponse> {
+        // custom scope attributes can only be mapped, if we have a user
+        let cust = Scope::extract_custom(&scope);
+        let scps;
+        let attrs;
+        let customs_access = match user {
+            Some(user) if !cust.is_empty() => {
+                scps = Scope::find_all(data).await?;
+                let customs = scps
+                    .iter()
+                    .filter(|s| cust.contains(s.name.as_str()) && s.attr_include_access.is_some())
+                    .collect::<Vec<&Scope>>();
+
+                if customs.is_empty() {
+                    None
+                } else {
+                    let values = UserAttrValueEntity::find_for_user(data, &user.id).await?;
+                    let mut res = HashMap::with_capacity(values.len());
+                    values.into_iter().for_each(|a| {
+                        res.insert(a.key, a.value);
+                    });
+                    attrs = Some(res);
+                    Some((customs, &attrs))
+                }
+            }
+            _ => None,
+        };
+
+        let token_type = Self::token_type(&cnf);
+        let access_token = auth::build_access_token(
+            user,
+            data,
+            client,
+            cnf,
+            lifetime,
+            Some(scope),
+            customs_access,
+            None,
+            Some(audience),
+            act,
+        )
+        .await?;
+
+        Ok(Self {
+            access_token,
+            token_type,
+            id_token: None,
+            expires_in: lifetime as i32,
+            refresh_token: None,
+            issued_token_type: Some(TOKEN_TYPE_ACCESS_TOKEN.to_string()),
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn from_user(
         user: &User,
         data: &web::Data<AppState>,
         client: &Client,
-        dpop_fingerprint: Option<String>,
+        cnf: Option<JktClaim>,
         nonce: Option<String>,
         scopes: Option<String>,
+        claims: Option<&ClaimsRequest>,
+        acr: Option<String>,
+        amr: Option<Vec<String>>,
+        auth_time: Option<i64>,
         is_auth_code_flow: bool,
+        session_id: Option<&str>,
+        refresh_origin: Option<&RefreshToken>,
+        ip: Option<String>,
     ) -> Result<Self, ErrorResponse> {
         let scope = if let Some(s) = &scopes {
             s.clone()
@@ -74,7 +154,6 @@ impl TokenSet {
         let cust = Scope::extract_custom(&scope);
 
         let scps;
-        let attrs;
         let (customs_access, customs_id) = if !cust.is_empty() {
             scps = Some(Scope::find_all(data).await?);
 
@@ -92,34 +171,59 @@ impl TokenSet {
                 }
             }
 
-            // if there was any custom mapping, we need the additional user attributes
-            attrs = if !customs_access.is_empty() || !customs_id.is_empty() {
-                let attrs = UserAttrValueEntity::find_for_user(data, &user.id).await?;
-                let mut res = HashMap::with_capacity(attrs.len());
-                attrs.iter().for_each(|a| {
-                    res.insert(a.key.clone(), a.value.clone());
-                });
-                Some(res)
-            } else {
-                None
-            };
+            (customs_access, customs_id)
+        } else {
+            (Vec::default(), Vec::default())
+        };
 
-            // prepare the result
-            let access = if customs_access.is_empty() {
-                None
-            } else {
-                Some((customs_access, &attrs))
-            };
-            let id = if customs_id.is_empty() {
-                None
-            } else {
-                Some((customs_id, &attrs))
-            };
+        // custom user attributes may only be requested via `claims`, if a granted scope maps them
+        let claims = claims.cloned().map(|mut claims| {
+            let granted = customs_access
+                .iter()
+                .chain(customs_id.iter())
+                .flat_map(|s| s.attr_include_access.iter().chain(s.attr_include_id.iter()))
+                .flat_map(|csv| csv.split(','))
+                .collect::<HashSet<&str>>();
+            claims.retain_granted(&granted);
+            claims
+        });
+        let claims = claims.as_ref();
+
+        // if there was any custom mapping or custom claims have been requested directly,
+        // we need the additional user attributes
+        let claims_custom_id = claims
+            .map(|c| !c.custom_id_token().is_empty())
+            .unwrap_or(false);
+        let attrs = if !customs_access.is_empty() || !customs_id.is_empty() || claims_custom_id {
+            let attrs = UserAttrValueEntity::find_for_user(data, &user.id).await?;
+            let mut res = HashMap::with_capacity(attrs.len());
+            attrs.iter().for_each(|a| {
+                res.insert(a.key.clone(), a.value.clone());
+            });
+            Some(res)
+        } else {
+            None
+        };
 
-            (access, id)
+        // prepare the result
+        let customs_access = if customs_access.is_empty() {
+            None
+        } else {
+            Some((customs_access, &attrs))
+        };
+        let customs_id = if customs_id.is_empty() {
+            None
         } else {
-            (None, None)
+            Some((customs_id, &attrs))
         };
+        let userinfo_claims = claims
+            .map(|c| {
+                c.custom_userinfo()
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<String>>()
+            })
+            .filter(|names| !names.is_empty());
 
         // set the correct lifetime
         let lifetime = if let Some(ts) = user.user_expires {
@@ -138,17 +242,14 @@ impl TokenSet {
             } else {
                 diff
             }
-        } else if scope.contains("offline_access") {
-            *OFFLINE_TOKEN_LT
         } else {
             client.access_token_lifetime.unsigned_abs() as i64
         };
 
-        let token_type = if dpop_fingerprint.is_some() {
-            JwtTokenType::DPoP
-        } else {
-            JwtTokenType::Bearer
-        };
+        let token_type = Self::token_type(&cnf);
+        // ID and refresh tokens are only bound to a DPoP key - a certificate bound client must
+        // authenticate with its certificate for the refresh anyway
+        let dpop_fingerprint = cnf.as_ref().and_then(|cnf| cnf.jkt.clone());
         let id_token = auth::build_id_token(
             user,
             data,
@@ -158,19 +259,37 @@ impl TokenSet {
             nonce,
             &scope,
             customs_id,
+            claims.map(|c| (c, &attrs)),
+            acr,
+            amr.clone(),
+            auth_time,
             is_auth_code_flow,
+            session_id.map(Session::oidc_sid),
         )
         .await?;
         let access_token = auth::build_access_token(
             Some(user),
             data,
             client,
-            dpop_fingerprint.clone(),
+            cnf,
             lifetime,
             Some(scope),
             customs_access,
+            userinfo_claims,
+            None,
+            None,
         )
         .await?;
+        // a login without a 2nd factor, like with a recovery code only, must never turn into an
+        // MFA login with a refresh
+        let is_mfa = is_auth_code_flow
+            && amr
+                .as_ref()
+                .map(|amr| {
+                    amr.iter()
+                        .any(|v| matches!(JwtAmrValue::from_str(v), Ok(JwtAmrValue::Mfa)))
+                })
+                .unwrap_or(true);
         let refresh_token = if client.refresh_token {
             Some(
                 auth::build_refresh_token(
@@ -180,7 +299,10 @@ impl TokenSet {
                     client,
                     lifetime,
                     scopes,
-                    is_auth_code_flow,
+                    is_mfa,
+                    refresh_origin,
+                    session_id.map(String::from),
+                    ip,
                 )
                 .await?,
             )
@@ -194,6 +316,15 @@ impl TokenSet {
             id_token: Some(id_token),
             expires_in: client.access_token_lifetime,
             refresh_token,
+            issued_token_type: None,
         })
     }
+
+    fn token_type(cnf: &Option<JktClaim>) -> JwtTokenType {
+        if cnf.as_ref().and_then(|cnf| cnf.jkt.as_ref()).is_some() {
+            JwtTokenType::DPoP
+        } else {
+            JwtTokenType::Bearer
+        }
+    }
 }
@@ -0,0 +1,99 @@
+use actix_web::web;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::totp::UserTotp;
+use rauthy_models::entity::users::{AccountType, User};
+use rauthy_models::entity::webauthn::WebauthnLoginReq;
+use rauthy_models::request::{TotpAuthRequest, TotpRegFinishRequest};
+use rauthy_models::response::TotpRegStartResponse;
+use tracing::{info, warn};
+
+/// # Business logic for [POST /users/{id}/totp/register/start](crate::handlers::post_totp_reg_start)
+///
+/// Starts a new enrollment, which will only become active after a first valid code.
+pub async fn reg_start(
+    data: &web::Data<AppState>,
+    user_id: String,
+) -> Result<TotpRegStartResponse, ErrorResponse> {
+    let user = User::find(data, user_id).await?;
+    if user.totp_enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "TOTP is already enabled for this user".to_string(),
+        ));
+    }
+    // passkey only accounts never provide a password, which a TOTP could be the 2nd factor for
+    if user.account_type() != AccountType::Password {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "TOTP can only be added to accounts with a password".to_string(),
+        ));
+    }
+
+    let (_, secret) = UserTotp::create(data, user.id).await?;
+
+    Ok(TotpRegStartResponse {
+        secret: UserTotp::secret_b32(&secret),
+        provisioning_uri: UserTotp::provisioning_uri(&user.email, &secret),
+    })
+}
+
+/// # Business logic for [POST /users/{id}/totp/register/finish](crate::handlers::post_totp_reg_finish)
+pub async fn reg_finish(
+    data: &web::Data<AppState>,
+    user_id: String,
+    req_data: TotpRegFinishRequest,
+) -> Result<(), ErrorResponse> {
+    let mut user = User::find(data, user_id).await?;
+    if user.totp_enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "TOTP is already enabled for this user".to_string(),
+        ));
+    }
+
+    let totp = UserTotp::find(data, &user.id).await?;
+    totp.verify(data, &req_data.totp).await?;
+
+    user.totp_enabled = true;
+    user.save(data, None, None).await?;
+    info!("TOTP enabled for user {}", user.id);
+
+    Ok(())
+}
+
+/// # Business logic for [POST /users/{id}/totp/auth](crate::handlers::post_totp_auth)
+///
+/// Finishes a login, which is waiting for the 2nd factor. The login request can only be used
+/// once, no matter if the code was valid, to not allow guessing the code.
+pub async fn auth(
+    data: &web::Data<AppState>,
+    user_id: String,
+    req_data: TotpAuthRequest,
+) -> Result<WebauthnLoginReq, ErrorResponse> {
+    let login_req = WebauthnLoginReq::find(data, req_data.code).await?;
+    if login_req.user_id != user_id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "The login request belongs to another user".to_string(),
+        ));
+    }
+    login_req.delete(data).await?;
+
+    let user = User::find(data, user_id).await?;
+    if !user.totp_enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "TOTP is not enabled for this user".to_string(),
+        ));
+    }
+
+    let totp = UserTotp::find(data, &user.id).await?;
+    if let Err(err) = totp.verify(data, &req_data.totp).await {
+        warn!("Invalid TOTP during login for user {}", user.id);
+        return Err(err);
+    }
+    info!("TOTP Authentication successful for user {}", user.id);
+
+    Ok(login_req)
+}
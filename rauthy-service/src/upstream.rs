@@ -0,0 +1,277 @@
+use crate::auth::{requested_acr, validate_acr, validate_auth_req_param};
+use crate::consent;
+use actix_web::{web, HttpRequest};
+use rauthy_common::constants::SESSION_LIFETIME;
+use rauthy_common::error_response::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::base64_url_no_pad_encode;
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::upstream_providers::{
+    UpstreamAuthState, UpstreamClaims, UpstreamProvider, UpstreamProviderLink,
+};
+use rauthy_models::entity::users::User;
+use rauthy_models::events::event::Event;
+use rauthy_models::request::{ClaimsRequest, UpstreamAuthRequest, UpstreamCallbackRequest};
+use ring::digest;
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+/// # Business logic for [GET /oidc/authorize/upstream](crate::handlers::get_authorize_upstream)
+///
+/// Validates the downstream authorization request, saves it and returns the location of the
+/// upstream authorization endpoint the user agent should be redirected to.
+pub async fn authorize_upstream(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    req_data: UpstreamAuthRequest,
+) -> Result<String, ErrorResponse> {
+    // the downstream request must be valid in the exact same way as for a local login
+    let (client, _) = validate_auth_req_param(
+        data,
+        req,
+        &req_data.client_id,
+        &req_data.redirect_uri,
+        &req_data.code_challenge,
+        &req_data.code_challenge_method,
+    )
+    .await?;
+    // upstream logins cannot be started from a pushed authorization request
+    client.validate_par(false)?;
+
+    let provider = UpstreamProvider::find(data, &req_data.provider_id).await?;
+    if !provider.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Disabled,
+            format!("Upstream provider '{}' is disabled", provider.name),
+        ));
+    }
+
+    // fail early on an invalid claims request instead of after the upstream login
+    req_data
+        .claims
+        .as_deref()
+        .map(ClaimsRequest::parse)
+        .transpose()?;
+
+    let auth_state = UpstreamAuthState::new(req_data, provider.use_pkce);
+    // a login at the upstream provider always needs user interaction
+    if auth_state.has_prompt("none") {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::OAuth("interaction_required".to_string()),
+            "An upstream login is not possible with 'prompt=none'".to_string(),
+        ));
+    }
+    let challenge = auth_state.upstream_verifier.as_ref().map(|verifier| {
+        let hash = digest::digest(&digest::SHA256, verifier.as_bytes());
+        base64_url_no_pad_encode(hash.as_ref())
+    });
+    auth_state.save(data).await?;
+
+    provider.authorization_url(
+        &UpstreamProvider::callback_uri(data),
+        &auth_state.id,
+        challenge.as_deref(),
+        auth_state.has_prompt("login"),
+        auth_state.max_age,
+    )
+}
+
+/// # Business logic for [GET /oidc/authorize/upstream/callback](crate::handlers::get_authorize_upstream_callback)
+///
+/// Finishes the login at the upstream provider, links or provisions the local user and returns
+/// the location for the downstream client redirect together with the new session.
+pub async fn callback_upstream(
+    data: &web::Data<AppState>,
+    req_data: UpstreamCallbackRequest,
+    remote_ip: Option<String>,
+) -> Result<(String, Session), ErrorResponse> {
+    let auth_state = UpstreamAuthState::find(data, req_data.state)
+        .await?
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                String::from("The upstream login is invalid or has expired"),
+            )
+        })?;
+    // the state is single use only
+    auth_state.delete(data).await?;
+
+    if let Some(err) = req_data.error {
+        let msg = match req_data.error_description {
+            None => format!("Upstream login failed: {}", err),
+            Some(desc) => format!("Upstream login failed: {} - {}", err, desc),
+        };
+        return Err(ErrorResponse::new(ErrorResponseType::Unauthorized, msg));
+    }
+    let code = req_data.code.ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            String::from("'code' is missing"),
+        )
+    })?;
+
+    let provider = UpstreamProvider::find(data, &auth_state.provider_id).await?;
+    if !provider.enabled {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Disabled,
+            format!("Upstream provider '{}' is disabled", provider.name),
+        ));
+    }
+
+    let secret = provider.get_secret_cleartext(data)?;
+    let claims = provider
+        .fetch_claims(
+            secret,
+            &code,
+            &UpstreamProvider::callback_uri(data),
+            auth_state.upstream_verifier.as_deref(),
+        )
+        .await?;
+
+    let mut user = find_or_provision_user(data, &provider, claims, remote_ip.clone()).await?;
+    user.check_enabled()?;
+    user.check_expired()?;
+
+    // The 2nd factor of a user cannot be verified during the redirect from the upstream
+    // provider. The login must be rejected, because it would bypass the users own MFA otherwise.
+    if user.has_mfa_enabled() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::MfaRequired,
+            "MFA is enabled for this account, which is not possible with an upstream login"
+                .to_string(),
+        ));
+    }
+
+    let client = Client::find_maybe_ephemeral(data, auth_state.client_id).await?;
+    // an upstream login can never be verified as MFA from our side
+    if client.force_mfa {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::MfaRequired,
+            "MFA is required for this client, which is not possible with an upstream login"
+                .to_string(),
+        ));
+    }
+
+    let claims = auth_state
+        .claims
+        .as_deref()
+        .map(ClaimsRequest::parse)
+        .transpose()?;
+    let acr = validate_acr(
+        &requested_acr(auth_state.acr_values.as_deref(), claims.as_ref()),
+        &user,
+    )?;
+
+    user.last_login = Some(OffsetDateTime::now_utc().unix_timestamp());
+    user.save(data, None, None).await?;
+
+    let session = Session::try_new(&user, *SESSION_LIFETIME, remote_ip)?;
+    session.save(data).await?;
+
+    let scopes = client.sanitize_login_scopes(&Some(
+        auth_state.scope.split(' ').map(|s| s.to_string()).collect(),
+    ))?;
+    let challenge_method = auth_state.code_challenge.as_ref().map(|_| {
+        auth_state
+            .code_challenge_method
+            .clone()
+            .unwrap_or_else(|| "plain".to_string())
+    });
+    let mut code = AuthCode::new(
+        user.id,
+        client.id.clone(),
+        Some(session.id.clone()),
+        auth_state.code_challenge.clone(),
+        challenge_method,
+        auth_state.nonce.clone(),
+        scopes,
+        claims,
+        acr,
+        // `prompt=login` and `max_age` have been forwarded and enforced upstream
+        Some(OffsetDateTime::now_utc().unix_timestamp()),
+        client.auth_code_lifetime,
+    );
+    code.save(data).await?;
+
+    // the code will only be handed out after a possibly needed consent
+    let loc = auth_state.code_redirect_uri(&code.id);
+    let loc = consent::consent_redirect(
+        data,
+        &client,
+        &mut code,
+        &auth_state.redirect_uri,
+        auth_state.state.as_deref(),
+        auth_state.prompt.as_deref(),
+        loc,
+    )
+    .await?;
+
+    Ok((loc, session))
+}
+
+/// Returns the local user linked to the upstream `sub`. If no link exists yet, an existing user
+/// will be linked or a new one will be created, depending on the provider config.
+async fn find_or_provision_user(
+    data: &web::Data<AppState>,
+    provider: &UpstreamProvider,
+    claims: UpstreamClaims,
+    remote_ip: Option<String>,
+) -> Result<User, ErrorResponse> {
+    if let Some(link) = UpstreamProviderLink::find(data, &provider.id, &claims.sub).await? {
+        return User::find(data, link.user_id).await;
+    }
+
+    let existing = match User::find_by_email(data, claims.email.clone()).await {
+        Ok(user) => Some(user),
+        Err(err) if err.error == ErrorResponseType::NotFound => None,
+        Err(err) => return Err(err),
+    };
+    if let Some(user) = existing {
+        // Linking without a verified email would make an account takeover possible for anyone,
+        // who can register an account with a foreign email at the upstream provider.
+        if !provider.auto_link || !claims.email_verified {
+            warn!(
+                "Upstream login from '{}' for existing user {} without an existing link",
+                provider.name, user.email
+            );
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                String::from("The account exists already and cannot be linked automatically"),
+            ));
+        }
+
+        UpstreamProviderLink::create(data, provider.id.clone(), claims.sub, user.id.clone())
+            .await?;
+        info!(
+            "Linked user {} to upstream provider '{}'",
+            user.email, provider.name
+        );
+        return Ok(user);
+    }
+
+    if !provider.auto_provision {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            String::from("No account exists for this upstream user"),
+        ));
+    }
+
+    let new_user = User {
+        email: claims.email,
+        email_verified: claims.email_verified,
+        given_name: claims.given_name.unwrap_or_default(),
+        family_name: claims.family_name.unwrap_or_default(),
+        ..Default::default()
+    };
+    let user = User::create(data, new_user).await?;
+    UpstreamProviderLink::create(data, provider.id.clone(), claims.sub, user.id.clone()).await?;
+
+    data.tx_events
+        .send_async(Event::new_user(user.email.clone(), remote_ip))
+        .await
+        .unwrap();
+
+    Ok(user)
+}